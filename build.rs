@@ -0,0 +1,12 @@
+//! Compiles `proto/key_service.proto` for the gRPC server. protox parses the
+//! schema in Rust, so building does not need `protoc` installed.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto");
+    #[cfg(feature = "service")]
+    {
+        let descriptors = protox::compile(["key_service.proto"], ["proto"])?;
+        tonic_build::configure().build_client(false).compile_fds(descriptors)?;
+    }
+    Ok(())
+}
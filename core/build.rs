@@ -0,0 +1,19 @@
+//! Finds libgmp for the `gmp` feature, so a missing library fails the build
+//! here with an install hint rather than as an unresolved symbol at link
+//! time. `GMP_LIB_DIR` points at a libgmp that pkg-config does not know.
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GMP_LIB_DIR");
+    #[cfg(feature = "gmp")]
+    {
+        if let Some(dir) = std::env::var_os("GMP_LIB_DIR") {
+            println!("cargo:rustc-link-search=native={}", std::path::Path::new(&dir).display());
+        } else if let Err(err) = pkg_config::Config::new().cargo_metadata(true).probe("gmp") {
+            panic!(
+                "the `gmp` feature needs the GMP library, which pkg-config could not find: {err}\n\
+                 install it (`libgmp-dev` on Debian and Ubuntu, `gmp-devel` on Fedora, `gmp` on Homebrew) \
+                 or set GMP_LIB_DIR to the directory holding libgmp"
+            );
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! `modpow` through the system GMP library, for the `gmp` feature. Its
+//! assembly kernels beat portable Rust on large moduli, which matters most
+//! for primality testing.
+//!
+//! Only the handful of `mpz` functions needed are declared; `mpz_*` in the
+//! GMP manual are macros for these `__gmpz_*` symbols.
+
+use alloc::vec;
+use core::ffi::{c_int, c_void};
+
+use num_bigint::BigUint;
+
+use crate::montgomery::from_limbs;
+
+/// `__mpz_struct`, which GMP has kept unchanged since version 3.
+#[repr(C)]
+struct Mpz {
+    alloc: c_int,
+    size: c_int,
+    limbs: *mut c_void,
+}
+
+#[link(name = "gmp")]
+unsafe extern "C" {
+    fn __gmpz_init(x: *mut Mpz);
+    fn __gmpz_clear(x: *mut Mpz);
+    fn __gmpz_import(
+        rop: *mut Mpz,
+        count: usize,
+        order: c_int,
+        size: usize,
+        endian: c_int,
+        nails: usize,
+        op: *const c_void,
+    );
+    fn __gmpz_export(
+        rop: *mut c_void,
+        countp: *mut usize,
+        order: c_int,
+        size: usize,
+        endian: c_int,
+        nails: usize,
+        op: *const Mpz,
+    ) -> *mut c_void;
+    fn __gmpz_powm(rop: *mut Mpz, base: *const Mpz, exp: *const Mpz, modulus: *const Mpz);
+    fn __gmpz_sizeinbase(op: *const Mpz, base: c_int) -> usize;
+}
+
+/// An initialized `mpz_t`, cleared on drop.
+struct Integer(Mpz);
+
+/// Least significant word first, native byte order within words.
+const WORDS_LOW_FIRST: c_int = -1;
+const NATIVE_ENDIAN: c_int = 0;
+
+impl Integer {
+    fn new() -> Integer {
+        let mut value = Mpz {
+            alloc: 0,
+            size: 0,
+            limbs: core::ptr::null_mut(),
+        };
+        // SAFETY: mpz_init only writes the struct it is given.
+        unsafe { __gmpz_init(&mut value) };
+        Integer(value)
+    }
+
+    fn from_biguint(value: &BigUint) -> Integer {
+        let words = value.to_u64_digits();
+        let mut integer = Integer::new();
+        // SAFETY: `words` holds `words.len()` 8-byte words, and the target
+        // was initialized by `new`.
+        unsafe {
+            __gmpz_import(
+                &mut integer.0,
+                words.len(),
+                WORDS_LOW_FIRST,
+                8,
+                NATIVE_ENDIAN,
+                0,
+                words.as_ptr().cast(),
+            )
+        };
+        integer
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        // SAFETY: `self` is initialized.
+        let bits = unsafe { __gmpz_sizeinbase(&self.0, 2) };
+        let mut words = vec![0u64; bits.div_ceil(64)];
+        let mut count = 0;
+        // SAFETY: `words` has room for every 8-byte word of the value, which
+        // `sizeinbase` just measured; mpz_export writes `count` of them.
+        unsafe {
+            __gmpz_export(
+                words.as_mut_ptr().cast(),
+                &mut count,
+                WORDS_LOW_FIRST,
+                8,
+                NATIVE_ENDIAN,
+                0,
+                &self.0,
+            )
+        };
+        words.truncate(count);
+        from_limbs(&words)
+    }
+}
+
+impl Drop for Integer {
+    fn drop(&mut self) {
+        // SAFETY: the value was initialized by `new` and is cleared once.
+        unsafe { __gmpz_clear(&mut self.0) };
+    }
+}
+
+/// `base^exponent mod modulus` via `mpz_powm`. `modulus` must not be zero.
+pub fn modpow(base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    let (base, exponent, modulus) =
+        (Integer::from_biguint(base), Integer::from_biguint(exponent), Integer::from_biguint(modulus));
+    let mut result = Integer::new();
+    // SAFETY: all four values are initialized, and a zero modulus (which
+    // GMP would divide by) is ruled out by the caller.
+    unsafe { __gmpz_powm(&mut result.0, &base.0, &exponent.0, &modulus.0) };
+    result.to_biguint()
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use super::*;
+    use crate::montgomery::tests::{Rng, bases, exponents, moduli};
+
+    #[test]
+    fn modpow_matches_biguint() {
+        let mut rng = Rng(0x676d_7000);
+        let mut moduli = moduli(&mut rng);
+        // mpz_powm takes any non-zero modulus, not only the odd ones
+        // Montgomery form needs.
+        moduli.extend([1u32, 2, 4, 6].map(BigUint::from));
+        moduli.extend((1..=5).map(|limbs| rng.number(limbs) << 1u32 | BigUint::from(2u32)));
+        moduli.push(BigUint::from(1u32) << 128u32);
+        for modulus in &moduli {
+            for exponent in exponents(&mut rng, modulus) {
+                for base in bases(&mut rng, modulus) {
+                    assert_eq!(
+                        modpow(&base, &exponent, modulus),
+                        base.modpow(&exponent, modulus),
+                        "{base:x}^{exponent:x} mod {modulus:x}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn values_survive_the_round_trip_through_mpz() {
+        let mut rng = Rng(0x6d70_7a00);
+        for limbs in 0..=9 {
+            let value = rng.number(limbs);
+            assert_eq!(Integer::from_biguint(&value).to_biguint(), value);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! The finite-field Diffie-Hellman arithmetic behind `create-private-key`,
+//! for `no_std` targets with an allocator.
+//!
+//! Everything here is pure computation on `BigUint`s. Randomness comes from
+//! the caller as a `fill` function, so firmware can plug in its TRNG. The
+//! `create-private-key` library and command call these same functions.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::One;
+
+#[cfg(feature = "gmp")]
+mod gmp;
+pub mod montgomery;
+
+pub use montgomery::{Constants, Montgomery};
+
+/// RFC 3526 MODP group 14 (2048-bit safe prime, generator 2), in hex.
+pub const RFC3526_MODP14_PRIME_HEX: &str = concat!(
+    "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
+    "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
+    "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
+    "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
+    "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
+    "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
+    "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
+    "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
+    "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
+    "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
+    "15728E5A8AACAA68FFFFFFFFFFFFFFFF"
+);
+
+/// RFC 7919 ffdhe2048 prime (generator 2).
+pub const RFC7919_FFDHE2048_PRIME_HEX: &str = concat!(
+    "FFFFFFFFFFFFFFFFADF85458A2BB4A9AAFDC5620273D3CF1D8B9C583CE2D3695",
+    "A9E13641146433FBCC939DCE249B3EF97D2FE363630C75D8F681B202AEC4617A",
+    "D3DF1ED5D5FD65612433F51F5F066ED0856365553DED1AF3B557135E7F57C935",
+    "984F0C70E0E68B77E2A689DAF3EFE8721DF158A136ADE73530ACCA4F483A797A",
+    "BC0AB182B324FB61D108A94BB2C8E3FBB96ADAB760D7F4681D4F42A3DE394DF4",
+    "AE56EDE76372BB190B07A7C8EE0A6D709E02FCE1CDF7E2ECC03404CD28342F61",
+    "9172FE9CE98583FF8E4F1232EEF28183C3FE3B1B4C6FAD733BB5FCBC2EC22005",
+    "C58EF1837D1683B2C6F34A26C1B2EFFA886B423861285C97FFFFFFFFFFFFFFFF"
+);
+
+/// RFC 5114 section 2.3: 2048-bit MODP group with a 256-bit prime order
+/// subgroup.
+pub const RFC5114_2048_256_PRIME_HEX: &str = concat!(
+    "87A8E61DB4B6663CFFBBD19C651959998CEEF608660DD0F25D2CEED4435E3B00",
+    "E00DF8F1D61957D4FAF7DF4561B2AA3016C3D91134096FAA3BF4296D830E9A7C",
+    "209E0C6497517ABD5A8A9D306BCF67ED91F9E6725B4758C022E0B1EF4275BF7B",
+    "6C5BFC11D45F9088B941F54EB1E59BB8BC39A0BF12307F5C4FDB70C581B23F76",
+    "B63ACAE1CAA6B7902D52526735488A0EF13C6D9A51BFA4AB3AD8347796524D8E",
+    "F6A167B5A41825D967E144E5140564251CCACB83E6B486F6B3CA3F7971506026",
+    "C0B857F689962856DED4010ABD0BE621C3A3960A54E710C375F26375D7014103",
+    "A4B54330C198AF126116D2276E11715F693877FAD7EF09CADB094AE91E1A1597"
+);
+
+/// The published primes as limbs with their Montgomery constants, all
+/// worked out by the compiler.
+pub static RFC3526_MODP14: Constants<32> = Constants::from_hex(RFC3526_MODP14_PRIME_HEX);
+pub static RFC7919_FFDHE2048: Constants<32> = Constants::from_hex(RFC7919_FFDHE2048_PRIME_HEX);
+pub static RFC5114_2048_256: Constants<32> = Constants::from_hex(RFC5114_2048_256_PRIME_HEX);
+
+/// What [`Montgomery::new`] looks a modulus up in before computing anything.
+static PUBLISHED_GROUPS: [&Constants<32>; 3] = [&RFC3526_MODP14, &RFC7919_FFDHE2048, &RFC5114_2048_256];
+
+/// Smallest private exponent size accepted by [`check_private_bits`]: twice
+/// the 112-bit security level of a 2048-bit group (NIST SP 800-56A).
+pub const MIN_PRIVATE_BITS: u64 = 224;
+
+/// Why a value was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    PrimeTooSmall,
+    PrimeEven,
+    GeneratorTooSmall,
+    GeneratorTooLarge,
+    PrivateBitsTooSmall,
+    PrivateBitsTooLarge,
+    PrivateKeyOutOfRange,
+    PeerOutOfRange,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rejection::PrivateBitsTooSmall => return write!(f, "private_bits must be at least {MIN_PRIVATE_BITS}"),
+            Rejection::PrimeTooSmall => "prime modulus must be greater than 3",
+            Rejection::PrimeEven => "prime modulus must be odd",
+            Rejection::GeneratorTooSmall => "generator must be greater than 1",
+            Rejection::GeneratorTooLarge => "generator must be less than the prime modulus",
+            Rejection::PrivateBitsTooLarge => "private_bits must be less than the size of the prime",
+            Rejection::PrivateKeyOutOfRange => "private key is out of range for the group",
+            Rejection::PeerOutOfRange => "peer public key is out of range",
+        })
+    }
+}
+
+/// Basic sanity checks every prime/generator pair must pass.
+pub fn validate(prime: &BigUint, generator: &BigUint) -> Result<(), Rejection> {
+    if *prime <= BigUint::from(3u32) {
+        return Err(Rejection::PrimeTooSmall);
+    }
+    if prime.is_even() {
+        return Err(Rejection::PrimeEven);
+    }
+    if *generator <= BigUint::one() {
+        return Err(Rejection::GeneratorTooSmall);
+    }
+    if generator >= prime {
+        return Err(Rejection::GeneratorTooLarge);
+    }
+    Ok(())
+}
+
+/// Checks a short-exponent size against the group.
+pub fn check_private_bits(prime: &BigUint, bits: u64) -> Result<(), Rejection> {
+    if bits < MIN_PRIVATE_BITS {
+        return Err(Rejection::PrivateBitsTooSmall);
+    }
+    if bits >= prime.bits() {
+        return Err(Rejection::PrivateBitsTooLarge);
+    }
+    Ok(())
+}
+
+/// Private exponents must lie in `[2, p - 2]`.
+pub fn check_private_key(prime: &BigUint, private_key: &BigUint) -> Result<(), Rejection> {
+    if *private_key <= BigUint::one() || *private_key >= prime - 1u32 {
+        return Err(Rejection::PrivateKeyOutOfRange);
+    }
+    Ok(())
+}
+
+/// A peer's public value must lie in `[2, p - 2]`; 0, 1 and p - 1 would
+/// force the shared secret into a tiny subgroup.
+pub fn check_peer(prime: &BigUint, peer: &BigUint) -> Result<(), Rejection> {
+    if *peer <= BigUint::one() || *peer >= prime - 1u32 {
+        return Err(Rejection::PeerOutOfRange);
+    }
+    Ok(())
+}
+
+/// A uniform integer in `[low, high)`, by rejection sampling over the bytes
+/// `fill` provides. Panics if the range is empty.
+pub fn random_range<E>(
+    low: &BigUint,
+    high: &BigUint,
+    mut fill: impl FnMut(&mut [u8]) -> Result<(), E>,
+) -> Result<BigUint, E> {
+    assert!(low < high, "empty random range");
+    let span = high - low;
+    let bits = (span.clone() - 1u32).bits();
+    let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+    let excess = bytes.len() as u64 * 8 - bits;
+    loop {
+        fill(&mut bytes)?;
+        if let Some(first) = bytes.first_mut() {
+            *first &= 0xFF >> excess;
+        }
+        let candidate = BigUint::from_bytes_be(&bytes);
+        if candidate < span {
+            return Ok(candidate + low);
+        }
+    }
+}
+
+/// Draws a private exponent from `[2, p - 2]`, or from `[2, 2^bits)` for a
+/// short exponent. `prime` must pass [`validate`] and `private_bits`
+/// [`check_private_bits`].
+pub fn generate_private_key<E>(
+    prime: &BigUint,
+    private_bits: Option<u64>,
+    fill: impl FnMut(&mut [u8]) -> Result<(), E>,
+) -> Result<BigUint, E> {
+    let upper = match private_bits {
+        Some(bits) => BigUint::one() << bits,
+        None => prime - BigUint::one(),
+    };
+    random_range(&BigUint::from(2u32), &upper, fill)
+}
+
+/// `g^x mod p`.
+pub fn public_key(prime: &BigUint, generator: &BigUint, private_key: &BigUint) -> BigUint {
+    montgomery::modpow(generator, private_key, prime)
+}
+
+/// `peer^x mod p`, left-padded to the byte length of the prime, after
+/// checking the peer value.
+pub fn shared_secret(prime: &BigUint, private_key: &BigUint, peer: &BigUint) -> Result<Vec<u8>, Rejection> {
+    check_peer(prime, peer)?;
+    let width = prime.to_bytes_be().len();
+    let secret = montgomery::modpow(peer, private_key, prime).to_bytes_be();
+    let mut padded = vec![0u8; width - secret.len()];
+    padded.extend_from_slice(&secret);
+    Ok(padded)
+}
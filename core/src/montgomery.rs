@@ -0,0 +1,700 @@
+//! Modular exponentiation in Montgomery form, for odd moduli.
+//!
+//! Numbers are kept as `n` 64-bit limbs times `R = 2^(64n)` mod `m`, so a
+//! modular multiplication is one column-by-column multiply-and-reduce pass
+//! with no division and no allocation, and squarings share their cross
+//! products. Exponents are walked with a sliding window over a table of odd
+//! powers of the base.
+//!
+//! The published groups' constants are worked out at compile time (see
+//! [`Constants`]), so [`Montgomery::new`] on one of their primes costs no
+//! division.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use num_bigint::BigUint;
+
+/// An odd modulus with its Montgomery constants, reusable across
+/// exponentiations.
+#[derive(Clone, Debug)]
+pub struct Montgomery {
+    /// The modulus, least significant limb first.
+    modulus: Vec<u64>,
+    /// `-m^-1 mod 2^64`.
+    m_inv: u64,
+    /// `R^2 mod m`, which takes numbers into Montgomery form.
+    r_squared: Vec<u64>,
+    /// `R mod m`: 1 in Montgomery form.
+    one: Vec<u64>,
+}
+
+impl Montgomery {
+    /// `None` unless `modulus` is odd and greater than 1.
+    pub fn new(modulus: &BigUint) -> Option<Montgomery> {
+        if !modulus.bit(0) || modulus.bits() < 2 {
+            return None;
+        }
+        let limbs = modulus.to_u64_digits();
+        if let Some(constants) = crate::PUBLISHED_GROUPS.iter().find(|constants| constants.modulus == *limbs) {
+            return Some(constants.montgomery());
+        }
+        let r = BigUint::from(1u32) << (64 * limbs.len());
+        let one = &r % modulus;
+        let r_squared = (&one * &one) % modulus;
+        Some(Montgomery {
+            m_inv: inverse(limbs[0]).wrapping_neg(),
+            r_squared: padded(&r_squared, limbs.len()),
+            one: padded(&one, limbs.len()),
+            modulus: limbs,
+        })
+    }
+
+    /// The modulus this was built for.
+    pub fn modulus(&self) -> BigUint {
+        from_limbs(&self.modulus)
+    }
+
+    /// `base^exponent mod m`.
+    pub fn pow(&self, base: &BigUint, exponent: &BigUint) -> BigUint {
+        let mut scratch = self.scratch();
+        let base = self.enter(base, &mut scratch);
+        let table = self.odd_powers(&base, window(exponent.bits()), &mut scratch);
+        let result = self.pow_with(&table, exponent, &mut scratch);
+        self.leave(&result, &mut scratch)
+    }
+
+    /// `base^(2k+1)` in Montgomery form for `k < 2^(width-1)`.
+    fn odd_powers(&self, base: &[u64], width: u32, scratch: &mut [u64]) -> Vec<Vec<u64>> {
+        let mut square = vec![0; self.modulus.len()];
+        self.square(base, &mut square, scratch);
+        let mut table = Vec::with_capacity(1 << (width - 1));
+        table.push(base.to_vec());
+        for k in 1..1usize << (width - 1) {
+            let mut next = vec![0; self.modulus.len()];
+            self.mul(&table[k - 1], &square, &mut next, scratch);
+            table.push(next);
+        }
+        table
+    }
+
+    /// Left-to-right sliding window over `exponent`, with `table` from
+    /// [`Montgomery::odd_powers`]. The result stays in Montgomery form.
+    fn pow_with(&self, table: &[Vec<u64>], exponent: &BigUint, scratch: &mut [u64]) -> Vec<u64> {
+        let width = table.len().trailing_zeros() + 1;
+        let mut acc = self.one.clone();
+        let mut tmp = vec![0; self.modulus.len()];
+        // Nothing to square until the first window is in.
+        let mut started = false;
+        let mut i = exponent.bits();
+        while i > 0 {
+            if !exponent.bit(i - 1) {
+                if started {
+                    self.square(&acc, &mut tmp, scratch);
+                    core::mem::swap(&mut acc, &mut tmp);
+                }
+                i -= 1;
+                continue;
+            }
+            // The window runs from bit i - 1 down to the lowest set bit in reach.
+            let mut low = i.saturating_sub(u64::from(width));
+            while !exponent.bit(low) {
+                low += 1;
+            }
+            let mut value = 0usize;
+            for bit in (low..i).rev() {
+                value = value << 1 | usize::from(exponent.bit(bit));
+            }
+            if started {
+                for _ in low..i {
+                    self.square(&acc, &mut tmp, scratch);
+                    core::mem::swap(&mut acc, &mut tmp);
+                }
+                self.mul(&acc, &table[value >> 1], &mut tmp, scratch);
+                core::mem::swap(&mut acc, &mut tmp);
+            } else {
+                acc.copy_from_slice(&table[value >> 1]);
+                started = true;
+            }
+            i = low;
+        }
+        acc
+    }
+
+    fn scratch(&self) -> Vec<u64> {
+        vec![0; self.modulus.len()]
+    }
+
+    /// Takes `value` into Montgomery form, reducing it first if needed.
+    fn enter(&self, value: &BigUint, scratch: &mut [u64]) -> Vec<u64> {
+        let modulus = self.modulus();
+        let reduced = if *value >= modulus {
+            padded(&(value % modulus), self.modulus.len())
+        } else {
+            padded(value, self.modulus.len())
+        };
+        let mut out = vec![0; self.modulus.len()];
+        self.mul(&reduced, &self.r_squared, &mut out, scratch);
+        out
+    }
+
+    /// Takes `value` out of Montgomery form.
+    fn leave(&self, value: &[u64], scratch: &mut [u64]) -> BigUint {
+        let mut unit = vec![0; self.modulus.len()];
+        unit[0] = 1;
+        let mut out = vec![0; self.modulus.len()];
+        self.mul(value, &unit, &mut out, scratch);
+        from_limbs(&out)
+    }
+
+    /// `out = a * b / R mod m`, for `a` and `b` below `m`, by product
+    /// scanning (the FIPS method): column by column, with `q`, the multiples
+    /// of `m` that clear the low columns, found on the way into `scratch`.
+    fn mul(&self, a: &[u64], b: &[u64], out: &mut [u64], scratch: &mut [u64]) {
+        let n = self.modulus.len();
+        let m = &self.modulus;
+        let q = &mut scratch[..n];
+        let mut acc = Accumulator::default();
+        for i in 0..n {
+            acc.add_products(&a[..i], &b[1..=i]);
+            acc.add_products(&q[..i], &m[1..=i]);
+            acc.add(a[i], b[0]);
+            q[i] = acc.low().wrapping_mul(self.m_inv);
+            acc.add(q[i], m[0]);
+            acc.shift();
+        }
+        for i in n..2 * n {
+            acc.add_products(&a[i - n + 1..], &b[i - n + 1..]);
+            acc.add_products(&q[i - n + 1..], &m[i - n + 1..]);
+            out[i - n] = acc.low();
+            acc.shift();
+        }
+        if acc.low() != 0 || !less_than(out, m) {
+            subtract(out, m);
+        }
+    }
+
+    /// `out = a^2 / R mod m`, like [`Montgomery::mul`] but taking each
+    /// cross product once and doubling it: about a quarter fewer
+    /// multiplications.
+    fn square(&self, a: &[u64], out: &mut [u64], scratch: &mut [u64]) {
+        let n = self.modulus.len();
+        let m = &self.modulus;
+        let q = &mut scratch[..n];
+        let mut acc = Accumulator::default();
+        for i in 0..2 * n {
+            let first = (i + 1).saturating_sub(n);
+            let mut cross = Accumulator::default();
+            let half = i.div_ceil(2);
+            cross.add_products(&a[first..half], &a[i + 1 - half..=i - first]);
+            acc.add_twice(&cross);
+            if i % 2 == 0 {
+                acc.add(a[i / 2], a[i / 2]);
+            }
+            let last = i.min(n);
+            acc.add_products(&q[first..last], &m[i + 1 - last..=i - first]);
+            if i < n {
+                q[i] = acc.low().wrapping_mul(self.m_inv);
+                acc.add(q[i], m[0]);
+            } else {
+                out[i - n] = acc.low();
+            }
+            acc.shift();
+        }
+        if acc.low() != 0 || !less_than(out, m) {
+            subtract(out, m);
+        }
+    }
+}
+
+/// A modulus of `n` limbs with its Montgomery constants, computed by a
+/// `const fn` so that a `static` holds them ready in the binary.
+#[derive(Debug)]
+pub struct Constants<const N: usize> {
+    modulus: [u64; N],
+    m_inv: u64,
+    r_squared: [u64; N],
+    one: [u64; N],
+}
+
+impl<const N: usize> Constants<N> {
+    /// Parses `hex`, which must be exactly `16 * N` digits of an odd number
+    /// with its top bit set; anything else fails to compile in a `static`.
+    pub const fn from_hex(hex: &str) -> Constants<N> {
+        let digits = hex.as_bytes();
+        assert!(digits.len() == 16 * N, "a precompiled modulus fills exactly N limbs");
+        let mut modulus = [0u64; N];
+        let mut i = 0;
+        while i < digits.len() {
+            let digit = match digits[i] {
+                digit @ b'0'..=b'9' => digit - b'0',
+                digit @ b'a'..=b'f' => digit - b'a' + 10,
+                digit @ b'A'..=b'F' => digit - b'A' + 10,
+                _ => panic!("a precompiled modulus is hex"),
+            };
+            let position = digits.len() - 1 - i;
+            modulus[position / 16] |= (digit as u64) << (4 * (position % 16));
+            i += 1;
+        }
+        assert!(modulus[0] & 1 == 1 && modulus[N - 1] >> 63 == 1, "a precompiled modulus is odd and full-size");
+
+        // With the top bit set, m < R < 2m, so R mod m is R - m; doubling it
+        // 64n times (subtracting m whenever it passes m) gives R^2 mod m.
+        let mut one = [0u64; N];
+        let mut borrow = false;
+        let mut i = 0;
+        while i < N {
+            let (diff, first) = 0u64.overflowing_sub(modulus[i]);
+            let (diff, second) = diff.overflowing_sub(borrow as u64);
+            one[i] = diff;
+            borrow = first || second;
+            i += 1;
+        }
+        let mut r_squared = one;
+        let mut doubling = 0;
+        while doubling < 64 * N {
+            let carry = r_squared[N - 1] >> 63 == 1;
+            let mut i = N - 1;
+            while i > 0 {
+                r_squared[i] = r_squared[i] << 1 | r_squared[i - 1] >> 63;
+                i -= 1;
+            }
+            r_squared[0] <<= 1;
+            if carry || !const_less_than(&r_squared, &modulus) {
+                let mut borrow = false;
+                let mut i = 0;
+                while i < N {
+                    let (diff, first) = r_squared[i].overflowing_sub(modulus[i]);
+                    let (diff, second) = diff.overflowing_sub(borrow as u64);
+                    r_squared[i] = diff;
+                    borrow = first || second;
+                    i += 1;
+                }
+            }
+            doubling += 1;
+        }
+        Constants {
+            modulus,
+            m_inv: inverse(modulus[0]).wrapping_neg(),
+            r_squared,
+            one,
+        }
+    }
+
+    /// The modulus, without parsing anything.
+    pub fn modulus(&self) -> BigUint {
+        from_limbs(&self.modulus)
+    }
+
+    /// A [`Montgomery`] context for the modulus, copying the constants.
+    pub fn montgomery(&self) -> Montgomery {
+        Montgomery {
+            modulus: self.modulus.to_vec(),
+            m_inv: self.m_inv,
+            r_squared: self.r_squared.to_vec(),
+            one: self.one.to_vec(),
+        }
+    }
+}
+
+/// Precomputed powers of one base for many exponentiations with it: the
+/// exponent is cut into `width`-bit digits, and window `i` of the table
+/// holds `base^(d * 2^(width * i))` for every nonzero digit `d`. Each power
+/// is then one multiplication per digit and no squarings.
+#[derive(Clone, Debug)]
+pub struct FixedBase {
+    montgomery: Montgomery,
+    base: BigUint,
+    width: u32,
+    /// Exponents up to this many bits come from the table.
+    bits: u64,
+    /// `windows * (2^width - 1)` entries of `n` limbs, window by window.
+    table: Vec<u64>,
+}
+
+/// Largest table [`FixedBase::for_uses`] builds.
+const MAX_TABLE_BYTES: u64 = 16 << 20;
+
+impl FixedBase {
+    /// A table for about `uses` exponentiations of `base` with exponents
+    /// of at most `bits` bits, or `None` when building it would cost more
+    /// than the sliding window it saves, or the modulus is even.
+    pub fn for_uses(modulus: &BigUint, base: &BigUint, bits: u64, uses: u64) -> Option<FixedBase> {
+        let montgomery = Montgomery::new(modulus)?;
+        let limbs = montgomery.modulus.len() as u64;
+        // In multiplications, counting a squaring as one.
+        let sliding = uses.saturating_mul(bits + bits / 6);
+        let (cost, width) = (1..=8u32)
+            .filter(|&width| bits.div_ceil(u64::from(width)) * ((1 << width) - 1) * limbs * 8 <= MAX_TABLE_BYTES)
+            .map(|width| (bits.div_ceil(u64::from(width)) * ((1 << width) - 1 + uses), width))
+            .min()?;
+        if cost >= sliding {
+            return None;
+        }
+        Some(FixedBase::new(montgomery, base, width, bits))
+    }
+
+    fn new(montgomery: Montgomery, base: &BigUint, width: u32, bits: u64) -> FixedBase {
+        let n = montgomery.modulus.len();
+        let digits = (1usize << width) - 1;
+        let windows = bits.div_ceil(u64::from(width)) as usize;
+        let mut scratch = montgomery.scratch();
+        let mut table = vec![0; windows * digits * n];
+        // base^(2^(width * i)), for the window being filled.
+        let mut start = montgomery.enter(base, &mut scratch);
+        for window in table.chunks_exact_mut(digits * n) {
+            window[..n].copy_from_slice(&start);
+            for d in 1..digits {
+                let (filled, rest) = window.split_at_mut(d * n);
+                montgomery.mul(&filled[(d - 1) * n..], &start, &mut rest[..n], &mut scratch);
+            }
+            let mut next = vec![0; n];
+            montgomery.mul(&window[(digits - 1) * n..], &start, &mut next, &mut scratch);
+            start = next;
+        }
+        FixedBase {
+            montgomery,
+            base: base.clone(),
+            width,
+            bits,
+            table,
+        }
+    }
+
+    /// The modulus and base the table was built for.
+    pub fn params(&self) -> (BigUint, &BigUint) {
+        (self.montgomery.modulus(), &self.base)
+    }
+
+    /// `base^exponent mod m`; exponents longer than the table fall back to
+    /// [`Montgomery::pow`].
+    pub fn pow(&self, exponent: &BigUint) -> BigUint {
+        if exponent.bits() > self.bits {
+            return self.montgomery.pow(&self.base, exponent);
+        }
+        let n = self.montgomery.modulus.len();
+        let digits = (1usize << self.width) - 1;
+        let mut scratch = self.montgomery.scratch();
+        let mut acc = self.montgomery.one.clone();
+        let mut tmp = vec![0; n];
+        for (index, window) in self.table.chunks_exact(digits * n).enumerate() {
+            let low = index as u64 * u64::from(self.width);
+            let digit = (0..u64::from(self.width))
+                .filter(|&bit| exponent.bit(low + bit))
+                .fold(0usize, |digit, bit| digit | 1 << bit);
+            if digit != 0 {
+                self.montgomery.mul(&acc, &window[(digit - 1) * n..digit * n], &mut tmp, &mut scratch);
+                core::mem::swap(&mut acc, &mut tmp);
+            }
+        }
+        self.montgomery.leave(&acc, &mut scratch)
+    }
+}
+
+/// `base^exponent mod modulus`: in Montgomery form for odd moduli, where
+/// every Diffie-Hellman prime falls, and by `BigUint::modpow` otherwise.
+/// With the `gmp` feature, GMP's `mpz_powm` does it instead.
+pub fn modpow(base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    #[cfg(feature = "gmp")]
+    if modulus.bits() > 0 {
+        return crate::gmp::modpow(base, exponent, modulus);
+    }
+    match Montgomery::new(modulus) {
+        Some(montgomery) => montgomery.pow(base, exponent),
+        None => base.modpow(exponent, modulus),
+    }
+}
+
+/// Window width for an exponent of `bits` bits, trading table size
+/// against multiplications.
+fn window(bits: u64) -> u32 {
+    match bits {
+        0..=23 => 1,
+        24..=79 => 3,
+        80..=239 => 4,
+        240..=671 => 5,
+        _ => 6,
+    }
+}
+
+/// `x^-1 mod 2^64` for odd `x`, by Newton's iteration.
+const fn inverse(x: u64) -> u64 {
+    let mut y = x;
+    // Each step doubles the correct low bits; x is its own inverse mod 8.
+    let mut step = 0;
+    while step < 5 {
+        y = y.wrapping_mul(2u64.wrapping_sub(x.wrapping_mul(y)));
+        step += 1;
+    }
+    y
+}
+
+/// A three-limb column sum.
+#[derive(Default)]
+struct Accumulator {
+    low: u128,
+    high: u64,
+}
+
+impl Accumulator {
+    fn add(&mut self, a: u64, b: u64) {
+        let (sum, overflow) = self.low.overflowing_add(u128::from(a) * u128::from(b));
+        self.low = sum;
+        self.high += u64::from(overflow);
+    }
+
+    /// Adds `x[j] * y[len - 1 - j]` for every `j`: one column's products,
+    /// with `y` running downwards.
+    fn add_products(&mut self, x: &[u64], y: &[u64]) {
+        // Two independent sums keep the multiplier busy while each addition
+        // waits for its carry.
+        let mut other = Accumulator::default();
+        let (x_pairs, y_pairs) = (x.chunks_exact(2), y.rchunks_exact(2));
+        if let (&[x], &[y]) = (x_pairs.remainder(), y_pairs.remainder()) {
+            self.add(x, y);
+        }
+        for (x, y) in x_pairs.zip(y_pairs) {
+            self.add(x[0], y[1]);
+            other.add(x[1], y[0]);
+        }
+        self.add_sum(&other);
+    }
+
+    fn low(&self) -> u64 {
+        self.low as u64
+    }
+
+    fn add_sum(&mut self, other: &Accumulator) {
+        let (sum, overflow) = self.low.overflowing_add(other.low);
+        self.low = sum;
+        self.high += other.high + u64::from(overflow);
+    }
+
+    /// Adds `2 * other`.
+    fn add_twice(&mut self, other: &Accumulator) {
+        let (sum, overflow) = self.low.overflowing_add(other.low << 1);
+        self.low = sum;
+        self.high += (other.high << 1 | (other.low >> 127) as u64) + u64::from(overflow);
+    }
+
+    /// Moves on to the next column.
+    fn shift(&mut self) {
+        self.low = self.low >> 64 | u128::from(self.high) << 64;
+        self.high = 0;
+    }
+}
+
+/// [`less_than`] for [`Constants::from_hex`], which cannot use iterators.
+const fn const_less_than(a: &[u64], b: &[u64]) -> bool {
+    let mut i = a.len();
+    while i > 0 {
+        i -= 1;
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+    }
+    false
+}
+
+fn less_than(a: &[u64], b: &[u64]) -> bool {
+    for (x, y) in a.iter().zip(b).rev() {
+        if x != y {
+            return x < y;
+        }
+    }
+    false
+}
+
+/// `a -= b`, wrapping at `2^(64n)`.
+fn subtract(a: &mut [u64], b: &[u64]) {
+    let mut borrow = false;
+    for (x, &y) in a.iter_mut().zip(b) {
+        let (diff, first) = x.overflowing_sub(y);
+        let (diff, second) = diff.overflowing_sub(u64::from(borrow));
+        *x = diff;
+        borrow = first || second;
+    }
+}
+
+fn padded(value: &BigUint, len: usize) -> Vec<u64> {
+    let mut limbs = value.to_u64_digits();
+    limbs.resize(len, 0);
+    limbs
+}
+
+pub(crate) fn from_limbs(limbs: &[u64]) -> BigUint {
+    BigUint::from_slice(&limbs.iter().flat_map(|&limb| [limb as u32, (limb >> 32) as u32]).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use alloc::vec::Vec;
+
+    use num_bigint::BigUint;
+
+    use super::*;
+
+    /// SplitMix64, so the cases are the same on every run.
+    pub(crate) struct Rng(pub(crate) u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        pub(crate) fn number(&mut self, limbs: usize) -> BigUint {
+            from_limbs(&(0..limbs).map(|_| self.next()).collect::<Vec<_>>())
+        }
+
+        /// An odd modulus of exactly `limbs` limbs.
+        pub(crate) fn modulus(&mut self, limbs: usize) -> BigUint {
+            let mut words: Vec<u64> = (0..limbs).map(|_| self.next()).collect();
+            words[0] |= 1;
+            words[limbs - 1] |= 1 << 63;
+            from_limbs(&words)
+        }
+    }
+
+    /// Odd moduli from one to nine limbs, random and at the edges of their
+    /// limb count, plus the published groups' primes.
+    pub(crate) fn moduli(rng: &mut Rng) -> Vec<BigUint> {
+        let mut moduli = Vec::new();
+        for limbs in 1..=9 {
+            moduli.push(rng.modulus(limbs));
+            moduli.push(rng.modulus(limbs) >> 1 | BigUint::from(1u32));
+            moduli.push((BigUint::from(1u32) << (64 * limbs)) - 1u32);
+            if limbs > 1 {
+                moduli.push((BigUint::from(1u32) << (64 * limbs - 64)) + 1u32);
+            }
+        }
+        moduli.push(BigUint::from(3u32));
+        moduli.extend(crate::PUBLISHED_GROUPS.iter().map(|constants| constants.modulus()));
+        moduli
+    }
+
+    /// Exponents 0 and 1, small ones, and random ones as long as the
+    /// modulus and twice as long. Past four limbs they stop growing, which
+    /// is still longer than a private exponent and keeps a debug build's
+    /// `BigUint::modpow` fast enough to compare against.
+    pub(crate) fn exponents(rng: &mut Rng, modulus: &BigUint) -> Vec<BigUint> {
+        let limbs = modulus.to_u64_digits().len().min(4);
+        let mut exponents = Vec::from([0u32, 1, 2, 3, 65537].map(BigUint::from));
+        exponents.push(rng.number(1) >> 40);
+        exponents.push(rng.number(limbs));
+        exponents.push(rng.number(2 * limbs));
+        exponents
+    }
+
+    /// Bases below the modulus, and at and above it.
+    pub(crate) fn bases(rng: &mut Rng, modulus: &BigUint) -> Vec<BigUint> {
+        let limbs = modulus.to_u64_digits().len();
+        Vec::from([
+            BigUint::from(0u32),
+            BigUint::from(1u32),
+            BigUint::from(2u32),
+            rng.number(limbs) % modulus,
+            modulus - 1u32,
+            modulus.clone(),
+            modulus + 1u32,
+            rng.number(2 * limbs + 1),
+        ])
+    }
+
+    #[test]
+    fn modpow_matches_biguint() {
+        let mut rng = Rng(1);
+        for modulus in moduli(&mut rng) {
+            let montgomery = Montgomery::new(&modulus).unwrap();
+            for base in bases(&mut rng, &modulus) {
+                for exponent in exponents(&mut rng, &modulus) {
+                    let expected = base.modpow(&exponent, &modulus);
+                    assert_eq!(montgomery.pow(&base, &exponent), expected, "{base}^{exponent} mod {modulus}");
+                    assert_eq!(modpow(&base, &exponent, &modulus), expected, "{base}^{exponent} mod {modulus}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn modpow_falls_back_for_even_and_tiny_moduli() {
+        let mut rng = Rng(2);
+        assert!(Montgomery::new(&BigUint::from(1u32)).is_none());
+        for modulus in [BigUint::from(1u32), BigUint::from(2u32), rng.number(4) << 1u32] {
+            assert!(Montgomery::new(&modulus).is_none() || modulus == BigUint::from(1u32));
+            for base in bases(&mut rng, &modulus) {
+                for exponent in exponents(&mut rng, &modulus) {
+                    assert_eq!(modpow(&base, &exponent, &modulus), base.modpow(&exponent, &modulus));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_base_matches_biguint() {
+        let mut rng = Rng(3);
+        for modulus in moduli(&mut rng) {
+            // Tables as long as a private exponent; longer exponents take
+            // the fallback. Wide tables over large moduli are slow to build
+            // in a debug build, and the narrow ones run the same code.
+            let bits = modulus.bits().min(320);
+            let widths: &[u32] = match modulus.bits() {
+                0..=128 => &[1, 3, 5, 8],
+                129..=256 => &[1, 3, 5],
+                _ => &[1, 4],
+            };
+            // Every bit set, the most digits a window can hold.
+            let all_ones = (BigUint::from(1u32) << bits) - 1u32;
+            for base in bases(&mut rng, &modulus) {
+                let cases: Vec<_> = exponents(&mut rng, &modulus)
+                    .into_iter()
+                    .chain([all_ones.clone()])
+                    .map(|exponent| (base.modpow(&exponent, &modulus), exponent))
+                    .collect();
+                for &width in widths {
+                    let table = FixedBase::new(Montgomery::new(&modulus).unwrap(), &base, width, bits);
+                    for (expected, exponent) in &cases {
+                        assert_eq!(table.pow(exponent), *expected, "{base}^{exponent} mod {modulus}, width {width}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_base_for_uses_matches_biguint() {
+        let mut rng = Rng(4);
+        let modulus = crate::RFC3526_MODP14.modulus();
+        let table = FixedBase::for_uses(&modulus, &BigUint::from(2u32), 256, 1000).unwrap();
+        for _ in 0..20 {
+            let exponent = rng.number(4);
+            assert_eq!(table.pow(&exponent), BigUint::from(2u32).modpow(&exponent, &modulus));
+        }
+        assert!(FixedBase::for_uses(&modulus, &BigUint::from(2u32), 256, 1).is_none());
+    }
+
+    #[test]
+    fn published_constants_match_the_computed_ones() {
+        for constants in crate::PUBLISHED_GROUPS {
+            let modulus = constants.modulus();
+            let limbs = modulus.to_u64_digits();
+            let r = BigUint::from(1u32) << (64 * limbs.len());
+            let one = &r % &modulus;
+            let montgomery = constants.montgomery();
+            assert_eq!(montgomery.m_inv, inverse(limbs[0]).wrapping_neg());
+            assert_eq!(montgomery.one, padded(&one, limbs.len()));
+            assert_eq!(montgomery.r_squared, padded(&((&one * &one) % &modulus), limbs.len()));
+        }
+    }
+
+    #[test]
+    fn inverse_inverts_odd_words() {
+        let mut rng = Rng(5);
+        for x in [1, 3, u64::MAX].into_iter().chain((0..100).map(|_| rng.next() | 1)) {
+            assert_eq!(x.wrapping_mul(inverse(x)), 1);
+        }
+    }
+}
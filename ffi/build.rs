@@ -0,0 +1,13 @@
+//! Regenerates `include/create_private_key.h` from the `extern "C"`
+//! functions in `src/lib.rs`, so the header never drifts from the library.
+
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let crate_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?);
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))?;
+    cbindgen::generate_with_config(&crate_dir, config)?.write_to_file(crate_dir.join("include/create_private_key.h"));
+    Ok(())
+}
@@ -0,0 +1,76 @@
+/* Generated by cbindgen from ffi/src/lib.rs; do not edit. */
+
+#ifndef CREATE_PRIVATE_KEY_H
+#define CREATE_PRIVATE_KEY_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success.
+ */
+#define CPK_OK 0
+
+/**
+ * Any other failure, including invalid arguments.
+ */
+#define CPK_ERR_OTHER 1
+
+/**
+ * Input could not be parsed: PEM, DER or a number.
+ */
+#define CPK_ERR_PARSE 2
+
+/**
+ * Parameters or keys failed validation.
+ */
+#define CPK_ERR_VALIDATION 3
+
+/**
+ * The random number generator failed.
+ */
+#define CPK_ERR_RNG 5
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Generates a key pair in `group` (e.g. `"modp14"`; NULL means group 14)
+ * and stores its PEM encodings in `*private_key_pem` and `*public_key_pem`.
+ *
+ * # Safety
+ *
+ * `group` is NULL or a NUL-terminated string. `private_key_pem` and
+ * `public_key_pem` point to writable `char *`s. `error` is NULL or does.
+ */
+int cpk_generate(const char *group, char **private_key_pem, char **public_key_pem, char **error);
+
+/**
+ * Parses a PKCS#8 private key and stores its public key PEM in
+ * `*public_key_pem`.
+ *
+ * # Safety
+ *
+ * `private_key_pem` is a NUL-terminated string. `public_key_pem` points to
+ * a writable `char *`. `error` is NULL or does.
+ */
+int cpk_derive_public(const char *private_key_pem, char **public_key_pem, char **error);
+
+/**
+ * Releases a string returned by this library. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `string` is NULL or came from this library and has not been freed.
+ */
+void cpk_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CREATE_PRIVATE_KEY_H */
@@ -0,0 +1,166 @@
+//! C ABI for provisioning daemons that link the key generation directly.
+//!
+//! Keys cross the boundary as NUL-terminated PEM strings, private keys as
+//! PKCS#8 `PRIVATE KEY` and public keys as `PUBLIC KEY`, exactly as the
+//! command writes them. Every string handed out is owned by the caller and
+//! must be released with [`cpk_free`]. Functions return one of the `CPK_*`
+//! status codes, which are the command's exit codes, and on failure store a
+//! message in `*error` when `error` is not NULL (it is set to NULL on
+//! success).
+//!
+//! ```c
+//! char *private_pem, *public_pem, *error;
+//! if (cpk_generate("modp14", &private_pem, &public_pem, &error) != CPK_OK) {
+//!     fprintf(stderr, "%s\n", error);
+//!     cpk_free(error);
+//! }
+//! ```
+
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use create_private_key::{DhGroup, DhParams, Error, KeyPair, generate_keypair};
+
+/// Success.
+pub const CPK_OK: c_int = 0;
+/// Any other failure, including invalid arguments.
+pub const CPK_ERR_OTHER: c_int = 1;
+/// Input could not be parsed: PEM, DER or a number.
+pub const CPK_ERR_PARSE: c_int = 2;
+/// Parameters or keys failed validation.
+pub const CPK_ERR_VALIDATION: c_int = 3;
+/// The random number generator failed.
+pub const CPK_ERR_RNG: c_int = 5;
+
+/// Generates a key pair in `group` (e.g. `"modp14"`; NULL means group 14)
+/// and stores its PEM encodings in `*private_key_pem` and `*public_key_pem`.
+///
+/// # Safety
+///
+/// `group` is NULL or a NUL-terminated string. `private_key_pem` and
+/// `public_key_pem` point to writable `char *`s. `error` is NULL or does.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cpk_generate(
+    group: *const c_char,
+    private_key_pem: *mut *mut c_char,
+    public_key_pem: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    guard(error, || {
+        if private_key_pem.is_null() || public_key_pem.is_null() {
+            return Err(invalid("output pointers must not be NULL"));
+        }
+        let group = match unsafe { optional_str(group, "group")? } {
+            Some(name) => DhGroup::from_name(name).ok_or_else(|| invalid(&format!("unknown group '{name}'")))?,
+            None => DhGroup::default(),
+        };
+        let pair = generate_keypair(&DhParams::group(group), &mut rand::rngs::OsRng)?;
+        let private = owned(pair.private_key_pem());
+        let public = owned(pair.public_key_pem());
+        unsafe {
+            *private_key_pem = private;
+            *public_key_pem = public;
+        }
+        Ok(())
+    })
+}
+
+/// Parses a PKCS#8 private key and stores its public key PEM in
+/// `*public_key_pem`.
+///
+/// # Safety
+///
+/// `private_key_pem` is a NUL-terminated string. `public_key_pem` points to
+/// a writable `char *`. `error` is NULL or does.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cpk_derive_public(
+    private_key_pem: *const c_char,
+    public_key_pem: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    guard(error, || {
+        if public_key_pem.is_null() {
+            return Err(invalid("output pointers must not be NULL"));
+        }
+        let text = unsafe { optional_str(private_key_pem, "private key")? }
+            .ok_or_else(|| invalid("the private key must not be NULL"))?;
+        let pair = KeyPair::from_pkcs8_pem(text)?;
+        unsafe { *public_key_pem = owned(pair.public_key_pem()) };
+        Ok(())
+    })
+}
+
+/// Releases a string returned by this library. NULL is ignored.
+///
+/// # Safety
+///
+/// `string` is NULL or came from this library and has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn cpk_free(string: *mut c_char) {
+    if !string.is_null() {
+        // Private keys pass through here, so wipe them before the allocator
+        // gets the memory back.
+        let mut bytes = unsafe { CString::from_raw(string) }.into_bytes_with_nul();
+        bytes.iter_mut().for_each(|byte| unsafe { ptr::write_volatile(byte, 0) });
+    }
+}
+
+enum Failure {
+    Library(Error),
+    Invalid(String),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Failure::Library(err)
+    }
+}
+
+fn invalid(message: &str) -> Failure {
+    Failure::Invalid(message.to_string())
+}
+
+/// Runs `body`, turning errors and panics into a status code and message;
+/// unwinding must not cross into C.
+fn guard(error: *mut *mut c_char, body: impl FnOnce() -> Result<(), Failure>) -> c_int {
+    if !error.is_null() {
+        unsafe { *error = ptr::null_mut() };
+    }
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return CPK_OK,
+        Ok(Err(Failure::Library(err))) => (status(&err), err.to_string()),
+        Ok(Err(Failure::Invalid(message))) => (CPK_ERR_OTHER, message),
+        Err(_) => (CPK_ERR_OTHER, "internal error".to_string()),
+    };
+    if !error.is_null() {
+        unsafe { *error = owned(message) };
+    }
+    status
+}
+
+fn status(err: &Error) -> c_int {
+    match err {
+        Error::Parse(_) => CPK_ERR_PARSE,
+        Error::Validation(_) => CPK_ERR_VALIDATION,
+        Error::Rng(_) => CPK_ERR_RNG,
+        Error::Io { .. } => CPK_ERR_OTHER,
+    }
+}
+
+/// # Safety
+///
+/// `string` is NULL or a NUL-terminated string that outlives the result.
+unsafe fn optional_str<'a>(string: *const c_char, what: &str) -> Result<Option<&'a str>, Failure> {
+    if string.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(string) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| invalid(&format!("the {what} is not valid UTF-8")))
+}
+
+fn owned(string: String) -> *mut c_char {
+    CString::new(string).expect("PEM and messages contain no NUL").into_raw()
+}
@@ -0,0 +1,66 @@
+//! Python bindings, built with PyO3 and packaged with maturin.
+//!
+//! ```python
+//! import create_private_key
+//!
+//! key = create_private_key.generate(group="modp14")
+//! key["key_id"], key["public_key_pem"]
+//! ```
+//!
+//! `generate` returns a dict with the fields of `--json` output plus both
+//! keys as PEM, so scripts that parsed the command's stdout can switch with
+//! no other changes.
+
+use create_private_key::{DhGroup, DhParams, Error, FingerprintHash, generate_keypair};
+use num_bigint::BigUint;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Generates a Diffie-Hellman key pair and returns it as a dict.
+#[pyfunction]
+#[pyo3(signature = (group = "modp14", private_bits = None))]
+fn generate<'py>(py: Python<'py>, group: &str, private_bits: Option<u64>) -> PyResult<Bound<'py, PyDict>> {
+    let group = DhGroup::from_name(group).ok_or_else(|| PyValueError::new_err(format!("unknown group '{group}'")))?;
+    let mut builder = DhParams::builder().group(group);
+    if let Some(bits) = private_bits {
+        builder = builder.private_bits(bits);
+    }
+    let params = builder.build().map_err(exception)?;
+    let pair = py
+        .allow_threads(|| generate_keypair(&params, &mut rand::rngs::OsRng))
+        .map_err(exception)?;
+
+    let key = PyDict::new(py);
+    key.set_item("key_id", pair.key_id())?;
+    key.set_item("fingerprint", pair.fingerprint(FingerprintHash::Sha256))?;
+    key.set_item("group", group.name())?;
+    key.set_item("prime_bits", params.prime().bits())?;
+    key.set_item("prime_hex", hex(params.prime()))?;
+    key.set_item("generator", params.generator().to_string())?;
+    key.set_item("private_key_hex", hex(pair.private_key().expose()))?;
+    key.set_item("public_key_hex", hex(pair.public_key()))?;
+    key.set_item("private_key_pem", pair.private_key_pem())?;
+    key.set_item("public_key_pem", pair.public_key_pem())?;
+    Ok(key)
+}
+
+#[pymodule]
+#[pyo3(name = "create_private_key")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add_function(wrap_pyfunction!(generate, module)?)
+}
+
+/// Bad parameters are the caller's mistake; anything else is not.
+fn exception(err: Error) -> PyErr {
+    match err {
+        Error::Parse(_) | Error::Validation(_) => PyValueError::new_err(err.to_string()),
+        Error::Rng(_) | Error::Io { .. } => PyRuntimeError::new_err(err.to_string()),
+    }
+}
+
+fn hex(value: &BigUint) -> String {
+    let hex = format!("{value:X}");
+    if hex.len() % 2 == 1 { format!("0{hex}") } else { hex }
+}
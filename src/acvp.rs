@@ -0,0 +1,390 @@
+//! `acvp`: a client for NIST's Automated Cryptographic Validation Protocol,
+//! for labs taking the DH code through algorithm validation.
+//!
+//! The registration claims KAS-FFC SSC (SP 800-56A Rev. 3 shared secret
+//! computation) with the `dhEphem` scheme on the published 2048-bit safe-prime
+//! groups. Vector sets are fetched, answered with the same key generation
+//! and shared-secret code that makes keys, and uploaded; the server's
+//! verdict on each is returned. [`answer`] is also usable offline on a vector
+//! set saved to a file.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use create_private_key::dh;
+use create_private_key_core as ffdh;
+use data_encoding::{HEXLOWER_PERMISSIVE, HEXUPPER};
+use hmac::{Hmac, Mac};
+use num_bigint::BigUint;
+use num_traits::One;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+use crate::known_groups::KnownGroup;
+use crate::random;
+
+/// The protocol version every message is prefixed with.
+const ACV_VERSION: &str = "1.0";
+
+/// How long to wait for a vector set or verdict the server is still working
+/// on, when it does not say.
+const DEFAULT_RETRY: Duration = Duration::from_secs(5);
+
+/// Polls for results before giving up on a session.
+const RESULT_POLLS: u32 = 120;
+
+/// `domainParameterGenerationMethods` values and the groups they name.
+const GROUPS: [(&str, &str); 2] = [("MODP-2048", "modp14"), ("ffdhe2048", "ffdhe2048")];
+
+/// A `hashFunctionZ`.
+type HashZ = fn(&[u8]) -> Vec<u8>;
+
+/// The capabilities registered for a test session.
+fn registration() -> Value {
+    json!({
+        "algorithm": "KAS-FFC",
+        "mode": "SSC",
+        "revision": "Sp800-56Ar3",
+        "scheme": { "dhEphem": { "kasRole": ["initiator", "responder"] } },
+        "domainParameterGenerationMethods": GROUPS.map(|(method, _)| method),
+    })
+}
+
+/// The response to one vector set (the object after the version header).
+pub fn answer(vector_set: &Value) -> Result<Value, String> {
+    let id = vector_set.get("vsId").and_then(Value::as_u64).ok_or("the vector set has no vsId")?;
+    let algorithm = text(vector_set, "algorithm")?;
+    let mode = vector_set.get("mode").and_then(Value::as_str).unwrap_or_default();
+    if (algorithm, mode) != ("KAS-FFC", "SSC") {
+        return Err(format!("vector set {id} is {algorithm} {mode}; only KAS-FFC SSC is implemented"));
+    }
+    let groups = vector_set
+        .get("testGroups")
+        .and_then(Value::as_array)
+        .ok_or(format!("vector set {id} has no testGroups"))?;
+    let groups = groups.iter().map(answer_group).collect::<Result<Vec<_>, _>>()?;
+    Ok(json!({ "vsId": id, "testGroups": groups }))
+}
+
+fn answer_group(group: &Value) -> Result<Value, String> {
+    let id = group.get("tgId").and_then(Value::as_u64).ok_or("a test group has no tgId")?;
+    let scheme = text(group, "scheme")?;
+    if scheme != "dhEphem" {
+        return Err(format!("test group {id} uses {scheme}; only dhEphem is implemented"));
+    }
+    let domain = Domain::read(group).map_err(|err| format!("test group {id}: {err}"))?;
+    let hash = group.get("hashFunctionZ").and_then(Value::as_str).map(hash_function).transpose()?;
+    let test_type = text(group, "testType")?;
+    let mut answers = Vec::new();
+    for test in group.get("tests").and_then(Value::as_array).into_iter().flatten() {
+        let case = test.get("tcId").and_then(Value::as_u64).ok_or(format!("a test in group {id} has no tcId"))?;
+        let fail = |err: String| format!("test group {id}, case {case}: {err}");
+        let answer = match test_type {
+            "AFT" => {
+                let (public, z) = domain.agree(test).map_err(fail)?;
+                let mut answer = json!({ "tcId": case, "ephemeralPublicIut": public });
+                match hash {
+                    Some(hash) => answer["hashZ"] = HEXUPPER.encode(&hash(&z)).into(),
+                    None => answer["z"] = HEXUPPER.encode(&z).into(),
+                }
+                answer
+            }
+            "VAL" => {
+                let outcome = domain.check(test, hash);
+                if let Err(ref reason) = outcome {
+                    tracing::debug!(group = id, case, reason = %reason, "validation case refused");
+                }
+                json!({ "tcId": case, "testPassed": outcome.is_ok() })
+            }
+            other => return Err(format!("test group {id} has testType {other}; expected AFT or VAL")),
+        };
+        answers.push(answer);
+    }
+    Ok(json!({ "tgId": id, "tests": answers }))
+}
+
+/// A test group's `p`, `q` and `g`.
+struct Domain {
+    p: BigUint,
+    q: BigUint,
+    g: BigUint,
+}
+
+impl Domain {
+    /// Stated in the group, or named by `domainParameterGenerationMode`.
+    fn read(group: &Value) -> Result<Domain, String> {
+        if group.get("p").is_some() {
+            return Ok(Domain {
+                p: number(group, "p")?,
+                q: number(group, "q")?,
+                g: number(group, "g")?,
+            });
+        }
+        let method = text(group, "domainParameterGenerationMode")?;
+        let known = GROUPS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(method))
+            .and_then(|&(_, name)| KnownGroup::by_name(name))
+            .ok_or(format!("no p, q and g, and {method} is not a supported group"))?;
+        Ok(Domain {
+            p: known.prime(),
+            q: known.order(),
+            g: known.generator(),
+        })
+    }
+
+    /// Hex of `value` padded to the byte length of `p`.
+    fn hex(&self, value: &BigUint) -> String {
+        let width = self.p.to_bytes_be().len();
+        let bytes = value.to_bytes_be();
+        HEXUPPER.encode(&[vec![0; width.saturating_sub(bytes.len())], bytes].concat())
+    }
+
+    /// SP 800-56A Rev. 3, 5.6.2.3.1 full public-key validation.
+    fn check_public(&self, public: &BigUint) -> Result<(), String> {
+        dh::check_peer(&self.p, public).map_err(|err| err.to_string())?;
+        if !public.modpow(&self.q, &self.p).is_one() {
+            return Err("public key is not in the subgroup of order q".into());
+        }
+        Ok(())
+    }
+
+    /// AFT: a fresh IUT key pair (5.6.1.1.4, `x` in `[2, q - 1]`) and `Z`
+    /// with the server's public key.
+    fn agree(&self, test: &Value) -> Result<(String, Vec<u8>), String> {
+        let server = number(test, "ephemeralPublicServer")?;
+        self.check_public(&server)?;
+        let private = ffdh::random_range(&BigUint::from(2u32), &self.q, random::fill).map_err(|err| err.to_string())?;
+        let public = ffdh::public_key(&self.p, &self.g, &private);
+        let z = dh::shared_secret(&self.p, &private, &server).map_err(|err| err.to_string())?;
+        Ok((self.hex(&public), z))
+    }
+
+    /// VAL: whether the case's IUT key pair and `Z` (or its hash) hold.
+    fn check(&self, test: &Value, hash: Option<HashZ>) -> Result<(), String> {
+        let private = number(test, "ephemeralPrivateIut")?;
+        let public = number(test, "ephemeralPublicIut")?;
+        let server = number(test, "ephemeralPublicServer")?;
+        if private < BigUint::one() || private >= self.q {
+            return Err("private key is outside [1, q - 1]".into());
+        }
+        if ffdh::public_key(&self.p, &self.g, &private) != public {
+            return Err("public key is not g^x mod p".into());
+        }
+        self.check_public(&server)?;
+        let z = dh::shared_secret(&self.p, &private, &server).map_err(|err| err.to_string())?;
+        let (computed, expected) = match hash {
+            Some(hash) => (hash(&z), bytes(test, "hashZ")?),
+            None => (z, bytes(test, "z")?),
+        };
+        if computed != expected {
+            return Err("Z differs".into());
+        }
+        Ok(())
+    }
+}
+
+fn hash_function(name: &str) -> Result<HashZ, String> {
+    Ok(match name {
+        "SHA2-224" => |data| Sha224::digest(data).to_vec(),
+        "SHA2-256" => |data| Sha256::digest(data).to_vec(),
+        "SHA2-384" => |data| Sha384::digest(data).to_vec(),
+        "SHA2-512" => |data| Sha512::digest(data).to_vec(),
+        other => return Err(format!("hashFunctionZ {other} is not implemented")),
+    })
+}
+
+fn text<'a>(value: &'a Value, name: &str) -> Result<&'a str, String> {
+    value.get(name).and_then(Value::as_str).ok_or(format!("no {name}"))
+}
+
+fn bytes(value: &Value, name: &str) -> Result<Vec<u8>, String> {
+    HEXLOWER_PERMISSIVE
+        .decode(text(value, name)?.as_bytes())
+        .map_err(|err| format!("{name} is not hex: {err}"))
+}
+
+fn number(value: &Value, name: &str) -> Result<BigUint, String> {
+    Ok(BigUint::from_bytes_be(&bytes(value, name)?))
+}
+
+/// RFC 6238 TOTP as ACVP servers expect it: HMAC-SHA-256, 30-second steps,
+/// eight digits.
+fn totp(seed: &[u8], now: u64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(seed).expect("HMAC takes any key length");
+    mac.update(&(now / 30).to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+    let word: [u8; 4] = digest[offset..offset + 4].try_into().expect("four bytes");
+    format!("{:08}", (u32::from_be_bytes(word) & 0x7fff_ffff) % 100_000_000)
+}
+
+/// How to reach and authenticate to the server.
+pub struct Login<'a> {
+    /// e.g. `https://demo.acvts.nist.gov`.
+    pub server: &'a str,
+    /// PEM certificate chain and private key for TLS client authentication.
+    pub cert: &'a Path,
+    pub key: &'a Path,
+    /// The base64 TOTP seed file, for servers that ask for one-time passwords.
+    pub totp_seed: Option<&'a Path>,
+}
+
+/// An authenticated connection.
+pub struct Client {
+    agent: ureq::Agent,
+    server: String,
+    token: String,
+}
+
+/// The server's verdict on one vector set.
+pub struct Disposition {
+    pub url: String,
+    pub status: String,
+}
+
+impl Client {
+    /// Sets up mutual TLS and logs in. Plain `http://` is only accepted for
+    /// a server on this machine.
+    pub fn login(login: &Login) -> Result<Client, String> {
+        let server = login.server.trim_end_matches('/');
+        let local = ["http://localhost", "http://127.0.0.1", "http://[::1]"]
+            .iter()
+            .any(|prefix| server.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '/'])));
+        if !server.starts_with("https://") && !local {
+            return Err(format!("--server {server} is not an https:// URL"));
+        }
+        let agent = ureq::AgentBuilder::new()
+            .tls_config(Arc::new(tls_config(login.cert, login.key)?))
+            .timeout(Duration::from_secs(300))
+            .build();
+        let mut client = Client {
+            agent,
+            server: server.to_string(),
+            token: String::new(),
+        };
+        let mut credentials = json!({});
+        if let Some(path) = login.totp_seed {
+            let encoded = std::fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+            let seed = BASE64
+                .decode(encoded.trim())
+                .map_err(|err| format!("{} is not a base64 TOTP seed: {err}", path.display()))?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|err| err.to_string())?;
+            credentials["password"] = totp(&seed, now.as_secs()).into();
+        }
+        let response = client.send("POST", "/acvp/v1/login", Some(credentials))?;
+        client.token = text(&response, "accessToken").map_err(|err| format!("login: {err}"))?.to_string();
+        Ok(client)
+    }
+
+    /// Opens a test session, answers every vector set and returns the
+    /// server's verdicts. `sample` asks for expected answers too, as the
+    /// demo server allows.
+    pub fn run_session(&mut self, sample: bool) -> Result<(String, Vec<Disposition>), String> {
+        let request = json!({ "isSample": sample, "algorithms": [registration()] });
+        let session = self.send("POST", "/acvp/v1/testSessions", Some(request))?;
+        let url = text(&session, "url").map_err(|err| format!("test session: {err}"))?.to_string();
+        // Later requests are authorized by the session's own token.
+        if let Some(token) = session.get("accessToken").and_then(Value::as_str) {
+            self.token = token.to_string();
+        }
+        tracing::info!(session = %url, "test session registered");
+        let vector_sets = session.get("vectorSetUrls").and_then(Value::as_array).cloned().unwrap_or_default();
+        for vector_set_url in vector_sets.iter().filter_map(Value::as_str) {
+            let vector_set = self.poll(vector_set_url)?;
+            let response = answer(&vector_set)?;
+            self.send("POST", &format!("{vector_set_url}/results"), Some(response))?;
+            tracing::info!(vector_set = vector_set_url, "responses uploaded");
+        }
+        for _ in 0..RESULT_POLLS {
+            let results = self.send("GET", &format!("{url}/results"), None)?;
+            let dispositions: Vec<Disposition> = results
+                .get("results")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|result| Disposition {
+                    url: result.get("vectorSetUrl").and_then(Value::as_str).unwrap_or("?").to_string(),
+                    status: result.get("status").and_then(Value::as_str).unwrap_or("?").to_string(),
+                })
+                .collect();
+            let pending = |status: &str| matches!(status, "incomplete" | "unreceived" | "processing");
+            if !dispositions.iter().any(|disposition| pending(&disposition.status)) {
+                return Ok((url, dispositions));
+            }
+            std::thread::sleep(DEFAULT_RETRY);
+        }
+        Err(format!("the server had not judged {url} after {RESULT_POLLS} polls"))
+    }
+
+    /// GETs `path` until the server stops answering `{"retry": seconds}`.
+    fn poll(&self, path: &str) -> Result<Value, String> {
+        loop {
+            let body = self.send("GET", path, None)?;
+            match body.get("retry").and_then(Value::as_u64) {
+                Some(seconds) => std::thread::sleep(Duration::from_secs(seconds.max(1))),
+                None => return Ok(body),
+            }
+        }
+    }
+
+    /// One request: `[{"acvVersion": ...}, payload]` both ways.
+    fn send(&self, method: &str, path: &str, payload: Option<Value>) -> Result<Value, String> {
+        let url = format!("{}{path}", self.server);
+        let mut request = self.agent.request(method, &url);
+        if !self.token.is_empty() {
+            request = request.set("Authorization", &format!("Bearer {}", self.token));
+        }
+        let response = match payload {
+            Some(payload) => request.send_json(json!([{ "acvVersion": ACV_VERSION }, payload])),
+            None => request.call(),
+        };
+        let body: Value = match response {
+            Ok(response) => response.into_json().map_err(|err| format!("{method} {url}: {err}"))?,
+            Err(ureq::Error::Status(status, response)) => {
+                let detail = response.into_string().unwrap_or_default();
+                return Err(format!("{method} {url}: HTTP {status}: {}", detail.trim()));
+            }
+            Err(err) => return Err(format!("{method} {err}")),
+        };
+        match body {
+            Value::Array(mut parts) if parts.len() == 2 => Ok(parts.pop().expect("two parts")),
+            _ => Err(format!("{method} {url}: not an ACVP message")),
+        }
+    }
+}
+
+/// rustls with the Mozilla roots and the lab's client certificate.
+fn tls_config(cert: &Path, key: &Path) -> Result<rustls::ClientConfig, String> {
+    use rustls_pki_types::pem::PemObject;
+    use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| format!("failed to read certificates from {}: {err}", cert.display()))?;
+    if chain.is_empty() {
+        return Err(format!("{} holds no certificate", cert.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|err| format!("failed to read {}: {err}", key.display()))?;
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .with_root_certificates(roots)
+        .with_client_auth_cert(chain, key)
+        .map_err(|err| format!("client certificate: {err}"))
+}
+
+/// Reads a saved vector set, with or without the version header.
+pub fn read_vector_set(text: &str) -> Result<Value, String> {
+    let value: Value = serde_json::from_str(text).map_err(|err| format!("not an ACVP vector set: {err}"))?;
+    Ok(match value {
+        Value::Array(mut parts) if parts.len() == 2 => parts.pop().expect("two parts"),
+        value => value,
+    })
+}
@@ -0,0 +1,517 @@
+//! `agent`: holds DH private keys in locked memory and answers requests over
+//! a Unix domain socket, in the manner of `ssh-agent`.
+//!
+//! Every message is a u32 big-endian length followed by that many bytes. A
+//! request starts with an opcode; a response starts with a status byte (0
+//! for success, 1 for failure followed by a UTF-8 message). Variable fields
+//! are u32-length-prefixed byte strings and numbers are big-endian unsigned.
+//!
+//! | opcode     | request fields                      | response fields             |
+//! |------------|-------------------------------------|-----------------------------|
+//! | 1 `ADD`    | name, PKCS#8 DER (empty: generate)  | key ID, public key          |
+//! | 2 `LIST`   |                                     | count u32, (name, key ID)*  |
+//! | 3 `PUBLIC` | name                                | SPKI DER                    |
+//! | 4 `DH`     | name, peer public key               | shared secret               |
+//! | 5 `HKDF`   | name, peer, salt, info, length u32  | HKDF-SHA256 output          |
+//! | 6 `REMOVE` | name                                |                             |
+//!
+//! Requests over a rate limit, and `ADD`s that would generate a key beyond
+//! the hourly quota, fail with a message saying when to retry.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the agent socket, as printed by `agent`.
+pub const SOCKET_ENV: &str = "CPK_AGENT_SOCK";
+/// Largest message either side accepts.
+const MAX_MESSAGE: usize = 1 << 20;
+
+const OP_ADD: u8 = 1;
+const OP_LIST: u8 = 2;
+const OP_PUBLIC: u8 = 3;
+const OP_DH: u8 = 4;
+const OP_HKDF: u8 = 5;
+const OP_REMOVE: u8 = 6;
+const STATUS_OK: u8 = 0;
+const STATUS_ERROR: u8 = 1;
+
+/// The socket to use: `--socket`, then `$CPK_AGENT_SOCK`, then a per-user
+/// default under `$XDG_RUNTIME_DIR` or the temp directory.
+pub fn socket_path(explicit: Option<&Path>) -> PathBuf {
+    if let Some(path) = explicit {
+        return path.to_path_buf();
+    }
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("create-private-key-agent.sock"),
+        None => std::env::temp_dir().join(format!("create-private-key-agent-{}.sock", user_id())),
+    }
+}
+
+#[cfg(unix)]
+fn user_id() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+fn user_id() -> u32 {
+    0
+}
+
+/// Request encoding shared by the clients.
+pub struct Request(Vec<u8>);
+
+impl Request {
+    fn new(opcode: u8) -> Self {
+        Request(vec![opcode])
+    }
+
+    fn field(mut self, bytes: &[u8]) -> Self {
+        self.0.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn number(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn add(name: &str, private_key_info: &[u8]) -> Self {
+        Request::new(OP_ADD).field(name.as_bytes()).field(private_key_info)
+    }
+
+    pub fn list() -> Self {
+        Request::new(OP_LIST)
+    }
+
+    pub fn public(name: &str) -> Self {
+        Request::new(OP_PUBLIC).field(name.as_bytes())
+    }
+
+    pub fn dh(name: &str, peer: &[u8]) -> Self {
+        Request::new(OP_DH).field(name.as_bytes()).field(peer)
+    }
+
+    pub fn hkdf(name: &str, peer: &[u8], salt: &[u8], info: &[u8], length: u32) -> Self {
+        Request::new(OP_HKDF)
+            .field(name.as_bytes())
+            .field(peer)
+            .field(salt)
+            .field(info)
+            .number(length)
+    }
+
+    pub fn remove(name: &str) -> Self {
+        Request::new(OP_REMOVE).field(name.as_bytes())
+    }
+}
+
+/// Cursor over the fields of a message.
+pub struct Fields<'a>(pub &'a [u8]);
+
+impl<'a> Fields<'a> {
+    pub fn field(&mut self) -> Result<&'a [u8], String> {
+        let len = self.number()? as usize;
+        if self.0.len() < len {
+            return Err("agent message is truncated".into());
+        }
+        let (field, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(field)
+    }
+
+    pub fn text(&mut self) -> Result<&'a str, String> {
+        std::str::from_utf8(self.field()?).map_err(|_| "agent message field is not UTF-8".to_string())
+    }
+
+    pub fn number(&mut self) -> Result<u32, String> {
+        if self.0.len() < 4 {
+            return Err("agent message is truncated".into());
+        }
+        let (number, rest) = self.0.split_at(4);
+        self.0 = rest;
+        Ok(u32::from_be_bytes(number.try_into().expect("four bytes")))
+    }
+}
+
+fn write_message(stream: &mut impl Write, body: &[u8]) -> std::io::Result<()> {
+    stream.write_all(&(body.len() as u32).to_be_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Reads one message; `None` at a clean end of stream.
+fn read_message(stream: &mut impl Read) -> Result<Option<Vec<u8>>, String> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(format!("agent connection failed: {err}")),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE {
+        return Err(format!("agent message of {len} bytes is too large"));
+    }
+    let mut body = vec![0u8; len];
+    stream
+        .read_exact(&mut body)
+        .map_err(|err| format!("agent connection failed: {err}"))?;
+    Ok(Some(body))
+}
+
+#[cfg(unix)]
+pub use unix::{request, serve};
+
+#[cfg(unix)]
+mod unix {
+    use num_bigint::BigUint;
+    use std::collections::BTreeMap;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use create_private_key::{DhParams, KeyPair, PrivateKey, generate_keypair};
+    use crate::limits::Limits;
+    use crate::{der, dh, metrics, random, systemd, telemetry};
+
+    /// A private key in memory that is locked against swapping and wiped on drop.
+    struct LockedKey(Box<[u8]>);
+
+    impl LockedKey {
+        fn new(value: &BigUint) -> Self {
+            let key = LockedKey(value.to_bytes_be().into_boxed_slice());
+            // SAFETY: the range is the live allocation owned by `key`. Failure
+            // (e.g. RLIMIT_MEMLOCK) leaves the key usable, just swappable.
+            unsafe {
+                libc::mlock(key.0.as_ptr().cast(), key.0.len());
+            }
+            key
+        }
+
+        /// A working copy for one exponentiation, wiped when it is dropped.
+        fn value(&self) -> PrivateKey {
+            PrivateKey::new(BigUint::from_bytes_be(&self.0))
+        }
+    }
+
+    impl Drop for LockedKey {
+        fn drop(&mut self) {
+            for byte in self.0.iter_mut() {
+                // SAFETY: `byte` is a valid, aligned reference; volatile so the
+                // wipe is not optimised away.
+                unsafe { std::ptr::write_volatile(byte, 0) };
+            }
+            // SAFETY: same range as locked in `new`.
+            unsafe {
+                libc::munlock(self.0.as_ptr().cast(), self.0.len());
+            }
+        }
+    }
+
+    struct AgentKey {
+        prime: BigUint,
+        generator: BigUint,
+        private_key: LockedKey,
+        public_key: BigUint,
+        key_id: String,
+    }
+
+    type Keys = Arc<Mutex<BTreeMap<String, AgentKey>>>;
+
+    /// Runs the agent until killed. New keys generated by `ADD` use
+    /// `prime` and `generator`. A socket passed by systemd replaces `socket`.
+    pub fn serve(socket: &Path, prime: BigUint, generator: BigUint, limits: Limits) -> Result<(), String> {
+        harden_process();
+        let listener = match systemd::listener()? {
+            Some(systemd::Listener::Unix(listener)) => listener,
+            Some(systemd::Listener::Tcp(_)) => return Err("the agent needs a Unix socket from systemd".into()),
+            None => bind(socket)?,
+        };
+        let bound = listener
+            .local_addr()
+            .ok()
+            .and_then(|address| address.as_pathname().map(Path::to_path_buf))
+            .unwrap_or_else(|| socket.to_path_buf());
+        println!("{SOCKET_ENV}={}; export {SOCKET_ENV};", bound.display());
+        systemd::ready(&bound.display().to_string());
+
+        let keys: Keys = Arc::default();
+        let group = Arc::new((prime, generator));
+        let limits = Arc::new(limits);
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let (keys, group, limits) = (Arc::clone(&keys), Arc::clone(&group), Arc::clone(&limits));
+            std::thread::spawn(move || {
+                if let Err(err) = handle(stream, &keys, &group, &limits) {
+                    tracing::warn!("agent: {err}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Binds a fresh socket readable only by this user. It is created under
+    /// a umask that leaves it 0600, so there is no moment when another user
+    /// could connect.
+    fn bind(socket: &Path) -> Result<UnixListener, String> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(format!("an agent is already listening on {}", socket.display()));
+            }
+            // A stale socket from an agent that did not shut down cleanly.
+            let _ = std::fs::remove_file(socket);
+        }
+        // SAFETY: umask only swaps the process's file mode mask; no other
+        // thread is running yet to create files under the narrower one.
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(socket);
+        // SAFETY: as above, restoring the previous mask.
+        unsafe { libc::umask(umask) };
+        listener.map_err(|err| format!("failed to bind {}: {err}", socket.display()))
+
+    }
+
+    /// Keeps keys out of core dumps and away from same-user debuggers.
+    fn harden_process() {
+        let no_core = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: plain syscalls on this process with valid arguments.
+        unsafe {
+            libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+            #[cfg(target_os = "linux")]
+            libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0);
+        }
+    }
+
+    /// Who is on the other end of a connection, as the kernel reports it.
+    struct Peer {
+        /// The process, where the platform says which it is.
+        pid: Option<i32>,
+        uid: u32,
+    }
+
+    #[cfg(target_os = "linux")]
+    fn peer(stream: &UnixStream) -> Option<Peer> {
+        use std::os::fd::AsRawFd;
+
+        let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: the buffer and length describe `credentials`, which
+        // outlives the call; the descriptor belongs to `stream`.
+        let found = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&raw mut credentials).cast(),
+                &mut length,
+            ) == 0
+        };
+        found.then_some(Peer {
+            pid: Some(credentials.pid),
+            uid: credentials.uid,
+        })
+    }
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    ))]
+    fn peer(stream: &UnixStream) -> Option<Peer> {
+        use std::os::fd::AsRawFd;
+
+        let (mut uid, mut gid) = (0, 0);
+        // SAFETY: both out-pointers are live locals; the descriptor belongs
+        // to `stream`.
+        let found = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) == 0 };
+        found.then_some(Peer { pid: None, uid })
+    }
+
+    /// Without a way to ask, no peer can be shown to be this user.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    )))]
+    fn peer(_stream: &UnixStream) -> Option<Peer> {
+        None
+    }
+
+    /// Serves one connection, from this user only. Requests are counted
+    /// against the peer process where the kernel says which it is.
+    fn handle(mut stream: UnixStream, keys: &Keys, group: &(BigUint, BigUint), limits: &Limits) -> Result<(), String> {
+        let peer = peer(&stream).ok_or("refused a connection whose user cannot be determined")?;
+        if peer.uid != user_id() {
+            return Err(format!("refused a connection from uid {}", peer.uid));
+        }
+        let client = peer.pid.map_or_else(|| "local".to_string(), |pid| format!("pid {pid}"));
+        while let Some(message) = read_message(&mut stream)? {
+            let operation = operation_name(message.first().copied().unwrap_or_default());
+            let span = telemetry::request_span(operation);
+            let result = span.in_scope(|| {
+                metrics::time(operation, || {
+                    limits.request(&client).map_err(|refusal| refusal.to_string())?;
+                    dispatch(&message, keys, group, limits)
+                })
+            });
+            if let Err(ref err) = result {
+                telemetry::record_error(&span, err);
+            }
+            let response = match result {
+                Ok(mut body) => {
+                    body.insert(0, STATUS_OK);
+                    body
+                }
+                Err(err) => {
+                    let mut body = vec![STATUS_ERROR];
+                    body.extend_from_slice(err.as_bytes());
+                    body
+                }
+            };
+            write_message(&mut stream, &response).map_err(|err| format!("agent connection failed: {err}"))?;
+        }
+        Ok(())
+    }
+
+    /// Label for the request latency metrics.
+    fn operation_name(opcode: u8) -> &'static str {
+        match opcode {
+            OP_ADD => "agent_add",
+            OP_LIST => "agent_list",
+            OP_PUBLIC => "agent_public",
+            OP_DH => "agent_dh",
+            OP_HKDF => "agent_hkdf",
+            OP_REMOVE => "agent_remove",
+            _ => "agent_unknown",
+        }
+    }
+
+    /// Parsing, validation and exponentiation happen outside the key table's
+    /// lock, which is held only to look a key up or to add or remove one.
+    fn dispatch(message: &[u8], keys: &Keys, group: &(BigUint, BigUint), limits: &Limits) -> Result<Vec<u8>, String> {
+        let (&opcode, rest) = message.split_first().ok_or("empty agent request")?;
+        let mut fields = Fields(rest);
+        let table = || keys.lock().map_err(|_| "agent key table is poisoned".to_string());
+        let missing = |name: &str| format!("the agent holds no key named '{name}'");
+        let mut response = Request(Vec::new());
+
+        match opcode {
+            OP_ADD => {
+                let name = fields.text()?.to_string();
+                let private_key_info = fields.field()?;
+                let taken = |name: &str| format!("the agent already holds a key named '{name}'");
+                if table()?.contains_key(&name) {
+                    return Err(taken(&name));
+                }
+                let pair = if private_key_info.is_empty() {
+                    limits.take_key().map_err(|refusal| refusal.to_string())?;
+                    let params = DhParams::new(group.0.clone(), group.1.clone())?;
+                    let pair = generate_keypair(&params, &mut random::Source)?;
+                    metrics::key_generated();
+                    pair
+                } else {
+                    // The parameters, their size limit and the exponent's
+                    // range are checked before any arithmetic with them.
+                    KeyPair::from_pkcs8_der(private_key_info)?
+                };
+                let key_id = pair.key_id();
+                let (params, private_key, public_key) = pair.into_parts();
+                let (prime, generator) = params.into_parts();
+                let key = AgentKey {
+                    key_id,
+                    private_key: LockedKey::new(private_key.expose()),
+                    prime,
+                    generator,
+                    public_key,
+                };
+                response = response.field(key.key_id.as_bytes()).field(&key.public_key.to_bytes_be());
+                let mut table = table()?;
+                // Another client may have taken the name meanwhile.
+                if table.contains_key(&name) {
+                    return Err(taken(&name));
+                }
+                table.insert(name, key);
+            }
+            OP_LIST => {
+                let table = table()?;
+                response = response.number(table.len() as u32);
+                for (name, key) in table.iter() {
+                    response = response.field(name.as_bytes()).field(key.key_id.as_bytes());
+                }
+            }
+            OP_PUBLIC => {
+                let name = fields.text()?;
+                let table = table()?;
+                let key = table.get(name).ok_or_else(|| missing(name))?;
+                response = response.field(&der::dh_subject_public_key_info(&key.prime, &key.generator, &key.public_key));
+            }
+            OP_DH | OP_HKDF => {
+                let name = fields.text()?;
+                let (prime, private_key) = {
+                    let table = table()?;
+                    let key = table.get(name).ok_or_else(|| missing(name))?;
+                    (key.prime.clone(), key.private_key.value())
+                };
+                let peer = BigUint::from_bytes_be(fields.field()?);
+                let secret = dh::shared_secret(&prime, private_key.expose(), &peer)
+                    .inspect_err(|_| metrics::validation_failed())?;
+                let output = if opcode == OP_HKDF {
+                    let (salt, info) = (fields.field()?, fields.field()?);
+                    dh::hkdf_sha256(&secret, salt, info, fields.number()? as usize)?
+                } else {
+                    secret
+                };
+                response = response.field(&output);
+            }
+            OP_REMOVE => {
+                let name = fields.text()?;
+                table()?.remove(name).ok_or_else(|| missing(name))?;
+            }
+            _ => return Err(format!("unknown agent opcode {opcode}")),
+        }
+        Ok(response.0)
+    }
+
+    /// Sends one request and returns the fields of a successful response.
+    pub fn request(socket: &Path, request: Request) -> Result<Vec<u8>, String> {
+        let mut stream = UnixStream::connect(socket)
+            .map_err(|err| format!("cannot reach the agent at {}: {err}", socket.display()))?;
+        write_message(&mut stream, &request.0).map_err(|err| format!("agent connection failed: {err}"))?;
+        let response = read_message(&mut stream)?.ok_or("the agent closed the connection")?;
+        match response.split_first() {
+            Some((&STATUS_OK, body)) => Ok(body.to_vec()),
+            Some((_, message)) => Err(format!("agent: {}", String::from_utf8_lossy(message))),
+            None => Err("empty agent response".into()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn serve(
+    _socket: &Path,
+    _prime: num_bigint::BigUint,
+    _generator: num_bigint::BigUint,
+    _limits: crate::limits::Limits,
+) -> Result<(), String> {
+    Err("the agent needs Unix domain sockets".into())
+}
+
+#[cfg(not(unix))]
+pub fn request(_socket: &Path, _request: Request) -> Result<Vec<u8>, String> {
+    Err("the agent needs Unix domain sockets".into())
+}
@@ -0,0 +1,204 @@
+//! Requests and responses shared by the service modes.
+//!
+//! Numbers are strings in the CLI's syntax (decimal, or hex with `0x`) on
+//! the way in and even-length hex without a prefix on the way out, matching
+//! the `cpk-v1` envelope.
+
+use clap::ValueEnum;
+use create_private_key::{DhParams, generate_keypair};
+use create_private_key_core as ffdh;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+use crate::dh;
+use crate::envelope;
+use crate::fingerprint::FingerprintHash;
+use crate::metadata::{self, Metadata};
+use crate::metrics;
+use crate::output::{KeyRecord, to_even_length_hex};
+use crate::params::{self, DhGroup};
+use crate::random;
+
+/// Group selection common to every request: a built-in group, optionally
+/// overridden by an explicit prime and generator, as on the command line.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GroupSpec {
+    pub group: Option<String>,
+    pub prime: Option<String>,
+    pub generator: Option<String>,
+}
+
+impl GroupSpec {
+    /// Whether no field is set, so a caller's default group applies.
+    pub fn is_empty(&self) -> bool {
+        self.group.is_none() && self.prime.is_none() && self.generator.is_none()
+    }
+
+    pub fn resolve(&self) -> Result<Group, String> {
+        let group = match self.group {
+            Some(ref name) => DhGroup::from_str(name, true).map_err(|_| format!("unknown group '{name}'"))?,
+            None => DhGroup::Modp14,
+        };
+        let (prime, generator) = params::resolve(group, self.prime.as_deref(), self.generator.as_deref())?;
+        let name = if self.prime.is_some() { "custom" } else { group.name() };
+        Ok(Group { prime, generator, name })
+    }
+}
+
+/// Validated group parameters.
+#[derive(Clone)]
+pub struct Group {
+    pub prime: BigUint,
+    pub generator: BigUint,
+    /// Name recorded in key metadata.
+    pub name: &'static str,
+}
+
+impl Group {
+    pub fn params(&self) -> DhParams {
+        DhParams::new(self.prime.clone(), self.generator.clone()).expect("groups are validated when resolved")
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerateRequest {
+    #[serde(flatten)]
+    pub group: GroupSpec,
+    pub label: Option<String>,
+    pub operator: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GeneratedKey {
+    pub key_id: String,
+    pub fingerprint: String,
+    pub public_key: String,
+    /// The full `cpk-v1` envelope, private key included; `--import` reads it.
+    pub key: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct GroupInfo {
+    pub name: &'static str,
+    pub prime_bits: u64,
+    pub prime: String,
+    pub generator: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidateRequest {
+    #[serde(flatten)]
+    pub group: GroupSpec,
+    /// Optional public value (hex) to range-check against the group.
+    pub public_key: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Validation {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// HKDF-SHA256 applied to a shared secret.
+pub struct Hkdf {
+    pub length: usize,
+    pub salt: Vec<u8>,
+    pub info: Vec<u8>,
+}
+
+/// Generates a fresh key pair in the requested group.
+pub fn generate_record(group: &Group, label: Option<String>, operator: Option<String>) -> Result<KeyRecord, String> {
+    metadata::check_text(label.as_deref(), operator.as_deref())?;
+    let pair = generate_keypair(&group.params(), &mut random::Source)?;
+    metrics::key_generated();
+    let metadata = Metadata::new(group.name, label, operator);
+    Ok(KeyRecord::from_key_pair(pair, Some(metadata), FingerprintHash::Sha256))
+}
+
+/// Generates a fresh key pair.
+pub fn generate(request: &GenerateRequest) -> Result<GeneratedKey, String> {
+    let group = request.group.resolve()?;
+    Ok(generated_key(&generate_record(&group, request.label.clone(), request.operator.clone())?))
+}
+
+/// The JSON form of a generated key.
+pub fn generated_key(record: &KeyRecord) -> GeneratedKey {
+    let key = serde_json::from_str(&envelope::render(record)).expect("envelope is JSON");
+    GeneratedKey {
+        key_id: record.key_id.clone(),
+        fingerprint: record.fingerprint.clone(),
+        public_key: to_even_length_hex(&record.public_key),
+        key,
+    }
+}
+
+/// The built-in groups.
+pub fn groups() -> Vec<GroupInfo> {
+    DhGroup::value_variants()
+        .iter()
+        .map(|&group| {
+            let prime = group.prime();
+            GroupInfo {
+                name: group.name(),
+                prime_bits: prime.bits(),
+                prime: to_even_length_hex(prime),
+                generator: group.default_generator().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Checks group parameters and, if given, a public value. Invalid input is
+/// a normal answer here, not an error.
+pub fn validate(request: &ValidateRequest) -> Validation {
+    let result = request
+        .group
+        .resolve()
+        .and_then(|group| check_public_key(&group, request.public_key.as_deref()));
+    validation(result)
+}
+
+/// Range-checks an optional hex public value against a resolved group.
+pub fn check_public_key(group: &Group, public_key: Option<&str>) -> Result<(), String> {
+    match public_key {
+        Some(public_key) => Ok(dh::check_peer(&group.prime, &parse_hex(public_key)?)?),
+        None => Ok(()),
+    }
+}
+
+pub fn validation(result: Result<(), String>) -> Validation {
+    if result.is_err() {
+        metrics::validation_failed();
+    }
+    Validation {
+        valid: result.is_ok(),
+        error: result.err(),
+    }
+}
+
+/// The DH shared secret of a private key and a peer's public value, or its
+/// HKDF output.
+pub fn shared_secret(group: &Group, private_key: &BigUint, peer: &BigUint, hkdf: Option<&Hkdf>) -> Result<Vec<u8>, String> {
+    let secret = check_private_key(group, private_key)
+        .and_then(|()| Ok(dh::shared_secret(&group.prime, private_key, peer)?))
+        .inspect_err(|_| metrics::validation_failed())?;
+    match hkdf {
+        Some(hkdf) => Ok(dh::hkdf_sha256(&secret, &hkdf.salt, &hkdf.info, hkdf.length)?),
+        None => Ok(secret),
+    }
+}
+
+/// Rejects private values outside `[2, p - 2]`, the range the core library
+/// enforces for every other entry point.
+pub fn check_private_key(group: &Group, private_key: &BigUint) -> Result<(), String> {
+    ffdh::check_private_key(&group.prime, private_key).map_err(|err| err.to_string())
+}
+
+pub fn parse_hex(value: &str) -> Result<BigUint, String> {
+    let digits = value.trim().trim_start_matches("0x");
+    BigUint::parse_bytes(digits.as_bytes(), 16).ok_or_else(|| format!("'{value}' is not a hex number"))
+}
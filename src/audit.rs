@@ -0,0 +1,175 @@
+//! `--audit-log`: an append-only, hash-chained record of key creation.
+//!
+//! Each line is a JSON object. Its `hash` is SHA-256 over the line's JSON
+//! without the `hash` field, and that JSON includes `prev`, the hash of the
+//! line before it (all zeros for the first). Editing, removing or reordering
+//! any line breaks every hash after it; `audit-verify` recomputes the chain.
+//! Only public information is recorded, never private key material.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::der;
+use crate::fingerprint::{self, FingerprintHash};
+use crate::metadata::{self, Metadata};
+
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Entry {
+    seq: u64,
+    time: String,
+    event: String,
+    key_id: String,
+    fingerprint: String,
+    group: String,
+    prime_bits: u64,
+    /// SHA-256 of the DER `DHParameter` (prime and generator).
+    params_sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operator: Option<String>,
+    tool_version: String,
+    prev: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    hash: String,
+}
+
+impl Entry {
+    fn digest(&self) -> String {
+        let mut unhashed = self.clone();
+        unhashed.hash.clear();
+        hex(&Sha256::digest(serde_json::to_vec(&unhashed).expect("audit entry serializes")))
+    }
+}
+
+/// An open audit log, locked against concurrent writers until dropped.
+pub struct AuditLog {
+    file: File,
+    seq: u64,
+    prev: String,
+}
+
+impl AuditLog {
+    /// Opens (or creates) the log and picks up the chain from its last line.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut options = OpenOptions::new();
+        options.read(true).append(true).create(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o644);
+        }
+        let file = options
+            .open(path)
+            .map_err(|err| format!("failed to open audit log {}: {err}", path.display()))?;
+        lock(&file).map_err(|err| format!("failed to lock audit log {}: {err}", path.display()))?;
+
+        let mut last = None;
+        for line in BufReader::new(&file).lines() {
+            let line = line.map_err(|err| format!("failed to read audit log {}: {err}", path.display()))?;
+            if !line.trim().is_empty() {
+                last = Some(line);
+            }
+        }
+        let (seq, prev) = match last {
+            Some(line) => {
+                let entry: Entry = serde_json::from_str(&line)
+                    .map_err(|err| format!("the last line of audit log {} is not valid: {err}", path.display()))?;
+                (entry.seq, entry.hash)
+            }
+            None => (0, GENESIS.to_string()),
+        };
+        Ok(AuditLog { file, seq, prev })
+    }
+
+    /// Records an event for the key whose SPKI is `spki` and syncs it to disk.
+    pub fn append(
+        &mut self,
+        event: &str,
+        prime: &BigUint,
+        generator: &BigUint,
+        spki: &[u8],
+        metadata: &Metadata,
+    ) -> Result<(), String> {
+        let mut entry = Entry {
+            seq: self.seq + 1,
+            time: metadata::rfc3339_utc(std::time::SystemTime::now()),
+            event: event.to_string(),
+            key_id: fingerprint::key_id(spki),
+            fingerprint: fingerprint::fingerprint(FingerprintHash::Sha256, spki),
+            group: metadata.group.clone(),
+            prime_bits: prime.bits(),
+            params_sha256: hex(&Sha256::digest(der::dh_parameters(prime, generator))),
+            label: metadata.label.clone(),
+            operator: metadata.operator.clone(),
+            tool_version: metadata.tool_version.clone(),
+            prev: self.prev.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.digest();
+
+        let mut line = serde_json::to_string(&entry).expect("audit entry serializes");
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.sync_data())
+            .map_err(|err| format!("failed to write the audit log: {err}"))?;
+        self.seq = entry.seq;
+        self.prev = entry.hash;
+        Ok(())
+    }
+}
+
+/// Recomputes the chain; returns the entry count and the final hash.
+pub fn verify(path: &Path) -> Result<(u64, String), String> {
+    let file = File::open(path).map_err(|err| format!("failed to open audit log {}: {err}", path.display()))?;
+    let (mut seq, mut prev) = (0, GENESIS.to_string());
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|err| format!("failed to read audit log {}: {err}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry =
+            serde_json::from_str(&line).map_err(|err| format!("line {line_number}: not an audit entry: {err}"))?;
+        if entry.seq != seq + 1 {
+            return Err(format!("line {line_number}: sequence {} follows {seq}", entry.seq));
+        }
+        if entry.prev != prev {
+            return Err(format!("line {line_number}: chain broken (prev does not match the previous hash)"));
+        }
+        if entry.hash != entry.digest() {
+            return Err(format!("line {line_number}: hash does not match the entry"));
+        }
+        seq = entry.seq;
+        prev = entry.hash;
+    }
+    Ok((seq, prev))
+}
+
+#[cfg(unix)]
+fn lock(file: &File) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // SAFETY: flock on a descriptor we own; released when the file closes.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_file: &File) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
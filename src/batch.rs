@@ -0,0 +1,112 @@
+//! `--output-dir` layout: one `.priv`/`.pub` pair per key plus a manifest.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use create_private_key::Error;
+
+use crate::output::KeyRecord;
+use crate::sink::{self, Overwrite};
+
+pub const DEFAULT_NAME_PATTERN: &str = "key-{n}";
+const MANIFEST_NAME: &str = "manifest.tsv";
+
+/// Writes key pairs into a directory, appending each one's manifest row as
+/// soon as its files are in place, so the manifest can be followed while a
+/// long batch runs.
+pub struct BatchWriter {
+    dir: PathBuf,
+    pattern: String,
+    index_width: usize,
+    overwrite: Overwrite,
+    manifest_path: PathBuf,
+    manifest: File,
+}
+
+impl BatchWriter {
+    /// Creates the directory if needed. `{n}` in `pattern` is the 1-based
+    /// index, zero-padded to fit `count` (at least 4 digits); `{key_id}` is
+    /// the short key ID.
+    pub fn new(
+        dir: &Path,
+        pattern: &str,
+        count: usize,
+        overwrite: Overwrite,
+    ) -> Result<Self, Error> {
+        if !pattern.contains("{n}") && !pattern.contains("{key_id}") {
+            return Err(Error::Parse("--name-pattern must contain {n} or {key_id} so file names differ".into()));
+        }
+        if pattern.contains('/') || pattern.contains('\\') {
+            return Err(Error::Parse("--name-pattern must not contain path separators".into()));
+        }
+        std::fs::create_dir_all(dir).map_err(|source| Error::Io {
+            context: format!("failed to create {}", dir.display()),
+            source,
+        })?;
+        // A previous batch's manifest is only truncated with --force, as its
+        // key files would be.
+        let manifest_path = dir.join(MANIFEST_NAME);
+        let mut manifest = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .create_new(overwrite == Overwrite::Refuse)
+            .open(&manifest_path)
+            .map_err(|source| match source.kind() {
+                io::ErrorKind::AlreadyExists => sink::already_exists(&manifest_path),
+                _ => Error::Io {
+                    context: format!("failed to create {}", manifest_path.display()),
+                    source,
+                },
+            })?;
+        manifest
+            .write_all(b"name\tkey_id\tfingerprint\n")
+            .map_err(|source| Error::Io {
+                context: format!("failed to write {}", manifest_path.display()),
+                source,
+            })?;
+
+        Ok(BatchWriter {
+            dir: dir.to_path_buf(),
+            pattern: pattern.to_string(),
+            index_width: count.to_string().len().max(4),
+            overwrite,
+            manifest_path,
+            manifest,
+        })
+    }
+
+    /// Writes `record`'s files, with `private_key_file` (already encrypted
+    /// if the batch is protected) as the private one.
+    pub fn write(&mut self, index: usize, record: &KeyRecord, private_key_file: &[u8]) -> Result<(), Error> {
+        let name = self
+            .pattern
+            .replace("{n}", &format!("{index:0width$}", width = self.index_width))
+            .replace("{key_id}", &record.key_id);
+
+        let private_path = self.dir.join(format!("{name}.priv"));
+        sink::write_private_file(&private_path, private_key_file, self.overwrite)?;
+        let public_path = self.dir.join(format!("{name}.pub"));
+        sink::write_public_file(&public_path, record.public_key_pem().as_bytes())?;
+        if let Some(ref document) = record.provenance {
+            sink::write_public_file(&self.dir.join(format!("{name}.provenance")), document.as_bytes())?;
+        }
+
+        // One unbuffered write per row, so a reader never sees half of one.
+        let row = format!("{name}\t{}\t{}\n", record.key_id, record.fingerprint);
+        self.manifest.write_all(row.as_bytes()).map_err(|source| Error::Io {
+            context: format!("failed to write {}", self.manifest_path.display()),
+            source,
+        })
+    }
+
+    /// Syncs the manifest and returns its path.
+    pub fn finish(self) -> Result<PathBuf, Error> {
+        self.manifest.sync_all().map_err(|source| Error::Io {
+            context: format!("failed to write {}", self.manifest_path.display()),
+            source,
+        })?;
+        Ok(self.manifest_path)
+    }
+}
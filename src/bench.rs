@@ -0,0 +1,123 @@
+//! `bench`: throughput of the expensive operations on each built-in group,
+//! for comparing hosts and catching performance regressions.
+
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use create_private_key::params::{self, DhGroup};
+use create_private_key::{DhParams, Error, dh, generate_keypair};
+use serde::Serialize;
+
+use crate::random;
+
+/// Short private exponents measured next to full-size ones, the size
+/// `--private-bits` is most often given.
+const SHORT_PRIVATE_BITS: u64 = 256;
+/// Every operation runs at least this often, however slow.
+const MIN_ITERATIONS: u32 = 3;
+
+#[derive(Debug, Serialize)]
+pub struct Measurement {
+    pub operation: &'static str,
+    pub group: &'static str,
+    pub prime_bits: u64,
+    pub iterations: u32,
+    pub mean_us: f64,
+    pub ops_per_sec: f64,
+}
+
+/// Runs each operation on each group for about `budget`.
+pub fn run(budget: Duration) -> Result<Vec<Measurement>, Error> {
+    let mut results = Vec::new();
+    for &group in DhGroup::value_variants() {
+        let full = DhParams::group(group);
+        let short = full.clone().with_private_bits(SHORT_PRIVATE_BITS)?;
+        let peer = generate_keypair(&full, &mut random::Source)?;
+        let ours = generate_keypair(&full, &mut random::Source)?;
+        let ours_short = generate_keypair(&short, &mut random::Source)?;
+        let mut measure = |operation, run: &mut dyn FnMut() -> Result<(), Error>| -> Result<(), Error> {
+            let (iterations, elapsed) = repeat(budget, run)?;
+            let mean = elapsed.as_secs_f64() / f64::from(iterations);
+            results.push(Measurement {
+                operation,
+                group: group.name(),
+                prime_bits: full.prime().bits(),
+                iterations,
+                mean_us: mean * 1e6,
+                ops_per_sec: 1.0 / mean,
+            });
+            Ok(())
+        };
+        measure("keygen", &mut || generate_keypair(&full, &mut random::Source).map(drop))?;
+        measure("keygen-256", &mut || generate_keypair(&short, &mut random::Source).map(drop))?;
+        measure("shared-secret", &mut || ours.shared_secret(peer.public_key()).map(drop))?;
+        measure("shared-secret-256", &mut || ours_short.shared_secret(peer.public_key()).map(drop))?;
+        measure("validate", &mut || {
+            params::validate(full.prime(), full.generator())?;
+            dh::check_peer(full.prime(), peer.public_key())
+        })?;
+    }
+    Ok(results)
+}
+
+fn repeat(budget: Duration, run: &mut dyn FnMut() -> Result<(), Error>) -> Result<(u32, Duration), Error> {
+    let started = Instant::now();
+    let mut iterations = 0;
+    while iterations < MIN_ITERATIONS || started.elapsed() < budget {
+        run()?;
+        iterations += 1;
+    }
+    Ok((iterations, started.elapsed()))
+}
+
+/// The results as an aligned table under a line describing the host.
+pub fn render_table(host: &Host, results: &[Measurement]) -> String {
+    let mut table = format!(
+        "# create-private-key {}, {} {}, cpus={}\n",
+        host.version, host.os, host.arch, host.cpus
+    );
+    table.push_str(&format!(
+        "{:<18} {:<8} {:>5} {:>10} {:>12} {:>12}\n",
+        "operation", "group", "bits", "iterations", "mean", "ops/s"
+    ));
+    for result in results {
+        table.push_str(&format!(
+            "{:<18} {:<8} {:>5} {:>10} {:>12} {:>12.1}\n",
+            result.operation,
+            result.group,
+            result.prime_bits,
+            result.iterations,
+            format_mean(result.mean_us),
+            result.ops_per_sec
+        ));
+    }
+    table
+}
+
+fn format_mean(mean_us: f64) -> String {
+    if mean_us >= 1000.0 {
+        format!("{:.2} ms", mean_us / 1000.0)
+    } else {
+        format!("{mean_us:.1} us")
+    }
+}
+
+/// The host details that make results from different machines comparable.
+#[derive(Debug, Serialize)]
+pub struct Host {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub cpus: usize,
+}
+
+impl Host {
+    pub fn current() -> Self {
+        Host {
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            cpus: std::thread::available_parallelism().map_or(1, usize::from),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! `--copy`: puts a key on the system clipboard through the platform's
+//! clipboard tools, and clears it again after a while.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::output::{KeyRecord, to_even_length_hex};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CopyTarget {
+    Private,
+    Public,
+}
+
+impl CopyTarget {
+    pub fn payload(self, record: &KeyRecord) -> String {
+        match self {
+            CopyTarget::Private => to_even_length_hex(record.private_key.expose()),
+            CopyTarget::Public => to_even_length_hex(&record.public_key),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CopyTarget::Private => "private",
+            CopyTarget::Public => "public",
+        }
+    }
+}
+
+/// A program that writes the clipboard from stdin and one that prints it.
+struct Tool {
+    copy: (&'static str, &'static [&'static str]),
+    paste: (&'static str, &'static [&'static str]),
+}
+
+fn tool() -> Result<Tool, String> {
+    if cfg!(target_os = "macos") {
+        return Ok(Tool {
+            copy: ("pbcopy", &[]),
+            paste: ("pbpaste", &[]),
+        });
+    }
+    if cfg!(windows) {
+        return Ok(Tool {
+            copy: ("clip.exe", &[]),
+            paste: ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"]),
+        });
+    }
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && on_path("wl-copy") {
+        return Ok(Tool {
+            copy: ("wl-copy", &[]),
+            paste: ("wl-paste", &["--no-newline"]),
+        });
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        if on_path("xclip") {
+            return Ok(Tool {
+                copy: ("xclip", &["-selection", "clipboard"]),
+                paste: ("xclip", &["-selection", "clipboard", "-o"]),
+            });
+        }
+        if on_path("xsel") {
+            return Ok(Tool {
+                copy: ("xsel", &["--clipboard", "--input"]),
+                paste: ("xsel", &["--clipboard", "--output"]),
+            });
+        }
+    }
+    Err("no clipboard found: --copy needs wl-copy (Wayland), xclip or xsel (X11), pbcopy or clip.exe".into())
+}
+
+/// Fails when there is no clipboard tool, so that can be found out before
+/// a key is generated.
+pub fn ensure_available() -> Result<(), String> {
+    tool().map(|_| ())
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| Path::new(&dir).join(program).is_file()))
+}
+
+/// Replaces the clipboard contents with `text`.
+pub fn copy(text: &str) -> Result<(), String> {
+    let (program, args) = tool()?.copy;
+    // X11 and Wayland tools stay behind to serve the selection, so their
+    // output cannot be piped back or waiting for it would never end.
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("failed to run `{program}`: {err}"))?;
+    let written = child.stdin.take().expect("stdin is piped").write_all(text.as_bytes());
+    let status = child.wait().map_err(|err| format!("failed to run `{program}`: {err}"))?;
+    written.map_err(|err| format!("failed to write to `{program}`: {err}"))?;
+    if !status.success() {
+        return Err(format!("`{program}` failed ({status})"));
+    }
+    Ok(())
+}
+
+/// Starts a detached copy of this program that clears the clipboard after
+/// `seconds`, unless something else has been copied by then. It is given
+/// only a digest of `text`, on stdin.
+pub fn schedule_clear(text: &str, seconds: u64) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|err| format!("failed to find this program: {err}"))?;
+    let mut command = Command::new(exe);
+    command
+        .args(["clipboard-clear", "--after", &seconds.to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Out of the terminal's process group, so ^C on the shell does not
+    // take it down early.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn().map_err(|err| format!("failed to start the clipboard timer: {err}"))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(digest(text).as_bytes())
+        .map_err(|err| format!("failed to start the clipboard timer: {err}"))
+}
+
+/// `clipboard-clear`: the other half of [`schedule_clear`].
+pub fn clear_later(seconds: u64) -> Result<(), String> {
+    let mut expected = String::new();
+    std::io::stdin()
+        .read_to_string(&mut expected)
+        .map_err(|err| format!("failed to read stdin: {err}"))?;
+    std::thread::sleep(Duration::from_secs(seconds));
+    let (program, args) = tool()?.paste;
+    // If the clipboard cannot be read back, clearing it is the safe side.
+    let pasted = Command::new(program).args(args).stdin(Stdio::null()).stderr(Stdio::null()).output();
+    if let Some(mut output) = pasted.ok().filter(|output| output.status.success()) {
+        let unchanged = digest(String::from_utf8_lossy(&output.stdout).trim_end()) == expected.trim();
+        output.stdout.zeroize();
+        if !unchanged {
+            return Ok(());
+        }
+    }
+    copy("")
+}
+
+fn digest(text: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(text.as_bytes()))
+}
@@ -0,0 +1,137 @@
+//! `--compliance-report`: which standards the generated keys meet, in a
+//! form that can be attached to a change ticket.
+
+use clap::ValueEnum;
+use create_private_key::params::DhGroup;
+use create_private_key::{DhParams, Error};
+use num_bigint::BigUint;
+use num_traits::One;
+use serde::Serialize;
+
+use crate::estimate::Estimate;
+use crate::{param_cache, primality};
+
+/// NIST SP 800-57 Part 1 Rev. 5, Table 2: finite-field prime sizes (L) and
+/// private exponent sizes (N) with the security strength they provide.
+const PRIME_STRENGTHS: [(u64, u64); 5] = [(1024, 80), (2048, 112), (3072, 128), (7680, 192), (15360, 256)];
+const PRIVATE_STRENGTHS: [(u64, u64); 5] = [(160, 80), (224, 112), (256, 128), (384, 192), (512, 256)];
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// The built-in group's name, or `custom`.
+    pub group: &'static str,
+    pub prime_bits: u64,
+    pub private_bits: u64,
+    /// Security strength in bits: the lower of what the prime and the
+    /// private exponent provide.
+    pub security_strength: u64,
+    /// The SP 800-57 verdict for that strength.
+    pub sp800_57: &'static str,
+    /// The cheapest discrete-log attack's cost in bits (see `estimate`), and
+    /// which attack it is.
+    pub estimated_strength: f64,
+    pub cheapest_attack: &'static str,
+    pub safe_prime: &'static str,
+    pub generator_order: &'static str,
+    /// The checks this run performed, in order.
+    pub validation: Vec<String>,
+}
+
+/// The strength of the last row of `table` that `bits` reaches; 0 below
+/// the first.
+fn strength(table: &[(u64, u64)], bits: u64) -> u64 {
+    table.iter().rev().find(|&&(size, _)| bits >= size).map_or(0, |&(_, strength)| strength)
+}
+
+/// The bits of security of a group with a `prime_bits` prime and
+/// `private_bits` exponents, the lower of what each provides.
+pub fn security_strength(prime_bits: u64, private_bits: u64) -> u64 {
+    strength(&PRIME_STRENGTHS, prime_bits).min(strength(&PRIVATE_STRENGTHS, private_bits))
+}
+
+impl Report {
+    /// Describes `params`, testing a custom prime for being a safe prime
+    /// with `bases`. `validation` lists the checks done before the report
+    /// was asked for.
+    pub fn new(
+        params: &DhParams,
+        mut validation: Vec<String>,
+        bases: primality::Bases,
+        rounds: u32,
+    ) -> Result<Report, Error> {
+        let prime = params.prime();
+        let prime_bits = prime.bits();
+        let private_bits = params.private_bits().unwrap_or(prime_bits);
+        let security_strength = security_strength(prime_bits, private_bits);
+        let sp800_57 = match security_strength {
+            0..112 => "disallowed: below 112 bits of security",
+            112..128 => "acceptable through 2030; 128 bits of security are required after",
+            _ => "acceptable through 2030 and beyond",
+        };
+        let group = DhGroup::value_variants()
+            .iter()
+            .copied()
+            .find(|&group| DhParams::group(group).into_parts() == (prime.clone(), params.generator().clone()));
+        let safe_prime = match group {
+            Some(_) => "yes: RFC 3526 group, listed in SP 800-56A Rev. 3 Appendix D",
+            None => {
+                let source = match bases {
+                    primality::Bases::Random => "random bases",
+                    primality::Bases::Derived => "bases derived from p",
+                };
+                let verdict = param_cache::is_safe_prime(prime, params.generator(), rounds, bases)?;
+                let cached = if verdict.cached { " (cached from an earlier run)" } else { "" };
+                validation.push(format!(
+                    "safe prime: Miller-Rabin, {rounds} rounds each on p and (p - 1) / 2, {source}{cached}"
+                ));
+                if verdict.safe {
+                    "yes: tested with Miller-Rabin"
+                } else {
+                    "no: p or (p - 1) / 2 is composite"
+                }
+            }
+        };
+        validation.push("generator order: g^q mod p with q = (p - 1) / 2".into());
+        let q: BigUint = (prime - 1u32) >> 1;
+        let generator_order = if params.generator().modpow(&q, prime).is_one() {
+            "q: generates the prime-order subgroup when p is a safe prime"
+        } else {
+            "not q: g does not lie in the subgroup of order (p - 1) / 2"
+        };
+        let estimate = Estimate::new(params);
+        Ok(Report {
+            group: group.map_or("custom", DhGroup::name),
+            prime_bits,
+            private_bits,
+            security_strength,
+            sp800_57,
+            estimated_strength: estimate.estimated_strength,
+            cheapest_attack: estimate.cheapest_attack,
+            safe_prime,
+            generator_order,
+            validation,
+        })
+    }
+
+    /// `key=value` lines under a `[compliance]` heading, one `validation`
+    /// line per check.
+    pub fn render_text(&self) -> String {
+        let mut text = format!(
+            "[compliance]\ngroup={}\nprime_bits={}\nprivate_bits={}\nsecurity_strength={}\nsp800_57={}\n\
+             estimated_strength={:.1}\ncheapest_attack={}\nsafe_prime={}\ngenerator_order={}\n",
+            self.group,
+            self.prime_bits,
+            self.private_bits,
+            self.security_strength,
+            self.sp800_57,
+            self.estimated_strength,
+            self.cheapest_attack,
+            self.safe_prime,
+            self.generator_order
+        );
+        for step in &self.validation {
+            text.push_str(&format!("validation={step}\n"));
+        }
+        text
+    }
+}
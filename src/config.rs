@@ -0,0 +1,217 @@
+//! `config.toml`: defaults for the command line options, and a policy the
+//! generated keys must meet, shared by everyone using one file.
+//!
+//! Values from the file become the options' defaults, so anything given on
+//! the command line still wins.
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use create_private_key::params::DhGroup;
+use create_private_key::{DhParams, Error};
+use serde::Deserialize;
+
+use crate::output::OutputFormat;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// `--group` everywhere a group can be chosen.
+    group: Option<String>,
+    /// `--private-format` of `generate`.
+    format: Option<String>,
+    /// `--public-format` of `generate`.
+    public_format: Option<String>,
+    /// `--private-bits` of `generate`.
+    private_bits: Option<u64>,
+    /// `--output-dir` of `generate`, unless another destination is given.
+    output_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub policy: Policy,
+    /// The file this was read from, if any.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+/// Requirements on every key `generate` creates.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Policy {
+    /// Smallest prime accepted, in bits.
+    min_prime_bits: Option<u64>,
+    /// Smallest private exponent accepted, in bits; full-size exponents
+    /// count as the prime's size.
+    min_private_bits: Option<u64>,
+    /// Whether `--prime` may replace the built-in groups.
+    #[serde(default = "allowed")]
+    allow_custom_prime: bool,
+}
+
+fn allowed() -> bool {
+    true
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            min_prime_bits: None,
+            min_private_bits: None,
+            allow_custom_prime: true,
+        }
+    }
+}
+
+/// Parameters that are valid but that the configured policy refuses; the
+/// CLI gives these their own exit code.
+#[derive(Debug)]
+pub struct Violation(String);
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (configured policy)", self.0)
+    }
+}
+
+impl Policy {
+    /// Rejects parameters the policy does not allow; `custom` is set when
+    /// they came from `--prime`.
+    pub fn check(&self, params: &DhParams, custom: bool) -> Result<(), Violation> {
+        if custom && !self.allow_custom_prime {
+            return Err(Violation("custom primes are not allowed".into()));
+        }
+        let prime_bits = params.prime().bits();
+        if let Some(min) = self.min_prime_bits.filter(|&min| prime_bits < min) {
+            return Err(Violation(format!("the prime is {prime_bits} bits, below the minimum of {min}")));
+        }
+        let private_bits = params.private_bits().unwrap_or(prime_bits);
+        if let Some(min) = self.min_private_bits.filter(|&min| private_bits < min) {
+            return Err(Violation(format!(
+                "private exponents are {private_bits} bits, below the minimum of {min}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/create-private-key/config.toml`, falling back to
+/// `~/.config`.
+pub fn default_path() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("create-private-key").join("config.toml")
+}
+
+/// Finds `--config FILE` before the real parse, since the file decides
+/// the defaults that parse uses. Errors are left for it to report.
+pub fn requested_path(command: clap::Command) -> Option<PathBuf> {
+    let command = command.ignore_errors(true).disable_help_flag(true);
+    let matches = command.try_get_matches().ok()?;
+    let mut matches = &matches;
+    while let Some((_, sub)) = matches.subcommand() {
+        matches = sub;
+    }
+    matches.get_one::<PathBuf>("config").cloned()
+}
+
+impl Config {
+    /// Reads `path`, or the default file when it exists.
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let default = default_path();
+        let path = match path {
+            Some(path) => path,
+            None if default.is_file() => &default,
+            None => return Ok(Config::default()),
+        };
+        let text = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            context: format!("failed to read config file {}", path.display()),
+            source,
+        })?;
+        let mut config: Config =
+            toml::from_str(&text).map_err(|err| Error::Parse(format!("{}: {}", path.display(), err.message())))?;
+        if let Some(group) = &config.group {
+            DhGroup::from_str(group, false).map_err(|err| Error::Parse(format!("{}: group: {err}", path.display())))?;
+        }
+        for (key, format) in [("format", &config.format), ("public-format", &config.public_format)] {
+            if let Some(format) = format {
+                OutputFormat::from_str(format, false)
+                    .map_err(|err| Error::Parse(format!("{}: {key}: {err}", path.display())))?;
+            }
+        }
+        config.source = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Argument IDs and the default values the file gives them.
+    fn defaults(&self) -> Vec<(&'static str, String)> {
+        let mut defaults = Vec::new();
+        if let Some(group) = &self.group {
+            defaults.push(("group", group.clone()));
+        }
+        if let Some(format) = &self.format {
+            defaults.push(("output_format", format.clone()));
+        }
+        if let Some(format) = &self.public_format {
+            defaults.push(("public_format", format.clone()));
+        }
+        if let Some(bits) = self.private_bits {
+            defaults.push(("private_bits", bits.to_string()));
+        }
+        if let Some(dir) = &self.output_dir {
+            defaults.push(("output_dir", dir.display().to_string()));
+        }
+        defaults
+    }
+
+    /// Makes the file's values the defaults of the matching arguments of
+    /// `command` and all its subcommands.
+    pub fn apply(&self, command: clap::Command) -> clap::Command {
+        with_defaults(command, &self.defaults())
+    }
+}
+
+fn with_defaults(mut command: clap::Command, defaults: &[(&'static str, String)]) -> clap::Command {
+    for (id, value) in defaults {
+        if command.get_arguments().any(|arg| arg.get_id() == id) {
+            command = command.mut_arg(id, |arg| arg.default_value(value.clone()));
+        }
+    }
+    let names: Vec<String> = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+    for name in names {
+        command = command.mut_subcommand(name, |sub| with_defaults(sub, defaults));
+    }
+    command
+}
+
+/// Whether the default of argument `id` in the command that ran yields to
+/// an option given explicitly that conflicts with it, such as a configured
+/// `output-dir` when `--output-private` is given.
+#[cfg(feature = "pem")]
+pub fn displaced(command: &clap::Command, matches: &clap::ArgMatches, id: &str) -> bool {
+    use clap::parser::ValueSource;
+
+    let (command, matches) = match matches.subcommand() {
+        Some((name, sub)) => match command.find_subcommand(name) {
+            Some(command) => (command, sub),
+            None => return false,
+        },
+        None => (command, matches),
+    };
+    let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else {
+        return false;
+    };
+    if matches.value_source(id) != Some(ValueSource::DefaultValue) {
+        return false;
+    }
+    command.get_arguments().any(|other| {
+        let explicit = matches!(
+            matches.value_source(other.get_id().as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        explicit
+            && (command.get_arg_conflicts_with(arg).contains(&other)
+                || command.get_arg_conflicts_with(other).contains(&arg))
+    })
+}
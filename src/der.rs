@@ -0,0 +1,194 @@
+//! Minimal DER encoding for the handful of ASN.1 structures the tool emits.
+
+use num_bigint::BigUint;
+
+use crate::error::{Error, Result};
+
+/// dhKeyAgreement (PKCS #3), as used by OpenSSL for "DH" keys.
+const DH_KEY_AGREEMENT_OID: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x03, 0x01];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+
+fn encode_length(len: usize, out: &mut Vec<u8>) {
+    if len < 0x80 {
+        out.push(len as u8);
+        return;
+    }
+    let bytes = len.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    out.push(0x80 | (bytes.len() - skip) as u8);
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 6);
+    out.push(tag);
+    encode_length(content.len(), &mut out);
+    out.extend_from_slice(content);
+    out
+}
+
+pub fn integer(value: &BigUint) -> Vec<u8> {
+    let mut bytes = value.to_bytes_be();
+    if bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+    tlv(TAG_INTEGER, &bytes)
+}
+
+pub fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    tlv(TAG_SEQUENCE, &items.concat())
+}
+
+pub fn octet_string(content: &[u8]) -> Vec<u8> {
+    tlv(TAG_OCTET_STRING, content)
+}
+
+/// OBJECT IDENTIFIER from its encoded content bytes.
+pub fn oid(content: &[u8]) -> Vec<u8> {
+    tlv(TAG_OID, content)
+}
+
+pub fn null() -> Vec<u8> {
+    vec![0x05, 0x00]
+}
+
+fn bit_string(content: &[u8]) -> Vec<u8> {
+    let mut bits = Vec::with_capacity(content.len() + 1);
+    bits.push(0);
+    bits.extend_from_slice(content);
+    tlv(TAG_BIT_STRING, &bits)
+}
+
+/// PKCS #3 `DHParameter ::= SEQUENCE { prime INTEGER, base INTEGER }`.
+pub fn dh_parameters(prime: &BigUint, generator: &BigUint) -> Vec<u8> {
+    sequence(&[integer(prime), integer(generator)])
+}
+
+fn dh_algorithm_identifier(prime: &BigUint, generator: &BigUint) -> Vec<u8> {
+    sequence(&[tlv(TAG_OID, DH_KEY_AGREEMENT_OID), dh_parameters(prime, generator)])
+}
+
+/// X.509 `SubjectPublicKeyInfo` for a DH public key.
+pub fn dh_subject_public_key_info(prime: &BigUint, generator: &BigUint, public_key: &BigUint) -> Vec<u8> {
+    sequence(&[
+        dh_algorithm_identifier(prime, generator),
+        bit_string(&integer(public_key)),
+    ])
+}
+
+/// PKCS #8 `PrivateKeyInfo` for a DH private key.
+pub fn dh_private_key_info(prime: &BigUint, generator: &BigUint, private_key: &BigUint) -> Vec<u8> {
+    sequence(&[
+        integer(&BigUint::from(0u32)),
+        dh_algorithm_identifier(prime, generator),
+        octet_string(&integer(private_key)),
+    ])
+}
+
+/// Cursor over DER input that reads one element at a time.
+struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Reader { input }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// Reads the next element, which must carry `tag`, and returns its content.
+    fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let (&actual, rest) = self.input.split_first().ok_or_else(|| Error::Parse("truncated DER input".into()))?;
+        if actual != tag {
+            return Err(Error::Parse(format!("unexpected DER tag 0x{actual:02X} (expected 0x{tag:02X})")));
+        }
+        let (&first, mut rest) = rest.split_first().ok_or_else(|| Error::Parse("truncated DER length".into()))?;
+        let len = if first < 0x80 {
+            usize::from(first)
+        } else {
+            let count = usize::from(first & 0x7F);
+            if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+                return Err(Error::Parse("invalid DER length".into()));
+            }
+            let len = rest[..count].iter().fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+            rest = &rest[count..];
+            len
+        };
+        if rest.len() < len {
+            return Err(Error::Parse("truncated DER content".into()));
+        }
+        let (content, rest) = rest.split_at(len);
+        self.input = rest;
+        Ok(content)
+    }
+
+    fn read_integer(&mut self) -> Result<BigUint> {
+        let content = self.read(TAG_INTEGER)?;
+        if content.first().is_some_and(|&b| b & 0x80 != 0) {
+            return Err(Error::Parse("negative DER integer".into()));
+        }
+        Ok(BigUint::from_bytes_be(content))
+    }
+}
+
+/// Parses DH group parameters, returning `(prime, generator, order)`: a
+/// PKCS #3 `DHParameter` (`p`, `g`, optional `privateValueLength`) or an
+/// X9.42 `DomainParameters` from RFC 3279 (`p`, `g`, `q`, ...). The third
+/// integer tells them apart: a `privateValueLength` fits in 64 bits and a
+/// subgroup order never does.
+pub fn parse_dh_parameters(input: &[u8]) -> Result<(BigUint, BigUint, Option<BigUint>)> {
+    let mut outer = Reader::new(input);
+    let mut parameters = Reader::new(outer.read(TAG_SEQUENCE)?);
+    if !outer.is_empty() {
+        return Err(Error::Parse("trailing data after DH parameters".into()));
+    }
+    let prime = parameters.read_integer()?;
+    let generator = parameters.read_integer()?;
+    if parameters.is_empty() {
+        return Ok((prime, generator, None));
+    }
+    let third = parameters.read_integer()?;
+    if third.bits() > 64 {
+        // The cofactor and validation parameters after q are not needed.
+        return Ok((prime, generator, Some(third)));
+    }
+    if !parameters.is_empty() {
+        return Err(Error::Parse("trailing data after PKCS#3 DH parameters".into()));
+    }
+    Ok((prime, generator, None))
+}
+
+/// Parses a PKCS #8 `PrivateKeyInfo` holding a DH key, returning
+/// `(prime, generator, private_key)`.
+pub fn parse_dh_private_key_info(input: &[u8]) -> Result<(BigUint, BigUint, BigUint)> {
+    let mut outer = Reader::new(input);
+    let mut info = Reader::new(outer.read(TAG_SEQUENCE)?);
+    if !outer.is_empty() {
+        return Err(Error::Parse("trailing data after PKCS#8 structure".into()));
+    }
+
+    let version = info.read_integer()?;
+    if version > BigUint::from(1u32) {
+        return Err(Error::Parse(format!("unsupported PKCS#8 version {version}")));
+    }
+
+    let mut algorithm = Reader::new(info.read(TAG_SEQUENCE)?);
+    if algorithm.read(TAG_OID)? != DH_KEY_AGREEMENT_OID {
+        return Err(Error::Parse("PKCS#8 key is not a PKCS#3 DH key".into()));
+    }
+    // The optional privateValueLength after p and g is ignored.
+    let mut parameters = Reader::new(algorithm.read(TAG_SEQUENCE)?);
+    let prime = parameters.read_integer()?;
+    let generator = parameters.read_integer()?;
+
+    let private_key = Reader::new(info.read(TAG_OCTET_STRING)?).read_integer()?;
+    Ok((prime, generator, private_key))
+}
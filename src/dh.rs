@@ -0,0 +1,42 @@
+//! Diffie-Hellman operations on existing keys.
+
+use create_private_key_core as ffdh;
+use hkdf::Hkdf;
+use num_bigint::BigUint;
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+
+/// Rejects peer public values outside `2..=p-2`, which would force the
+/// shared secret to 0, 1 or `p-1`.
+pub fn check_peer(prime: &BigUint, peer: &BigUint) -> Result<()> {
+    ffdh::check_peer(prime, peer).inspect_err(|_| {
+        tracing::debug!(prime_bits = prime.bits(), "rejected a peer public key outside [2, p-2]");
+    })?;
+    Ok(())
+}
+
+/// `peer^private mod p`, left-padded to the byte length of the prime.
+pub fn shared_secret(prime: &BigUint, private_key: &BigUint, peer: &BigUint) -> Result<Vec<u8>> {
+    check_peer(prime, peer)?;
+    Ok(ffdh::shared_secret(prime, private_key, peer)?)
+}
+
+/// The most HKDF-SHA256 can expand to: 255 blocks of 32 bytes.
+pub const MAX_HKDF_LENGTH: usize = 255 * 32;
+
+/// HKDF-SHA256 (RFC 5869) of a shared secret. The length is checked before
+/// the output is allocated, since it may come from a client.
+pub fn hkdf_sha256(secret: &[u8], salt: &[u8], info: &[u8], length: usize) -> Result<Vec<u8>> {
+    if length > MAX_HKDF_LENGTH {
+        return Err(Error::Validation(format!(
+            "HKDF output length {length} is too long (at most {MAX_HKDF_LENGTH} bytes)"
+        )));
+    }
+    let salt = if salt.is_empty() { None } else { Some(salt) };
+    let mut output = vec![0u8; length];
+    Hkdf::<Sha256>::new(salt, secret)
+        .expand(info, &mut output)
+        .map_err(|_| Error::Validation(format!("HKDF output length {length} is too long")))?;
+    Ok(output)
+}
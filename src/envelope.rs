@@ -0,0 +1,77 @@
+//! `cpk-v1`: the tool's own versioned JSON key document, readable by `--import`.
+
+use create_private_key::Error;
+use create_private_key_core as ffdh;
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::Metadata;
+use crate::output::{Imported, KeyRecord, to_even_length_hex};
+
+pub const FORMAT_V1: &str = "cpk-v1";
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    format: String,
+    /// Hex, no prefix.
+    prime: String,
+    /// Hex, no prefix.
+    generator: String,
+    /// Hex, no prefix.
+    private_key: String,
+    /// Hex, no prefix. Recomputed and checked on import.
+    public_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+}
+
+/// Serializes a record as a pretty-printed `cpk-v1` document.
+pub fn render(record: &KeyRecord) -> String {
+    let envelope = Envelope {
+        format: FORMAT_V1.to_string(),
+        prime: to_even_length_hex(&record.prime),
+        generator: to_even_length_hex(&record.generator),
+        private_key: to_even_length_hex(record.private_key.expose()),
+        public_key: to_even_length_hex(&record.public_key),
+        metadata: record.metadata.clone(),
+    };
+    let mut rendered = serde_json::to_string_pretty(&envelope).expect("envelope serializes");
+    rendered.push('\n');
+    rendered
+}
+
+/// Parses a `cpk-v1` document. The public key is recomputed from the private
+/// key and must match, so a corrupted or hand-edited file is rejected.
+pub fn parse(text: &str) -> Result<Imported, Error> {
+    let envelope: Envelope =
+        serde_json::from_str(text).map_err(|err| Error::Parse(format!("invalid key envelope: {err}")))?;
+    if envelope.format != FORMAT_V1 {
+        return Err(Error::Parse(format!(
+            "unsupported key envelope format '{}' (expected {FORMAT_V1})",
+            envelope.format
+        )));
+    }
+
+    let field = |name: &str, hex: &str| {
+        BigUint::parse_bytes(hex.as_bytes(), 16)
+            .ok_or_else(|| Error::Parse(format!("envelope field '{name}' is not valid hex")))
+    };
+    let prime = field("prime", &envelope.prime)?;
+    let generator = field("generator", &envelope.generator)?;
+    let private_key = field("private_key", &envelope.private_key)?;
+    let public_key = field("public_key", &envelope.public_key)?;
+
+    if prime <= BigUint::from(3u32) {
+        return Err(Error::Validation("envelope prime modulus must be greater than 3".into()));
+    }
+    if ffdh::public_key(&prime, &generator, &private_key) != public_key {
+        return Err(Error::Validation("envelope public key does not match its private key".into()));
+    }
+
+    Ok(Imported {
+        prime,
+        generator,
+        private_key,
+        metadata: envelope.metadata,
+    })
+}
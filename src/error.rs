@@ -0,0 +1,37 @@
+//! The library's error type.
+
+/// What went wrong, by class; the CLI maps each class to its own exit code.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Input that could not be decoded: a number, PEM block or DER structure.
+    #[error("{0}")]
+    Parse(String),
+    /// Well-formed values that fail a check, such as a generator outside the
+    /// group or a peer public key out of range.
+    #[error("{0}")]
+    Validation(String),
+    /// The random number generator reported a failure.
+    #[error("random number generator failed: {0}")]
+    Rng(String),
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<create_private_key_core::Rejection> for Error {
+    fn from(rejection: create_private_key_core::Rejection) -> Self {
+        Error::Validation(rejection.to_string())
+    }
+}
+
+/// For callers that only report the message.
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.to_string()
+    }
+}
@@ -0,0 +1,112 @@
+//! `estimate`: the symmetric-equivalent strength of a group and how much a
+//! discrete-log attack on one of its keys would cost, for design documents
+//! that have to justify a key size.
+//!
+//! Three attacks are costed and the cheapest one sets the strength: the
+//! number field sieve on `p`, Pollard's rho in the subgroup of order `q`, and
+//! Pollard's kangaroo on a short private exponent. The figures are
+//! asymptotic estimates in the style of SP 800-57, not predictions.
+
+use create_private_key::DhParams;
+use num_bigint::BigUint;
+use serde::Serialize;
+
+use crate::compliance;
+use crate::known_groups::KnownGroup;
+
+/// Operations one core is taken to do per second when turning an operation
+/// count into core-years.
+const OPERATIONS_PER_CORE_SECOND: f64 = 1e9;
+
+const SECONDS_PER_YEAR: f64 = 365.25 * 24.0 * 3600.0;
+
+#[derive(Debug, Serialize)]
+pub struct Estimate {
+    /// The published group whose prime this is, or `custom`.
+    pub group: &'static str,
+    pub prime_bits: u64,
+    /// Size of `q`: the published order, or `(p - 1) / 2` as for a safe prime.
+    pub subgroup_bits: u64,
+    pub private_bits: u64,
+    /// log2 of each attack's cost.
+    pub nfs_strength: f64,
+    pub rho_strength: f64,
+    pub kangaroo_strength: f64,
+    /// The cheapest of the three, and which attack it is.
+    pub estimated_strength: f64,
+    pub cheapest_attack: &'static str,
+    /// What that many operations take at 10^9 per core-second.
+    pub attack_core_years: f64,
+    /// The SP 800-57 table's strength for the same sizes, for comparison.
+    pub sp800_57_strength: u64,
+}
+
+/// log2 of the number field sieve's `L_p[1/3, (64/9)^(1/3)]`, with the o(1)
+/// term taken as the constant that makes a 1024-bit prime 80 bits, the SP
+/// 800-57 figure.
+fn nfs_strength(prime_bits: u64) -> f64 {
+    fn log2_l(bits: f64) -> f64 {
+        let ln_p = bits * std::f64::consts::LN_2;
+        (64.0f64 / 9.0).cbrt() * ln_p.cbrt() * ln_p.ln().powf(2.0 / 3.0) / std::f64::consts::LN_2
+    }
+    (log2_l(prime_bits as f64) - (log2_l(1024.0) - 80.0)).max(0.0)
+}
+
+/// One decimal place, as the figures are printed.
+fn rounded(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+impl Estimate {
+    pub fn new(params: &DhParams) -> Estimate {
+        let prime = params.prime();
+        let prime_bits = prime.bits();
+        let known = KnownGroup::by_prime(prime);
+        let order: BigUint = known.map_or_else(|| (prime - 1u32) >> 1, KnownGroup::order);
+        let subgroup_bits = order.bits();
+        let private_bits = params.private_bits().unwrap_or(prime_bits);
+        let nfs = nfs_strength(prime_bits);
+        // Rho takes about sqrt(q) steps; the kangaroo about 2 sqrt(2^N).
+        let rho = subgroup_bits as f64 / 2.0;
+        let kangaroo = private_bits as f64 / 2.0 + 1.0;
+        let (estimated_strength, cheapest_attack) = [(nfs, "nfs"), (rho, "rho"), (kangaroo, "kangaroo")]
+            .into_iter()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .expect("three attacks");
+        let core_years = estimated_strength.exp2() / OPERATIONS_PER_CORE_SECOND / SECONDS_PER_YEAR;
+        Estimate {
+            group: known.map_or("custom", |group| group.name),
+            prime_bits,
+            subgroup_bits,
+            private_bits,
+            nfs_strength: rounded(nfs),
+            rho_strength: rounded(rho),
+            kangaroo_strength: rounded(kangaroo),
+            estimated_strength: rounded(estimated_strength),
+            cheapest_attack,
+            attack_core_years: core_years,
+            sp800_57_strength: compliance::security_strength(prime_bits, private_bits),
+        }
+    }
+
+    /// `key=value` lines under an `[estimate]` heading.
+    pub fn render_text(&self) -> String {
+        format!(
+            "[estimate]\ngroup={}\nprime_bits={}\nsubgroup_bits={}\nprivate_bits={}\nnfs_strength={:.1}\n\
+             rho_strength={:.1}\nkangaroo_strength={:.1}\nestimated_strength={:.1}\ncheapest_attack={}\n\
+             attack_operations=2^{:.1}\nattack_core_years={:.1e}\nsp800_57_strength={}\n",
+            self.group,
+            self.prime_bits,
+            self.subgroup_bits,
+            self.private_bits,
+            self.nfs_strength,
+            self.rho_strength,
+            self.kangaroo_strength,
+            self.estimated_strength,
+            self.cheapest_attack,
+            self.estimated_strength,
+            self.attack_core_years,
+            self.sp800_57_strength
+        )
+    }
+}
@@ -0,0 +1,163 @@
+//! `check-expiry`: finds keys whose `--valid-for` not-after time has passed
+//! or is close, in key files, `--json` output, envelopes and the keystore.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use create_private_key::Error;
+
+use crate::metadata;
+
+/// Where a key stands relative to its not-after time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Expired,
+    /// Expires within the warning period.
+    Expiring,
+    Valid,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Expired => "expired",
+            Status::Expiring => "expiring",
+            Status::Valid => "valid",
+        }
+    }
+}
+
+/// A key that carries a not-after time.
+pub struct Found {
+    pub key_id: String,
+    pub not_after: String,
+    /// The file (or keystore) the key was seen in first.
+    pub source: String,
+}
+
+impl Found {
+    /// `None` when the not-after time is not in the form this tool writes.
+    pub fn status(&self, now: SystemTime, warn_within: Duration) -> Option<Status> {
+        let not_after = metadata::parse_rfc3339_utc(&self.not_after)?;
+        Some(if not_after <= now {
+            Status::Expired
+        } else if not_after <= now + warn_within {
+            Status::Expiring
+        } else {
+            Status::Valid
+        })
+    }
+}
+
+/// Collects keys, each key ID once: batches write the same metadata into the
+/// `.priv` and `.pub` files.
+#[derive(Default)]
+pub struct Scan {
+    pub found: Vec<Found>,
+    seen: HashSet<String>,
+}
+
+impl Scan {
+    /// Scans a file, or the files directly inside a directory. Keys without a
+    /// not-after time are skipped.
+    pub fn path(&mut self, path: &Path) -> Result<(), Error> {
+        if !path.is_dir() {
+            return self.file(path);
+        }
+        let failed = |source| Error::Io {
+            context: format!("failed to read {}", path.display()),
+            source,
+        };
+        let entries = std::fs::read_dir(path).map_err(failed)?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(failed)?;
+            if !entry.file_name().to_string_lossy().starts_with('.') && entry.path().is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+        files.iter().try_for_each(|file| self.file(file))
+    }
+
+    fn file(&mut self, path: &Path) -> Result<(), Error> {
+        let bytes = std::fs::read(path).map_err(|source| Error::Io {
+            context: format!("failed to read {}", path.display()),
+            source,
+        })?;
+        self.text(&String::from_utf8_lossy(&bytes), &path.display().to_string());
+        Ok(())
+    }
+
+    #[cfg(feature = "keystore")]
+    pub fn keystore(&mut self, keystore: &crate::keystore::Keystore, source: &str) -> Result<(), String> {
+        for entry in keystore.list(&crate::keystore::Filter::default())? {
+            if let Some(not_after) = entry.metadata.not_after {
+                self.add(entry.key_id, not_after, source);
+            }
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str, source: &str) {
+        // PEM metadata: `Key-Id` opens each header block, `-----BEGIN` ends it.
+        let (mut key_id, mut not_after) = (None, None);
+        for line in text.lines() {
+            if let Some(value) = line.strip_prefix("Key-Id: ") {
+                (key_id, not_after) = (Some(value.trim().to_string()), None);
+            } else if let Some(value) = line.strip_prefix("Not-After: ") {
+                not_after = Some(value.trim().to_string());
+            } else if line.starts_with("-----BEGIN")
+                && let (Some(key_id), Some(not_after)) = (key_id.take(), not_after.take())
+            {
+                self.add(key_id, not_after, source);
+            }
+        }
+        #[cfg(feature = "json")]
+        self.json(text, source);
+    }
+
+    /// A `cpk-v1` envelope, or `--json` records one per line.
+    #[cfg(feature = "json")]
+    fn json(&mut self, text: &str, source: &str) {
+        if !text.trim_start().starts_with('{') {
+            return;
+        }
+        let values: Vec<serde_json::Value> = match serde_json::from_str(text) {
+            Ok(value) => vec![value],
+            Err(_) => text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+        };
+        for value in values {
+            let Some(not_after) = value.pointer("/metadata/not_after").and_then(|v| v.as_str()) else { continue };
+            let key_id = match value.get("key_id").and_then(|v| v.as_str()) {
+                Some(key_id) => key_id.to_string(),
+                // Envelopes leave the key ID to be derived from the key.
+                None => match crate::envelope::parse(&value.to_string()) {
+                    Ok(imported) => {
+                        crate::output::KeyRecord::new(
+                            imported.prime,
+                            imported.generator,
+                            imported.private_key,
+                            None,
+                            crate::fingerprint::FingerprintHash::Sha256,
+                        )
+                        .key_id
+                    }
+                    Err(_) => continue,
+                },
+            };
+            self.add(key_id, not_after.to_string(), source);
+        }
+    }
+
+    fn add(&mut self, key_id: String, not_after: String, source: &str) {
+        if self.seen.insert(key_id.clone()) {
+            self.found.push(Found {
+                key_id,
+                not_after,
+                source: source.to_string(),
+            });
+        }
+    }
+}
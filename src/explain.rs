@@ -0,0 +1,197 @@
+//! `--explain`: the mathematics behind each output line, as comments.
+
+use create_private_key::{DhParams, MIN_PRIVATE_BITS};
+use num_traits::One;
+
+use crate::compliance;
+
+/// Column the notes are wrapped at, not counting the `# `.
+const WIDTH: usize = 76;
+
+const BASE64_NOTE: &str = "Here it is written as the base64 of its big-endian bytes.";
+
+/// What the notes depend on beyond the key itself.
+pub struct Explainer {
+    /// The built-in group's name, or `None` for `--prime`.
+    pub group: Option<&'static str>,
+    pub params: DhParams,
+    pub fips: bool,
+    pub imported: bool,
+}
+
+impl Explainer {
+    /// The notes for the field with default name `field`, wrapped to lines.
+    /// A `repeat` field shows the value before it again, in decimal.
+    pub fn notes(&self, field: &str, repeat: bool) -> Vec<String> {
+        let paragraphs = match field {
+            _ if repeat => {
+                let key = if field.starts_with("private") { 'x' } else { 'y' };
+                vec![format!("The same {key} in decimal.")]
+            }
+            "prime_bits" => self.prime(),
+            "generator" => self.generator(),
+            "private_key_hex" | "private_key_dec" => self.private_key(),
+            "private_key_b64" => [self.private_key(), vec![BASE64_NOTE.into()]].concat(),
+            "private_key_pem" => [
+                self.private_key(),
+                vec!["Here it is wrapped in a PKCS #8 PEM block, which also carries p and g.".into()],
+            ]
+            .concat(),
+            "public_key_hex" | "public_key_dec" => self.public_key(),
+            "public_key_b64" => [self.public_key(), vec![BASE64_NOTE.into()]].concat(),
+            "public_key_pem" => [
+                self.public_key(),
+                vec!["Here it is wrapped in an X.509 SubjectPublicKeyInfo PEM block, which also carries p and g."
+                    .into()],
+            ]
+            .concat(),
+            "public_key_fingerprint" => vec![
+                "A hash of the public key's DER encoding (X.509 SubjectPublicKeyInfo, which holds p, g and y). \
+                 It is easier to compare than the numbers themselves, and any change to them changes it."
+                    .into(),
+            ],
+            "key_id" => vec![
+                "The first 8 bytes of the SHA-256 of the same encoding, in base32: a short name for the key, \
+                 not a security value."
+                    .into(),
+            ],
+            "entropy_sources" => vec![
+                "Extra entropy mixed into the operating system's random numbers before x was drawn. \
+                 Mixing can only add unpredictability, never remove it."
+                    .into(),
+            ],
+            "provenance" => vec![
+                "A document naming the host that made this key and its values, signed with the host's Ed25519 \
+                 key and in base64; anyone with the host's public key can check it with verify-provenance."
+                    .into(),
+            ],
+            _ => Vec::new(),
+        };
+        let mut lines = Vec::new();
+        for (index, paragraph) in paragraphs.iter().enumerate() {
+            if index > 0 {
+                lines.push(String::new());
+            }
+            lines.extend(wrap(paragraph));
+        }
+        lines
+    }
+
+    fn prime(&self) -> Vec<String> {
+        let bits = self.params.prime().bits();
+        let strength = compliance::security_strength(bits, bits);
+        let mut notes = vec![format!(
+            "p is the public prime modulus, {bits} bits long. All arithmetic happens modulo p: numbers \
+             wrap around at p the way hours wrap around at 12."
+        )];
+        notes.push(match self.group {
+            Some(name) => format!(
+                "This is the {name} prime from RFC 3526, a safe prime: p = 2q + 1 with q also prime. The \
+                 nonzero numbers mod p then form a group of order p - 1 = 2q, whose only subgroups have 1, 2, \
+                 q or 2q elements, so there is no small subgroup for a key to be trapped in."
+            ),
+            None => "This prime came from --prime. A safe prime, p = 2q + 1 with q prime, leaves no small \
+                     subgroups; other primes need a generator of large prime order instead."
+                .into(),
+        });
+        notes.push(format!(
+            "Security rests on the discrete logarithm problem mod p being hard. At {bits} bits the best \
+             attack, the number field sieve, costs about 2^{strength} operations (NIST SP 800-57)."
+        ));
+        notes
+    }
+
+    fn generator(&self) -> Vec<String> {
+        let (prime, generator) = (self.params.prime(), self.params.generator());
+        let mut notes = vec![format!(
+            "g = {generator} is the generator: every public key is a power of g mod p. It must satisfy \
+             1 < g < p. Powers of 1 are all 1, g = p - 1 only reaches 1 and p - 1, and a g of p or more is \
+             the same as g mod p."
+        )];
+        let q = (prime - 1u32) >> 1;
+        if generator.modpow(&q, prime).is_one() {
+            notes.push(
+                "Here g^q mod p = 1, so the powers of g cycle through exactly q values: g generates the \
+                 subgroup of prime order q, and every public key lands in it."
+                    .into(),
+            );
+        }
+        notes
+    }
+
+    fn private_key(&self) -> Vec<String> {
+        let mut notes = vec!["x is the private key, an exponent known only to its owner.".to_string()];
+        if self.imported {
+            notes.push("It was read from the imported envelope rather than drawn in this run.".into());
+            return notes;
+        }
+        let sampling = "random bytes with the surplus top bits cleared, drawn again whenever the number \
+                        falls outside the range (rejection sampling), so every value in it is equally likely";
+        notes.push(match (self.params.private_bits(), self.fips) {
+            (Some(bits), false) => format!("It was drawn from [2, 2^{bits}) as {sampling}."),
+            (None, false) => format!(
+                "It was drawn from [2, p - 2] as {sampling}. 0 and 1 are left out because g^0 = 1 and \
+                 g^1 = g would give the key away, and p - 1 because g^(p-1) = 1 mod p (Fermat's little theorem)."
+            ),
+            (_, true) => format!(
+                "As NIST SP 800-56A requires, it was drawn from [1, min(2^N, q) - 1], N being the exponent \
+                 size, as {sampling}. Since g^q = 1, exponents beyond q would only repeat keys."
+            ),
+        });
+        if let Some(bits) = self.params.private_bits() {
+            notes.push(format!(
+                "A {bits}-bit exponent is much shorter than p, which makes the exponentiation faster. \
+                 Pollard's rho finds x in about 2^{} steps, half its size, so at least {MIN_PRIVATE_BITS} \
+                 bits are required for 112-bit security.",
+                bits / 2
+            ));
+        }
+        notes
+    }
+
+    fn public_key(&self) -> Vec<String> {
+        let exponent_bits = self.params.private_bits().unwrap_or_else(|| self.params.prime().bits());
+        vec![
+            "y = g^x mod p is the public key, safe to publish.".into(),
+            format!(
+                "It is computed by square-and-multiply: walking the bits of x, square at every bit and also \
+                 multiply by g at every 1 bit, about {exponent_bits} squarings mod p in all, instead of x \
+                 multiplications."
+            ),
+            "Getting x back from y is the discrete logarithm problem, infeasible at this size.".into(),
+            "In an exchange, the peer sends y' = g^x' mod p. Both sides reach the same secret, \
+             (y')^x = g^(x'x) = (y)^x' mod p, while an eavesdropper who sees only y and y' cannot."
+                .into(),
+        ]
+    }
+}
+
+/// Breaks `text` into lines of at most [`WIDTH`] characters at spaces,
+/// keeping sums and differences such as `p - 1` on one line.
+fn wrap(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match words.last_mut() {
+            Some(last) if matches!(word, "-" | "+") || last.ends_with(" -") || last.ends_with(" +") => {
+                last.push(' ');
+                last.push_str(word);
+            }
+            _ => words.push(word.to_string()),
+        }
+    }
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in &words {
+        if !line.is_empty() && line.len() + 1 + word.len() > WIDTH {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,37 @@
+//! Running helper programs (gpg, openssl, ...) as filters.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs `program` with `args`, feeds it `input` on stdin, and returns its
+/// stdout. A non-zero exit is an error carrying the program's stderr.
+pub fn run_filter(program: &str, args: &[&str], input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => format!("`{program}` was not found on PATH"),
+            _ => format!("failed to run `{program}`: {err}"),
+        })?;
+
+    // Write from a separate thread so a chatty child cannot deadlock on a
+    // full stdout pipe while we are still writing its stdin.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("failed to run `{program}`: {err}"))?;
+    let write_result = writer.join().expect("stdin writer does not panic");
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`{program}` failed ({}): {}", output.status, stderr.trim()));
+    }
+    write_result.map_err(|err| format!("failed to write to `{program}`: {err}"))?;
+    Ok(output.stdout)
+}
@@ -0,0 +1,42 @@
+use clap::ValueEnum;
+use data_encoding::BASE32_NOPAD;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum FingerprintHash {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl FingerprintHash {
+    fn name(self) -> &'static str {
+        match self {
+            FingerprintHash::Sha256 => "sha256",
+            FingerprintHash::Sha384 => "sha384",
+            FingerprintHash::Sha512 => "sha512",
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            FingerprintHash::Sha256 => Sha256::digest(data).to_vec(),
+            FingerprintHash::Sha384 => Sha384::digest(data).to_vec(),
+            FingerprintHash::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// Fingerprint of a public key's SPKI DER encoding, e.g. `sha256:3f9a...`.
+pub fn fingerprint(hash: FingerprintHash, spki_der: &[u8]) -> String {
+    let digest = hash.digest(spki_der);
+    let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("{}:{hex}", hash.name())
+}
+
+/// Short key ID: the first 8 bytes of the SHA-256 SPKI fingerprint, base32.
+///
+/// Always SHA-256 based, so the ID does not change with `--fingerprint-hash`.
+pub fn key_id(spki_der: &[u8]) -> String {
+    BASE32_NOPAD.encode(&Sha256::digest(spki_der)[..8])
+}
@@ -0,0 +1,110 @@
+//! `--fips`: approved groups and key sizes only, key generation and
+//! public-key validation as NIST SP 800-56A Rev. 3 specifies them for
+//! finite-field safe-prime groups.
+
+use clap::ValueEnum;
+use create_private_key::params::DhGroup;
+use create_private_key::{DhParams, Error, dh};
+use create_private_key_core as ffdh;
+use num_bigint::BigUint;
+use num_traits::One;
+
+use crate::random;
+
+/// Subcommands that work in FIPS mode; the rest are refused.
+pub const COMMANDS: [&str; 12] = [
+    "generate",
+    "derive-public",
+    "shared-secret",
+    "validate",
+    "params",
+    "groups",
+    "estimate",
+    "selftest",
+    "bench",
+    "acvp",
+    "completions",
+    "man",
+];
+
+/// An option or command FIPS mode does not allow. The CLI exits with the
+/// policy code for these.
+#[derive(Debug)]
+pub struct Refusal(pub String);
+
+impl std::fmt::Display for Refusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not allowed with --fips", self.0)
+    }
+}
+
+/// Security strength in bits of the approved RFC 3526 groups, by prime
+/// size (SP 800-56A Rev. 3, Appendix D).
+fn security_strength(prime_bits: u64) -> Option<u64> {
+    match prime_bits {
+        2048 => Some(112),
+        3072 => Some(128),
+        4096 => Some(152),
+        6144 => Some(176),
+        8192 => Some(200),
+        _ => None,
+    }
+}
+
+/// The subgroup order `q = (p - 1) / 2` of a safe prime.
+fn order(prime: &BigUint) -> BigUint {
+    (prime - 1u32) >> 1
+}
+
+/// Accepts only a built-in group, with a private exponent size of at least
+/// twice its security strength (and at most the size of `q`, which
+/// [`DhParams::with_private_bits`] already enforces).
+pub fn check_params(params: &DhParams) -> Result<(), Refusal> {
+    let approved = DhGroup::value_variants()
+        .iter()
+        .any(|&group| DhParams::group(group).into_parts() == (params.prime().clone(), params.generator().clone()));
+    let strength = security_strength(params.prime().bits()).filter(|_| approved);
+    let Some(strength) = strength else {
+        return Err(Refusal("a group other than the built-in RFC 3526 groups".into()));
+    };
+    if let Some(bits) = params.private_bits().filter(|&bits| bits < 2 * strength) {
+        return Err(Refusal(format!(
+            "--private-bits {bits} (this group needs at least {})",
+            2 * strength
+        )));
+    }
+    Ok(())
+}
+
+/// SP 800-56A Rev. 3, 5.6.1.1.4: `x` uniform in `[1, M - 1]` with
+/// `M = min(2^N, q)`, where `N` is the private exponent size or the size of
+/// `q`. `x = 1` is skipped as well, since its public key is the generator.
+/// The caller gets the owner's assurance of public-key validity
+/// (5.6.2.1.4) from [`check_public_key`].
+pub fn private_key(params: &DhParams) -> Result<BigUint, Error> {
+    let q = order(params.prime());
+    let upper = match params.private_bits() {
+        Some(bits) => (BigUint::one() << bits).min(q),
+        None => q,
+    };
+    ffdh::random_range(&BigUint::from(2u32), &upper, random::fill)
+}
+
+/// SP 800-56A Rev. 3, 5.6.2.3.1 full public-key validation: `2 <= y <= p - 2`
+/// and `y^q = 1 mod p`, so `y` lies in the subgroup of order `q`.
+pub fn check_public_key(prime: &BigUint, public_key: &BigUint) -> Result<(), Error> {
+    dh::check_peer(prime, public_key)?;
+    if !ffdh::montgomery::modpow(public_key, &order(prime), prime).is_one() {
+        return Err(Error::Validation("public key is not in the prime-order subgroup".into()));
+    }
+    Ok(())
+}
+
+/// A private exponent from elsewhere must lie in `[1, q - 1]` too.
+#[cfg(feature = "pem")]
+pub fn check_private_key(prime: &BigUint, private_key: &BigUint) -> Result<(), Error> {
+    if *private_key >= order(prime) {
+        return Err(Error::Validation("private key is outside [1, q - 1] for the group".into()));
+    }
+    Ok(())
+}
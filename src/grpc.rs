@@ -0,0 +1,185 @@
+//! `grpc`: the `KeyService` from `proto/key_service.proto`.
+
+use num_bigint::BigUint;
+use tonic::transport::server::TcpIncoming;
+use tracing::Instrument;
+use tonic::{Request, Response, Status};
+
+use crate::api;
+use crate::metadata;
+use crate::metrics;
+use crate::output;
+use crate::params;
+use crate::systemd;
+use crate::telemetry;
+
+mod proto {
+    tonic::include_proto!("create_private_key.v1");
+}
+
+use proto::key_service_server::{KeyService, KeyServiceServer};
+
+/// Serves until the process is stopped. Plaintext HTTP/2 only; terminate
+/// TLS in front of it if the address is not loopback. A TCP socket passed
+/// by systemd replaces `listen`.
+pub fn serve(listen: &str) -> Result<(), String> {
+    let inherited = match systemd::listener()? {
+        Some(systemd::Listener::Tcp(listener)) => Some(listener),
+        #[cfg(unix)]
+        Some(systemd::Listener::Unix(_)) => return Err("grpc needs a TCP socket from systemd".into()),
+        None => None,
+    };
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| format!("failed to start the runtime: {err}"))?;
+    runtime.block_on(async {
+        let (incoming, listening) = match inherited {
+            Some(listener) => {
+                let listener = listener
+                    .set_nonblocking(true)
+                    .and_then(|()| tokio::net::TcpListener::from_std(listener))
+                    .map_err(|err| format!("the socket from systemd is not usable: {err}"))?;
+                let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|err| err.to_string());
+                (incoming, "the socket from systemd")
+            }
+            None => {
+                let address = listen
+                    .parse()
+                    .map_err(|err| format!("--listen '{listen}' is not a socket address: {err}"))?;
+                (TcpIncoming::new(address, true, None).map_err(|err| err.to_string()), listen)
+            }
+        };
+        let incoming = incoming.map_err(|err| format!("failed to listen on {listening}: {err}"))?;
+        tracing::info!("listening on {listening}");
+        systemd::ready(listening);
+        tonic::transport::Server::builder()
+            .add_service(KeyServiceServer::new(Service))
+            .serve_with_incoming(incoming)
+            .await
+            .map_err(|err| format!("gRPC server failed: {err}"))
+    })
+}
+
+struct Service;
+
+/// Awaits an RPC handler, recording its latency and outcome.
+async fn observed<T>(operation: &'static str, handler: impl Future<Output = Result<T, Status>>) -> Result<T, Status> {
+    let span = telemetry::request_span(operation);
+    let start = std::time::Instant::now();
+    let result = handler.instrument(span.clone()).await;
+    metrics::observe(operation, start, result.is_ok());
+    if let Err(ref status) = result {
+        telemetry::record_error(&span, status.message());
+    }
+    result
+}
+
+/// Protobuf group fields in the `api` form: empty bytes mean "not given".
+fn group_spec(group: String, prime: &[u8], generator: &[u8]) -> api::GroupSpec {
+    let number = |bytes: &[u8]| (!bytes.is_empty()).then(|| BigUint::from_bytes_be(bytes).to_string());
+    api::GroupSpec {
+        group: (!group.is_empty()).then_some(group),
+        prime: number(prime),
+        generator: number(generator),
+    }
+}
+
+#[tonic::async_trait]
+impl KeyService for Service {
+    async fn generate(&self, request: Request<proto::GenerateRequest>) -> Result<Response<proto::KeyRecord>, Status> {
+        observed("generate", async move {
+            let request = request.into_inner();
+            let spec = group_spec(request.group, &request.prime, &request.generator);
+            let non_empty = |value: String| (!value.is_empty()).then_some(value);
+            let (label, operator) = (non_empty(request.label), non_empty(request.operator));
+            metadata::check_text(label.as_deref(), operator.as_deref()).map_err(Status::invalid_argument)?;
+            let group = spec.resolve().map_err(Status::invalid_argument)?;
+            let record = tokio::task::spawn_blocking(move || api::generate_record(&group, label, operator))
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?
+                .map_err(Status::internal)?;
+
+            let metadata = record.metadata.as_ref().map(|metadata| proto::Metadata {
+                created: metadata.created.clone(),
+                tool_version: metadata.tool_version.clone(),
+                group: metadata.group.clone(),
+                label: metadata.label.clone().unwrap_or_default(),
+                operator: metadata.operator.clone().unwrap_or_default(),
+                not_after: metadata.not_after.clone().unwrap_or_default(),
+                tags: metadata.tags.clone().into_iter().collect(),
+            });
+            Ok(Response::new(proto::KeyRecord {
+                key_id: record.key_id.clone(),
+                fingerprint: record.fingerprint.clone(),
+                prime_bits: record.prime.bits() as u32,
+                prime: record.prime.to_bytes_be(),
+                generator: record.generator.to_bytes_be(),
+                private_key: record.private_key.expose().to_bytes_be(),
+                public_key: record.public_key.to_bytes_be(),
+                metadata,
+            }))
+        })
+        .await
+    }
+
+    async fn list_groups(
+        &self,
+        _request: Request<proto::ListGroupsRequest>,
+    ) -> Result<Response<proto::ListGroupsResponse>, Status> {
+        observed("groups", async move {
+            let groups = api::groups()
+                .into_iter()
+                .map(|group| proto::Group {
+                    name: group.name.to_string(),
+                    prime_bits: group.prime_bits as u32,
+                    prime: params::parse_hex_biguint(&group.prime).to_bytes_be(),
+                    generator: BigUint::parse_bytes(group.generator.as_bytes(), 10)
+                        .expect("built-in generators are decimal")
+                        .to_bytes_be(),
+                })
+                .collect();
+            Ok(Response::new(proto::ListGroupsResponse { groups }))
+        })
+        .await
+    }
+
+    async fn validate(&self, request: Request<proto::ValidateRequest>) -> Result<Response<proto::ValidateResponse>, Status> {
+        observed("validate", async move {
+            let request = request.into_inner();
+            let validation = api::validate(&api::ValidateRequest {
+                group: group_spec(request.group, &request.prime, &request.generator),
+                public_key: (!request.public_key.is_empty())
+                    .then(|| output::to_even_length_hex(&BigUint::from_bytes_be(&request.public_key))),
+            });
+            Ok(Response::new(proto::ValidateResponse {
+                valid: validation.valid,
+                error: validation.error.unwrap_or_default(),
+            }))
+        })
+        .await
+    }
+
+    async fn shared_secret(
+        &self,
+        request: Request<proto::SharedSecretRequest>,
+    ) -> Result<Response<proto::SharedSecretResponse>, Status> {
+        observed("shared_secret", async move {
+            let request = request.into_inner();
+            let group = group_spec(request.group, &request.prime, &request.generator)
+                .resolve()
+                .map_err(Status::invalid_argument)?;
+            let hkdf = (request.hkdf_length > 0).then_some(api::Hkdf {
+                length: request.hkdf_length as usize,
+                salt: request.hkdf_salt,
+                info: request.hkdf_info,
+            });
+            let secret = api::shared_secret(
+                &group,
+                &BigUint::from_bytes_be(&request.private_key),
+                &BigUint::from_bytes_be(&request.peer_public_key),
+                hkdf.as_ref(),
+            )
+            .map_err(Status::invalid_argument)?;
+            Ok(Response::new(proto::SharedSecretResponse { secret }))
+        })
+        .await
+    }
+}
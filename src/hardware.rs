@@ -0,0 +1,121 @@
+//! `--hw-entropy`: hardware entropy sources read alongside the OS RNG.
+
+use std::io::Read;
+
+use clap::ValueEnum;
+use create_private_key::Error;
+
+/// Where the Linux hw_random framework exposes the platform's TRNG.
+const HWRNG_PATH: &str = "/dev/hwrng";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HwSource {
+    /// The x86-64 RDSEED instruction (conditioned entropy source output).
+    Rdseed,
+    /// The x86-64 RDRAND instruction (DRBG output reseeded by the CPU).
+    Rdrand,
+    /// The kernel's hardware RNG device, `/dev/hwrng`.
+    Hwrng,
+}
+
+impl HwSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            HwSource::Rdseed => "rdseed",
+            HwSource::Rdrand => "rdrand",
+            HwSource::Hwrng => "hwrng",
+        }
+    }
+
+    /// Fills `dest` from the source, failing if it is missing or stops
+    /// delivering.
+    pub fn read(self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            HwSource::Rdseed => cpu::fill(dest, cpu::Instruction::Rdseed),
+            HwSource::Rdrand => cpu::fill(dest, cpu::Instruction::Rdrand),
+            HwSource::Hwrng => std::fs::File::open(HWRNG_PATH)
+                .and_then(|mut device| device.read_exact(dest))
+                .map_err(|err| Error::Rng(format!("{HWRNG_PATH}: {err}"))),
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod cpu {
+    use std::arch::x86_64::{_rdrand64_step, _rdseed64_step};
+
+    use create_private_key::Error;
+
+    /// RDSEED runs dry under load and must be retried; RDRAND failing ten
+    /// times in a row indicates a broken CPU (Intel DRNG guide, 5.2.1).
+    const RDSEED_RETRIES: usize = 1024;
+    const RDRAND_RETRIES: usize = 10;
+
+    #[derive(Clone, Copy)]
+    pub enum Instruction {
+        Rdseed,
+        Rdrand,
+    }
+
+    pub fn fill(dest: &mut [u8], instruction: Instruction) -> Result<(), Error> {
+        let (name, supported) = match instruction {
+            Instruction::Rdseed => ("RDSEED", std::arch::is_x86_feature_detected!("rdseed")),
+            Instruction::Rdrand => ("RDRAND", std::arch::is_x86_feature_detected!("rdrand")),
+        };
+        if !supported {
+            return Err(Error::Rng(format!("this CPU does not support {name}")));
+        }
+        for chunk in dest.chunks_mut(8) {
+            // SAFETY: support for the instruction was checked above.
+            let value = match instruction {
+                Instruction::Rdseed => unsafe { rdseed() },
+                Instruction::Rdrand => unsafe { rdrand() },
+            };
+            let value = value.ok_or_else(|| Error::Rng(format!("{name} returned no data")))?;
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    #[target_feature(enable = "rdseed")]
+    fn rdseed() -> Option<u64> {
+        let mut value = 0;
+        for _ in 0..RDSEED_RETRIES {
+            if _rdseed64_step(&mut value) == 1 {
+                return Some(value);
+            }
+            std::hint::spin_loop();
+        }
+        None
+    }
+
+    #[target_feature(enable = "rdrand")]
+    fn rdrand() -> Option<u64> {
+        let mut value = 0;
+        for _ in 0..RDRAND_RETRIES {
+            if _rdrand64_step(&mut value) == 1 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod cpu {
+    use create_private_key::Error;
+
+    #[derive(Clone, Copy)]
+    pub enum Instruction {
+        Rdseed,
+        Rdrand,
+    }
+
+    pub fn fill(_dest: &mut [u8], instruction: Instruction) -> Result<(), Error> {
+        let name = match instruction {
+            Instruction::Rdseed => "RDSEED",
+            Instruction::Rdrand => "RDRAND",
+        };
+        Err(Error::Rng(format!("{name} is only available on x86-64")))
+    }
+}
@@ -0,0 +1,72 @@
+//! Startup health tests from NIST SP 800-90B section 4.4, run on the raw
+//! entropy inputs (the OS CSPRNG and each `--hw-entropy` source) before any
+//! key is generated. They catch a source that is stuck or badly biased; they
+//! cannot prove a source is good.
+
+use create_private_key::{Error, KeySource};
+use rand::rngs::OsRng;
+use zeroize::Zeroize;
+
+use crate::hardware::HwSource;
+
+/// Samples (bytes) tested per source; the standard's minimum for startup.
+const STARTUP_SAMPLES: usize = 1024;
+/// Repetition count cutoff, 1 + ceil(20 / H) for a false positive rate of
+/// 2^-20 with H = 4 bits of min-entropy claimed per byte. The claim is far
+/// below what a working source delivers, so only broken ones trip it.
+const REPETITION_CUTOFF: usize = 6;
+/// Adaptive proportion window for non-binary samples.
+const ADAPTIVE_WINDOW: usize = 512;
+/// Adaptive proportion cutoff, 1 + CRITBINOM(512, 2^-4, 1 - 2^-20).
+const ADAPTIVE_CUTOFF: usize = 62;
+
+/// Runs both tests on every source, failing on the first that does not pass.
+pub fn startup(hardware: &[HwSource]) -> Result<(), Error> {
+    let mut samples = [0u8; STARTUP_SAMPLES];
+    OsRng.fill(&mut samples)?;
+    let result = check(&samples).map_err(|err| Error::Rng(format!("health test failed for os: {err}")));
+    samples.zeroize();
+    result?;
+    for source in hardware {
+        let read = source.read(&mut samples);
+        let result = read.and_then(|()| {
+            check(&samples).map_err(|err| Error::Rng(format!("health test failed for {}: {err}", source.name())))
+        });
+        samples.zeroize();
+        result?;
+    }
+    tracing::debug!(samples = STARTUP_SAMPLES, sources = hardware.len() + 1, "entropy health tests passed");
+    Ok(())
+}
+
+fn check(samples: &[u8]) -> Result<(), String> {
+    repetition_count(samples)?;
+    samples.chunks_exact(ADAPTIVE_WINDOW).try_for_each(adaptive_proportion)
+}
+
+/// Fails when one value repeats [`REPETITION_CUTOFF`] times in a row.
+fn repetition_count(samples: &[u8]) -> Result<(), String> {
+    let mut run = 0;
+    let mut previous = None;
+    for &sample in samples {
+        run = if previous == Some(sample) { run + 1 } else { 1 };
+        previous = Some(sample);
+        if run >= REPETITION_CUTOFF {
+            return Err(format!("repetition count test: {sample:#04x} repeated {run} times"));
+        }
+    }
+    Ok(())
+}
+
+/// Fails when the window's first value fills [`ADAPTIVE_CUTOFF`] of it.
+fn adaptive_proportion(window: &[u8]) -> Result<(), String> {
+    let first = window[0];
+    let count = window.iter().filter(|&&sample| sample == first).count();
+    if count >= ADAPTIVE_CUTOFF {
+        return Err(format!(
+            "adaptive proportion test: {first:#04x} appeared {count} times in {} samples",
+            window.len()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! `serve`: the JSON API over HTTP/1.1.
+//!
+//! | Method and path     | Body                 | Response            |
+//! |---------------------|----------------------|---------------------|
+//! | `POST /v1/keys`     | `GenerateRequest`    | `GeneratedKey`      |
+//! | `GET /v1/groups`    |                      | `[GroupInfo]`       |
+//! | `POST /v1/validate` | `ValidateRequest`    | `Validation`        |
+//! | `GET /metrics`      |                      | Prometheus text     |
+//!
+//! Errors are `{"error": "..."}` with a 4xx status; requests over a rate
+//! limit or the key quota get a 429 with `Retry-After`. There is no TLS or
+//! authentication; bind to loopback or put a proxy in front.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::api;
+use crate::limits::Limits;
+use crate::metrics;
+use crate::systemd;
+use crate::telemetry;
+
+/// Largest request body accepted.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Status, body, and content type.
+type Reply = (u16, String, &'static str);
+
+const JSON: &str = "application/json";
+
+/// Serves requests until the process is stopped, one thread per request.
+/// A socket passed by systemd (TCP or Unix) replaces `listen`.
+pub fn serve(listen: &str, limits: Limits) -> Result<(), String> {
+    let (server, listening) = match systemd::listener()? {
+        Some(systemd::Listener::Tcp(listener)) => (Server::from_listener(listener, None), "the socket from systemd"),
+        #[cfg(unix)]
+        Some(systemd::Listener::Unix(listener)) => (Server::from_listener(listener, None), "the socket from systemd"),
+        None => (Server::http(listen), listen),
+    };
+    let server = server.map_err(|err| format!("failed to listen on {listening}: {err}"))?;
+    tracing::info!("listening on {listening}");
+    systemd::ready(listening);
+    let limits = Arc::new(limits);
+    for request in server.incoming_requests() {
+        let limits = Arc::clone(&limits);
+        std::thread::spawn(move || respond(request, &limits));
+    }
+    Ok(())
+}
+
+fn respond(mut request: Request, limits: &Limits) {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    let operation = match path.as_str() {
+        "/v1/keys" => Some("generate"),
+        "/v1/groups" => Some("groups"),
+        "/v1/validate" => Some("validate"),
+        _ => None,
+    };
+    let span = operation.map_or_else(tracing::Span::none, telemetry::request_span);
+    let start = std::time::Instant::now();
+    // Scrapes are not client traffic and are never limited.
+    let generates = operation == Some("generate") && *request.method() == Method::Post;
+    let admitted = if path == "/metrics" {
+        Ok(())
+    } else {
+        // Requests through a Unix socket from systemd all share one budget.
+        let client = request.remote_addr().map_or_else(|| "unix".to_string(), |address| address.ip().to_string());
+        limits.request(&client).and_then(|()| if generates { limits.take_key() } else { Ok(()) })
+    };
+    let (status, body, content_type) = match admitted {
+        Ok(()) => span.in_scope(|| route(&mut request)),
+        Err(ref refusal) => error(429, &refusal.to_string()),
+    };
+    if generates && admitted.is_ok() && status >= 400 {
+        limits.return_key();
+    }
+    if let Some(operation) = operation {
+        metrics::observe(operation, start, status < 400);
+    }
+    if status >= 400 {
+        telemetry::record_error(&span, &format!("HTTP {status}"));
+    }
+    let content_type = Header::from_bytes("Content-Type", content_type).expect("static header is valid");
+    let mut response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(refusal) = admitted {
+        let retry_after = refusal.retry_after_secs().to_string();
+        response.add_header(Header::from_bytes("Retry-After", retry_after).expect("digits are a valid header"));
+    }
+    let _ = request.respond(response);
+}
+
+fn route(request: &mut Request) -> Reply {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    match (request.method(), path.as_str()) {
+        (Method::Post, "/v1/keys") => match read_json::<api::GenerateRequest>(request) {
+            Ok(body) => api::generate(&body).map_or_else(|err| error(400, &err), |key| ok(&key)),
+            Err(reply) => reply,
+        },
+        (Method::Get, "/v1/groups") => ok(&api::groups()),
+        (Method::Post, "/v1/validate") => match read_json::<api::ValidateRequest>(request) {
+            Ok(body) => ok(&api::validate(&body)),
+            Err(reply) => reply,
+        },
+        (Method::Get, "/metrics") => (200, metrics::render(), metrics::CONTENT_TYPE),
+        (_, "/v1/keys" | "/v1/groups" | "/v1/validate" | "/metrics") => error(405, "method not allowed"),
+        _ => error(404, "not found"),
+    }
+}
+
+/// Parses the body; an empty body means "all defaults".
+fn read_json<T: DeserializeOwned + Default>(request: &mut Request) -> Result<T, Reply> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| error(400, &format!("failed to read the request body: {err}")))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(error(413, "request body is too large"));
+    }
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(&body).map_err(|err| error(400, &format!("invalid request body: {err}")))
+}
+
+fn ok(value: &impl Serialize) -> Reply {
+    (200, serde_json::to_string(value).expect("response serializes"), JSON)
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    (status, serde_json::json!({ "error": message }).to_string(), JSON)
+}
@@ -0,0 +1,371 @@
+//! The library's key types: validated group parameters and a key pair in
+//! that group.
+
+use create_private_key_core as ffdh;
+use num_bigint::BigUint;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{Error, Result};
+use crate::fingerprint::{self, FingerprintHash};
+use crate::params::{self, DhGroup};
+use crate::rng::KeySource;
+use crate::secret::PrivateKey;
+use crate::{der, dh, pem, serde_hex};
+
+/// Smallest `private_bits` accepted: twice the 112-bit security level of
+/// a 2048-bit group (NIST SP 800-56A).
+pub use ffdh::MIN_PRIVATE_BITS;
+/// Precomputed powers of a generator, from [`DhParams::fixed_base`].
+pub use ffdh::montgomery::FixedBase;
+
+/// A prime modulus and generator that passed `params::validate`, and the
+/// size of the private exponents to draw.
+///
+/// Serializes as `{"prime": hex, "generator": hex, "private_bits": n}`,
+/// and is validated again when deserialized.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "ParamsFields", try_from = "ParamsFields")]
+pub struct DhParams {
+    prime: BigUint,
+    generator: BigUint,
+    private_bits: Option<u64>,
+}
+
+impl DhParams {
+    /// Validates and wraps a prime and generator.
+    pub fn new(prime: BigUint, generator: BigUint) -> Result<Self> {
+        DhParams::builder().prime(prime).generator(generator).build()
+    }
+
+    /// Starts from group 14 with full-size private exponents.
+    pub fn builder() -> DhParamsBuilder {
+        DhParamsBuilder::default()
+    }
+
+    /// The parameters of a built-in group.
+    pub fn group(group: DhGroup) -> Self {
+        DhParams::builder().group(group).build().expect("built-in groups are valid")
+    }
+
+    /// Parses a prime and optional generator the way `--prime` and
+    /// `--generator` do (decimal or `0x` hex), falling back to `group`.
+    pub fn parse(group: DhGroup, prime: Option<&str>, generator: Option<&str>) -> Result<Self> {
+        let (prime, generator) = params::resolve(group, prime, generator)?;
+        DhParams::builder().prime(prime).generator(generator).build()
+    }
+
+    pub fn prime(&self) -> &BigUint {
+        &self.prime
+    }
+
+    pub fn generator(&self) -> &BigUint {
+        &self.generator
+    }
+
+    /// Bit length of generated private exponents, or `None` for the full
+    /// range `[2, p - 2]`.
+    pub fn private_bits(&self) -> Option<u64> {
+        self.private_bits
+    }
+
+    /// The same parameters with private exponents of at most `bits` bits,
+    /// checked like [`DhParamsBuilder::private_bits`].
+    pub fn with_private_bits(mut self, bits: u64) -> Result<Self> {
+        ffdh::check_private_bits(&self.prime, bits)?;
+        self.private_bits = Some(bits);
+        Ok(self)
+    }
+
+    /// A table of generator powers that makes deriving each of about `keys`
+    /// public keys a few times faster, or `None` when so few keys would not
+    /// pay for it. Pass it to [`KeyPair::from_private_key_with`].
+    pub fn fixed_base(&self, keys: u64) -> Option<FixedBase> {
+        let bits = self.private_bits.unwrap_or_else(|| self.prime.bits());
+        FixedBase::for_uses(&self.prime, &self.generator, bits, keys)
+    }
+
+    /// DER `DHParameter` (PKCS #3).
+    pub fn to_der(&self) -> Vec<u8> {
+        der::dh_parameters(&self.prime, &self.generator)
+    }
+
+    pub fn into_parts(self) -> (BigUint, BigUint) {
+        (self.prime, self.generator)
+    }
+}
+
+/// Collects the parameter choices and checks them together in `build`.
+#[derive(Clone, Debug, Default)]
+pub struct DhParamsBuilder {
+    group: DhGroup,
+    prime: Option<BigUint>,
+    generator: Option<BigUint>,
+    private_bits: Option<u64>,
+}
+
+impl DhParamsBuilder {
+    /// The built-in group whose prime and generator are used unless
+    /// overridden.
+    pub fn group(mut self, group: DhGroup) -> Self {
+        self.group = group;
+        self
+    }
+
+    pub fn prime(mut self, prime: BigUint) -> Self {
+        self.prime = Some(prime);
+        self
+    }
+
+    pub fn generator(mut self, generator: BigUint) -> Self {
+        self.generator = Some(generator);
+        self
+    }
+
+    /// Draw private exponents of at most this many bits instead of from the
+    /// whole group, which makes each key pair cheaper to use. At least
+    /// [`MIN_PRIVATE_BITS`], and less than the prime's size.
+    pub fn private_bits(mut self, bits: u64) -> Self {
+        self.private_bits = Some(bits);
+        self
+    }
+
+    /// Applies the same checks as the command line.
+    pub fn build(self) -> Result<DhParams> {
+        let prime = self
+            .prime
+            .unwrap_or_else(|| self.group.prime().clone());
+        let generator = match self.generator {
+            Some(generator) => generator,
+            None => params::parse_biguint(self.group.default_generator())?,
+        };
+        params::validate(&prime, &generator)?;
+        if let Some(bits) = self.private_bits {
+            ffdh::check_private_bits(&prime, bits)?;
+        }
+        Ok(DhParams {
+            prime,
+            generator,
+            private_bits: self.private_bits,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ParamsFields {
+    #[serde(with = "serde_hex")]
+    prime: BigUint,
+    #[serde(with = "serde_hex")]
+    generator: BigUint,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    private_bits: Option<u64>,
+}
+
+impl From<DhParams> for ParamsFields {
+    fn from(params: DhParams) -> Self {
+        ParamsFields {
+            prime: params.prime,
+            generator: params.generator,
+            private_bits: params.private_bits,
+        }
+    }
+}
+
+impl TryFrom<ParamsFields> for DhParams {
+    type Error = Error;
+
+    fn try_from(fields: ParamsFields) -> Result<Self> {
+        let mut builder = DhParams::builder().prime(fields.prime).generator(fields.generator);
+        if let Some(bits) = fields.private_bits {
+            builder = builder.private_bits(bits);
+        }
+        builder.build()
+    }
+}
+
+impl Default for DhParams {
+    /// RFC 3526 group 14.
+    fn default() -> Self {
+        DhParams::group(DhGroup::Modp14)
+    }
+}
+
+/// A private exponent and its public value `g^x mod p`.
+///
+/// Serializes as the parameters' fields plus `private_key` and
+/// `public_key` in hex; use [`KeyPair::redacted`] to leave the private key
+/// out. Deserializing checks the parameters and the private key, and that
+/// the public key matches. `Debug` leaves the private key out.
+#[derive(Clone, Debug)]
+pub struct KeyPair {
+    params: DhParams,
+    private_key: PrivateKey,
+    public_key: BigUint,
+}
+
+/// Draws a private exponent uniformly from `[2, p - 2]`, or from
+/// `[2, 2^private_bits)` when set, and derives the public value.
+pub fn generate_keypair<S: KeySource + ?Sized>(params: &DhParams, source: &mut S) -> Result<KeyPair> {
+    let private_key = ffdh::generate_private_key(&params.prime, params.private_bits, |dest| source.fill(dest))?;
+    Ok(KeyPair::derive(params.clone(), private_key))
+}
+
+impl KeyPair {
+    /// Rebuilds a key pair from an existing private exponent, which must lie
+    /// in `[2, p - 2]`.
+    pub fn from_private_key(params: DhParams, private_key: BigUint) -> Result<Self> {
+        ffdh::check_private_key(&params.prime, &private_key)?;
+        Ok(KeyPair::derive(params, private_key))
+    }
+
+    /// Like [`KeyPair::from_private_key`], with the public key from a
+    /// [`DhParams::fixed_base`] table for the same parameters.
+    pub fn from_private_key_with(params: DhParams, private_key: BigUint, table: &FixedBase) -> Result<Self> {
+        ffdh::check_private_key(&params.prime, &private_key)?;
+        if table.params() != (params.prime.clone(), &params.generator) {
+            return Err(Error::Validation("the fixed-base table was built for other parameters".into()));
+        }
+        let public_key = table.pow(&private_key);
+        Ok(KeyPair {
+            params,
+            private_key: PrivateKey::new(private_key),
+            public_key,
+        })
+    }
+
+    /// Parses a PKCS #8 `PrivateKeyInfo` DH key.
+    pub fn from_pkcs8_der(der: &[u8]) -> Result<Self> {
+        let (prime, generator, private_key) = der::parse_dh_private_key_info(der)?;
+        KeyPair::from_private_key(DhParams::new(prime, generator)?, private_key)
+    }
+
+    /// Parses the first `PRIVATE KEY` PEM block in `text`.
+    pub fn from_pkcs8_pem(text: &str) -> Result<Self> {
+        KeyPair::from_pkcs8_der(&pem::decode(pem::PRIVATE_KEY_LABEL, text)?)
+    }
+
+    fn derive(params: DhParams, private_key: BigUint) -> Self {
+        let public_key = ffdh::public_key(&params.prime, &params.generator, &private_key);
+        KeyPair {
+            params,
+            private_key: PrivateKey::new(private_key),
+            public_key,
+        }
+    }
+
+    pub fn params(&self) -> &DhParams {
+        &self.params
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn public_key(&self) -> &BigUint {
+        &self.public_key
+    }
+
+    /// PKCS #8 `PrivateKeyInfo` DER, as OpenSSL writes for DH keys.
+    pub fn private_key_der(&self) -> Vec<u8> {
+        der::dh_private_key_info(&self.params.prime, &self.params.generator, self.private_key.expose())
+    }
+
+    /// `SubjectPublicKeyInfo` DER.
+    pub fn public_key_der(&self) -> Vec<u8> {
+        der::dh_subject_public_key_info(&self.params.prime, &self.params.generator, &self.public_key)
+    }
+
+    pub fn private_key_pem(&self) -> String {
+        pem::encode(pem::PRIVATE_KEY_LABEL, &self.private_key_der())
+    }
+
+    pub fn public_key_pem(&self) -> String {
+        pem::encode(pem::PUBLIC_KEY_LABEL, &self.public_key_der())
+    }
+
+    /// Hash of the SPKI DER, e.g. `sha256:3f9a...`.
+    pub fn fingerprint(&self, hash: FingerprintHash) -> String {
+        fingerprint::fingerprint(hash, &self.public_key_der())
+    }
+
+    /// The short base32 key ID the CLI prints and uses for file names.
+    pub fn key_id(&self) -> String {
+        fingerprint::key_id(&self.public_key_der())
+    }
+
+    /// `peer^x mod p`, left-padded to the byte length of the prime. The peer
+    /// value is range-checked first.
+    pub fn shared_secret(&self, peer: &BigUint) -> Result<Vec<u8>> {
+        dh::shared_secret(&self.params.prime, self.private_key.expose(), peer)
+    }
+
+    /// A view that serializes everything but the private key, for logs,
+    /// inventories and anything else sent off the host.
+    pub fn redacted(&self) -> Redacted<'_> {
+        Redacted(self)
+    }
+
+    pub fn into_parts(self) -> (DhParams, PrivateKey, BigUint) {
+        (self.params, self.private_key, self.public_key)
+    }
+}
+
+/// [`KeyPair::redacted`].
+pub struct Redacted<'a>(&'a KeyPair);
+
+#[derive(Serialize)]
+struct KeyPairRef<'a> {
+    #[serde(flatten)]
+    params: &'a DhParams,
+    #[serde(serialize_with = "serialize_private_key", skip_serializing_if = "Option::is_none")]
+    private_key: Option<&'a BigUint>,
+    #[serde(with = "serde_hex")]
+    public_key: &'a BigUint,
+}
+
+fn serialize_private_key<S: Serializer>(private_key: &Option<&BigUint>, serializer: S) -> Result<S::Ok, S::Error> {
+    let private_key = private_key.expect("skipped when None");
+    serde_hex::serialize(private_key, serializer)
+}
+
+impl Serialize for KeyPair {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        KeyPairRef {
+            params: &self.params,
+            private_key: Some(self.private_key.expose()),
+            public_key: &self.public_key,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for Redacted<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        KeyPairRef {
+            params: &self.0.params,
+            private_key: None,
+            public_key: &self.0.public_key,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize)]
+struct KeyPairFields {
+    #[serde(flatten)]
+    params: DhParams,
+    #[serde(with = "serde_hex")]
+    private_key: BigUint,
+    #[serde(with = "serde_hex")]
+    public_key: BigUint,
+}
+
+impl<'de> Deserialize<'de> for KeyPair {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = KeyPairFields::deserialize(deserializer)?;
+        let pair = KeyPair::from_private_key(fields.params, fields.private_key).map_err(serde::de::Error::custom)?;
+        if pair.public_key != fields.public_key {
+            return Err(serde::de::Error::custom("public key does not match its private key"));
+        }
+        Ok(pair)
+    }
+}
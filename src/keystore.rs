@@ -0,0 +1,348 @@
+//! Local SQLite keystore behind `--keystore` and the `keys` subcommands.
+//!
+//! Key IDs, metadata and public keys are stored in the clear so listing
+//! needs no passphrase. Each private key is its PKCS#8 DER sealed with the
+//! `--password-protect` format (Argon2id plus an AEAD). A sealed marker in
+//! the `meta` table lets a mistyped passphrase be rejected before it is used
+//! for a new key.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::der;
+use crate::metadata::Metadata;
+use crate::output::{Imported, KeyRecord};
+use crate::sealed::{self, Aead, Sealing};
+
+const SCHEMA_VERSION: i64 = 3;
+const VERIFIER: &[u8] = b"create-private-key keystore";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    name TEXT PRIMARY KEY,
+    value BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS keys (
+    key_id TEXT PRIMARY KEY,
+    created TEXT NOT NULL,
+    group_name TEXT NOT NULL,
+    label TEXT,
+    operator TEXT,
+    tool_version TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    prime_bits INTEGER NOT NULL,
+    public_key_spki BLOB NOT NULL,
+    sealed_private_key BLOB NOT NULL,
+    not_after TEXT
+);
+CREATE TABLE IF NOT EXISTS key_tags (
+    key_id TEXT NOT NULL REFERENCES keys (key_id),
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (key_id, name)
+);
+CREATE INDEX IF NOT EXISTS key_tags_by_value ON key_tags (name, value);
+CREATE INDEX IF NOT EXISTS keys_by_label ON keys (label);
+";
+
+/// What `list` narrows the keys down to; the default matches every key.
+#[derive(Default)]
+pub struct Filter {
+    pub label: Option<String>,
+    /// Tag names, each with the value it must have or `None` for any value.
+    pub tags: Vec<(String, Option<String>)>,
+}
+
+/// The stored, non-secret part of a key.
+pub struct Entry {
+    pub key_id: String,
+    pub metadata: Metadata,
+    pub fingerprint: String,
+    pub prime_bits: u64,
+    pub public_key_spki: Vec<u8>,
+}
+
+pub struct Keystore {
+    connection: Connection,
+}
+
+/// `$XDG_DATA_HOME/create-private-key/keystore.db`, falling back to
+/// `~/.local/share` (or `%APPDATA%` on Windows).
+pub fn default_path() -> PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("create-private-key").join("keystore.db")
+}
+
+impl Keystore {
+    /// Opens the keystore, creating the file (mode 0600) and schema when
+    /// `create` is set.
+    pub fn open(path: &Path, create: bool) -> Result<Self, String> {
+        if !create && !path.exists() {
+            return Err(format!("no keystore at {}", path.display()));
+        }
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+        }
+        if !path.exists() {
+            // Created up front so the database (and SQLite's journal, which
+            // copies its mode) is never readable by others.
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            options
+                .open(path)
+                .map_err(|err| format!("failed to create {}: {err}", path.display()))?;
+        }
+        let connection =
+            Connection::open(path).map_err(|err| format!("failed to open keystore {}: {err}", path.display()))?;
+        connection.execute_batch(SCHEMA).map_err(sql_error)?;
+        let keystore = Keystore { connection };
+        match keystore.meta("schema_version")? {
+            None => {
+                keystore.set_meta("schema_version", &SCHEMA_VERSION.to_string().into_bytes())?;
+            }
+            Some(version) if version == SCHEMA_VERSION.to_string().into_bytes() => {}
+            // Version 1 predates `--valid-for` and version 2 `--tag`, whose
+            // table the schema above has just created.
+            Some(version) if version == b"1" || version == b"2" => {
+                if version == b"1" {
+                    keystore
+                        .connection
+                        .execute_batch("ALTER TABLE keys ADD COLUMN not_after TEXT")
+                        .map_err(sql_error)?;
+                }
+                keystore.set_meta("schema_version", &SCHEMA_VERSION.to_string().into_bytes())?;
+            }
+            Some(version) => {
+                return Err(format!(
+                    "keystore schema version {} is not supported",
+                    String::from_utf8_lossy(&version)
+                ));
+            }
+        }
+        Ok(keystore)
+    }
+
+    /// Whether a passphrase has been set, i.e. a key was ever stored.
+    pub fn has_passphrase(&self) -> Result<bool, String> {
+        Ok(self.meta("verifier")?.is_some())
+    }
+
+    /// Checks `passphrase` against the keystore, or adopts it if the
+    /// keystore has none yet.
+    pub fn unlock(&self, passphrase: &str) -> Result<(), String> {
+        match self.meta("verifier")? {
+            Some(verifier) => sealed::open(&verifier, passphrase)
+                .ok()
+                .filter(|opened| opened == VERIFIER)
+                .map(|_| ())
+                .ok_or_else(|| "wrong keystore passphrase".to_string()),
+            None => self.set_meta("verifier", &sealed::seal(VERIFIER, &sealing(passphrase))?),
+        }
+    }
+
+    /// Stores a key; the caller has unlocked the keystore with `passphrase`.
+    pub fn insert(&self, record: &KeyRecord, passphrase: &str) -> Result<(), String> {
+        let metadata = record
+            .metadata
+            .as_ref()
+            .ok_or("keystore entries need metadata; drop --no-metadata")?;
+        let der = der::dh_private_key_info(&record.prime, &record.generator, record.private_key.expose());
+        let spki = der::dh_subject_public_key_info(&record.prime, &record.generator, &record.public_key);
+        let transaction = self.connection.unchecked_transaction().map_err(sql_error)?;
+        transaction
+            .execute(
+                "INSERT INTO keys (key_id, created, group_name, label, operator, tool_version, fingerprint,
+                                   prime_bits, public_key_spki, sealed_private_key, not_after)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    record.key_id,
+                    metadata.created,
+                    metadata.group,
+                    metadata.label,
+                    metadata.operator,
+                    metadata.tool_version,
+                    record.fingerprint,
+                    record.prime.bits() as i64,
+                    spki,
+                    sealed::seal(&der, &sealing(passphrase))?,
+                    metadata.not_after,
+                ],
+            )
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(failure, _)
+                    if failure.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    format!("the keystore already holds key {}", record.key_id)
+                }
+                err => sql_error(err),
+            })?;
+        for (name, value) in &metadata.tags {
+            transaction
+                .execute(
+                    "INSERT INTO key_tags (key_id, name, value) VALUES (?1, ?2, ?3)",
+                    params![record.key_id, name, value],
+                )
+                .map_err(sql_error)?;
+        }
+        transaction.commit().map_err(sql_error)
+    }
+
+    /// The entries `filter` matches, oldest first.
+    pub fn list(&self, filter: &Filter) -> Result<Vec<Entry>, String> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(ref label) = filter.label {
+            values.push(label.as_str());
+            conditions.push(format!("label = ?{}", values.len()));
+        }
+        for (name, value) in &filter.tags {
+            values.push(name.as_str());
+            let mut condition = format!(
+                "EXISTS (SELECT 1 FROM key_tags WHERE key_tags.key_id = keys.key_id AND name = ?{}",
+                values.len()
+            );
+            if let Some(value) = value {
+                values.push(value.as_str());
+                condition.push_str(&format!(" AND value = ?{}", values.len()));
+            }
+            condition.push(')');
+            conditions.push(condition);
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT {ENTRY_COLUMNS} FROM keys {filter} ORDER BY created, key_id"))
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(values), entry)
+            .map_err(sql_error)?;
+        let mut entries = rows.collect::<Result<Vec<_>, _>>().map_err(sql_error)?;
+        for entry in &mut entries {
+            self.load_tags(entry)?;
+        }
+        Ok(entries)
+    }
+
+    pub fn get(&self, key_id: &str) -> Result<Entry, String> {
+        let mut entry = self
+            .connection
+            .query_row(
+                &format!("SELECT {ENTRY_COLUMNS} FROM keys WHERE key_id = ?1"),
+                [key_id],
+                entry,
+            )
+            .optional()
+            .map_err(sql_error)?
+            .ok_or_else(|| not_found(key_id))?;
+        self.load_tags(&mut entry)?;
+        Ok(entry)
+    }
+
+    fn load_tags(&self, entry: &mut Entry) -> Result<(), String> {
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT name, value FROM key_tags WHERE key_id = ?1")
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map([&entry.key_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(sql_error)?;
+        entry.metadata.tags = rows.collect::<Result<_, _>>().map_err(sql_error)?;
+        Ok(())
+    }
+
+    /// Unseals a private key.
+    pub fn private_key(&self, key_id: &str, passphrase: &str) -> Result<Imported, String> {
+        let entry = self.get(key_id)?;
+        let sealed: Vec<u8> = self
+            .connection
+            .query_row("SELECT sealed_private_key FROM keys WHERE key_id = ?1", [key_id], |row| row.get(0))
+            .map_err(sql_error)?;
+        let der = sealed::open(&sealed, passphrase).map_err(|_| "wrong keystore passphrase".to_string())?;
+        let (prime, generator, private_key) = der::parse_dh_private_key_info(&der)?;
+        Ok(Imported {
+            prime,
+            generator,
+            private_key,
+            metadata: Some(entry.metadata),
+        })
+    }
+
+    pub fn delete(&self, key_id: &str) -> Result<(), String> {
+        let transaction = self.connection.unchecked_transaction().map_err(sql_error)?;
+        transaction
+            .execute("DELETE FROM key_tags WHERE key_id = ?1", [key_id])
+            .map_err(sql_error)?;
+        match transaction.execute("DELETE FROM keys WHERE key_id = ?1", [key_id]) {
+            Ok(0) => Err(not_found(key_id)),
+            Ok(_) => transaction.commit().map_err(sql_error),
+            Err(err) => Err(sql_error(err)),
+        }
+    }
+
+    fn meta(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        self.connection
+            .query_row("SELECT value FROM meta WHERE name = ?1", [name], |row| row.get(0))
+            .optional()
+            .map_err(sql_error)
+    }
+
+    fn set_meta(&self, name: &str, value: &[u8]) -> Result<(), String> {
+        self.connection
+            .execute("INSERT OR REPLACE INTO meta (name, value) VALUES (?1, ?2)", params![name, value])
+            .map(|_| ())
+            .map_err(sql_error)
+    }
+}
+
+const ENTRY_COLUMNS: &str =
+    "key_id, created, group_name, label, operator, tool_version, fingerprint, prime_bits, public_key_spki, not_after";
+
+fn entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<Entry> {
+    Ok(Entry {
+        key_id: row.get(0)?,
+        metadata: Metadata {
+            created: row.get(1)?,
+            group: row.get(2)?,
+            label: row.get(3)?,
+            operator: row.get(4)?,
+            tool_version: row.get(5)?,
+            not_after: row.get(9)?,
+            tags: Default::default(),
+        },
+        fingerprint: row.get(6)?,
+        prime_bits: row.get::<_, i64>(7)? as u64,
+        public_key_spki: row.get(8)?,
+    })
+}
+
+fn sealing(passphrase: &str) -> Sealing {
+    Sealing {
+        passphrase: passphrase.to_string(),
+        aead: Aead::ChaCha20Poly1305,
+        memory_kib: sealed::DEFAULT_MEMORY_KIB,
+        iterations: sealed::DEFAULT_ITERATIONS,
+        lanes: sealed::DEFAULT_LANES,
+    }
+}
+
+fn not_found(key_id: &str) -> String {
+    format!("the keystore holds no key {key_id}")
+}
+
+fn sql_error(err: rusqlite::Error) -> String {
+    format!("keystore: {err}")
+}
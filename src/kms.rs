@@ -0,0 +1,172 @@
+//! Envelope encryption under a cloud KMS key (`--encrypt-with`).
+//!
+//! The KMS issues a fresh AES-256 data key, wrapped under the master key;
+//! the key file is encrypted locally with AES-256-GCM and stored next to the
+//! wrapped data key in a small JSON document (`cpk-kms-v1`). Decrypting it
+//! needs `kms:Decrypt` on the master key and nothing else.
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::external;
+use crate::random;
+
+pub const FORMAT_V1: &str = "cpk-kms-v1";
+const ALGORITHM: &str = "AES-256-GCM";
+const NONCE_LEN: usize = 12;
+
+/// A master key, written `<provider>:<key id>` on the command line.
+#[derive(Clone, Debug)]
+pub enum KmsKey {
+    /// `aws-kms:<key ARN, alias ARN or key ID>`, used through the `aws` CLI.
+    Aws(String),
+}
+
+impl FromStr for KmsKey {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        match spec.split_once(':') {
+            Some(("aws-kms", key)) if !key.is_empty() => Ok(KmsKey::Aws(key.to_string())),
+            _ => Err(format!("unsupported KMS key '{spec}' (expected aws-kms:<key ARN>)")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    format: String,
+    kms: String,
+    key_id: String,
+    encrypted_data_key: String,
+    algorithm: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypts `plaintext` under a new data key from `key`.
+pub fn encrypt(key: &KmsKey, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let (kms, data_key) = match key {
+        KmsKey::Aws(key_id) => ("aws-kms", aws_generate_data_key(key_id)?),
+    };
+
+    let mut nonce = [0u8; NONCE_LEN];
+    random::fill(&mut nonce)?;
+    let cipher = Aes256Gcm::new_from_slice(&data_key.plaintext).map_err(|_| "KMS data key is not 256 bits")?;
+    let ciphertext = cipher
+        .encrypt(
+            (&nonce).into(),
+            Payload {
+                msg: plaintext,
+                aad: data_key.key_id.as_bytes(),
+            },
+        )
+        .map_err(|_| "encryption failed".to_string())?;
+
+    let envelope = Envelope {
+        format: FORMAT_V1.to_string(),
+        kms: kms.to_string(),
+        key_id: data_key.key_id,
+        encrypted_data_key: BASE64.encode(data_key.wrapped),
+        algorithm: ALGORITHM.to_string(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    let mut rendered = serde_json::to_string_pretty(&envelope).expect("KMS envelope serializes");
+    rendered.push('\n');
+    Ok(rendered.into_bytes())
+}
+
+/// Whether `input` looks like a KMS envelope.
+pub fn is_envelope(input: &[u8]) -> bool {
+    std::str::from_utf8(input).is_ok_and(|text| text.trim_start().starts_with('{') && text.contains(FORMAT_V1))
+}
+
+/// Unwraps the data key with the KMS and decrypts the original bytes.
+pub fn decrypt(input: &[u8]) -> Result<Vec<u8>, String> {
+    let envelope: Envelope = serde_json::from_slice(input).map_err(|err| format!("invalid KMS envelope: {err}"))?;
+    if envelope.format != FORMAT_V1 || envelope.algorithm != ALGORITHM {
+        return Err(format!("unsupported KMS envelope '{}' / {}", envelope.format, envelope.algorithm));
+    }
+    let field = |name: &str, value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|err| format!("KMS envelope field '{name}' is not base64: {err}"))
+    };
+    let wrapped = field("encrypted_data_key", &envelope.encrypted_data_key)?;
+    let nonce: [u8; NONCE_LEN] = field("nonce", &envelope.nonce)?
+        .try_into()
+        .map_err(|_| "KMS envelope nonce has the wrong length".to_string())?;
+    let ciphertext = field("ciphertext", &envelope.ciphertext)?;
+
+    let data_key = match envelope.kms.as_str() {
+        "aws-kms" => aws_decrypt(&envelope.key_id, &wrapped)?,
+        other => return Err(format!("unsupported KMS '{other}' in envelope")),
+    };
+    let cipher = Aes256Gcm::new_from_slice(&data_key).map_err(|_| "KMS data key is not 256 bits")?;
+    cipher
+        .decrypt(
+            (&nonce).into(),
+            Payload {
+                msg: &ciphertext,
+                aad: envelope.key_id.as_bytes(),
+            },
+        )
+        .map_err(|_| "KMS envelope failed to decrypt (corrupted or wrong key)".to_string())
+}
+
+struct DataKey {
+    key_id: String,
+    plaintext: Vec<u8>,
+    wrapped: Vec<u8>,
+}
+
+fn aws_generate_data_key(key_id: &str) -> Result<DataKey, String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Response {
+        key_id: String,
+        plaintext: String,
+        ciphertext_blob: String,
+    }
+
+    let output = external::run_filter(
+        "aws",
+        &["kms", "generate-data-key", "--key-id", key_id, "--key-spec", "AES_256", "--output", "json"],
+        &[],
+    )?;
+    let response: Response =
+        serde_json::from_slice(&output).map_err(|err| format!("unexpected `aws kms` output: {err}"))?;
+    let decode = |value: &str| BASE64.decode(value.trim()).map_err(|err| format!("unexpected `aws kms` output: {err}"));
+    Ok(DataKey {
+        plaintext: decode(&response.plaintext)?,
+        wrapped: decode(&response.ciphertext_blob)?,
+        key_id: response.key_id,
+    })
+}
+
+fn aws_decrypt(key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>, String> {
+    let output = external::run_filter(
+        "aws",
+        &[
+            "kms",
+            "decrypt",
+            "--key-id",
+            key_id,
+            "--ciphertext-blob",
+            "fileb:///dev/stdin",
+            "--query",
+            "Plaintext",
+            "--output",
+            "text",
+        ],
+        wrapped,
+    )?;
+    BASE64
+        .decode(String::from_utf8_lossy(&output).trim())
+        .map_err(|err| format!("unexpected `aws kms` output: {err}"))
+}
@@ -0,0 +1,85 @@
+//! Published groups that vendors and other tools ship, so parameters can be
+//! recognised by value rather than by comparing hex by hand.
+
+use create_private_key_core::{self as ffdh, Constants};
+use num_bigint::BigUint;
+
+/// RFC 5114 section 2.3's generator and subgroup order; its prime is in the
+/// core crate with the others.
+pub const RFC5114_2048_256_GENERATOR_HEX: &str = concat!(
+    "3FB32C9B73134D0B2E77506660EDBD484CA7B18F21EF205407F4793A1A0BA125",
+    "10DBC15077BE463FFF4FED4AAC0BB555BE3A6C1B0C6B47B1BC3773BF7E8C6F62",
+    "901228F8C28CBB18A55AE31341000A650196F931C77A57F2DDF463E5E9EC144B",
+    "777DE62AAAB8A8628AC376D282D6ED3864E67982428EBC831D14348F6F2F9193",
+    "B5045AF2767164E1DFC967C1FB3F2E55A4BD1BFFE83B9C80D052B985D182EA0A",
+    "DB2A3B7313D3FE14C8484B1E052588B9B7D2BBD2DF016199ECD06E1557CD0915",
+    "B3353BBB64E0EC377FD028370DF92B52C7891428CDC67EB6184B523D1DB246C3",
+    "2F63078490F00EF8D647D148D47954515E2327CFEF98C582664B4C0F6CC41659"
+);
+pub const RFC5114_2048_256_ORDER_HEX: &str = "8CF83642A709A097B447997640129DA299B1A47D1EB3750BA308B0FE64F5FBD3";
+
+pub struct KnownGroup {
+    pub name: &'static str,
+    /// The document that publishes it.
+    pub source: &'static str,
+    /// The prime, parsed at compile time.
+    pub prime: &'static Constants<32>,
+    pub generator_hex: &'static str,
+    /// Subgroup order; `None` for a safe prime, where it is `(p - 1) / 2`.
+    pub order_hex: Option<&'static str>,
+}
+
+pub const KNOWN_GROUPS: [KnownGroup; 3] = [
+    KnownGroup {
+        name: "modp14",
+        source: "RFC 3526 group 14",
+        prime: &ffdh::RFC3526_MODP14,
+        generator_hex: "2",
+        order_hex: None,
+    },
+    KnownGroup {
+        name: "ffdhe2048",
+        source: "RFC 7919",
+        prime: &ffdh::RFC7919_FFDHE2048,
+        generator_hex: "2",
+        order_hex: None,
+    },
+    KnownGroup {
+        name: "rfc5114-2048-256",
+        source: "RFC 5114 section 2.3",
+        prime: &ffdh::RFC5114_2048_256,
+        generator_hex: RFC5114_2048_256_GENERATOR_HEX,
+        order_hex: Some(RFC5114_2048_256_ORDER_HEX),
+    },
+];
+
+impl KnownGroup {
+    pub fn by_name(name: &str) -> Option<&'static KnownGroup> {
+        KNOWN_GROUPS.iter().find(|group| group.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The group whose prime is `prime`.
+    pub fn by_prime(prime: &BigUint) -> Option<&'static KnownGroup> {
+        KNOWN_GROUPS.iter().find(|group| group.prime() == *prime)
+    }
+
+    pub fn prime(&self) -> BigUint {
+        self.prime.modulus()
+    }
+
+    pub fn generator(&self) -> BigUint {
+        hex(self.generator_hex)
+    }
+
+    /// The order of the generator's subgroup.
+    pub fn order(&self) -> BigUint {
+        match self.order_hex {
+            Some(order_hex) => hex(order_hex),
+            None => (self.prime() - 1u32) >> 1,
+        }
+    }
+}
+
+fn hex(digits: &str) -> BigUint {
+    BigUint::parse_bytes(digits.as_bytes(), 16).expect("published group constants are hex")
+}
@@ -0,0 +1,34 @@
+//! Finite-field Diffie-Hellman key generation, as used by the
+//! `create-private-key` command.
+//!
+//! ```no_run
+//! use create_private_key::{DhParams, FingerprintHash, generate_keypair};
+//!
+//! let params = DhParams::default(); // RFC 3526 group 14
+//! let pair = generate_keypair(&params, &mut rand::rngs::OsRng)?;
+//! println!("{} {}", pair.key_id(), pair.fingerprint(FingerprintHash::Sha256));
+//! print!("{}", pair.private_key_pem());
+//! # Ok::<(), create_private_key::Error>(())
+//! ```
+//!
+//! The modules below hold the lower-level pieces: parameter parsing and
+//! validation, the DER and PEM encodings, fingerprints and the DH
+//! computation itself.
+
+pub mod der;
+pub mod dh;
+mod error;
+pub mod fingerprint;
+mod keypair;
+pub mod params;
+pub mod pem;
+pub mod rng;
+mod secret;
+mod serde_hex;
+
+pub use error::{Error, Result};
+pub use fingerprint::FingerprintHash;
+pub use keypair::{DhParams, DhParamsBuilder, FixedBase, KeyPair, MIN_PRIVATE_BITS, Redacted, generate_keypair};
+pub use params::DhGroup;
+pub use rng::KeySource;
+pub use secret::PrivateKey;
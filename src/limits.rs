@@ -0,0 +1,137 @@
+//! Per-client request rate limits and a global key quota for `serve` and
+//! `agent`, so one misbehaving client cannot drain the entropy pool or
+//! flood the logs.
+//!
+//! Rates are token buckets: a client may burst up to `burst` requests, then
+//! gets `rate` more per second. The quota counts keys generated in fixed
+//! one-hour windows, across all clients.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+
+const HOUR: Duration = Duration::from_secs(3600);
+/// Clients tracked before full buckets are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Why a request was turned away; HTTP answers it with a 429.
+#[derive(Debug)]
+pub enum Refusal {
+    RateLimited { retry_after: Duration },
+    QuotaExhausted { quota: u64, retry_after: Duration },
+}
+
+impl Refusal {
+    /// Whole seconds until a retry can succeed, at least 1.
+    pub fn retry_after_secs(&self) -> u64 {
+        let (Refusal::RateLimited { retry_after } | Refusal::QuotaExhausted { retry_after, .. }) = self;
+        retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+impl std::fmt::Display for Refusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Refusal::RateLimited { .. } => {
+                write!(f, "too many requests from this client; retry in {} s", self.retry_after_secs())
+            }
+            Refusal::QuotaExhausted { quota, .. } => write!(
+                f,
+                "the hourly key quota ({quota}) is used up; retry in {} s",
+                self.retry_after_secs()
+            ),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Quota {
+    window_start: Instant,
+    used: u64,
+}
+
+#[derive(Default)]
+pub struct Limits {
+    /// Requests per second per client, and the burst allowance.
+    rate: Option<(f64, f64)>,
+    keys_per_hour: Option<u64>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    quota: Mutex<Option<Quota>>,
+}
+
+impl Limits {
+    /// `burst` defaults to one second's worth of requests, and at least 1.
+    pub fn new(rate: Option<f64>, burst: Option<u32>, keys_per_hour: Option<u64>) -> Result<Limits, String> {
+        if rate.is_some_and(|rate| !(rate > 0.0 && rate.is_finite())) {
+            return Err("--rate-limit must be a positive number of requests per second".into());
+        }
+        if burst == Some(0) {
+            return Err("--burst must be at least 1".into());
+        }
+        Ok(Limits {
+            rate: rate.map(|rate| (rate, burst.map_or(rate.ceil().max(1.0), f64::from))),
+            keys_per_hour,
+            ..Limits::default()
+        })
+    }
+
+    /// Spends one of `client`'s requests.
+    pub fn request(&self, client: &str) -> Result<(), Refusal> {
+        let Some((rate, burst)) = self.rate else { return Ok(()) };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(client) {
+            // A full bucket is the same as a fresh one, so forgetting it
+            // changes nothing for that client.
+            buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            metrics::request_refused();
+            let retry_after = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+            tracing::warn!(client, "rate limit exceeded");
+            return Err(Refusal::RateLimited { retry_after });
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Spends one key of the hourly quota.
+    pub fn take_key(&self) -> Result<(), Refusal> {
+        let Some(quota) = self.keys_per_hour else { return Ok(()) };
+        let now = Instant::now();
+        let mut window = self.quota.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window = match *window {
+            Some(ref mut window) if now.duration_since(window.window_start) < HOUR => window,
+            _ => window.insert(Quota { window_start: now, used: 0 }),
+        };
+        if window.used >= quota {
+            metrics::request_refused();
+            tracing::warn!(quota, "key quota exhausted");
+            let retry_after = HOUR - now.duration_since(window.window_start);
+            return Err(Refusal::QuotaExhausted { quota, retry_after });
+        }
+        window.used += 1;
+        Ok(())
+    }
+
+    /// Gives back a key taken for a request that then failed before
+    /// generating it.
+    pub fn return_key(&self) {
+        if self.keys_per_hour.is_none() {
+            return;
+        }
+        let mut window = self.quota.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(ref mut window) = *window {
+            window.used = window.used.saturating_sub(1);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Diagnostics on stderr, via `tracing`: `-v` for progress, `-vv` for
+//! validation decisions and timings, `-vvv` for everything.
+//!
+//! Events carry key IDs, group names, sizes and durations only. Private
+//! keys, shared secrets and passphrases are never passed to a log macro.
+
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use tracing::Level;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+#[cfg(feature = "service")]
+use crate::telemetry;
+#[cfg(feature = "service")]
+pub use crate::telemetry::Telemetry;
+
+/// Without the `service` feature there is no exporter to keep alive.
+#[cfg(not(feature = "service"))]
+pub enum Telemetry {}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per event.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// Installs the global subscriber. Without `-v` only warnings are shown.
+/// With `otlp` set, request spans are also exported there whatever the
+/// verbosity; keep the returned guard alive for as long as they should be.
+pub fn init(verbosity: u8, format: LogFormat, otlp: Option<&str>) -> Result<Option<Telemetry>, String> {
+    let level = match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let logs = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(false);
+    let logs = match format {
+        LogFormat::Text => logs.with_ansi(std::io::stderr().is_terminal()).boxed(),
+        #[cfg(feature = "json")]
+        LogFormat::Json => logs.json().with_current_span(false).boxed(),
+    };
+    #[cfg(feature = "service")]
+    let (spans, telemetry) = match otlp {
+        Some(endpoint) => {
+            let (layer, telemetry) = telemetry::layer(endpoint)?;
+            (Some(layer), Some(telemetry))
+        }
+        None => (None, None),
+    };
+    #[cfg(not(feature = "service"))]
+    let (spans, telemetry) = {
+        debug_assert!(otlp.is_none(), "only the services take --otlp-endpoint");
+        (None::<tracing_subscriber::layer::Identity>, None)
+    };
+    tracing_subscriber::registry()
+        .with(logs.with_filter(LevelFilter::from_level(level)))
+        .with(spans)
+        .init();
+    Ok(telemetry)
+}
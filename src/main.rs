@@ -1,174 +1,3526 @@
-use clap::{Parser, ValueEnum};
-use num_bigint::{BigUint, RandBigInt};
-use num_integer::Integer;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use num_bigint::BigUint;
+#[cfg(feature = "hsm")]
 use num_traits::One;
-use rand::rngs::OsRng;
-use rand::Rng;
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Instant;
+use zeroize::Zeroize;
 
-/// Default RFC 3526 MODP group used when no custom prime is supplied.
-const RFC3526_MODP14_PRIME_HEX: &str = concat!(
-    "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD1",
-    "29024E088A67CC74020BBEA63B139B22514A08798E3404DD",
-    "EF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245",
-    "E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED",
-    "EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3D",
-    "C2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F",
-    "83655D23DCA3AD961C62F356208552BB9ED529077096966D",
-    "670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B",
-    "E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9",
-    "DE2BCBF6955817183995497CEA956AE515D2261898FA0510",
-    "15728E5A8AACAA68FFFFFFFFFFFFFFFF"
-);
-
-#[derive(ValueEnum, Clone, Copy, Debug)]
-enum DhGroup {
-    /// RFC 3526 MODP group 14 (2048-bit safe prime, generator 2).
-    Modp14,
-}
-
-impl DhGroup {
-    fn default_prime_hex(self) -> &'static str {
+use create_private_key::dh;
+#[cfg(any(feature = "pem", feature = "json"))]
+use create_private_key::der;
+use create_private_key::pem;
+use create_private_key::params::Radix;
+use create_private_key::{DhParams, Error, KeyPair, fingerprint, params};
+use create_private_key_core as ffdh;
+
+#[cfg(feature = "acvp")]
+mod acvp;
+#[cfg(feature = "service")]
+mod agent;
+#[cfg(feature = "service")]
+mod api;
+#[cfg(feature = "json")]
+mod audit;
+#[cfg(feature = "pem")]
+mod batch;
+mod bench;
+mod clipboard;
+mod compliance;
+mod config;
+#[cfg(feature = "pem")]
+mod convert;
+mod drbg;
+#[cfg(feature = "json")]
+mod envelope;
+mod estimate;
+mod expiry;
+mod explain;
+#[cfg(feature = "pem")]
+mod external;
+mod fips;
+#[cfg(feature = "service")]
+mod grpc;
+mod hardware;
+mod known_groups;
+mod health;
+#[cfg(feature = "service")]
+mod http;
+#[cfg(feature = "keystore")]
+mod keystore;
+#[cfg(feature = "service")]
+mod limits;
+#[cfg(feature = "cloud")]
+mod kms;
+mod logging;
+mod metadata;
+#[cfg(feature = "service")]
+mod metrics;
+#[cfg(feature = "pem")]
+mod openssl;
+mod output;
+mod paper;
+mod param_cache;
+mod param_diff;
+mod primality;
+mod progress;
+#[cfg(feature = "pem")]
+mod passphrase;
+#[cfg(feature = "pem")]
+mod pbes2;
+#[cfg(feature = "hsm")]
+mod pkcs11;
+#[cfg(feature = "hsm")]
+mod piv;
+#[cfg(feature = "json")]
+mod pipeline;
+#[cfg(feature = "pem")]
+mod pool;
+#[cfg(feature = "pem")]
+mod protect;
+mod protobuf;
+#[cfg(feature = "pem")]
+mod provenance;
+#[cfg(feature = "qr")]
+mod qr;
+mod random;
+#[cfg(feature = "service")]
+mod rpc;
+#[cfg(feature = "pem")]
+mod sealed;
+mod selftest;
+#[cfg(feature = "pem")]
+mod shamir;
+#[cfg(feature = "pem")]
+mod signal;
+mod sink;
+#[cfg(feature = "pem")]
+mod sodium;
+#[cfg(feature = "cloud")]
+mod sops;
+#[cfg(feature = "cloud")]
+mod store;
+#[cfg(feature = "service")]
+mod systemd;
+#[cfg(feature = "service")]
+mod telemetry;
+mod testvectors;
+mod timings;
+mod transcript;
+#[cfg(feature = "hsm")]
+mod tpm;
+mod wizard;
+
+#[cfg(feature = "json")]
+use audit::AuditLog;
+#[cfg(feature = "pem")]
+use batch::BatchWriter;
+use clipboard::CopyTarget;
+use config::Config;
+#[cfg(feature = "pem")]
+use convert::KeyFormat;
+use fingerprint::FingerprintHash;
+use hardware::HwSource;
+#[cfg(feature = "keystore")]
+use keystore::Keystore;
+use logging::LogFormat;
+#[cfg(feature = "cloud")]
+use kms::KmsKey;
+use metadata::Metadata;
+use params::DhGroup;
+use random::{DrbgOptions, RngBackend};
+#[cfg(feature = "hsm")]
+use piv::PivAlgorithm;
+#[cfg(feature = "pem")]
+use pbes2::{Encryption, Kdf};
+#[cfg(feature = "pem")]
+use protect::Protection;
+#[cfg(feature = "json")]
+use output::{render_json, render_msgpack};
+use output::{
+    KeyRecord, Labels, OutputFormat, PrintTarget, render_explained, render_plain, render_pretty, render_quiet,
+    render_template,
+};
+use progress::Progress;
+use timings::{Stage, Timings};
+#[cfg(feature = "qr")]
+use qr::QrTarget;
+#[cfg(feature = "pem")]
+use sealed::{Aead, Sealing};
+#[cfg(feature = "pem")]
+use shamir::SplitSpec;
+#[cfg(feature = "pem")]
+use sink::Overwrite;
+#[cfg(feature = "cloud")]
+use sops::{SopsFormat, SopsKeys};
+#[cfg(feature = "cloud")]
+use store::Store;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorChoice {
+    /// Color when stdout is a terminal and NO_COLOR is unset.
+    Auto,
+    Always,
+    Never,
+}
+
+/// Output modes a SOPS document replaces. A SOPS file holds exactly one key.
+#[cfg(feature = "cloud")]
+const SOPS_CONFLICTS: [&str; 9] = [
+    "template",
+    "paper_backup",
+    "quiet",
+    "json",
+    "msgpack",
+    "protobuf",
+    "envelope",
+    "count",
+    "output_dir",
+];
+
+/// Command line arguments for the DH key tool.
+#[derive(Parser, Debug)]
+#[command(
+    name = "create-private-key",
+    version,
+    propagate_version = true,
+    about = "Generate, inspect and use Diffie-Hellman keys; without a subcommand, runs `generate`",
+    args_conflicts_with_subcommands = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Log to stderr: -v progress, -vv validation decisions and timings,
+    /// -vvv everything. Key material is never logged.
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Format of the -v logs.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        env = "CPK_LOG_FORMAT",
+        default_value_t = LogFormat::Text,
+        global = true
+    )]
+    log_format: LogFormat,
+
+    /// Random number generator for keys, salts and nonces.
+    #[arg(long, value_enum, value_name = "RNG", env = "CPK_RNG", default_value_t = RngBackend::Os, global = true)]
+    rng: RngBackend,
+
+    /// Hardware entropy source to mix into everything drawn from the OS
+    /// RNG (never replacing it); repeat or comma-separate for several.
+    #[arg(long, value_enum, value_name = "SOURCE", value_delimiter = ',', env = "CPK_HW_ENTROPY", global = true)]
+    hw_entropy: Vec<HwSource>,
+
+    /// Hash this file (its first MiB) into everything drawn from the OS RNG,
+    /// like `openssl -rand`; may be repeated.
+    #[arg(long, value_name = "FILE", global = true)]
+    entropy_file: Vec<PathBuf>,
+
+    /// Like --entropy-file, reading the seed material from stdin.
+    #[arg(long, global = true)]
+    entropy_stdin: bool,
+
+    /// Skip the SP 800-90B startup health tests on the entropy sources.
+    #[arg(long, global = true)]
+    skip_health_tests: bool,
+
+    /// Run the known-answer self-tests (see `selftest`) before anything
+    /// else and stop if any fails.
+    #[arg(long, global = true)]
+    self_test: bool,
+
+    /// FIPS mode: approved groups and key sizes only, SP 800-56A key
+    /// generation and public-key validation, the HMAC_DRBG, and self-tests
+    /// first. Every other option is refused.
+    #[arg(long, global = true)]
+    fips: bool,
+
+    /// Refuse primes, and any other number, longer than this many bits,
+    /// before parsing them or computing with them.
+    #[arg(
+        long,
+        value_name = "BITS",
+        env = "CPK_MAX_PRIME_BITS",
+        default_value_t = params::DEFAULT_MAX_PRIME_BITS,
+        global = true,
+        value_parser = clap::value_parser!(u64).range(2048..)
+    )]
+    max_prime_bits: u64,
+
+    /// Remember the safe-prime test on custom parameters between runs, in
+    /// `~/.cache/create-private-key/params`, and reuse it when a later test
+    /// would be no more thorough.
+    #[arg(long, env = "CPK_PARAM_CACHE", global = true)]
+    param_cache: bool,
+
+    /// Read option defaults and the key policy from this file instead of
+    /// `~/.config/create-private-key/config.toml`.
+    #[arg(long, value_name = "FILE", env = "CPK_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    drbg: DrbgArgs,
+
+    /// The arguments of `generate`, which runs when no subcommand is given.
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+/// Generation options: group, output formats and destinations.
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+    /// RFC 3526 MODP group to base parameters on (ignored when --prime is provided).
+    #[arg(long, value_enum, env = "CPK_GROUP", default_value_t = DhGroup::Modp14)]
+    group: DhGroup,
+
+    /// Diffie-Hellman prime modulus: decimal, or hex with 0x (see --prime-radix).
+    #[arg(long, env = "CPK_PRIME")]
+    prime: Option<String>,
+
+    /// How --prime is read: hex with 0x and decimal without (auto), or
+    /// always hex or always decimal.
+    #[arg(long, value_enum, value_name = "RADIX", env = "CPK_PRIME_RADIX", default_value_t = Radix::Auto)]
+    prime_radix: Radix,
+
+    /// With --prime-radix auto, refuse an unprefixed --prime that may be
+    /// hex: one with a leading zero or a common prime size's hex length.
+    #[arg(long, env = "CPK_STRICT_PRIME")]
+    strict_prime: bool,
+
+    /// Generator to use (defaults to group generator).
+    #[arg(long, env = "CPK_GENERATOR")]
+    generator: Option<String>,
+
+    /// Draw private exponents of this many bits (at least 224) instead of
+    /// from the whole group.
+    #[arg(long, value_name = "BITS", env = "CPK_PRIVATE_BITS")]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "import"))]
+    private_bits: Option<u64>,
+
+    /// Output format for the private key.
+    #[arg(
+        long = "private-format",
+        visible_alias = "format",
+        value_enum,
+        env = "CPK_FORMAT",
+        default_value_t = OutputFormat::Hex
+    )]
+    output_format: OutputFormat,
+
+    /// Output format for the public key.
+    #[arg(long, value_enum, env = "CPK_PUBLIC_FORMAT", default_value_t = OutputFormat::Hex)]
+    public_format: OutputFormat,
+
+    /// Hash used for the public key fingerprint (taken over the SPKI DER encoding).
+    #[arg(long, value_enum, env = "CPK_FINGERPRINT_HASH", default_value_t = FingerprintHash::Sha256)]
+    fingerprint_hash: FingerprintHash,
+
+    /// Render the key record through a template file instead of the default output.
+    /// Placeholders look like `{{private_key_hex}}`; see README for the full list.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output_format", "public_format"])]
+    template: Option<PathBuf>,
+
+    /// Ask for the group, key size, format and destination step by step,
+    /// starting from the values the other options give, and confirm before
+    /// anything is written.
+    #[arg(long)]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "import"))]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    interactive: bool,
+
+    /// Print only bare key values, with no labels or parameter lines.
+    #[arg(long, short, conflicts_with_all = ["template", "paper_backup"])]
+    quiet: bool,
+
+    /// Which values `--quiet` prints, in `--private-format` and `--public-format`.
+    #[arg(long, value_enum, value_name = "KEY", default_value_t = PrintTarget::Both, requires = "quiet")]
+    print: PrintTarget,
+
+    /// Run as a coprocess answering newline-delimited JSON-RPC 2.0 requests
+    /// on stdin/stdout. --group/--prime/--generator set the default group.
+    #[cfg(feature = "service")]
+    #[arg(long, conflicts_with = "import")]
+    rpc: bool,
+
+    /// Read newline-delimited JSON parameter records from stdin and write one
+    /// `--json` key record per line to stdout until stdin closes. Fields a
+    /// record leaves out come from the other options.
+    #[cfg(feature = "json")]
+    #[arg(long, conflicts_with_all = [
+        "import", "interactive", "count", "template", "paper_backup", "quiet", "protobuf", "msgpack", "envelope",
+        "private_fd", "copy", "compliance_report", "explain", "timings",
+    ])]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    pipeline: bool,
+
+    /// Print each key record as a single-line JSON object, including metadata.
+    #[cfg(feature = "json")]
+    #[arg(long, conflicts_with_all = ["template", "paper_backup", "quiet", "protobuf"])]
+    json: bool,
+
+    /// Write each key record as a MessagePack map (same fields as `--json`).
+    #[cfg(feature = "json")]
+    #[arg(long, conflicts_with_all = ["template", "paper_backup", "quiet", "json", "protobuf"])]
+    msgpack: bool,
+
+    /// Write each key record as a length-delimited protobuf message
+    /// (schema: proto/key_record.proto).
+    #[arg(long, conflicts_with_all = ["template", "paper_backup", "quiet"])]
+    protobuf: bool,
+
+    /// Print each key as a versioned `cpk-v1` JSON envelope that `--import` can read back.
+    #[cfg(feature = "json")]
+    #[arg(long, conflicts_with_all = ["template", "paper_backup", "quiet", "json", "msgpack", "protobuf"])]
+    envelope: bool,
+
+    /// Print the `cpk-v1` envelope encrypted by `sops` to this age recipient; repeatable.
+    #[cfg(feature = "cloud")]
+    #[arg(long, value_name = "RECIPIENT", conflicts_with_all = SOPS_CONFLICTS)]
+    sops_age: Vec<String>,
+
+    /// Print the `cpk-v1` envelope encrypted by `sops` with this AWS KMS key ARN; repeatable.
+    #[cfg(feature = "cloud")]
+    #[arg(long, value_name = "ARN", conflicts_with_all = SOPS_CONFLICTS)]
+    sops_kms: Vec<String>,
+
+    /// Print the `cpk-v1` envelope encrypted by `sops` with this GCP KMS key
+    /// resource ID; repeatable.
+    #[cfg(feature = "cloud")]
+    #[arg(long, value_name = "RESOURCE", conflicts_with_all = SOPS_CONFLICTS)]
+    sops_gcp_kms: Vec<String>,
+
+    /// Document format for the SOPS output.
+    #[cfg(feature = "cloud")]
+    #[arg(long, value_enum, default_value_t = SopsFormat::Yaml)]
+    sops_format: SopsFormat,
+
+    /// Re-export a key from a `cpk-v1` envelope (`-` for stdin) instead of
+    /// generating a new one.
+    #[cfg(feature = "json")]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["prime", "generator", "group", "count", "valid_for"])]
+    import: Option<PathBuf>,
+
+    /// Free-form label recorded in the key metadata.
+    #[arg(long, value_name = "TEXT", env = "CPK_LABEL", value_parser = metadata::parse_text)]
+    label: Option<String>,
+
+    /// Operator name recorded in the key metadata (defaults to $USER).
+    #[arg(long, value_name = "NAME", env = "CPK_OPERATOR", value_parser = metadata::parse_text)]
+    operator: Option<String>,
+
+    /// Searchable tag recorded in the key metadata, e.g. `env=prod`;
+    /// repeatable (see `keys list --tag`).
+    #[arg(long = "tag", value_name = "NAME=VALUE", value_parser = metadata::parse_tag)]
+    tags: Vec<(String, String)>,
+
+    /// Stamp a not-after time this far past creation into the key metadata,
+    /// e.g. `90d` (units: s, m, h, d, w); see `check-expiry`.
+    #[arg(long, value_name = "PERIOD", env = "CPK_VALID_FOR", value_parser = metadata::parse_validity)]
+    valid_for: Option<std::time::Duration>,
+
+    /// Leave creation time, tool version, group, label and operator out of
+    /// JSON and PEM output.
+    #[arg(long, conflicts_with_all = ["label", "operator", "valid_for", "tags"])]
+    no_metadata: bool,
+
+    /// Print the private key even when stdout is a terminal, where it is
+    /// left out by default.
+    #[arg(long)]
+    allow_tty: bool,
+
+    /// Force the plain `key=value` output even when stdout is a terminal.
+    #[arg(long)]
+    plain: bool,
+
+    /// When to color the pretty terminal output.
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Print a paper backup sheet (base32 lines with per-line CRCs) instead of the default output.
+    #[arg(long, conflicts_with_all = ["output_format", "public_format", "template"])]
+    paper_backup: bool,
+
+    /// After the keys, report which standards they meet: SP 800-57 strength,
+    /// safe-prime status and the validation performed (JSON with --json).
+    #[arg(long, conflicts_with_all = ["template", "paper_backup", "quiet", "protobuf"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with_all = ["msgpack", "envelope"]))]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    #[cfg_attr(feature = "cloud", arg(conflicts_with_all = ["sops_age", "sops_kms", "sops_gcp_kms"]))]
+    compliance_report: bool,
+
+    /// Miller-Rabin rounds on p and (p - 1) / 2 for a custom prime in the
+    /// compliance report and `--check-only`; each round quarters the chance
+    /// a composite passes.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = primality::ROUNDS,
+        value_parser = clap::value_parser!(u32).range(1..=256)
+    )]
+    prime_checks: u32,
+
+    /// Parse and validate the parameters and print the compliance report,
+    /// without starting the random number generator or making a key.
+    #[arg(long, conflicts_with_all = ["interactive", "compliance_report", "explain", "timings"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with_all = ["import", "pipeline"]))]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    #[cfg_attr(feature = "pem", arg(conflicts_with = "verify_with_openssl"))]
+    check_only: bool,
+
+    /// Record the run in this new file for key-ceremony documentation: the
+    /// command line, parameters, validation steps and each public key, never
+    /// the private key.
+    #[arg(long, value_name = "FILE")]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "pipeline"))]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    transcript: Option<PathBuf>,
+
+    /// Replay a `--transcript` file (`-` for stdin): check its checksum and
+    /// parameters, and that each public key belongs to them. No key is made.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["transcript", "check_only", "interactive", "compliance_report", "explain", "timings"]
+    )]
+    verify_transcript: Option<PathBuf>,
+
+    /// Precede each output line with comments on the mathematics behind it:
+    /// how the private key was drawn, what g^x mod p means, and so on.
+    #[arg(long, conflicts_with_all = ["template", "paper_backup", "quiet", "protobuf"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with_all = ["json", "msgpack", "envelope"]))]
+    #[cfg_attr(feature = "pem", arg(conflicts_with = "output_dir"))]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    #[cfg_attr(feature = "cloud", arg(conflicts_with_all = ["sops_age", "sops_kms", "sops_gcp_kms"]))]
+    explain: bool,
+
+    /// Round-trip the parameters and keys through the openssl command
+    /// (`dhparam -check`, `pkey -check`, `pkey -pubout`, ...) and fail
+    /// unless it agrees with this tool.
+    #[cfg(feature = "pem")]
+    #[arg(long)]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    verify_with_openssl: bool,
+
+    /// Print to stderr how long parameter parsing, validation, private-key
+    /// sampling and the public-key modpow took, and the peak memory.
+    #[arg(long)]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    timings: bool,
+
+    /// Prefix prepended to every output label (e.g. `DH_`).
+    #[arg(long, value_name = "PREFIX", default_value = "", conflicts_with = "template")]
+    label_prefix: String,
+
+    /// Label used for the private key line instead of `private_key_hex`/`private_key_dec`.
+    /// With `--private-format both` the `_hex`/`_dec` suffixes are appended to it.
+    #[arg(long, value_name = "NAME", conflicts_with = "template")]
+    label_private: Option<String>,
+
+    /// Label used for the public key line instead of `public_key_hex`.
+    /// With `--public-format both` the `_hex`/`_dec` suffixes are appended to it.
+    #[arg(long, value_name = "NAME", conflicts_with = "template")]
+    label_public: Option<String>,
+
+    /// Also emit the private key (default) or public key as a QR code.
+    #[cfg(feature = "qr")]
+    #[arg(long, value_enum, value_name = "KEY", num_args = 0..=1, default_missing_value = "private")]
+    qr: Option<QrTarget>,
+
+    /// Put the private key (which then stays off stdout) or the public key
+    /// on the system clipboard instead.
+    #[arg(long, value_enum, value_name = "KEY")]
+    #[cfg_attr(feature = "pem", arg(conflicts_with = "output_dir"))]
+    copy: Option<CopyTarget>,
+
+    /// Clear the clipboard this many seconds after `--copy`, unless
+    /// something else was copied meanwhile; 0 leaves it.
+    #[arg(long, value_name = "SECS", default_value_t = 45, requires = "copy")]
+    clear_after: u64,
+
+    /// Write the QR code to a .png or .svg file instead of the terminal.
+    #[cfg(feature = "qr")]
+    #[arg(long, value_name = "FILE", requires = "qr")]
+    qr_file: Option<PathBuf>,
+
+    /// Write the private key to this already-open file descriptor (e.g. `3` with
+    /// `3>secret.txt`) and keep it out of stdout entirely.
+    #[arg(long, value_name = "FD", conflicts_with_all = ["template", "paper_backup"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "envelope"))]
+    private_fd: Option<i32>,
+
+    /// Write the private key as PKCS#8 PEM to this file (mode 0600) instead of stdout.
+    #[cfg(feature = "pem")]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["template", "paper_backup", "private_fd"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "envelope"))]
+    output_private: Option<PathBuf>,
+
+    /// Write the public key as SubjectPublicKeyInfo PEM to this file (mode 0644).
+    #[cfg(feature = "pem")]
+    #[arg(long, value_name = "FILE")]
+    output_public: Option<PathBuf>,
+
+    /// Generate the key pair inside a PKCS#11 token with this module (e.g.
+    /// `/usr/lib/softhsm/libsofthsm2.so`); the private key never leaves it.
+    #[cfg(feature = "hsm")]
+    #[arg(
+        long,
+        value_name = "MODULE",
+        requires = "slot",
+        conflicts_with_all = [
+            "count", "template", "paper_backup", "protobuf", "quiet", "private_fd", "output_private", "output_dir", "split"
+        ]
+    )]
+    #[cfg_attr(feature = "json", arg(conflicts_with_all = ["import", "envelope", "json", "msgpack"]))]
+    #[cfg_attr(feature = "cloud", arg(conflicts_with_all = ["store", "sops_age", "sops_kms", "sops_gcp_kms"]))]
+    #[cfg_attr(feature = "qr", arg(conflicts_with = "qr"))]
+    pkcs11: Option<PathBuf>,
+
+    /// PKCS#11 slot ID for `--pkcs11`.
+    #[cfg(feature = "hsm")]
+    #[arg(long, value_name = "ID", requires = "pkcs11")]
+    slot: Option<u64>,
+
+    /// Read the token user PIN from the first line of this file instead of prompting.
+    #[cfg(feature = "hsm")]
+    #[arg(long, value_name = "FILE", requires = "pkcs11")]
+    pin_file: Option<PathBuf>,
+
+    /// `CKA_LABEL` for the token key objects.
+    #[cfg(feature = "hsm")]
+    #[arg(long, value_name = "LABEL", default_value = "create-private-key", requires = "pkcs11")]
+    pkcs11_label: String,
+
+    /// Seal the private key to this machine's TPM 2.0 under a PCR policy and
+    /// write the sealed blob to FILE instead of printing the key; `convert`
+    /// unseals it on the same device. Needs tpm2-tools.
+    #[cfg(feature = "hsm")]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["template", "paper_backup", "count", "output_dir", "pkcs11"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "envelope"))]
+    tpm_seal: Option<PathBuf>,
+
+    /// PCR selection the sealed key is bound to, in tpm2-tools syntax.
+    #[cfg(feature = "hsm")]
+    #[arg(long, value_name = "BANK:PCRS", default_value = tpm::DEFAULT_PCRS, requires = "tpm_seal")]
+    tpm_pcrs: String,
+
+    /// Split the private key into N Shamir shares, any K of which rebuild it
+    /// (e.g. `3-of-5`), written as separate files instead of printing the key.
+    #[cfg(feature = "pem")]
+    #[arg(
+        long,
+        value_name = "K-of-N",
+        conflicts_with_all = ["template", "paper_backup", "private_fd", "output_private", "count", "output_dir"]
+    )]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "envelope"))]
+    split: Option<SplitSpec>,
+
+    /// Directory for the `--split` share files (`<key_id>.share-<i>-of-<n>`).
+    #[cfg(feature = "pem")]
+    #[arg(long, value_name = "DIR", default_value = ".", requires = "split")]
+    share_dir: PathBuf,
+
+    /// Save the private key in a secret store instead of printing it; only the
+    /// public key and key ID are shown.
+    #[cfg(feature = "cloud")]
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = ["template", "paper_backup", "envelope", "count", "output_dir", "split"]
+    )]
+    store: Option<Store>,
+
+    /// Entry or secret name for `--store` (all stores except vault and azure-kv).
+    #[cfg(feature = "cloud")]
+    #[arg(long, value_name = "NAME", requires = "store")]
+    name: Option<String>,
+
+    /// Secret path for `--store vault`, starting with the KV mount (e.g.
+    /// `secret/dh/my-key`), or `VAULT/SECRET` for `--store azure-kv`.
+    #[cfg(feature = "cloud")]
+    #[arg(long, value_name = "PATH", requires = "store")]
+    path: Option<String>,
+
+    /// Append a hash-chained record of each key created (no private
+    /// material) to this file; check it with `audit-verify`.
+    #[cfg(feature = "json")]
+    #[arg(long, value_name = "FILE", env = "CPK_AUDIT_LOG")]
+    audit_log: Option<PathBuf>,
+
+    /// Sign each public key, its parameters and metadata with this Ed25519
+    /// key (PKCS#8 PEM, see `provenance-key`), naming this host; check the
+    /// document with `verify-provenance`.
+    #[cfg(feature = "pem")]
+    #[arg(long, value_name = "FILE", env = "CPK_PROVENANCE_KEY")]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "pipeline"))]
+    #[cfg_attr(feature = "service", arg(conflicts_with = "rpc"))]
+    #[cfg_attr(feature = "hsm", arg(conflicts_with = "pkcs11"))]
+    provenance_key: Option<PathBuf>,
+
+    /// Host name for the provenance document's `host` line [default: this
+    /// machine's].
+    #[cfg(feature = "pem")]
+    #[arg(long, value_name = "NAME", requires = "provenance_key")]
+    provenance_host: Option<String>,
+
+    /// Save each key in the local SQLite keystore (see `keys`); without a
+    /// value, the default keystore under the user's data directory.
+    #[cfg(feature = "keystore")]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["no_metadata", "template", "paper_backup"])]
+    #[cfg_attr(feature = "json", arg(conflicts_with = "envelope"))]
+    #[cfg_attr(feature = "cloud", arg(conflicts_with_all = ["sops_age", "sops_kms", "sops_gcp_kms"]))]
+    keystore: Option<Option<PathBuf>>,
+
+    /// Read the keystore passphrase from the first line of this file
+    /// instead of prompting.
+    #[cfg(feature = "keystore")]
+    #[arg(long, value_name = "FILE", requires = "keystore")]
+    keystore_passphrase_file: Option<PathBuf>,
+
+    /// Allow private key files (`--output-private`, `--output-dir`) and
+    /// `--store` entries to replace existing ones.
+    #[cfg(feature = "pem")]
+    #[arg(long)]
+    force: bool,
+
+    #[cfg(feature = "pem")]
+    #[command(flatten)]
+    encryption: EncryptionArgs,
+
+    /// Don't show the progress bar that `--count` runs draw on a terminal.
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Number of key pairs to generate from the same parameters.
+    #[arg(long, env = "CPK_COUNT", default_value_t = 1)]
+    count: usize,
+
+    /// Write each key pair as `<name>.priv`/`<name>.pub` PEM files into this
+    /// directory, plus a `manifest.tsv` index, instead of printing them.
+    #[cfg(feature = "pem")]
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "CPK_OUTPUT_DIR",
+        conflicts_with_all = ["template", "paper_backup", "quiet", "private_fd", "output_private", "output_public"]
+    )]
+    #[cfg_attr(feature = "qr", arg(conflicts_with = "qr"))]
+    output_dir: Option<PathBuf>,
+
+    /// File name pattern for `--output-dir`; `{n}` is the zero-padded index and
+    /// `{key_id}` the short key ID.
+    #[cfg(feature = "pem")]
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        env = "CPK_NAME_PATTERN",
+        default_value = batch::DEFAULT_NAME_PATTERN,
+        requires = "output_dir"
+    )]
+    name_pattern: String,
+}
+
+impl GenerateArgs {
+    /// Metadata for a new key from `--label`, `--operator`, `--valid-for` and
+    /// `--tag`.
+    fn metadata(&self, group_name: &str) -> Metadata {
+        Metadata::new(group_name, self.label.clone(), self.operator.clone())
+            .valid_for(self.valid_for)
+            .tagged(&self.tags)
+    }
+
+    /// The first option given that `--fips` does not allow.
+    fn fips_refusal(&self) -> Option<String> {
+        #[cfg(feature = "pem")]
+        let kdf = matches!(self.encryption.kdf, Kdf::Scrypt).then(|| "--kdf scrypt".to_string());
+        #[cfg(not(feature = "pem"))]
+        let kdf = None;
+        let refused = [
+            (self.prime.is_some(), "--prime"),
+            (self.generator.is_some(), "--generator"),
+            #[cfg(feature = "json")]
+            (self.import.is_some(), "--import"),
+            #[cfg(feature = "service")]
+            (self.rpc, "--rpc"),
+            #[cfg(feature = "json")]
+            (self.pipeline, "--pipeline"),
+            #[cfg(feature = "hsm")]
+            (self.pkcs11.is_some(), "--pkcs11"),
+            #[cfg(feature = "pem")]
+            (self.split.is_some(), "--split"),
+            #[cfg(feature = "pem")]
+            (!self.encryption.encrypt_to.is_empty(), "--encrypt-to"),
+            #[cfg(feature = "pem")]
+            (!self.encryption.pgp_recipient.is_empty(), "--pgp-recipient"),
+            #[cfg(feature = "pem")]
+            (self.encryption.pgp_symmetric, "--pgp-symmetric"),
+            #[cfg(feature = "pem")]
+            (self.encryption.password_protect, "--password-protect"),
+        ];
+        kdf.or_else(|| refused.iter().find(|&&(given, _)| given).map(|&(_, option)| option.to_string()))
+    }
+
+    /// Outputs that hold one key, which `--count` cannot fill.
+    fn single_key_output(&self) -> bool {
+        [
+            self.private_fd.is_some(),
+            self.copy.is_some(),
+            #[cfg(feature = "pem")]
+            self.output_private.is_some(),
+            #[cfg(feature = "pem")]
+            self.output_public.is_some(),
+        ]
+        .contains(&true)
+    }
+
+    /// Whether the private key goes somewhere other than stdout.
+    fn private_elsewhere(&self) -> bool {
+        [
+            self.private_fd.is_some(),
+            matches!(self.copy, Some(CopyTarget::Private)),
+            #[cfg(feature = "pem")]
+            self.output_private.is_some(),
+            #[cfg(feature = "pem")]
+            self.split.is_some(),
+            #[cfg(feature = "cloud")]
+            self.store.is_some(),
+            #[cfg(feature = "keystore")]
+            self.keystore.is_some(),
+            #[cfg(feature = "hsm")]
+            self.tpm_seal.is_some(),
+        ]
+        .contains(&true)
+    }
+
+    /// Whether generation reads stdin (`--rpc`, `--pipeline` or `--import -`).
+    fn reads_stdin(&self) -> bool {
+        [
+            self.interactive,
+            #[cfg(feature = "service")]
+            self.rpc,
+            #[cfg(feature = "json")]
+            self.pipeline,
+            #[cfg(feature = "json")]
+            (self.import.as_deref() == Some(Path::new("-"))),
+            (self.verify_transcript.as_deref() == Some(Path::new("-"))),
+        ]
+        .contains(&true)
+    }
+
+    /// Output formats with one record per line (or per message), which
+    /// need no blank line between keys.
+    fn record_per_line(&self) -> bool {
+        [
+            self.quiet,
+            self.protobuf,
+            #[cfg(feature = "json")]
+            self.json,
+            #[cfg(feature = "json")]
+            self.envelope,
+            #[cfg(feature = "json")]
+            self.msgpack,
+        ]
+        .contains(&true)
+    }
+}
+
+/// Inputs to `--rng hmac-drbg`.
+#[derive(clap::Args, Debug)]
+struct DrbgArgs {
+    /// Personalization string for the HMAC_DRBG instantiation, such as a
+    /// host or service name.
+    #[arg(long, value_name = "TEXT", global = true)]
+    drbg_personalization: Option<String>,
+
+    /// Additional input mixed into every HMAC_DRBG request.
+    #[arg(long, value_name = "TEXT", global = true)]
+    drbg_additional_input: Option<String>,
+
+    /// HMAC_DRBG requests between reseeds from the operating system
+    /// (default and maximum 2^48).
+    #[arg(
+        long,
+        value_name = "N",
+        global = true,
+        value_parser = clap::value_parser!(u64).range(1..=drbg::MAX_RESEED_INTERVAL)
+    )]
+    drbg_reseed_interval: Option<u64>,
+}
+
+impl DrbgArgs {
+    fn resolve(&self, rng: RngBackend) -> Result<DrbgOptions, String> {
+        let set = self.drbg_personalization.is_some()
+            || self.drbg_additional_input.is_some()
+            || self.drbg_reseed_interval.is_some();
+        if set && rng != RngBackend::HmacDrbg {
+            return Err("--drbg-* options need --rng hmac-drbg".into());
+        }
+        let defaults = DrbgOptions::default();
+        Ok(DrbgOptions {
+            personalization: self.drbg_personalization.clone().map(String::into_bytes).unwrap_or_default(),
+            additional_input: self.drbg_additional_input.clone().map(String::into_bytes).unwrap_or_default(),
+            reseed_interval: self.drbg_reseed_interval.unwrap_or(defaults.reseed_interval),
+        })
+    }
+}
+
+/// Passphrase protection for private key files.
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct EncryptionArgs {
+    /// Encrypt private key files as PKCS#8 (PBES2, AES-256-CBC) under a passphrase.
+    #[arg(long)]
+    encrypt: bool,
+
+    /// Key derivation function for `--encrypt`.
+    #[arg(long, value_enum, default_value_t = Kdf::Pbkdf2, requires = "encrypt")]
+    kdf: Kdf,
+
+    /// Read the passphrase (for `--encrypt`, `--pgp-symmetric` or
+    /// `--password-protect`) from the first line of this file instead of prompting.
+    #[arg(long, value_name = "FILE")]
+    passphrase_file: Option<PathBuf>,
+
+    /// Encrypt private key files to this age recipient (`age1...`); repeatable.
+    #[arg(long, value_name = "RECIPIENT", conflicts_with = "encrypt")]
+    encrypt_to: Vec<String>,
+
+    /// Encrypt private key files with gpg to this OpenPGP key (ID, fingerprint
+    /// or email); repeatable. Output is ASCII-armored.
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["encrypt", "encrypt_to"])]
+    pgp_recipient: Vec<String>,
+
+    /// Encrypt private key files with gpg under a passphrase (AES-256), ASCII-armored.
+    #[arg(long, conflicts_with_all = ["encrypt", "encrypt_to", "pgp_recipient"])]
+    pgp_symmetric: bool,
+
+    /// Seal private key files under a passphrase with Argon2id and an AEAD
+    /// cipher; `convert` can open them again.
+    #[arg(long, conflicts_with_all = ["encrypt", "encrypt_to", "pgp_recipient", "pgp_symmetric"])]
+    password_protect: bool,
+
+    /// AEAD cipher for `--password-protect`.
+    #[arg(long, value_enum, default_value_t = Aead::ChaCha20Poly1305, requires = "password_protect")]
+    aead: Aead,
+
+    /// Argon2id memory cost in KiB for `--password-protect`.
+    #[arg(long, value_name = "KIB", default_value_t = sealed::DEFAULT_MEMORY_KIB, requires = "password_protect")]
+    argon2_memory: u32,
+
+    /// Argon2id iterations for `--password-protect`.
+    #[arg(long, value_name = "N", default_value_t = sealed::DEFAULT_ITERATIONS, requires = "password_protect")]
+    argon2_iterations: u32,
+
+    /// Argon2id parallelism (lanes) for `--password-protect`.
+    #[arg(long, value_name = "N", default_value_t = sealed::DEFAULT_LANES, requires = "password_protect")]
+    argon2_lanes: u32,
+
+    /// Envelope-encrypt private key files under a cloud KMS key, e.g.
+    /// `aws-kms:arn:aws:kms:...`; `convert` decrypts them again.
+    #[cfg(feature = "cloud")]
+    #[arg(
+        long,
+        value_name = "KMS:KEY",
+        conflicts_with_all = ["encrypt", "encrypt_to", "pgp_recipient", "pgp_symmetric", "password_protect"]
+    )]
+    encrypt_with: Option<KmsKey>,
+}
+
+#[cfg(feature = "pem")]
+impl EncryptionArgs {
+    fn enabled(&self) -> bool {
+        #[cfg(feature = "cloud")]
+        if self.encrypt_with.is_some() {
+            return true;
+        }
+        self.encrypt
+            || self.pgp_symmetric
+            || self.password_protect
+            || !self.encrypt_to.is_empty()
+            || !self.pgp_recipient.is_empty()
+    }
+
+    fn resolve(&self) -> Result<Option<Protection>, String> {
+        if self.passphrase_file.is_some() && !(self.encrypt || self.pgp_symmetric || self.password_protect) {
+            return Err("--passphrase-file needs --encrypt, --pgp-symmetric or --password-protect".into());
+        }
+        if !self.encrypt_to.is_empty() {
+            return Ok(Some(Protection::Age(protect::age_recipients(&self.encrypt_to)?)));
+        }
+        #[cfg(feature = "cloud")]
+        if let Some(ref key) = self.encrypt_with {
+            return Ok(Some(Protection::Kms(key.clone())));
+        }
+        if !self.pgp_recipient.is_empty() {
+            return Ok(Some(Protection::Pgp(self.pgp_recipient.clone())));
+        }
+        if self.pgp_symmetric {
+            let passphrase = passphrase::obtain(self.passphrase_file.as_deref())?;
+            return Ok(Some(Protection::PgpSymmetric(passphrase)));
+        }
+        if self.password_protect {
+            return Ok(Some(Protection::Sealed(Sealing {
+                passphrase: passphrase::obtain(self.passphrase_file.as_deref())?,
+                aead: self.aead,
+                memory_kib: self.argon2_memory,
+                iterations: self.argon2_iterations,
+                lanes: self.argon2_lanes,
+            })));
+        }
+        if !self.encrypt {
+            return Ok(None);
+        }
+        Ok(Some(Protection::Pkcs8(Encryption {
+            passphrase: passphrase::obtain(self.passphrase_file.as_deref())?,
+            kdf: self.kdf,
+        })))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate key pairs (the default when no subcommand is given).
+    Generate(Box<GenerateArgs>),
+    /// Print the public key of an existing private key.
+    #[cfg(feature = "pem")]
+    DerivePublic(DerivePublicArgs),
+    /// Compute the DH shared secret (or HKDF output) of a private key and a
+    /// peer's public value.
+    #[cfg(feature = "pem")]
+    SharedSecret(SharedSecretArgs),
+    /// Check group parameters and, optionally, a public value against them.
+    Validate(ValidateArgs),
+    /// Print group parameters as PKCS #3 PEM or as text.
+    Params(ParamsArgs),
+    /// List the built-in groups.
+    Groups,
+    /// Compare two sets of group parameters and name the published group
+    /// each one is.
+    DiffParams(DiffParamsArgs),
+    /// Estimate the symmetric-equivalent strength of a group and the cost
+    /// of a discrete-log attack on its keys.
+    Estimate(EstimateArgs),
+    /// Translate a key between the formats this tool reads and writes.
+    #[cfg(feature = "pem")]
+    Convert(ConvertArgs),
+    /// Rebuild a private key from `--split` share files.
+    #[cfg(feature = "pem")]
+    Combine(CombineArgs),
+    /// Keep a directory (or the readers of a named pipe) supplied with
+    /// pre-generated ephemeral key pairs.
+    #[cfg(feature = "pem")]
+    Pool(PoolArgs),
+    /// Generate an EC key pair on a YubiKey PIV slot and print its public key
+    /// and attestation certificate (needs ykman).
+    #[cfg(feature = "hsm")]
+    Piv(PivArgs),
+    /// Generate an X25519 key pair for libsodium's crypto_box and crypto_kx.
+    #[cfg(feature = "pem")]
+    Sodium(SodiumArgs),
+    /// Generate a libsignal identity key, signed prekey and one-time prekeys.
+    #[cfg(feature = "pem")]
+    Signal(SignalArgs),
+    /// Hold private keys in locked memory and serve DH requests on a Unix socket.
+    #[cfg(feature = "service")]
+    Agent(AgentArgs),
+    /// Load a key into the agent, or have the agent generate one.
+    #[cfg(feature = "service")]
+    AgentAdd(AgentAddArgs),
+    /// Compute a shared secret (or HKDF output) with a key held by the agent.
+    #[cfg(feature = "service")]
+    AgentDh(AgentDhArgs),
+    /// List the keys the agent holds.
+    #[cfg(feature = "service")]
+    AgentList(AgentSocketArgs),
+    /// Print the SubjectPublicKeyInfo PEM of an agent key.
+    #[cfg(feature = "service")]
+    AgentPublic(AgentKeyArgs),
+    /// Forget an agent key.
+    #[cfg(feature = "service")]
+    AgentRemove(AgentKeyArgs),
+    /// Manage keys saved with `--keystore`.
+    #[cfg(feature = "keystore")]
+    Keys(KeysArgs),
+    /// Report keys whose `--valid-for` time has passed or is close, in key
+    /// files, directories of them or the keystore.
+    CheckExpiry(CheckExpiryArgs),
+    /// Check the hash chain of an `--audit-log` file.
+    #[cfg(feature = "json")]
+    AuditVerify {
+        file: PathBuf,
+    },
+    /// Create the Ed25519 signing identity for `--provenance-key` and print
+    /// its public key, which verifiers of this host's keys need.
+    #[cfg(feature = "pem")]
+    ProvenanceKey {
+        /// Where to write the private key (PKCS#8 PEM, mode 0600).
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+        /// Replace an existing file.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check a provenance document (a `.provenance` file, or the base64
+    /// `provenance` field; `-` for stdin) against a host's public key.
+    #[cfg(feature = "pem")]
+    VerifyProvenance {
+        /// The host's Ed25519 public key, as `provenance-key` printed it.
+        #[arg(long, value_name = "FILE")]
+        signer: PathBuf,
+        document: PathBuf,
+    },
+    /// Serve the JSON API over HTTP (key generation, groups, validation).
+    #[cfg(feature = "service")]
+    Serve(ServeArgs),
+    /// Serve the gRPC `KeyService` from proto/key_service.proto.
+    #[cfg(feature = "service")]
+    Grpc(GrpcArgs),
+    /// Run the DRBG, modpow and KDF known-answer tests and exit non-zero on
+    /// any mismatch.
+    Selftest,
+    /// Run a published test-vector file (Wycheproof or CAVP) through the DH
+    /// and KDF code and report each case.
+    Testvectors(TestVectorsArgs),
+    /// Fetch KAS-FFC vector sets from an ACVP server, answer them and upload
+    /// the responses, or answer a saved vector set offline.
+    #[cfg(feature = "acvp")]
+    Acvp(AcvpArgs),
+    /// Measure key generation, shared secrets and validation on each
+    /// built-in group.
+    Bench(BenchArgs),
+    /// Print a completion script for SHELL, e.g.
+    /// `source <(create-private-key completions bash)`.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the roff man page, or write pages for every subcommand too.
+    Man {
+        /// Write `create-private-key.1` and a `create-private-key-<command>.1`
+        /// page per subcommand into this directory instead.
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Started by `--copy` to clear the clipboard later.
+    #[command(hide = true)]
+    ClipboardClear {
+        #[arg(long)]
+        after: u64,
+    },
+}
+
+impl Command {
+    /// Whether the command reads stdin, which `--entropy-stdin` needs for
+    /// itself.
+    fn reads_stdin(&self) -> bool {
+        match self {
+            Command::Generate(generate_args) => generate_args.reads_stdin(),
+            #[cfg(feature = "pem")]
+            Command::Convert(ConvertArgs { key, .. })
+            | Command::DerivePublic(DerivePublicArgs { key, .. })
+            | Command::SharedSecret(SharedSecretArgs { key, .. }) => key.input == Path::new("-"),
+            #[cfg(feature = "pem")]
+            Command::Combine(combine_args) => combine_args.shares.iter().any(|path| path == Path::new("-")),
+            #[cfg(feature = "service")]
+            Command::AgentAdd(add_args) => add_args.input.as_deref() == Some(Path::new("-")),
+            Command::DiffParams(DiffParamsArgs { a, b, .. }) => a == "-" || b == "-",
+            Command::Testvectors(TestVectorsArgs { file, .. }) => file == Path::new("-"),
+            #[cfg(feature = "pem")]
+            Command::VerifyProvenance { document, .. } => document == Path::new("-"),
+            #[cfg(feature = "acvp")]
+            Command::Acvp(AcvpArgs {
+                vector_set: Some(file), ..
+            }) => file == Path::new("-"),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct GrpcArgs {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:50051")]
+    listen: String,
+
+    /// Also serve Prometheus metrics at http://ADDR/metrics.
+    #[arg(long, value_name = "ADDR")]
+    metrics_listen: Option<String>,
+
+    /// Export a span per request to this OTLP/gRPC collector, e.g.
+    /// `http://localhost:4317`.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckExpiryArgs {
+    /// Key files, `--json` output or envelopes, or directories of them.
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// Check the keys in the local keystore too; without a value, the
+    /// default keystore.
+    #[cfg(feature = "keystore")]
+    #[arg(long, value_name = "FILE")]
+    keystore: Option<Option<PathBuf>>,
+
+    /// Report keys expiring within this period as well as expired ones.
+    #[arg(long, value_name = "PERIOD", default_value = "30d", value_parser = metadata::parse_validity)]
+    warn_within: std::time::Duration,
+
+    /// List keys that are still valid too.
+    #[arg(long)]
+    all: bool,
+}
+
+#[cfg(feature = "keystore")]
+#[derive(clap::Args, Debug)]
+struct KeysArgs {
+    /// Keystore file (default: under $XDG_DATA_HOME/create-private-key).
+    #[arg(long, value_name = "FILE", global = true)]
+    keystore: Option<PathBuf>,
+
+    /// Read the keystore passphrase from the first line of this file
+    /// instead of prompting (`export` only).
+    #[arg(long, value_name = "FILE", global = true)]
+    keystore_passphrase_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    action: KeysCommand,
+}
+
+#[cfg(feature = "keystore")]
+#[derive(Subcommand, Debug)]
+enum KeysCommand {
+    /// List stored keys: ID, creation time, group, label and tags.
+    List {
+        /// Only keys with this label.
+        #[arg(long, value_name = "TEXT")]
+        label: Option<String>,
+
+        /// Only keys with this tag, with any value when `=VALUE` is left
+        /// off; repeatable, and a key must match every one.
+        #[arg(long = "tag", value_name = "NAME[=VALUE]", value_parser = metadata::parse_tag_filter)]
+        tags: Vec<(String, Option<String>)>,
+    },
+    /// Print a key's metadata and public key.
+    Show {
+        key_id: String,
+    },
+    /// Remove a key from the keystore.
+    Delete {
+        key_id: String,
+    },
+    /// Decrypt a key and write it out.
+    Export {
+        key_id: String,
+
+        /// Where to write the key (`-` for stdout).
+        #[arg(long = "out", value_name = "FILE", default_value = "-")]
+        output: PathBuf,
+
+        /// Format to write.
+        #[arg(long, value_enum, default_value_t = KeyFormat::Pkcs8)]
+        out_format: KeyFormat,
+
+        /// Allow replacing an existing private key file.
+        #[arg(long)]
+        force: bool,
+
+        /// Print the key even when stdout is a terminal.
+        #[arg(long)]
+        allow_tty: bool,
+    },
+}
+
+/// Request limits shared by `serve` and `agent`.
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct LimitArgs {
+    /// Requests per second each client may make on average.
+    #[arg(long, value_name = "N")]
+    rate_limit: Option<f64>,
+
+    /// Requests a client may make at once before --rate-limit applies
+    /// (default: one second's worth).
+    #[arg(long, value_name = "N", requires = "rate_limit")]
+    burst: Option<u32>,
+
+    /// Keys that may be generated per hour across all clients.
+    #[arg(long, value_name = "N")]
+    keys_per_hour: Option<u64>,
+}
+
+#[cfg(feature = "service")]
+impl LimitArgs {
+    fn limits(&self) -> Result<limits::Limits, String> {
+        limits::Limits::new(self.rate_limit, self.burst, self.keys_per_hour)
+    }
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+
+    /// Export a span per request to this OTLP/gRPC collector, e.g.
+    /// `http://localhost:4317`.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct AgentSocketArgs {
+    /// Agent socket (default: $CPK_AGENT_SOCK).
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct AgentKeyArgs {
+    /// Agent socket (default: $CPK_AGENT_SOCK).
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Name of the agent key.
+    #[arg(long)]
+    name: String,
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct AgentArgs {
+    /// Socket to listen on (default: $CPK_AGENT_SOCK, else under $XDG_RUNTIME_DIR).
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Group for keys the agent generates.
+    #[arg(long, value_enum, env = "CPK_GROUP", default_value_t = DhGroup::Modp14)]
+    group: DhGroup,
+
+    /// Prime modulus for keys the agent generates.
+    #[arg(long, env = "CPK_PRIME")]
+    prime: Option<String>,
+
+    /// Generator for keys the agent generates.
+    #[arg(long, env = "CPK_GENERATOR")]
+    generator: Option<String>,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+
+    /// Also serve Prometheus metrics at http://ADDR/metrics.
+    #[arg(long, value_name = "ADDR")]
+    metrics_listen: Option<String>,
+
+    /// Export a span per request to this OTLP/gRPC collector, e.g.
+    /// `http://localhost:4317`.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct AgentAddArgs {
+    /// Agent socket (default: $CPK_AGENT_SOCK).
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Name the key is held under.
+    #[arg(long)]
+    name: String,
+
+    /// Existing private key to load (`-` for stdin; any `convert` input
+    /// format). Without it the agent generates the key itself.
+    #[arg(long = "in", value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Format of `--in`; guessed when omitted.
+    #[arg(long, value_enum, requires = "input")]
+    in_format: Option<KeyFormat>,
+}
+
+#[cfg(feature = "service")]
+#[derive(clap::Args, Debug)]
+struct AgentDhArgs {
+    /// Agent socket (default: $CPK_AGENT_SOCK).
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Name of the agent key.
+    #[arg(long)]
+    name: String,
+
+    /// Peer public key in hex.
+    #[arg(long, value_name = "HEX")]
+    peer: String,
+
+    /// Print this many bytes of HKDF-SHA256 output instead of the raw shared secret.
+    #[arg(long, value_name = "BYTES")]
+    hkdf_length: Option<u32>,
+
+    /// HKDF salt in hex.
+    #[arg(long, value_name = "HEX", default_value = "", requires = "hkdf_length")]
+    hkdf_salt: String,
+
+    /// HKDF info string.
+    #[arg(long, value_name = "TEXT", default_value = "", requires = "hkdf_length")]
+    hkdf_info: String,
+}
+
+#[cfg(feature = "hsm")]
+#[derive(clap::Args, Debug)]
+struct PivArgs {
+    /// PIV slot, e.g. `9a` (authentication) or `9d` (key management).
+    #[arg(long, default_value = "9a")]
+    slot: String,
+
+    /// Key algorithm; PIV has no finite-field DH.
+    #[arg(long, value_enum, default_value_t = PivAlgorithm::EccP256)]
+    algorithm: PivAlgorithm,
+
+    /// Read the hex PIV management key from the first line of this file
+    /// (the factory default is used otherwise).
+    #[arg(long, value_name = "FILE")]
+    management_key_file: Option<PathBuf>,
+
+    /// Also write the public key PEM to this file.
+    #[arg(long, value_name = "FILE")]
+    output_public: Option<PathBuf>,
+
+    /// Also write the attestation certificate PEM to this file.
+    #[arg(long, value_name = "FILE")]
+    output_attestation: Option<PathBuf>,
+
+    /// Also have the device sign a certificate signing request for the new
+    /// key, printed after the attestation.
+    #[arg(long, requires = "subject")]
+    csr: bool,
+
+    /// Also have the device sign a certificate for the new key with itself
+    /// and store it in the slot, printed last; for lab and test PKI.
+    #[arg(long, requires = "subject")]
+    self_signed: bool,
+
+    /// Days the `--self-signed` certificate is valid for.
+    #[arg(long, value_name = "N", default_value_t = 365, requires = "self_signed")]
+    days: u32,
+
+    /// Subject of the `--csr` request and `--self-signed` certificate, e.g.
+    /// `CN=device-123`.
+    #[arg(long, value_name = "DN")]
+    subject: Option<String>,
+
+    /// Read the PIV PIN that `--csr` and `--self-signed` sign with from the
+    /// first line of this file instead of prompting.
+    #[arg(long, value_name = "FILE")]
+    pin_file: Option<PathBuf>,
+
+    /// Also write the `--csr` request PEM to this file.
+    #[arg(long, value_name = "FILE", requires = "csr")]
+    output_csr: Option<PathBuf>,
+
+    /// Also write the `--self-signed` certificate PEM to this file.
+    #[arg(long, value_name = "FILE", requires = "self_signed")]
+    output_certificate: Option<PathBuf>,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct SodiumArgs {
+    /// How the keys are printed.
+    #[arg(long, value_enum, default_value_t = sodium::Encoding::Base64)]
+    encoding: sodium::Encoding,
+
+    /// Write the raw 32-byte secret key to this file (mode 0600) instead of
+    /// printing it.
+    #[arg(long, value_name = "FILE")]
+    output_secret: Option<PathBuf>,
+
+    /// Write the raw 64-byte secret-and-public keypair to this file (mode
+    /// 0600) instead of printing it.
+    #[arg(long, value_name = "FILE")]
+    output_keypair: Option<PathBuf>,
+
+    /// Also write the raw 32-byte public key to this file.
+    #[arg(long, value_name = "FILE")]
+    output_public: Option<PathBuf>,
+
+    /// Replace existing secret key files.
+    #[arg(long)]
+    force: bool,
+
+    /// Print the secret key even when stdout is a terminal.
+    #[arg(long)]
+    allow_tty: bool,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct SignalArgs {
+    /// ID of the signed prekey.
+    #[arg(long, value_name = "ID", default_value_t = 1)]
+    signed_pre_key_id: u32,
+
+    /// ID of the first one-time prekey; the rest follow in order.
+    #[arg(long, value_name = "ID", default_value_t = 1)]
+    first_pre_key_id: u32,
+
+    /// One-time prekeys to make.
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    pre_keys: u32,
+
+    /// Write the whole bundle, records with private keys included, to this
+    /// file (mode 0600) and print only the public keys.
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+
+    /// Replace an existing `--out` file.
+    #[arg(long, requires = "out")]
+    force: bool,
+
+    /// Print the private records even when stdout is a terminal.
+    #[arg(long, conflicts_with = "out")]
+    allow_tty: bool,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct PoolArgs {
+    /// Directory to keep stocked with `<key_id>.pem` private key files, or
+    /// a named pipe that hands each reader one key.
+    #[arg(value_name = "PATH")]
+    path: PathBuf,
+
+    #[command(flatten)]
+    group: GroupArgs,
+
+    /// Private exponent size in bits (default: full size).
+    #[arg(long, value_name = "BITS")]
+    private_bits: Option<u64>,
+
+    /// Keys to have ready after each top-up.
+    #[arg(long, value_name = "N", default_value_t = 64)]
+    size: usize,
+
+    /// Top up once fewer than N keys are ready (default: --size, so every
+    /// key taken is replaced).
+    #[arg(long, value_name = "N")]
+    low_watermark: Option<usize>,
+
+    /// Generate at most this many keys per second.
+    #[arg(long, value_name = "KEYS")]
+    rate: Option<f64>,
+
+    /// Seconds between counts of the directory.
+    #[arg(long, value_name = "SECS", default_value_t = 1.0)]
+    interval: f64,
+
+    /// Top the directory up once and exit, e.g. from cron.
+    #[arg(long)]
+    once: bool,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct CombineArgs {
+    /// Share files; at least the split threshold of them.
+    #[arg(required = true, value_name = "SHARE")]
+    shares: Vec<PathBuf>,
+
+    /// Where to write the rebuilt key (`-` for stdout).
+    #[arg(long = "out", value_name = "FILE", default_value = "-")]
+    output: PathBuf,
+
+    /// Format to write.
+    #[arg(long, value_enum, default_value_t = KeyFormat::Pkcs8)]
+    out_format: KeyFormat,
+
+    /// Allow replacing an existing private key file.
+    #[arg(long)]
+    force: bool,
+
+    /// Print the rebuilt key even when stdout is a terminal.
+    #[arg(long)]
+    allow_tty: bool,
+}
+
+/// A private key to read, in any format `convert` reads.
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct KeyInputArgs {
+    /// Key to read (`-` for stdin).
+    #[arg(long = "in", value_name = "FILE")]
+    input: PathBuf,
+
+    /// Format of the input; guessed from the content when omitted (raw must be explicit).
+    #[arg(long, value_enum)]
+    in_format: Option<KeyFormat>,
+
+    /// Group for hex/raw input, which carries no parameters.
+    #[arg(long, value_enum, env = "CPK_GROUP", default_value_t = DhGroup::Modp14)]
+    group: DhGroup,
+
+    /// Prime modulus for hex/raw input.
+    #[arg(long, env = "CPK_PRIME")]
+    prime: Option<String>,
+
+    /// Generator for hex/raw input.
+    #[arg(long, env = "CPK_GENERATOR")]
+    generator: Option<String>,
+
+    /// Passphrase for a `--password-protect` input, read from the first line
+    /// of this file instead of prompting.
+    #[arg(long, value_name = "FILE")]
+    in_passphrase_file: Option<PathBuf>,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct ConvertArgs {
+    #[command(flatten)]
+    key: KeyInputArgs,
+
+    /// Where to write the converted key (`-` for stdout).
+    #[arg(long = "out", value_name = "FILE", default_value = "-")]
+    output: PathBuf,
+
+    /// Format to write.
+    #[arg(long, value_enum)]
+    out_format: KeyFormat,
+
+    /// Allow replacing an existing private key file.
+    #[arg(long)]
+    force: bool,
+
+    /// Print a private key even when stdout is a terminal.
+    #[arg(long)]
+    allow_tty: bool,
+
+    #[command(flatten)]
+    encryption: EncryptionArgs,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct DerivePublicArgs {
+    #[command(flatten)]
+    key: KeyInputArgs,
+
+    /// Where to write the public key (`-` for stdout).
+    #[arg(long = "out", value_name = "FILE", default_value = "-")]
+    output: PathBuf,
+
+    /// Write SubjectPublicKeyInfo DER instead of PEM.
+    #[arg(long)]
+    der: bool,
+}
+
+#[cfg(feature = "pem")]
+#[derive(clap::Args, Debug)]
+struct SharedSecretArgs {
+    #[command(flatten)]
+    key: KeyInputArgs,
+
+    /// Peer public key in hex.
+    #[arg(long, value_name = "HEX")]
+    peer: String,
+
+    /// Print this many bytes of HKDF-SHA256 output instead of the raw shared secret.
+    #[arg(long, value_name = "BYTES")]
+    hkdf_length: Option<u32>,
+
+    /// HKDF salt in hex.
+    #[arg(long, value_name = "HEX", default_value = "", requires = "hkdf_length")]
+    hkdf_salt: String,
+
+    /// HKDF info string.
+    #[arg(long, value_name = "TEXT", default_value = "", requires = "hkdf_length")]
+    hkdf_info: String,
+}
+
+/// A built-in group, optionally replaced by an explicit prime and generator.
+#[derive(clap::Args, Debug)]
+struct GroupArgs {
+    /// RFC 3526 MODP group to base parameters on (ignored when --prime is provided).
+    #[arg(long, value_enum, env = "CPK_GROUP", default_value_t = DhGroup::Modp14)]
+    group: DhGroup,
+
+    /// Diffie-Hellman prime modulus: decimal, or hex with 0x (see --prime-radix).
+    #[arg(long, env = "CPK_PRIME")]
+    prime: Option<String>,
+
+    /// How --prime is read: hex with 0x and decimal without (auto), or
+    /// always hex or always decimal.
+    #[arg(long, value_enum, value_name = "RADIX", env = "CPK_PRIME_RADIX", default_value_t = Radix::Auto)]
+    prime_radix: Radix,
+
+    /// With --prime-radix auto, refuse an unprefixed --prime that may be
+    /// hex: one with a leading zero or a common prime size's hex length.
+    #[arg(long, env = "CPK_STRICT_PRIME")]
+    strict_prime: bool,
+
+    /// Generator to use (defaults to group generator).
+    #[arg(long, env = "CPK_GENERATOR")]
+    generator: Option<String>,
+}
+
+impl GroupArgs {
+    fn resolve(&self) -> Result<DhParams, Error> {
+        let prime = read_prime(self.prime.as_deref(), self.prime_radix, self.strict_prime)?;
+        DhParams::parse(self.group, prime.as_deref(), self.generator.as_deref())
+    }
+}
+
+/// `--prime` in the `0x` hex form every parser reads the same way, after
+/// `--prime-radix` and `--strict-prime` have been applied.
+fn read_prime(prime: Option<&str>, radix: Radix, strict: bool) -> Result<Option<String>, Error> {
+    let Some(prime) = prime else { return Ok(None) };
+    let prime = params::parse_biguint_radix(prime, radix, strict).map_err(|err| match err {
+        Error::Parse(message) => Error::Parse(format!("--prime: {message}")),
+        err => err,
+    })?;
+    Ok(Some(format!("0x{}", prime.to_str_radix(16))))
+}
+
+#[derive(clap::Args, Debug)]
+struct ValidateArgs {
+    #[command(flatten)]
+    group: GroupArgs,
+
+    /// Public value in hex to range-check against the group.
+    #[arg(long, value_name = "HEX")]
+    public_key: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ParamsArgs {
+    #[command(flatten)]
+    group: GroupArgs,
+
+    /// Print `prime_bits`, `prime_hex` and `generator` lines instead of PEM.
+    #[arg(long)]
+    text: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct EstimateArgs {
+    #[command(flatten)]
+    group: GroupArgs,
+
+    /// Private exponent size in bits (default: full size).
+    #[arg(long, value_name = "BITS", env = "CPK_PRIVATE_BITS")]
+    private_bits: Option<u64>,
+
+    /// Print the estimate as one JSON object instead of `key=value` lines.
+    #[cfg(feature = "json")]
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct TestVectorsArgs {
+    /// Which project's file layout to read.
+    #[arg(long, value_enum)]
+    suite: testvectors::Suite,
+
+    /// The test-vector file (`-` for stdin).
+    #[arg(long, value_name = "FILE")]
+    file: PathBuf,
+}
+
+#[cfg(feature = "acvp")]
+#[derive(clap::Args, Debug)]
+struct AcvpArgs {
+    /// The ACVP server, e.g. `https://demo.acvts.nist.gov`.
+    #[arg(long, value_name = "URL", env = "CPK_ACVP_SERVER", required_unless_present = "vector_set")]
+    server: Option<String>,
+
+    /// PEM certificate chain the server issued for TLS client authentication.
+    #[arg(long, value_name = "FILE", required_unless_present = "vector_set")]
+    cert: Option<PathBuf>,
+
+    /// PEM private key for `--cert`.
+    #[arg(long, value_name = "FILE", required_unless_present = "vector_set")]
+    key: Option<PathBuf>,
+
+    /// The base64 seed for the server's one-time passwords.
+    #[arg(long, value_name = "FILE")]
+    totp_seed: Option<PathBuf>,
+
+    /// Register a sample session, which the server answers with the expected
+    /// results as well.
+    #[arg(long)]
+    sample: bool,
+
+    /// Answer this saved vector set (`-` for stdin) and print the response
+    /// instead of talking to a server.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["server", "cert", "key", "totp_seed", "sample"])]
+    vector_set: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffParamsArgs {
+    /// A published group (`modp14`, `ffdhe2048`, `rfc5114-2048-256`),
+    /// `0xPRIME[:GENERATOR]` (generator 2 by default), or a PEM or DER
+    /// parameters file (`-` for stdin).
+    #[arg(value_name = "PARAMS")]
+    a: String,
+
+    /// The parameters to compare with, in the same forms.
+    #[arg(value_name = "PARAMS")]
+    b: String,
+
+    /// Miller-Rabin rounds when checking that an unpublished prime is safe.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = primality::ROUNDS,
+        value_parser = clap::value_parser!(u32).range(1..=256)
+    )]
+    prime_checks: u32,
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// Time spent on each operation and group, in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 1.0)]
+    seconds: f64,
+
+    /// Print the host and results as one JSON object instead of a table.
+    #[cfg(feature = "json")]
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err}");
+        process::exit(err.exit_code());
+    }
+}
+
+/// Why `run` failed. Library errors keep their class so the exit code can
+/// tell them apart, policy refusals get their own, and everything else
+/// exits 1.
+enum Failure {
+    Library(Error),
+    Policy(config::Violation),
+    Fips(fips::Refusal),
+    Other(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
         match self {
-            DhGroup::Modp14 => RFC3526_MODP14_PRIME_HEX,
+            Failure::Library(Error::Parse(_)) => 2,
+            Failure::Library(Error::Validation(_)) => 3,
+            Failure::Policy(_) | Failure::Fips(_) => 4,
+            Failure::Library(Error::Rng(_)) => 5,
+            Failure::Library(Error::Io { .. }) => 6,
+            Failure::Other(_) => 1,
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Failure::Library(err)
+    }
+}
+
+impl From<config::Violation> for Failure {
+    fn from(violation: config::Violation) -> Self {
+        Failure::Policy(violation)
+    }
+}
+
+impl From<fips::Refusal> for Failure {
+    fn from(refusal: fips::Refusal) -> Self {
+        Failure::Fips(refusal)
+    }
+}
+
+impl From<String> for Failure {
+    fn from(err: String) -> Self {
+        Failure::Other(err)
+    }
+}
+
+impl From<&str> for Failure {
+    fn from(err: &str) -> Self {
+        Failure::Other(err.to_string())
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Library(err) => err.fmt(f),
+            Failure::Policy(violation) => violation.fmt(f),
+            Failure::Fips(refusal) => refusal.fmt(f),
+            Failure::Other(err) => f.write_str(err),
+        }
+    }
+}
+
+fn run() -> Result<(), Failure> {
+    let config = Config::load(config::requested_path(Args::command()).as_deref())?;
+    let mut cli = config.apply(Args::command());
+    let matches = cli.get_matches_mut();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    params::set_max_prime_bits(args.max_prime_bits);
+    if args.param_cache {
+        param_cache::enable(&param_cache::default_dir())?;
+    }
+    if args.fips {
+        fips_globals(&args, &matches)?;
+    }
+    let command = match args.command.unwrap_or_else(|| Command::Generate(Box::new(args.generate))) {
+        // A configured output directory gives way to other destinations.
+        #[cfg(feature = "pem")]
+        Command::Generate(mut generate_args) if config::displaced(&cli, &matches, "output_dir") => {
+            generate_args.output_dir = None;
+            Command::Generate(generate_args)
+        }
+        command => command,
+    };
+    #[cfg(feature = "service")]
+    let otlp_endpoint = match command {
+        Command::Serve(ServeArgs { ref otlp_endpoint, .. })
+        | Command::Grpc(GrpcArgs { ref otlp_endpoint, .. })
+        | Command::Agent(AgentArgs { ref otlp_endpoint, .. }) => otlp_endpoint.clone(),
+        _ => None,
+    };
+    #[cfg(not(feature = "service"))]
+    let otlp_endpoint: Option<String> = None;
+    let _telemetry = logging::init(args.verbose, args.log_format, otlp_endpoint.as_deref())?;
+    if let Some(ref path) = config.source {
+        tracing::debug!(path = %path.display(), explicit = args.config.is_some(), "read defaults from config file");
+    }
+    let rng = if args.fips { RngBackend::HmacDrbg } else { args.rng };
+    if args.self_test || args.fips {
+        selftest::power_on()?;
+    }
+    // Before the health tests, which draw from the entropy sources.
+    if let Command::Generate(ref generate_args) = command
+        && generate_args.check_only
+    {
+        return run_check_only(generate_args, &config.policy, args.fips);
+    }
+    if let Command::Generate(ref generate_args) = command
+        && let Some(ref path) = generate_args.verify_transcript
+    {
+        let summary = transcript::verify(&read_input(path)?)?;
+        println!("transcript=ok\ngroup={}\nprime_bits={}\nkeys={}", summary.group, summary.prime_bits, summary.keys);
+        return Ok(());
+    }
+    if args.skip_health_tests {
+        tracing::warn!("entropy health tests skipped");
+    } else {
+        health::startup(&args.hw_entropy)?;
+    }
+    if args.entropy_stdin && command.reads_stdin() {
+        return Err("--entropy-stdin cannot be used with another input read from stdin".into());
+    }
+    let seed = random::Seed::read(&args.entropy_file, args.entropy_stdin)?;
+    random::select(rng, args.drbg.resolve(rng)?, args.hw_entropy.clone(), seed)?;
+    tracing::info!(source = rng.name(), "random source: {}", rng.description());
+    let startup = Startup {
+        fips: args.fips,
+        self_tested: args.self_test || args.fips,
+        health_tested: !args.skip_health_tests,
+        rng,
+        entropy_sources: random::sources(),
+    };
+    if startup.entropy_sources.len() > 1 {
+        tracing::info!(sources = %startup.entropy_sources.join(","), "mixing extra entropy into the OS RNG");
+    }
+    match command {
+        Command::Generate(generate_args) => run_generate(*generate_args, &config.policy, &startup),
+        #[cfg(feature = "pem")]
+        Command::DerivePublic(derive_args) => run_derive_public(derive_args, args.fips),
+        #[cfg(feature = "pem")]
+        Command::SharedSecret(secret_args) => run_shared_secret(secret_args, args.fips),
+        Command::Validate(validate_args) => run_validate(validate_args, args.fips),
+        Command::Params(params_args) => run_params(params_args, args.fips),
+        Command::DiffParams(diff_args) => run_diff_params(diff_args),
+        Command::Estimate(estimate_args) => run_estimate(estimate_args),
+        Command::Groups => {
+            for &group in DhGroup::value_variants() {
+                let prime = group.prime();
+                let description = group.to_possible_value().and_then(|value| value.get_help().map(ToString::to_string));
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    group.name(),
+                    prime.bits(),
+                    group.default_generator(),
+                    description.unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        #[cfg(feature = "pem")]
+        Command::Convert(convert_args) => run_convert(convert_args),
+        #[cfg(feature = "pem")]
+        Command::Combine(combine_args) => run_combine(combine_args),
+        #[cfg(feature = "pem")]
+        Command::Pool(pool_args) => run_pool(pool_args, &config.policy),
+        #[cfg(feature = "hsm")]
+        Command::Piv(piv_args) => run_piv(piv_args),
+        #[cfg(feature = "pem")]
+        Command::Sodium(sodium_args) => run_sodium(sodium_args),
+        #[cfg(feature = "pem")]
+        Command::Signal(signal_args) => run_signal(signal_args),
+        #[cfg(feature = "service")]
+        Command::Agent(agent_args) => run_agent(agent_args),
+        #[cfg(feature = "service")]
+        Command::AgentAdd(add_args) => run_agent_add(add_args),
+        #[cfg(feature = "service")]
+        Command::AgentDh(dh_args) => run_agent_dh(dh_args),
+        #[cfg(feature = "service")]
+        Command::AgentList(list_args) => run_agent_list(list_args),
+        #[cfg(feature = "service")]
+        Command::AgentPublic(key_args) => run_agent_public(key_args),
+        #[cfg(feature = "service")]
+        Command::AgentRemove(key_args) => run_agent_remove(key_args),
+        #[cfg(feature = "keystore")]
+        Command::Keys(keys_args) => run_keys(keys_args),
+        Command::CheckExpiry(expiry_args) => run_check_expiry(expiry_args),
+        #[cfg(feature = "json")]
+        Command::AuditVerify { file } => {
+            let (entries, head) = audit::verify(&file)?;
+            println!("entries={entries}");
+            println!("head={head}");
+            Ok(())
+        }
+        #[cfg(feature = "pem")]
+        Command::ProvenanceKey { out, force } => {
+            let signer = provenance::Signer::generate()?;
+            let overwrite = if force { Overwrite::Replace } else { Overwrite::Refuse };
+            sink::write_private_file(&out, signer.private_pem().as_bytes(), overwrite)?;
+            eprintln!("wrote {} ({})", out.display(), signer.fingerprint());
+            print!("{}", signer.public_pem());
+            Ok(())
+        }
+        #[cfg(feature = "pem")]
+        Command::VerifyProvenance { signer, document } => {
+            let signer = provenance::load_public(&signer)?;
+            let fields = provenance::verify(&read_input(&document)?, &signer).map_err(Error::Validation)?;
+            println!("valid=true");
+            for (name, value) in fields {
+                println!("{name}={value}");
+            }
+            Ok(())
+        }
+        #[cfg(feature = "service")]
+        Command::Serve(serve_args) => Ok(http::serve(&serve_args.listen, serve_args.limits.limits()?)?),
+        #[cfg(feature = "service")]
+        Command::Grpc(grpc_args) => {
+            if let Some(ref listen) = grpc_args.metrics_listen {
+                metrics::spawn_server(listen)?;
+            }
+            Ok(grpc::serve(&grpc_args.listen)?)
+        }
+        Command::Selftest => {
+            let results = selftest::run();
+            for &(name, passed) in &results {
+                println!("{name}={}", if passed { "ok" } else { "FAIL" });
+            }
+            match results.iter().filter(|&&(_, passed)| !passed).count() {
+                0 => Ok(()),
+                failed => Err(format!("{failed} of {} self-tests failed", results.len()).into()),
+            }
+        }
+        Command::Testvectors(vector_args) => run_testvectors(vector_args),
+        #[cfg(feature = "acvp")]
+        Command::Acvp(acvp_args) => run_acvp(acvp_args),
+        Command::Bench(bench_args) => run_bench(bench_args),
+        Command::Completions { shell } => {
+            let name = cli.get_name().to_string();
+            clap_complete::generate(shell, &mut cli, name, &mut std::io::stdout());
+            Ok(())
+        }
+        // The pages document the built-in defaults, not this machine's config.
+        Command::Man { out_dir: Some(dir) } => Ok(clap_mangen::generate_to(Args::command(), &dir).map_err(|source| {
+            Error::Io {
+                context: format!("failed to write man pages to {}", dir.display()),
+                source,
+            }
+        })?),
+        Command::ClipboardClear { after } => Ok(clipboard::clear_later(after)?),
+        Command::Man { out_dir: None } => Ok(clap_mangen::Man::new(Args::command())
+            .render(&mut std::io::stdout())
+            .map_err(|source| Error::Io {
+                context: "failed to write the man page".into(),
+                source,
+            })?),
+    }
+}
+
+/// Refuses the global options and the subcommands `--fips` does not allow.
+fn fips_globals(args: &Args, matches: &clap::ArgMatches) -> Result<(), fips::Refusal> {
+    if let Some(name) = matches.subcommand_name().filter(|name| !fips::COMMANDS.contains(name)) {
+        return Err(fips::Refusal(format!("`{name}`")));
+    }
+    let mut matches = matches;
+    while let Some((_, sub)) = matches.subcommand() {
+        matches = sub;
+    }
+    // Only an explicit choice of another generator is an error; the default
+    // gives way to the DRBG.
+    if args.rng != RngBackend::HmacDrbg && matches.value_source("rng") != Some(clap::parser::ValueSource::DefaultValue) {
+        return Err(fips::Refusal(format!("--rng {}", args.rng.name())));
+    }
+    let refused = [
+        (!args.hw_entropy.is_empty(), "--hw-entropy"),
+        (!args.entropy_file.is_empty(), "--entropy-file"),
+        (args.entropy_stdin, "--entropy-stdin"),
+        (args.skip_health_tests, "--skip-health-tests"),
+    ];
+    match refused.iter().find(|&&(given, _)| given) {
+        Some(&(_, option)) => Err(fips::Refusal(option.into())),
+        None => Ok(()),
+    }
+}
+
+/// `testvectors`: one `id=pass|FAIL|skipped` line per case, failing when
+/// any case fails or the file holds none that could be run.
+fn run_testvectors(args: TestVectorsArgs) -> Result<(), Failure> {
+    let bytes = read_input_bytes(&args.file)?;
+    let text = String::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8 text", args.file.display()))?;
+    let cases = testvectors::run(args.suite, &text)?;
+    let (mut passed, mut failed) = (0, 0);
+    for case in &cases {
+        match case.status {
+            testvectors::Status::Pass => {
+                passed += 1;
+                println!("{}=pass", case.id);
+            }
+            testvectors::Status::Fail(ref reason) => {
+                failed += 1;
+                println!("{}=FAIL {reason}", case.id);
+            }
+            testvectors::Status::Skipped(ref reason) => println!("{}=skipped {reason}", case.id),
+        }
+    }
+    println!("passed={passed}\nfailed={failed}\nskipped={}", cases.len() - passed - failed);
+    match (passed, failed) {
+        (0, 0) => Err(format!("{} holds no case this tool can run", args.file.display()).into()),
+        (_, 0) => Ok(()),
+        (_, failed) => Err(format!("{failed} of {} test vectors failed", passed + failed).into()),
+    }
+}
+
+/// `acvp`: one test session against the server, or one saved vector set.
+#[cfg(feature = "acvp")]
+fn run_acvp(args: AcvpArgs) -> Result<(), Failure> {
+    if let Some(path) = args.vector_set {
+        let bytes = read_input_bytes(&path)?;
+        let text = String::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8 text", path.display()))?;
+        let response = acvp::answer(&acvp::read_vector_set(&text)?)?;
+        println!("{}", serde_json::json!([{ "acvVersion": "1.0" }, response]));
+        return Ok(());
+    }
+    // clap requires these three without --vector-set.
+    let (Some(server), Some(cert), Some(key)) = (args.server, args.cert, args.key) else {
+        return Err("--server, --cert and --key are required".to_string().into());
+    };
+    let mut client = acvp::Client::login(&acvp::Login {
+        server: &server,
+        cert: &cert,
+        key: &key,
+        totp_seed: args.totp_seed.as_deref(),
+    })?;
+    let (session, dispositions) = client.run_session(args.sample)?;
+    println!("session={session}");
+    for disposition in &dispositions {
+        println!("{}={}", disposition.url, disposition.status);
+    }
+    match dispositions.iter().filter(|disposition| disposition.status != "passed").count() {
+        0 => Ok(()),
+        failed => Err(format!("{failed} of {} vector sets did not pass", dispositions.len()).into()),
+    }
+}
+
+/// `bench`: times the operations and prints the table or JSON.
+fn run_bench(args: BenchArgs) -> Result<(), Failure> {
+    let budget = std::time::Duration::try_from_secs_f64(args.seconds)
+        .map_err(|_| format!("--seconds {} is not a usable duration", args.seconds))?;
+    let host = bench::Host::current();
+    let results = bench::run(budget)?;
+    #[cfg(feature = "json")]
+    if args.json {
+        let report = serde_json::json!({ "host": host, "results": results });
+        println!("{report}");
+        return Ok(());
+    }
+    print!("{}", bench::render_table(&host, &results));
+    Ok(())
+}
+
+/// `--interactive`: asks for the generation choices, shows the equivalent
+/// command and returns whether the user wants to go ahead.
+fn run_wizard(args: &mut GenerateArgs) -> Result<bool, Failure> {
+    wizard::require_terminal()?;
+    eprintln!("Creating a Diffie-Hellman key pair. Press Enter to keep the [default].");
+    eprintln!();
+
+    let groups = DhGroup::value_variants();
+    let mut options: Vec<(String, String)> = groups
+        .iter()
+        .map(|group| {
+            let description = group.to_possible_value().and_then(|value| value.get_help().map(ToString::to_string));
+            (group.name().to_string(), description.unwrap_or_default())
+        })
+        .collect();
+    options.push(("custom".into(), "your own prime and generator".into()));
+    let default = match args.prime {
+        Some(_) => groups.len(),
+        None => groups.iter().position(|&group| group == args.group).unwrap_or(0),
+    };
+    match groups.get(wizard::choose("Group", &options, default)?) {
+        Some(&group) => {
+            args.group = group;
+            args.prime = None;
+            args.generator = None;
+        }
+        None => {
+            let group = args.group;
+            let prime = wizard::ask("Prime, decimal or 0x hex", args.prime.as_deref(), |answer| {
+                DhParams::parse(group, Some(answer), None).map(|_| answer.to_string()).map_err(|err| err.to_string())
+            })?;
+            let generator = wizard::ask("Generator", Some(args.generator.as_deref().unwrap_or("2")), |answer| {
+                DhParams::parse(group, Some(&prime), Some(answer))
+                    .map(|_| answer.to_string())
+                    .map_err(|err| err.to_string())
+            })?;
+            args.prime = Some(prime);
+            args.generator = Some(generator);
         }
     }
+    let dh_params = DhParams::parse(args.group, args.prime.as_deref(), args.generator.as_deref())?;
+    eprintln!();
 
-    fn default_generator(self) -> &'static str {
-        match self {
-            DhGroup::Modp14 => "2",
+    let default = args.private_bits.map_or_else(|| "full".to_string(), |bits| bits.to_string());
+    args.private_bits = wizard::ask(
+        "Private exponent bits (\"full\" draws from the whole group; 256 is faster)",
+        Some(&default),
+        |answer| {
+            if answer == "full" {
+                return Ok(None);
+            }
+            let bits = answer.parse().map_err(|_| format!("{answer} is not a number of bits"))?;
+            dh_params.clone().with_private_bits(bits).map_err(|err| err.to_string())?;
+            Ok(Some(bits))
+        },
+    )?;
+    eprintln!();
+
+    #[cfg(feature = "pem")]
+    {
+        let destinations = [
+            ("terminal".to_string(), "print the keys here".to_string()),
+            ("files".to_string(), "write PKCS#8 and SPKI PEM files".to_string()),
+        ];
+        let default = usize::from(args.output_private.is_some());
+        if wizard::choose("Destination", &destinations, default)? == 1 {
+            let private = args.output_private.as_deref().unwrap_or(Path::new("private.pem")).display().to_string();
+            let public = args.output_public.as_deref().unwrap_or(Path::new("public.pem")).display().to_string();
+            let path = |answer: &str| Ok(PathBuf::from(answer));
+            args.output_private = Some(wizard::ask("Private key file", Some(&private), path)?);
+            args.output_public = Some(wizard::ask("Public key file", Some(&public), path)?);
+        } else {
+            args.output_private = None;
+            args.output_public = None;
+        }
+        eprintln!();
+    }
+
+    if !args.private_elsewhere() {
+        // Choosing the terminal is the user's consent to see the key there.
+        args.allow_tty = true;
+        let formats: Vec<(String, String)> = OutputFormat::value_variants()
+            .iter()
+            .map(|&format| {
+                let description = match format {
+                    OutputFormat::Hex => "hexadecimal",
+                    OutputFormat::Decimal => "decimal",
+                    OutputFormat::Both => "hexadecimal and decimal",
+                    OutputFormat::Base64 => "base64",
+                    #[cfg(feature = "pem")]
+                    OutputFormat::Pem => "PKCS #8 PEM block",
+                };
+                (output_format_name(format), description.to_string())
+            })
+            .collect();
+        let default = OutputFormat::value_variants()
+            .iter()
+            .position(|&format| format == args.output_format)
+            .unwrap_or(0);
+        args.output_format = OutputFormat::value_variants()[wizard::choose("Private key format", &formats, default)?];
+        eprintln!();
+    }
+
+    let mut command = vec!["create-private-key".to_string()];
+    match args.prime {
+        Some(ref prime) => {
+            command.extend(["--prime".to_string(), prime.clone()]);
+            command.extend(["--generator".to_string(), args.generator.clone().unwrap_or_default()]);
+        }
+        None => command.extend(["--group".to_string(), args.group.name().to_string()]),
+    }
+    if let Some(bits) = args.private_bits {
+        command.extend(["--private-bits".to_string(), bits.to_string()]);
+    }
+    #[cfg(feature = "pem")]
+    for (flag, path) in [("--output-private", &args.output_private), ("--output-public", &args.output_public)] {
+        if let Some(path) = path {
+            command.extend([flag.to_string(), path.display().to_string()]);
         }
     }
+    if !args.private_elsewhere() {
+        command.extend(["--private-format".to_string(), output_format_name(args.output_format)]);
+        command.push("--allow-tty".to_string());
+    }
+    if args.public_format != OutputFormat::Hex {
+        command.extend(["--public-format".to_string(), output_format_name(args.public_format)]);
+    }
+    let command: Vec<String> = command.iter().map(|word| wizard::shell_quote(word)).collect();
+    eprintln!("Equivalent command:");
+    eprintln!("  {}", command.join(" "));
+    eprintln!();
+    Ok(wizard::confirm("Generate the key pair now?")?)
 }
 
-#[derive(ValueEnum, Clone, Copy, Debug)]
-enum OutputFormat {
-    Hex,
-    Decimal,
-    Both,
+fn output_format_name(format: OutputFormat) -> String {
+    format.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
 }
 
-/// Command line arguments for the DH private key generator.
-#[derive(Parser, Debug)]
-#[command(
-    name = "create-private-key",
-    about = "Generate a Diffie-Hellman private key (and matching public key) for a chosen group"
-)]
-struct Args {
-    /// RFC 3526 MODP group to base parameters on (ignored when --prime is provided).
-    #[arg(long, value_enum, default_value_t = DhGroup::Modp14)]
-    group: DhGroup,
+/// What `run` set up before the command, for the commands that report it.
+struct Startup {
+    fips: bool,
+    self_tested: bool,
+    health_tested: bool,
+    rng: RngBackend,
+    entropy_sources: Vec<&'static str>,
+}
 
-    /// Diffie-Hellman prime modulus in decimal or hex (hex may start with 0x).
-    #[arg(long)]
-    prime: Option<String>,
+/// `generate`: creates `--count` key pairs (or re-exports `--import`) and
+/// writes each to the selected outputs.
+fn run_generate(mut args: GenerateArgs, policy: &config::Policy, startup: &Startup) -> Result<(), Failure> {
+    let fips = startup.fips;
+    args.prime = read_prime(args.prime.as_deref(), args.prime_radix, args.strict_prime)?;
+    if args.interactive && !run_wizard(&mut args)? {
+        eprintln!("Nothing was written.");
+        return Ok(());
+    }
+    if let Some(option) = args.fips_refusal().filter(|_| fips) {
+        return Err(fips::Refusal(option).into());
+    }
+    let run_started = Instant::now();
+    let mut timings = Timings::default();
+    #[cfg(feature = "json")]
+    let imported = args.import.as_deref().map(|path| import(&args, path)).transpose()?;
+    #[cfg(not(feature = "json"))]
+    let imported = None;
+    let importing = imported.is_some();
+    let (dh_params, mut imported) = match imported {
+        Some((dh_params, record)) => (dh_params, Some(record)),
+        None => {
+            let (prime, generator) = timings.time(Stage::Parse, || {
+                params::parse(args.group, args.prime.as_deref(), args.generator.as_deref())
+            })?;
+            let dh_params = timings.time(Stage::Validation, || -> Result<DhParams, Failure> {
+                let dh_params = DhParams::new(prime, generator)?;
+                let dh_params = match args.private_bits {
+                    Some(bits) => dh_params.with_private_bits(bits)?,
+                    None => dh_params,
+                };
+                policy.check(&dh_params, args.prime.is_some())?;
+                if fips {
+                    fips::check_params(&dh_params)?;
+                }
+                Ok(dh_params)
+            })?;
+            (dh_params, None)
+        }
+    };
+    tracing::info!(
+        group = if args.prime.is_some() { "custom" } else { args.group.name() },
+        prime_bits = dh_params.prime().bits(),
+        imported = importing,
+        "using group parameters"
+    );
 
-    /// Generator to use (defaults to group generator).
-    #[arg(long)]
-    generator: Option<String>,
+    #[cfg(feature = "service")]
+    if args.rpc {
+        let name = if args.prime.is_some() { "custom" } else { args.group.name() };
+        let (prime, generator) = dh_params.into_parts();
+        return Ok(rpc::serve(api::Group { prime, generator, name })?);
+    }
 
-    /// Output format for the private key.
-    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Hex)]
-    output_format: OutputFormat,
+    #[cfg(feature = "hsm")]
+    if let Some(ref module) = args.pkcs11 {
+        return run_pkcs11(&args, module, dh_params.prime(), dh_params.generator());
+    }
+
+    let count = args.count;
+    if count == 0 {
+        return Err("--count must be at least 1".into());
+    }
+    if count > 1 && args.single_key_output() {
+        return Err("--count above 1 needs --output-dir instead of single-key outputs".into());
+    }
+    #[cfg(feature = "qr")]
+    if count > 1 && args.qr.is_some() {
+        return Err("--qr works with a single key only".into());
+    }
+    // These outputs always hold the whole key, so it would reach stdout too.
+    if matches!(args.copy, Some(CopyTarget::Private)) {
+        let whole_key = [
+            args.template.is_some(),
+            args.paper_backup,
+            #[cfg(feature = "json")]
+            args.envelope,
+            #[cfg(feature = "cloud")]
+            !(args.sops_age.is_empty() && args.sops_kms.is_empty() && args.sops_gcp_kms.is_empty()),
+        ];
+        if whole_key.contains(&true) {
+            return Err("--copy private keeps the private key off stdout, but this output prints it".into());
+        }
+    }
+    if args.copy.is_some() {
+        clipboard::ensure_available()?;
+    }
+    #[cfg(feature = "pem")]
+    if args.verify_with_openssl {
+        openssl::ensure_available()?;
+    }
+
+    let private_elsewhere = args.private_elsewhere();
+    // Screens get shared and recorded, so unless --allow-tty is given a
+    // private key bound for a terminal is left out.
+    let prints_private = [
+        !private_elsewhere,
+        !(args.quiet && matches!(args.print, PrintTarget::Public)),
+        #[cfg(feature = "pem")]
+        args.output_dir.is_none(),
+        #[cfg(feature = "cloud")]
+        (args.sops_age.is_empty() && args.sops_kms.is_empty() && args.sops_gcp_kms.is_empty()),
+    ]
+    .iter()
+    .all(|&condition| condition);
+    let withhold = prints_private && !args.allow_tty && std::io::stdout().is_terminal();
+    if withhold {
+        let whole_key = [
+            args.template.is_some(),
+            args.paper_backup,
+            #[cfg(feature = "json")]
+            args.envelope,
+        ];
+        if whole_key.contains(&true) {
+            return Err(
+                "this output contains the private key and stdout is a terminal; redirect it or add --allow-tty".into(),
+            );
+        }
+    }
+    let private_elsewhere = private_elsewhere || withhold;
+
+    #[cfg(feature = "qr")]
+    if private_elsewhere && matches!(args.qr, Some(QrTarget::Private)) && args.qr_file.is_none() {
+        return Err("--qr private would print the private key to stdout; use --qr-file or --qr public".into());
+    }
+    if withhold {
+        eprintln!("private key withheld from the terminal; redirect stdout, use --output-private or add --allow-tty");
+    }
+
+    #[cfg(feature = "json")]
+    if args.pipeline {
+        let defaults = pipeline::Defaults {
+            params: dh_params,
+            group: args.group,
+            group_name: if args.prime.is_some() { "custom" } else { args.group.name() },
+            label: args.label.clone(),
+            operator: args.operator.clone(),
+            metadata: !args.no_metadata,
+            valid_for: args.valid_for,
+            tags: args.tags.clone(),
+            fingerprint_hash: args.fingerprint_hash,
+            private: (!private_elsewhere).then_some(args.output_format),
+            public: args.public_format,
+            entropy_sources: startup.entropy_sources.clone(),
+        };
+        return Ok(pipeline::run(&defaults, policy)?);
+    }
+
+    let mut private_sink = args.private_fd.map(sink::open_fd).transpose()?;
+    #[cfg(feature = "cloud")]
+    let store_location = args
+        .store
+        .map(|store| store.location(args.name.as_deref(), args.path.as_deref()))
+        .transpose()?;
+    #[cfg(feature = "keystore")]
+    let keystore = match args.keystore {
+        Some(ref path) => {
+            let keystore = Keystore::open(&path.clone().unwrap_or_else(keystore::default_path), true)?;
+            let file = args.keystore_passphrase_file.as_deref();
+            let passphrase = if keystore.has_passphrase()? {
+                passphrase::enter(file, "Keystore passphrase")?
+            } else {
+                passphrase::obtain(file)?
+            };
+            keystore.unlock(&passphrase)?;
+            Some((keystore, passphrase))
+        }
+        None => None,
+    };
+    #[cfg(feature = "json")]
+    let mut audit_log = args.audit_log.as_deref().map(AuditLog::open).transpose()?;
+    #[cfg(feature = "pem")]
+    let overwrite = if args.force { Overwrite::Replace } else { Overwrite::Refuse };
+    #[cfg(feature = "pem")]
+    if let Some(ref path) = args.output_private {
+        sink::ensure_writable(path, overwrite)?;
+    }
+    #[cfg(feature = "hsm")]
+    if let Some(ref path) = args.tpm_seal {
+        sink::ensure_writable(path, overwrite)?;
+    }
+
+    let template = match args.template {
+        Some(ref path) => Some(
+            fs::read_to_string(path).map_err(|source| Error::Io {
+                context: format!("failed to read template {}", path.display()),
+                source,
+            })?,
+        ),
+        None => None,
+    };
+
+    #[cfg(feature = "pem")]
+    if args.encryption.enabled() && args.output_private.is_none() && args.output_dir.is_none() {
+        return Err("key encryption applies to key files; add --output-private or --output-dir".into());
+    }
+    #[cfg(feature = "pem")]
+    let protection = args.encryption.resolve()?;
+
+    #[cfg(feature = "pem")]
+    let mut batch = match args.output_dir {
+        Some(ref dir) => Some(BatchWriter::new(dir, &args.name_pattern, count, overwrite)?),
+        None => None,
+    };
+
+    let labels = Labels {
+        prefix: args.label_prefix.clone(),
+        private: args.label_private.clone(),
+        public: args.label_public.clone(),
+    };
+    let pretty = std::io::stdout().is_terminal() && !args.plain;
+    let color = match args.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none(),
+    };
+
+    let group_name = if args.prime.is_some() { "custom" } else { args.group.name() };
+
+    // Before any key, so a custom prime's safe-prime test cannot fail after
+    // keys have been written.
+    let compliance = if args.compliance_report {
+        let steps = validation_steps(&dh_params, importing, startup);
+        Some(compliance::Report::new(&dh_params, steps, primality::Bases::Random, args.prime_checks)?)
+    } else {
+        None
+    };
+
+    let mut transcript = match args.transcript {
+        Some(ref path) => {
+            let mut transcript = transcript::Transcript::create(path)?;
+            transcript.begin(&transcript::Inputs {
+                group: group_name,
+                params: &dh_params,
+                rng: startup.rng.description(),
+                fips,
+                imported: importing,
+                validation: &validation_steps(&dh_params, importing, startup),
+            })?;
+            Some(transcript)
+        }
+        None => None,
+    };
+    #[cfg(feature = "pem")]
+    let provenance = match args.provenance_key {
+        Some(ref path) => Some((
+            provenance::Signer::load(path)?,
+            args.provenance_host.clone().unwrap_or_else(provenance::host_name),
+        )),
+        None => None,
+    };
+
+    let explainer = args.explain.then(|| explain::Explainer {
+        group: args.prime.is_none().then(|| args.group.name()),
+        params: dh_params.clone(),
+        fips,
+        imported: importing,
+    });
+
+    let started = Instant::now();
+    #[cfg(feature = "cloud")]
+    let sops_keys = SopsKeys {
+        age: args.sops_age.clone(),
+        kms: args.sops_kms.clone(),
+        gcp_kms: args.sops_gcp_kms.clone(),
+    };
+
+    // The bar is only drawn where it doesn't mix with keys on the terminal.
+    #[cfg(feature = "pem")]
+    let quiet_stdout = batch.is_some() || !std::io::stdout().is_terminal();
+    #[cfg(not(feature = "pem"))]
+    let quiet_stdout = !std::io::stdout().is_terminal();
+    let mut progress = Progress::new(
+        count as u64,
+        count > 1 && !args.no_progress && quiet_stdout && std::io::stderr().is_terminal(),
+    );
+
+    // One generator for the whole batch: a table of its powers replaces the
+    // squarings of each modpow once there are enough keys to pay for it.
+    let table_started = Instant::now();
+    let fixed_base = if importing { None } else { dh_params.fixed_base(count as u64) };
+    if fixed_base.is_some() {
+        tracing::debug!(elapsed_us = table_started.elapsed().as_micros() as u64, "built a fixed-base table");
+    }
+
+    #[cfg(feature = "pem")]
+    let mut openssl_skipped = 0;
+    for index in 1..=count {
+        let key_started = Instant::now();
+        let record = match imported.take() {
+            Some(record) => record,
+            None => {
+                let metadata = (!args.no_metadata).then(|| args.metadata(group_name));
+                let private_key = timings.time(Stage::Sampling, || {
+                    if fips {
+                        fips::private_key(&dh_params)
+                    } else {
+                        ffdh::generate_private_key(dh_params.prime(), dh_params.private_bits(), random::fill)
+                    }
+                })?;
+                let pair = timings.time(Stage::Modpow, || match fixed_base {
+                    Some(ref table) => KeyPair::from_private_key_with(dh_params.clone(), private_key, table),
+                    None => KeyPair::from_private_key(dh_params.clone(), private_key),
+                })?;
+                if fips {
+                    // The owner's assurance of public-key validity (SP 800-56A 5.6.2.1.4).
+                    timings.time(Stage::Validation, || fips::check_public_key(dh_params.prime(), pair.public_key()))?;
+                }
+                let mut record = KeyRecord::from_key_pair(pair, metadata, args.fingerprint_hash);
+                if startup.entropy_sources.len() > 1 {
+                    record.entropy_sources = startup.entropy_sources.clone();
+                }
+                record
+            }
+        };
+        tracing::debug!(
+            key_id = %record.key_id,
+            index,
+            elapsed_us = key_started.elapsed().as_micros() as u64,
+            "derived key pair"
+        );
+        #[cfg(feature = "pem")]
+        let record = match provenance {
+            Some((ref signer, ref host)) => KeyRecord {
+                provenance: Some(signer.document(&record, host)),
+                ..record
+            },
+            None => record,
+        };
+        // Before the key goes anywhere, so one openssl rejects is never written.
+        #[cfg(feature = "pem")]
+        if args.verify_with_openssl {
+            let checks = openssl::verify(&record)?;
+            openssl_skipped += usize::from(checks.contains(&openssl::CHECK_SKIPPED));
+            if count == 1 {
+                for check in checks {
+                    eprintln!("openssl: {check}");
+                }
+            }
+        }
+        if let Some(ref mut progress) = progress {
+            progress.tick();
+        }
+
+        #[cfg(feature = "json")]
+        if let Some(ref mut audit_log) = audit_log {
+            let metadata = record.metadata.clone().unwrap_or_else(|| args.metadata(group_name));
+            let spki = der::dh_subject_public_key_info(&record.prime, &record.generator, &record.public_key);
+            let event = if importing { "import" } else { "generate" };
+            audit_log.append(event, &record.prime, &record.generator, &spki, &metadata)?;
+        }
+        if let Some(ref mut transcript) = transcript {
+            transcript.key(&record)?;
+        }
+
+        #[cfg(feature = "keystore")]
+        if let Some((ref keystore, ref passphrase)) = keystore {
+            keystore.insert(&record, passphrase)?;
+        }
+
+        #[cfg(feature = "pem")]
+        if let Some(ref mut batch) = batch {
+            batch.write(index, &record, &protect::private_key_file(&record, protection.as_ref())?)?;
+            continue;
+        }
+
+        #[cfg(feature = "pem")]
+        if let Some(ref path) = args.output_private {
+            let contents = protect::private_key_file(&record, protection.as_ref())?;
+            sink::write_private_file(path, &contents, overwrite)?;
+        }
+        #[cfg(feature = "pem")]
+        if let Some(ref path) = args.output_public {
+            sink::write_public_file(path, record.public_key_pem().as_bytes())?;
+        }
+        #[cfg(feature = "cloud")]
+        if let (Some(store), Some(location)) = (args.store, store_location) {
+            eprintln!("stored {}", store::save(store, location, &record, args.force)?);
+        }
+        #[cfg(feature = "hsm")]
+        if let Some(ref path) = args.tpm_seal {
+            let secret = record.private_key_pem();
+            sink::write_private_file(path, &tpm::seal(secret.as_bytes(), &args.tpm_pcrs)?, overwrite)?;
+        }
+        #[cfg(feature = "pem")]
+        if let Some(spec) = args.split {
+            let secret = der::dh_private_key_info(&record.prime, &record.generator, record.private_key.expose());
+            for (index, share) in shamir::split(&secret, spec, &record.key_id)?.iter().enumerate() {
+                let name = format!("{}.share-{}-of-{}", record.key_id, index + 1, spec.shares);
+                sink::write_private_file(&args.share_dir.join(name), share.as_bytes(), overwrite)?;
+            }
+        }
+
+        if index > 1 && !args.record_per_line() {
+            println!();
+        }
+        let private = if private_elsewhere { None } else { Some(args.output_format) };
+        let rendered = 'render: {
+            if let Some(ref template) = template {
+                break 'render render_template(template, &record)?.into_bytes();
+            }
+            if args.paper_backup {
+                break 'render paper::render(&record).into_bytes();
+            }
+            #[cfg(feature = "cloud")]
+            if !sops_keys.is_empty() {
+                break 'render sops::render(&record, &sops_keys, args.sops_format)?;
+            }
+            #[cfg(feature = "json")]
+            if args.envelope {
+                break 'render envelope::render(&record).into_bytes();
+            }
+            #[cfg(feature = "json")]
+            if args.msgpack {
+                break 'render render_msgpack(&record, private, args.public_format);
+            }
+            if args.protobuf {
+                break 'render protobuf::encode_delimited(&record, !private_elsewhere);
+            }
+            #[cfg(feature = "json")]
+            if args.json {
+                break 'render render_json(&record, private, args.public_format).into_bytes();
+            }
+            if args.quiet {
+                let target = if private_elsewhere { PrintTarget::Public } else { args.print };
+                break 'render render_quiet(&record, args.output_format, args.public_format, target).into_bytes();
+            }
+            if let Some(ref explainer) = explainer {
+                let notes = |field, repeat| explainer.notes(field, repeat);
+                break 'render render_explained(&record, private, args.public_format, &labels, notes).into_bytes();
+            }
+            if pretty {
+                render_pretty(&record, private, args.public_format, &labels, color).into_bytes()
+            } else {
+                render_plain(&record, private, args.public_format, &labels).into_bytes()
+            }
+        };
+        // Flushed per record: stdout only flushes itself at newlines, which
+        // the binary formats may not end with.
+        let mut stdout = std::io::stdout();
+        stdout.write_all(&rendered).and_then(|()| stdout.flush()).map_err(|source| Error::Io {
+            context: "failed to write stdout".into(),
+            source,
+        })?;
+
+        if let Some(ref mut file) = private_sink {
+            let secret = render_quiet(&record, args.output_format, args.public_format, PrintTarget::Private);
+            file.write_all(secret.as_bytes()).map_err(|source| Error::Io {
+                context: "failed to write the private key".into(),
+                source,
+            })?;
+        }
+
+        #[cfg(feature = "qr")]
+        if let Some(target) = args.qr {
+            match args.qr_file {
+                Some(ref path) => qr::write_file(target, &record, path)?,
+                None => println!("{}", qr::render_terminal(target, &record)?),
+            }
+        }
+
+        if let Some(target) = args.copy {
+            let mut value = target.payload(&record);
+            let copied = clipboard::copy(&value).and_then(|()| match args.clear_after {
+                0 => Ok(()),
+                seconds => clipboard::schedule_clear(&value, seconds),
+            });
+            value.zeroize();
+            copied?;
+            match args.clear_after {
+                0 => eprintln!("copied the {} key to the clipboard", target.name()),
+                seconds => eprintln!("copied the {} key to the clipboard; clearing it in {seconds}s", target.name()),
+            }
+        }
+    }
+
+    drop(progress);
+    if let Some(transcript) = transcript {
+        transcript.finish()?;
+    }
+    tracing::info!(count, elapsed_ms = started.elapsed().as_millis() as u64, "done");
+    if args.timings {
+        eprint!("{}", timings.render(run_started.elapsed()));
+    }
+    #[cfg(feature = "pem")]
+    if args.verify_with_openssl && count > 1 {
+        eprintln!("openssl: agreed with this tool on all {count} keys");
+        if openssl_skipped > 0 {
+            eprintln!("openssl: pkey -check skipped for {openssl_skipped} full-size keys at or above q = (p - 1) / 2");
+        }
+    }
+
+    #[cfg(feature = "pem")]
+    if let Some(batch) = batch {
+        let manifest = batch.finish()?;
+        println!("keys_written={count}");
+        println!("manifest={}", manifest.display());
+    }
+
+    if let Some(report) = compliance {
+        #[cfg(feature = "json")]
+        if args.json {
+            let report = serde_json::to_string(&report).expect("compliance report serializes");
+            println!("{{\"compliance\":{report}}}");
+            return Ok(());
+        }
+        if !args.record_per_line() {
+            println!();
+        }
+        print!("{}", report.render_text());
+    }
+
+    Ok(())
 }
 
-fn main() {
-    if let Err(err) = run() {
-        eprintln!("Error: {err}");
-        process::exit(1);
+/// The checks a `generate` run performs, for `--compliance-report`.
+fn validation_steps(dh_params: &DhParams, importing: bool, startup: &Startup) -> Vec<String> {
+    let mut steps = Vec::new();
+    if startup.self_tested {
+        steps.push("power-on self-tests: known-answer tests of the DRBG, modpow and KDFs".to_string());
     }
+    if startup.health_tested {
+        steps.push("SP 800-90B startup health tests on the entropy sources".into());
+    }
+    steps.push(format!("random source: {}", startup.rng.description()));
+    steps.extend(parameter_steps(dh_params));
+    if importing {
+        steps.push("imported private key: in [2, p - 2]".into());
+    } else if startup.fips {
+        steps.push("private keys: SP 800-56A Rev. 3 5.6.1.1.4, uniform in [1, min(2^N, q) - 1]".into());
+        steps.push("public keys: SP 800-56A Rev. 3 5.6.2.3.1 full validation".into());
+    } else {
+        steps.push("private keys: uniform in [2, p - 2], or [2, 2^N) for short exponents, by rejection sampling".into());
+    }
+    steps
 }
 
-fn run() -> Result<(), String> {
-    let args = Args::parse();
+/// The checks every set of parameters passes before it is used.
+fn parameter_steps(dh_params: &DhParams) -> Vec<String> {
+    let mut steps = vec!["parameters: p odd and greater than 3, 1 < g < p".to_string()];
+    if let Some(bits) = dh_params.private_bits() {
+        steps.push(format!(
+            "private exponent size: {bits} bits, at least {} and below the size of p",
+            create_private_key::MIN_PRIVATE_BITS
+        ));
+    }
+    steps
+}
 
-    let prime = if let Some(ref prime_str) = args.prime {
-        parse_biguint(prime_str)?
+/// `--check-only`: the parameter checks and compliance report of a
+/// `generate` run, without the RNG. A prime that is not a safe prime fails
+/// the run after the report.
+fn run_check_only(args: &GenerateArgs, policy: &config::Policy, fips: bool) -> Result<(), Failure> {
+    if let Some(option) = args.fips_refusal().filter(|_| fips) {
+        return Err(fips::Refusal(option).into());
+    }
+    let prime = read_prime(args.prime.as_deref(), args.prime_radix, args.strict_prime)?;
+    let dh_params = DhParams::parse(args.group, prime.as_deref(), args.generator.as_deref())?;
+    let dh_params = match args.private_bits {
+        Some(bits) => dh_params.with_private_bits(bits)?,
+        None => dh_params,
+    };
+    policy.check(&dh_params, prime.is_some())?;
+    let mut steps = parameter_steps(&dh_params);
+    steps.push("configured policy".into());
+    if fips {
+        fips::check_params(&dh_params)?;
+        steps.push("FIPS mode: a SP 800-56A safe-prime group with a large enough exponent".into());
+    }
+    let report = compliance::Report::new(&dh_params, steps, primality::Bases::Derived, args.prime_checks)?;
+    let result = if report.safe_prime.starts_with("no") {
+        Err(Error::Validation("p is not a safe prime".into()).into())
     } else {
-        parse_hex_biguint(args.group.default_prime_hex())
+        Ok(())
+    };
+    #[cfg(feature = "json")]
+    if args.json {
+        let report = serde_json::to_string(&report).expect("compliance report serializes");
+        println!("{{\"compliance\":{report}}}");
+        return result;
+    }
+    print!("{}", report.render_text());
+    result
+}
+
+/// `--import`: the parameters and key record of a `cpk-v1` envelope.
+#[cfg(feature = "json")]
+fn import(args: &GenerateArgs, path: &Path) -> Result<(DhParams, KeyRecord), Failure> {
+    let imported = envelope::parse(&read_input(path)?)?;
+    let dh_params = DhParams::new(imported.prime, imported.generator)?;
+    let metadata = if args.no_metadata { None } else { imported.metadata };
+    let (prime, generator) = dh_params.clone().into_parts();
+    let record = KeyRecord::new(prime, generator, imported.private_key, metadata, args.fingerprint_hash);
+    Ok((dh_params, record))
+}
+
+/// `--pkcs11`: the token generates the key pair; only public values and
+/// the object handles are printed.
+#[cfg(feature = "hsm")]
+fn run_pkcs11(args: &GenerateArgs, module: &Path, prime: &BigUint, generator: &BigUint) -> Result<(), Failure> {
+    let slot = args.slot.expect("--slot is required with --pkcs11");
+    let pin = passphrase::enter(args.pin_file.as_deref(), "PIN")?;
+    let key = pkcs11::generate(module, slot as _, &pin, &args.pkcs11_label, prime, generator)?;
+    if key.public_key <= BigUint::one() || key.public_key >= prime - 1u32 {
+        return Err("the token returned an out-of-range public value".into());
+    }
+
+    let spki = der::dh_subject_public_key_info(prime, generator, &key.public_key);
+    #[cfg(feature = "json")]
+    if let Some(ref path) = args.audit_log {
+        let group_name = if args.prime.is_some() { "custom" } else { args.group.name() };
+        let metadata = args.metadata(group_name);
+        AuditLog::open(path)?.append("pkcs11-generate", prime, generator, &spki, &metadata)?;
+    }
+    if let Some(ref path) = args.output_public {
+        sink::write_public_file(path, pem::encode(pem::PUBLIC_KEY_LABEL, &spki).as_bytes())?;
+    }
+    let id: String = key.id.iter().map(|byte| format!("{byte:02X}")).collect();
+    let lines = [
+        ("prime_bits", prime.bits().to_string()),
+        ("generator", generator.to_string()),
+        ("public_key_hex", output::to_even_length_hex(&key.public_key)),
+        ("public_key_fingerprint", fingerprint::fingerprint(args.fingerprint_hash, &spki)),
+        ("key_id", fingerprint::key_id(&spki)),
+        ("pkcs11_label", args.pkcs11_label.clone()),
+        ("pkcs11_id", id),
+        ("pkcs11_public_handle", key.public_handle.to_string()),
+        ("pkcs11_private_handle", key.private_handle.to_string()),
+    ];
+    for (name, value) in lines {
+        println!("{}{name}={value}", args.label_prefix);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "pem")]
+fn run_convert(args: ConvertArgs) -> Result<(), Failure> {
+    if args.encryption.enabled() && !args.out_format.is_private() {
+        return Err("public key formats are not encrypted".into());
+    }
+    if args.encryption.encrypt && !matches!(args.out_format, KeyFormat::Pkcs8 | KeyFormat::Der) {
+        return Err("--encrypt needs --out-format pkcs8 or der".into());
+    }
+    let record = args.key.load()?;
+    let encoded = match args.encryption.resolve()? {
+        Some(ref protection) => convert::write_protected(&record, args.out_format, protection)?,
+        None => convert::write(&record, args.out_format),
     };
 
-    if prime <= BigUint::from(3u32) {
-        return Err("prime modulus must be greater than 3".into());
+    write_key_output(&args.output, args.out_format, &encoded, args.force, args.allow_tty)
+}
+
+/// The format `convert::detect` recognises in `input`.
+#[cfg(feature = "pem")]
+fn detect_format(input: &[u8]) -> Result<KeyFormat, Error> {
+    convert::detect(input).ok_or_else(|| Error::Parse("could not detect the input format; pass --in-format".into()))
+}
+
+#[cfg(feature = "pem")]
+impl KeyInputArgs {
+    /// Reads the key, first opening a sealed, KMS or TPM wrapping, and
+    /// checks its parameters.
+    fn load(&self) -> Result<KeyRecord, Failure> {
+        let mut input = read_input_bytes(&self.input)?;
+        if sealed::is_sealed(&input) {
+            input = sealed::open(&input, &passphrase::enter(self.in_passphrase_file.as_deref(), "Passphrase")?)?;
+        }
+        #[cfg(feature = "cloud")]
+        if kms::is_envelope(&input) {
+            input = kms::decrypt(&input)?;
+        }
+        #[cfg(feature = "hsm")]
+        if tpm::is_sealed(&input) {
+            input = tpm::unseal(&input)?;
+        }
+        let in_format = match self.in_format {
+            Some(format) => format,
+            None => detect_format(&input)?,
+        };
+        let imported = convert::read(&input, in_format, || {
+            Ok(params::resolve(self.group, self.prime.as_deref(), self.generator.as_deref())?)
+        })
+        .map_err(Error::Parse)?;
+        params::validate(&imported.prime, &imported.generator)?;
+
+        Ok(KeyRecord::new(
+            imported.prime,
+            imported.generator,
+            imported.private_key,
+            imported.metadata,
+            FingerprintHash::Sha256,
+        ))
+    }
+}
+
+#[cfg(feature = "pem")]
+fn run_derive_public(args: DerivePublicArgs, fips: bool) -> Result<(), Failure> {
+    let record = args.key.load()?;
+    if fips {
+        fips_check_key(&record)?;
+    }
+    let format = if args.der { KeyFormat::SpkiDer } else { KeyFormat::Spki };
+    write_key_output(&args.output, format, &convert::write(&record, format), false, false)
+}
+
+/// Under `--fips`, a key read from a file must be in an approved group with
+/// its private exponent in `[1, q - 1]`.
+#[cfg(feature = "pem")]
+fn fips_check_key(record: &KeyRecord) -> Result<(), Failure> {
+    fips::check_params(&DhParams::new(record.prime.clone(), record.generator.clone())?)?;
+    Ok(fips::check_private_key(&record.prime, record.private_key.expose())?)
+}
+
+#[cfg(feature = "pem")]
+fn run_shared_secret(args: SharedSecretArgs, fips: bool) -> Result<(), Failure> {
+    let record = args.key.load()?;
+    let peer = BigUint::from_bytes_be(&parse_hex_arg("peer", &args.peer)?);
+    if fips {
+        fips_check_key(&record)?;
+        fips::check_public_key(&record.prime, &peer)?;
     }
-    if prime.is_even() {
-        return Err("prime modulus must be odd".into());
+    let mut secret = dh::shared_secret(&record.prime, record.private_key.expose(), &peer)?;
+    if let Some(length) = args.hkdf_length {
+        let salt = parse_hex_arg("hkdf-salt", &args.hkdf_salt)?;
+        secret = dh::hkdf_sha256(&secret, &salt, args.hkdf_info.as_bytes(), length as usize)?;
     }
+    println!("{}", data_encoding::HEXUPPER.encode(&secret));
+    Ok(())
+}
+
+/// Decodes a hex option value, with or without a `0x` prefix.
+fn parse_hex_arg(name: &str, value: &str) -> Result<Vec<u8>, Error> {
+    data_encoding::HEXLOWER_PERMISSIVE
+        .decode(value.trim_start_matches("0x").as_bytes())
+        .map_err(|err| Error::Parse(format!("--{name} is not valid hex: {err}")))
+}
+
+fn run_validate(args: ValidateArgs, fips: bool) -> Result<(), Failure> {
+    let dh_params = args.group.resolve()?;
+    if fips {
+        fips::check_params(&dh_params)?;
+    }
+    if let Some(ref public_key) = args.public_key {
+        let public_key = BigUint::from_bytes_be(&parse_hex_arg("public-key", public_key)?);
+        if fips {
+            fips::check_public_key(dh_params.prime(), &public_key)?;
+        } else {
+            dh::check_peer(dh_params.prime(), &public_key)?;
+        }
+    }
+    println!("prime_bits={}", dh_params.prime().bits());
+    println!("valid=true");
+    Ok(())
+}
 
-    let generator = if let Some(ref gen_str) = args.generator {
-        parse_biguint(gen_str)?
+fn run_params(args: ParamsArgs, fips: bool) -> Result<(), Failure> {
+    let dh_params = args.group.resolve()?;
+    if fips {
+        fips::check_params(&dh_params)?;
+    }
+    if args.text {
+        println!("prime_bits={}", dh_params.prime().bits());
+        println!("prime_hex={}", output::to_even_length_hex(dh_params.prime()));
+        println!("generator={}", dh_params.generator());
     } else {
-        parse_biguint(args.group.default_generator())?
+        print!("{}", pem::encode(pem::DH_PARAMETERS_LABEL, &dh_params.to_der()));
+    }
+    Ok(())
+}
+
+/// `estimate`: the costed attacks on the group and the strength they give.
+fn run_estimate(args: EstimateArgs) -> Result<(), Failure> {
+    let dh_params = args.group.resolve()?;
+    let dh_params = match args.private_bits {
+        Some(bits) => dh_params.with_private_bits(bits)?,
+        None => dh_params,
     };
+    let estimate = estimate::Estimate::new(&dh_params);
+    #[cfg(feature = "json")]
+    if args.json {
+        println!("{}", serde_json::to_string(&estimate).expect("estimate serializes"));
+        return Ok(());
+    }
+    print!("{}", estimate.render_text());
+    Ok(())
+}
+
+/// `key=value` lines for each side and the comparison; differing groups
+/// fail, like `diff`.
+fn run_diff_params(args: DiffParamsArgs) -> Result<(), Failure> {
+    let (a, b) = (param_diff::Side::read(&args.a)?, param_diff::Side::read(&args.b)?);
+    let (a_order, b_order) = (a.generator_order(args.prime_checks)?, b.generator_order(args.prime_checks)?);
+    let order_bits = |order: &Option<BigUint>| order.as_ref().map_or("unknown".into(), |q| q.bits().to_string());
+    println!("a_group={}", a.identify());
+    println!("b_group={}", b.identify());
+    println!("a_order_bits={}", order_bits(&a_order));
+    println!("b_order_bits={}", order_bits(&b_order));
+    println!("prime={}", param_diff::compare(&a.prime, &b.prime));
+    println!("generator={}", param_diff::compare(&a.generator, &b.generator));
+    match (a_order, b_order) {
+        (Some(a_order), Some(b_order)) => println!("order={}", param_diff::compare(&a_order, &b_order)),
+        _ => println!("order=unknown"),
+    }
+    let same = a.prime == b.prime && a.generator == b.generator;
+    println!("same={same}");
+    if !same {
+        return Err("the parameters differ".into());
+    }
+    Ok(())
+}
 
-    if generator <= BigUint::one() {
-        return Err("generator must be greater than 1".into());
+#[cfg(feature = "pem")]
+fn run_pool(args: PoolArgs, policy: &config::Policy) -> Result<(), Failure> {
+    let dh_params = args.group.resolve()?;
+    let dh_params = match args.private_bits {
+        Some(bits) => dh_params.with_private_bits(bits)?,
+        None => dh_params,
+    };
+    policy.check(&dh_params, args.group.prime.is_some())?;
+    let low_watermark = args.low_watermark.unwrap_or(args.size);
+    if args.size == 0 || low_watermark > args.size {
+        return Err("--size must be at least 1 and at least --low-watermark".into());
     }
-    if generator >= prime {
-        return Err("generator must be less than the prime modulus".into());
+    if args.rate.is_some_and(|rate| !(rate > 0.0 && rate.is_finite())) {
+        return Err("--rate must be a positive number of keys per second".into());
     }
+    let interval = std::time::Duration::try_from_secs_f64(args.interval)
+        .map_err(|_| format!("--interval must be a non-negative number of seconds, not {}", args.interval))?;
+    let settings = pool::Settings {
+        params: dh_params,
+        group_name: if args.group.prime.is_some() { "custom" } else { args.group.group.name() },
+        size: args.size,
+        low_watermark,
+        rate: args.rate,
+        interval,
+        once: args.once,
+    };
+    Ok(pool::run(&args.path, &settings)?)
+}
 
-    let mut rng = OsRng;
-    let private_key = generate_private_key(&prime, &mut rng);
-    let public_key = generator.modpow(&private_key, &prime);
+#[cfg(feature = "pem")]
+fn run_combine(args: CombineArgs) -> Result<(), Failure> {
+    let shares = args
+        .shares
+        .iter()
+        .map(|path| {
+            shamir::parse(&read_input(path)?).map_err(|err| Error::Parse(format!("{}: {err}", path.display())))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let secret = shamir::combine(&shares).map_err(Error::Validation)?;
+    let imported = convert::read(&secret, KeyFormat::Der, || unreachable!("DER carries its parameters"))
+        .map_err(Error::Parse)?;
+    params::validate(&imported.prime, &imported.generator)?;
+
+    let record = KeyRecord::new(
+        imported.prime,
+        imported.generator,
+        imported.private_key,
+        None,
+        FingerprintHash::Sha256,
+    );
+    let encoded = convert::write(&record, args.out_format);
+    write_key_output(&args.output, args.out_format, &encoded, args.force, args.allow_tty)
+}
 
-    println!("prime_bits={}", prime.bits());
-    println!("generator={}", generator);
+/// One `status\tkey_id\tnot_after\tsource` line per key that is due, and an
+/// error when there is any, so a cron job can alert on the exit status.
+fn run_check_expiry(args: CheckExpiryArgs) -> Result<(), Failure> {
+    let mut scan = expiry::Scan::default();
+    #[cfg(feature = "keystore")]
+    if let Some(ref path) = args.keystore {
+        let path = path.clone().unwrap_or_else(keystore::default_path);
+        scan.keystore(&Keystore::open(&path, false)?, &path.display().to_string())?;
+    }
+    #[cfg(feature = "keystore")]
+    let nothing_to_check = args.paths.is_empty() && args.keystore.is_none();
+    #[cfg(not(feature = "keystore"))]
+    let nothing_to_check = args.paths.is_empty();
+    if nothing_to_check {
+        return Err("give key files or directories to check, or --keystore".into());
+    }
+    for path in &args.paths {
+        scan.path(path)?;
+    }
 
-    match args.output_format {
-        OutputFormat::Hex => println!("private_key_hex={}", to_even_length_hex(&private_key)),
-        OutputFormat::Decimal => println!("private_key_dec={}", private_key.to_str_radix(10)),
-        OutputFormat::Both => {
-            println!("private_key_hex={}", to_even_length_hex(&private_key));
-            println!("private_key_dec={}", private_key.to_str_radix(10));
+    let now = std::time::SystemTime::now();
+    let (mut expired, mut expiring) = (0, 0);
+    for found in &scan.found {
+        let status = match found.status(now, args.warn_within) {
+            Some(status) => status,
+            None => {
+                tracing::warn!(key_id = %found.key_id, not_after = %found.not_after, "unreadable not-after time");
+                continue;
+            }
+        };
+        match status {
+            expiry::Status::Expired => expired += 1,
+            expiry::Status::Expiring => expiring += 1,
+            expiry::Status::Valid if !args.all => continue,
+            expiry::Status::Valid => {}
         }
+        println!("{}\t{}\t{}\t{}", status.name(), found.key_id, found.not_after, found.source);
+    }
+    eprintln!("{} checked: {expired} expired, {expiring} expiring soon", scan.found.len());
+    if expired + expiring > 0 {
+        return Err("keys have expired or expire soon; rotate them".into());
     }
+    Ok(())
+}
 
-    println!("public_key_hex={}", to_even_length_hex(&public_key));
+#[cfg(feature = "keystore")]
+fn run_keys(args: KeysArgs) -> Result<(), Failure> {
+    let path = args.keystore.unwrap_or_else(keystore::default_path);
+    let keystore = Keystore::open(&path, false)?;
+    match args.action {
+        KeysCommand::List { label, tags } => {
+            for entry in keystore.list(&keystore::Filter { label, tags })? {
+                let metadata = &entry.metadata;
+                let tags = metadata.tag_list();
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.key_id,
+                    metadata.created,
+                    metadata.group,
+                    metadata.label.as_deref().unwrap_or("-"),
+                    if tags.is_empty() { "-" } else { &tags }
+                );
+            }
+        }
+        KeysCommand::Show { key_id } => {
+            let entry = keystore.get(&key_id)?;
+            println!("key_id={}", entry.key_id);
+            println!("prime_bits={}", entry.prime_bits);
+            println!("public_key_fingerprint={}", entry.fingerprint);
+            for (name, value) in entry.metadata.entries() {
+                println!("{}={value}", name.to_lowercase().replace('-', "_"));
+            }
+            print!("{}", pem::encode(pem::PUBLIC_KEY_LABEL, &entry.public_key_spki));
+        }
+        KeysCommand::Delete { key_id } => {
+            keystore.delete(&key_id)?;
+            eprintln!("deleted {key_id}");
+        }
+        KeysCommand::Export {
+            key_id,
+            output,
+            out_format,
+            force,
+            allow_tty,
+        } => {
+            let passphrase = passphrase::enter(args.keystore_passphrase_file.as_deref(), "Keystore passphrase")?;
+            let imported = keystore.private_key(&key_id, &passphrase)?;
+            let record = KeyRecord::new(
+                imported.prime,
+                imported.generator,
+                imported.private_key,
+                imported.metadata,
+                FingerprintHash::Sha256,
+            );
+            write_key_output(&output, out_format, &convert::write(&record, out_format), force, allow_tty)?;
+        }
+    }
+    Ok(())
+}
 
+/// `sodium`: the secret key goes to `--output-secret` or `--output-keypair`
+/// when given, and is printed otherwise.
+#[cfg(feature = "pem")]
+fn run_sodium(args: SodiumArgs) -> Result<(), Failure> {
+    let to_file = args.output_secret.is_some() || args.output_keypair.is_some();
+    if !to_file && !args.allow_tty && std::io::stdout().is_terminal() {
+        return Err("this output contains the secret key and stdout is a terminal; redirect it or add --allow-tty".into());
+    }
+    let pair = sodium::KeyPair::generate()?;
+    let overwrite = if args.force { Overwrite::Replace } else { Overwrite::Refuse };
+    if let Some(ref path) = args.output_secret {
+        sink::write_private_file(path, &*pair.secret_key, overwrite)?;
+    }
+    if let Some(ref path) = args.output_keypair {
+        sink::write_private_file(path, &pair.keypair(), overwrite)?;
+    }
+    if let Some(ref path) = args.output_public {
+        sink::write_public_file(path, &pair.public_key)?;
+    }
+    print!("{}", *pair.render(args.encoding, !to_file));
     Ok(())
 }
 
-fn generate_private_key<R>(prime: &BigUint, rng: &mut R) -> BigUint
-where
-    R: Rng + ?Sized,
-{
-    let two = BigUint::from(2u32);
-    let one = BigUint::one();
-    let upper_exclusive = prime - &one;
-    rng.gen_biguint_range(&two, &upper_exclusive)
+/// `signal`: the public keys always go to stdout; the records, which hold
+/// the private keys, go to `--out` when given.
+#[cfg(feature = "pem")]
+fn run_signal(args: SignalArgs) -> Result<(), Failure> {
+    if args.out.is_none() && !args.allow_tty && std::io::stdout().is_terminal() {
+        return Err(
+            "this output contains private keys and stdout is a terminal; redirect it, use --out or add --allow-tty".into(),
+        );
+    }
+    let bundle = signal::Bundle::generate(args.signed_pre_key_id, args.first_pre_key_id, args.pre_keys)?;
+    match args.out {
+        Some(ref path) => {
+            let overwrite = if args.force { Overwrite::Replace } else { Overwrite::Refuse };
+            sink::write_private_file(path, bundle.render().as_bytes(), overwrite)?;
+            print!("{}", bundle.render_public());
+        }
+        None => print!("{}", *bundle.render()),
+    }
+    Ok(())
 }
 
-fn parse_biguint(input: &str) -> Result<BigUint, String> {
-    let cleaned: String = input
-        .chars()
-        .filter(|c| !c.is_whitespace() && *c != '_')
-        .collect();
+#[cfg(feature = "hsm")]
+fn run_piv(args: PivArgs) -> Result<(), Failure> {
+    let management_key = args
+        .management_key_file
+        .as_deref()
+        .map(|path| passphrase::enter(Some(path), "management key"))
+        .transpose()?;
+    // Asked for before the key is made, so a failed prompt leaves the slot
+    // as it was.
+    let pin = (args.csr || args.self_signed)
+        .then(|| passphrase::enter(args.pin_file.as_deref(), "PIN"))
+        .transpose()?;
+    let provisioned = piv::generate(&args.slot, args.algorithm, management_key.as_deref())?;
+    let subject = args.subject.as_deref().unwrap_or_default();
+    let pin = pin.unwrap_or_default();
+    let csr = args.csr.then(|| piv::request(&args.slot, &provisioned.public_key, subject, &pin)).transpose()?;
+    let certificate = args
+        .self_signed
+        .then(|| {
+            let management_key = management_key.as_deref();
+            piv::self_sign(&args.slot, &provisioned.public_key, subject, args.days, &pin, management_key)
+        })
+        .transpose()?;
 
-    if cleaned.is_empty() {
-        return Err("value cannot be empty".into());
+    if let Some(ref path) = args.output_public {
+        sink::write_public_file(path, provisioned.public_key.as_bytes())?;
+    }
+    if let Some(ref path) = args.output_attestation {
+        sink::write_public_file(path, provisioned.attestation.as_bytes())?;
+    }
+    if let (Some(path), Some(csr)) = (&args.output_csr, &csr) {
+        sink::write_public_file(path, csr.as_bytes())?;
     }
+    if let (Some(path), Some(certificate)) = (&args.output_certificate, &certificate) {
+        sink::write_public_file(path, certificate.as_bytes())?;
+    }
+    print!(
+        "{}{}{}{}",
+        provisioned.public_key,
+        provisioned.attestation,
+        csr.unwrap_or_default(),
+        certificate.unwrap_or_default()
+    );
+    Ok(())
+}
 
-    let (radix, digits) = if let Some(stripped) = cleaned.strip_prefix("0x").or_else(|| cleaned.strip_prefix("0X")) {
-        (16, stripped)
-    } else {
-        (10, cleaned.as_str())
+#[cfg(feature = "service")]
+fn run_agent(args: AgentArgs) -> Result<(), Failure> {
+    let (prime, generator) = params::resolve(args.group, args.prime.as_deref(), args.generator.as_deref())?;
+    if let Some(ref listen) = args.metrics_listen {
+        metrics::spawn_server(listen)?;
+    }
+    Ok(agent::serve(&agent::socket_path(args.socket.as_deref()), prime, generator, args.limits.limits()?)?)
+}
+
+#[cfg(feature = "service")]
+fn run_agent_add(args: AgentAddArgs) -> Result<(), Failure> {
+    let private_key_info = match args.input {
+        Some(ref path) => {
+            let input = read_input_bytes(path)?;
+            let format = match args.in_format {
+                Some(format) => format,
+                None => detect_format(&input)?,
+            };
+            let imported = convert::read(&input, format, || {
+                Err("hex and raw keys carry no parameters; convert them to pkcs8 first".into())
+            })
+            .map_err(Error::Parse)?;
+            params::validate(&imported.prime, &imported.generator)?;
+            der::dh_private_key_info(&imported.prime, &imported.generator, &imported.private_key)
+        }
+        None => Vec::new(),
+    };
+
+    let response = agent::request(
+        &agent::socket_path(args.socket.as_deref()),
+        agent::Request::add(&args.name, &private_key_info),
+    )?;
+    let mut fields = agent::Fields(&response);
+    let key_id = fields.text()?;
+    let public_key = BigUint::from_bytes_be(fields.field()?);
+    println!("key_id={key_id}");
+    println!("public_key_hex={}", output::to_even_length_hex(&public_key));
+    Ok(())
+}
+
+#[cfg(feature = "service")]
+fn run_agent_dh(args: AgentDhArgs) -> Result<(), Failure> {
+    let peer = BigUint::from_bytes_be(&parse_hex_arg("peer", &args.peer)?);
+    let request = match args.hkdf_length {
+        Some(length) => agent::Request::hkdf(
+            &args.name,
+            &peer.to_bytes_be(),
+            &parse_hex_arg("hkdf-salt", &args.hkdf_salt)?,
+            args.hkdf_info.as_bytes(),
+            length,
+        ),
+        None => agent::Request::dh(&args.name, &peer.to_bytes_be()),
     };
+    let response = agent::request(&agent::socket_path(args.socket.as_deref()), request)?;
+    let output: String = agent::Fields(&response).field()?.iter().map(|byte| format!("{byte:02X}")).collect();
+    println!("{output}");
+    Ok(())
+}
+
+#[cfg(feature = "service")]
+fn run_agent_list(args: AgentSocketArgs) -> Result<(), Failure> {
+    let response = agent::request(&agent::socket_path(args.socket.as_deref()), agent::Request::list())?;
+    let mut fields = agent::Fields(&response);
+    for _ in 0..fields.number()? {
+        let name = fields.text()?;
+        println!("{name} key_id={}", fields.text()?);
+    }
+    Ok(())
+}
 
-    BigUint::parse_bytes(digits.as_bytes(), radix)
-        .ok_or_else(|| "failed to parse big integer".to_string())
+#[cfg(feature = "service")]
+fn run_agent_public(args: AgentKeyArgs) -> Result<(), Failure> {
+    let response = agent::request(
+        &agent::socket_path(args.socket.as_deref()),
+        agent::Request::public(&args.name),
+    )?;
+    print!("{}", pem::encode(pem::PUBLIC_KEY_LABEL, agent::Fields(&response).field()?));
+    Ok(())
 }
 
-fn parse_hex_biguint(hex: &str) -> BigUint {
-    let cleaned: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
-    BigUint::parse_bytes(cleaned.as_bytes(), 16).expect("invalid hex prime literal")
+#[cfg(feature = "service")]
+fn run_agent_remove(args: AgentKeyArgs) -> Result<(), Failure> {
+    agent::request(
+        &agent::socket_path(args.socket.as_deref()),
+        agent::Request::remove(&args.name),
+    )?;
+    Ok(())
 }
 
-fn to_even_length_hex(value: &BigUint) -> String {
-    let hex = format!("{value:X}");
-    if hex.len() % 2 == 0 {
-        hex
+/// Writes `convert`/`combine` output to stdout (`-`) or a key file with
+/// the permissions `format` calls for. As with `generate`, a private key is
+/// not printed on a terminal without `--allow-tty`.
+#[cfg(feature = "pem")]
+fn write_key_output(
+    path: &Path,
+    format: KeyFormat,
+    encoded: &[u8],
+    force: bool,
+    allow_tty: bool,
+) -> Result<(), Failure> {
+    if path == Path::new("-") {
+        if format.is_private() && !allow_tty && std::io::stdout().is_terminal() {
+            return Err(
+                "this output contains the private key and stdout is a terminal; redirect it, use --out or add --allow-tty"
+                    .into(),
+            );
+        }
+        let mut stdout = std::io::stdout();
+        return Ok(stdout.write_all(encoded).and_then(|()| stdout.flush()).map_err(|source| Error::Io {
+            context: "failed to write stdout".into(),
+            source,
+        })?);
+    }
+    if format.is_private() {
+        let overwrite = if force { Overwrite::Replace } else { Overwrite::Refuse };
+        Ok(sink::write_private_file(path, encoded, overwrite)?)
     } else {
-        format!("0{hex}")
+        Ok(sink::write_public_file(path, encoded)?)
+    }
+}
+
+/// Reads a whole file, or stdin when the path is `-`.
+fn read_input_bytes(path: &Path) -> Result<Vec<u8>, Error> {
+    if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes).map_err(|source| Error::Io {
+            context: "failed to read stdin".into(),
+            source,
+        })?;
+        return Ok(bytes);
     }
+    fs::read(path).map_err(|source| Error::Io {
+        context: format!("failed to read {}", path.display()),
+        source,
+    })
+}
+
+/// Like [`read_input_bytes`], for text input.
+fn read_input(path: &Path) -> Result<String, Error> {
+    String::from_utf8(read_input_bytes(path)?).map_err(|_| Error::Parse(format!("{} is not valid UTF-8", path.display())))
 }
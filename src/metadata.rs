@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "pem")]
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Descriptive fields stored alongside a key so key files explain themselves.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Metadata {
+    pub created: String,
+    pub tool_version: String,
+    pub group: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// When the key should be retired, from `--valid-for`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+    /// `--tag` pairs, for finding keys by device, environment or purpose.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// Captures the current time and tool version. The operator defaults to
+    /// the login name from `USER`/`USERNAME` when not given explicitly.
+    pub fn new(group: &str, label: Option<String>, operator: Option<String>) -> Self {
+        let operator = operator.or_else(|| {
+            std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok()
+                .filter(|name| !name.is_empty() && !name.chars().any(char::is_control))
+        });
+
+        Metadata {
+            created: rfc3339_utc(SystemTime::now()),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            group: group.to_string(),
+            label,
+            operator,
+            not_after: None,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `--tag` pairs; a repeated name keeps its last value.
+    pub fn tagged(mut self, tags: &[(String, String)]) -> Self {
+        self.tags.extend(tags.iter().cloned());
+        self
+    }
+
+    /// Stamps a not-after time `validity` past the creation time.
+    pub fn valid_for(mut self, validity: Option<Duration>) -> Self {
+        if let Some(validity) = validity {
+            let created = parse_rfc3339_utc(&self.created).unwrap_or_else(SystemTime::now);
+            self.not_after = Some(rfc3339_utc(created + validity));
+        }
+        self
+    }
+
+    /// `Name: value` pairs in display order, for text layouts. Each tag is a
+    /// `Tag: name=value` entry.
+    #[cfg(feature = "pem")]
+    pub fn entries(&self) -> Vec<(&'static str, Cow<'_, str>)> {
+        let mut entries = vec![
+            ("Created", Cow::from(&self.created)),
+            ("Tool-Version", Cow::from(&self.tool_version)),
+            ("Group", Cow::from(&self.group)),
+        ];
+        if let Some(ref label) = self.label {
+            entries.push(("Label", Cow::from(label)));
+        }
+        if let Some(ref operator) = self.operator {
+            entries.push(("Operator", Cow::from(operator)));
+        }
+        if let Some(ref not_after) = self.not_after {
+            entries.push(("Not-After", Cow::from(not_after)));
+        }
+        for (name, value) in &self.tags {
+            entries.push(("Tag", Cow::from(format!("{name}={value}"))));
+        }
+        entries
+    }
+
+    /// Tags as `name=value` joined with commas.
+    pub fn tag_list(&self) -> String {
+        self.tags.iter().map(|(name, value)| format!("{name}={value}")).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Formats a time as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn rfc3339_utc(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Parses the `YYYY-MM-DDTHH:MM:SSZ` form [`rfc3339_utc`] writes.
+pub fn parse_rfc3339_utc(text: &str) -> Option<SystemTime> {
+    let bytes = text.as_bytes();
+    let layout_ok = bytes.len() == 20
+        && bytes.iter().enumerate().all(|(index, &byte)| match index {
+            4 | 7 => byte == b'-',
+            10 => byte == b'T',
+            13 | 16 => byte == b':',
+            19 => byte == b'Z',
+            _ => byte.is_ascii_digit(),
+        });
+    if !layout_ok {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| text[range].parse::<u32>().ok();
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(i64::from(year), month, day)).ok()?;
+    let secs = days * 86_400 + u64::from(hour * 3600 + minute * 60 + second);
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Parses a validity period such as `90d`: a whole number followed by `s`,
+/// `m`, `h`, `d` or `w`.
+pub fn parse_validity(text: &str) -> Result<Duration, String> {
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => return Err(format!("'{text}' is not a period like 90d (units: s, m, h, d, w)")),
+    };
+    match number.parse::<u64>().ok().and_then(|count| count.checked_mul(unit_secs)) {
+        Some(0) => Err("the period must be longer than zero".into()),
+        Some(secs) => Ok(Duration::from_secs(secs)),
+        None => Err(format!("'{text}' is not a period like 90d (units: s, m, h, d, w)")),
+    }
+}
+
+/// Parses a `--tag NAME=VALUE` pair. Names are letters, digits and `-_.:/`;
+/// values may be empty but cannot hold control characters, which would break
+/// the PEM header lines.
+pub fn parse_tag(text: &str) -> Result<(String, String), String> {
+    let (name, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("'{text}' is not a NAME=VALUE tag"))?;
+    check_tag(name, value)?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// The rules [`parse_tag`] applies, for tags that arrive already split.
+pub fn check_tag(name: &str, value: &str) -> Result<(), String> {
+    check_tag_name(name)?;
+    if value.chars().any(char::is_control) {
+        return Err(format!("the value of tag '{name}' has a control character"));
+    }
+    Ok(())
+}
+
+/// Parses a `--label` or `--operator` value, which like a tag value cannot
+/// hold control characters.
+pub fn parse_text(text: &str) -> Result<String, String> {
+    if text.chars().any(char::is_control) {
+        return Err("the value has a control character".into());
+    }
+    Ok(text.to_string())
+}
+
+/// The rules [`parse_text`] applies, for a label and operator that arrive
+/// from a request rather than the command line.
+#[cfg(feature = "json")]
+pub fn check_text(label: Option<&str>, operator: Option<&str>) -> Result<(), String> {
+    for (field, value) in [("label", label), ("operator", operator)] {
+        if value.is_some_and(|value| value.chars().any(char::is_control)) {
+            return Err(format!("the {field} has a control character"));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a tag filter: `NAME=VALUE`, or `NAME` for any value.
+#[cfg(feature = "keystore")]
+pub fn parse_tag_filter(text: &str) -> Result<(String, Option<String>), String> {
+    match text.split_once('=') {
+        Some(_) => parse_tag(text).map(|(name, value)| (name, Some(value))),
+        None => check_tag_name(text).map(|()| (text.to_string(), None)),
+    }
+}
+
+fn check_tag_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:/".contains(c)) {
+        return Err(format!("tag name '{name}' must be letters, digits and -_.:/"));
+    }
+    Ok(())
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's
+/// `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The inverse of [`civil_from_days`].
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
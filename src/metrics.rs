@@ -0,0 +1,126 @@
+//! Process-wide counters and latency histograms for the daemon modes, in the
+//! Prometheus text exposition format.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use tiny_http::{Header, Response, Server};
+
+/// Upper bounds of the latency buckets, in seconds.
+const BUCKETS: [f64; 12] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+static KEYS_GENERATED: AtomicU64 = AtomicU64::new(0);
+static VALIDATION_FAILURES: AtomicU64 = AtomicU64::new(0);
+static REQUESTS_REFUSED: AtomicU64 = AtomicU64::new(0);
+static OPERATIONS: Mutex<BTreeMap<&'static str, Operation>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct Operation {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+    errors: u64,
+}
+
+pub fn key_generated() {
+    KEYS_GENERATED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A group, public key or peer value that failed its checks.
+pub fn validation_failed() {
+    VALIDATION_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A request turned away by a rate limit or the key quota.
+pub fn request_refused() {
+    REQUESTS_REFUSED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Runs `f`, recording its latency and outcome under `operation`.
+pub fn time<T, E>(operation: &'static str, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    let start = Instant::now();
+    let result = f();
+    observe(operation, start, result.is_ok());
+    result
+}
+
+pub fn observe(operation: &'static str, start: Instant, ok: bool) {
+    let seconds = start.elapsed().as_secs_f64();
+    let mut operations = OPERATIONS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let entry = operations.entry(operation).or_default();
+    for (bucket, &bound) in entry.buckets.iter_mut().zip(&BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    entry.count += 1;
+    entry.sum += seconds;
+    if !ok {
+        entry.errors += 1;
+    }
+}
+
+/// The current values as a scrape response body.
+pub fn render() -> String {
+    let mut out = String::new();
+    let mut counter = |name: &str, help: &str, value: u64| {
+        out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n"));
+    };
+    counter(
+        "cpk_keys_generated_total",
+        "Key pairs generated.",
+        KEYS_GENERATED.load(Ordering::Relaxed),
+    );
+    counter(
+        "cpk_validation_failures_total",
+        "Group parameters or public values that failed validation.",
+        VALIDATION_FAILURES.load(Ordering::Relaxed),
+    );
+    counter(
+        "cpk_requests_refused_total",
+        "Requests refused by a rate limit or the key quota.",
+        REQUESTS_REFUSED.load(Ordering::Relaxed),
+    );
+
+    let operations = OPERATIONS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    out.push_str("# HELP cpk_request_errors_total Requests that returned an error.\n");
+    out.push_str("# TYPE cpk_request_errors_total counter\n");
+    for (name, operation) in operations.iter() {
+        out.push_str(&format!("cpk_request_errors_total{{operation=\"{name}\"}} {}\n", operation.errors));
+    }
+    out.push_str("# HELP cpk_request_duration_seconds Time spent handling requests.\n");
+    out.push_str("# TYPE cpk_request_duration_seconds histogram\n");
+    for (name, operation) in operations.iter() {
+        let metric = "cpk_request_duration_seconds";
+        for (count, bound) in operation.buckets.iter().zip(BUCKETS) {
+            out.push_str(&format!("{metric}_bucket{{operation=\"{name}\",le=\"{bound}\"}} {count}\n"));
+        }
+        out.push_str(&format!("{metric}_bucket{{operation=\"{name}\",le=\"+Inf\"}} {}\n", operation.count));
+        out.push_str(&format!("{metric}_sum{{operation=\"{name}\"}} {}\n", operation.sum));
+        out.push_str(&format!("{metric}_count{{operation=\"{name}\"}} {}\n", operation.count));
+    }
+    out
+}
+
+/// Serves `GET /metrics` on its own listener, for modes whose main
+/// protocol is not HTTP/1.1. Runs on a background thread.
+pub fn spawn_server(listen: &str) -> Result<(), String> {
+    let server = Server::http(listen).map_err(|err| format!("failed to listen on {listen}: {err}"))?;
+    tracing::info!("metrics on http://{listen}/metrics");
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                let content_type = Header::from_bytes("Content-Type", CONTENT_TYPE).expect("static header is valid");
+                Response::from_string(render()).with_header(content_type)
+            } else {
+                Response::from_string("not found\n").with_status_code(404)
+            };
+            let _ = request.respond(response);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! `--verify-with-openssl`: independent confirmation that exported keys and
+//! parameters are well-formed, by round-tripping them through `openssl`.
+
+use create_private_key::{Error, der, pem};
+use zeroize::Zeroize;
+
+use crate::external;
+use crate::output::KeyRecord;
+
+/// Listed in place of the `pkey -check` line for a key OpenSSL would
+/// refuse only for being at or above `q`.
+pub const CHECK_SKIPPED: &str = "private key re-encodes identically; pkey -check skipped, since OpenSSL holds \
+                                 private keys below q = (p - 1) / 2 and full-size keys are drawn from [2, p - 2]";
+
+/// Fails when there is no `openssl` to run, so that is found out before a
+/// key is generated.
+pub fn ensure_available() -> Result<(), String> {
+    external::run_filter("openssl", &["version"], &[])
+        .map(drop)
+        .map_err(|err| format!("--verify-with-openssl needs the openssl command: {err}"))
+}
+
+/// Feeds the record's parameters, private key and public key as this tool
+/// exports them to `openssl` and compares what it makes of them. Returns
+/// the checks that passed; the first disagreement is an error.
+///
+/// OpenSSL knows the subgroup order of the named groups and holds private
+/// keys to [1, q - 1], a stricter range than the [2, p - 2] full-size keys
+/// are drawn from. Such a key is correct, so `pkey -check` is skipped for it
+/// ([`CHECK_SKIPPED`]) rather than failing it.
+pub fn verify(record: &KeyRecord) -> Result<Vec<&'static str>, Error> {
+    let params = pem::encode(pem::DH_PARAMETERS_LABEL, &der::dh_parameters(&record.prime, &record.generator));
+    let public = record.public_key_pem();
+    let public_block = pem::encode(
+        pem::PUBLIC_KEY_LABEL,
+        &der::dh_subject_public_key_info(&record.prime, &record.generator, &record.public_key),
+    );
+    let mut private = record.private_key_pem();
+    let mut private_block = pem::encode(
+        pem::PRIVATE_KEY_LABEL,
+        &der::dh_private_key_info(&record.prime, &record.generator, record.private_key.expose()),
+    );
+
+    let checks = (|| {
+        openssl(&["dhparam", "-check", "-noout"], &params, "dhparam -check")?;
+        agree(&["dhparam"], &params, &params, "dhparam re-encoding")?;
+        let below_q = *record.private_key.expose() < (&record.prime - 1u32) >> 1;
+        if below_q {
+            openssl(&["pkey", "-check", "-noout"], &private, "pkey -check")?;
+        }
+        agree(&["pkey"], &private, &private_block, "pkey re-encoding of the private key")?;
+        agree(&["pkey", "-pubout"], &private, &public_block, "pkey -pubout")?;
+        openssl(&["pkey", "-pubin", "-pubcheck", "-noout"], &public, "pkey -pubcheck")?;
+        agree(&["pkey", "-pubin"], &public, &public_block, "pkey re-encoding of the public key")?;
+        Ok(vec![
+            "parameters pass dhparam -check and re-encode identically",
+            if below_q { "private key passes pkey -check and re-encodes identically" } else { CHECK_SKIPPED },
+            "the public key openssl derives from the private key is the same",
+            "public key passes pkey -pubcheck and re-encodes identically",
+        ])
+    })();
+    private.zeroize();
+    private_block.zeroize();
+    checks
+}
+
+fn openssl(args: &[&str], input: &str, step: &str) -> Result<Vec<u8>, Error> {
+    external::run_filter("openssl", args, input.as_bytes())
+        .map_err(|err| Error::Validation(format!("openssl {step} rejected the exported key: {err}")))
+}
+
+/// Runs `openssl` on `input` and requires it to print exactly `expected`.
+fn agree(args: &[&str], input: &str, expected: &str, step: &str) -> Result<(), Error> {
+    let mut output = openssl(args, input, step)?;
+    let same = output == expected.as_bytes();
+    output.zeroize();
+    if !same {
+        return Err(Error::Validation(format!("openssl disagrees with this tool: {step} differs")));
+    }
+    Ok(())
+}
@@ -0,0 +1,665 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use num_bigint::BigUint;
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+use create_private_key::{KeyPair, PrivateKey};
+#[cfg(any(feature = "pem", feature = "json"))]
+use create_private_key_core as ffdh;
+
+#[cfg(any(feature = "pem", feature = "json"))]
+use crate::der;
+#[cfg(any(feature = "pem", feature = "json"))]
+use crate::fingerprint;
+use crate::fingerprint::FingerprintHash;
+use crate::metadata::Metadata;
+#[cfg(feature = "pem")]
+use crate::pbes2::{self, Encryption};
+#[cfg(feature = "pem")]
+use crate::pem;
+
+/// How `--private-format` and `--public-format` write a key.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Hex,
+    Decimal,
+    /// Hex and decimal.
+    Both,
+    /// Base64 of the big-endian bytes.
+    Base64,
+    /// A PKCS #8 or SubjectPublicKeyInfo PEM block, spanning several lines.
+    #[cfg(feature = "pem")]
+    Pem,
+}
+
+/// One way of writing a key value; `OutputFormat::Both` shows two.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Hex,
+    Dec,
+    Base64,
+    #[cfg(feature = "pem")]
+    Pem,
+}
+
+impl OutputFormat {
+    fn encodings(self) -> &'static [Encoding] {
+        match self {
+            OutputFormat::Hex => &[Encoding::Hex],
+            OutputFormat::Decimal => &[Encoding::Dec],
+            OutputFormat::Both => &[Encoding::Hex, Encoding::Dec],
+            OutputFormat::Base64 => &[Encoding::Base64],
+            #[cfg(feature = "pem")]
+            OutputFormat::Pem => &[Encoding::Pem],
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn shows(format: Option<OutputFormat>, encoding: Encoding) -> bool {
+        format.is_some_and(|format| format.encodings().contains(&encoding))
+    }
+}
+
+impl Encoding {
+    /// The label suffix, as in `private_key_hex`.
+    fn suffix(self) -> &'static str {
+        match self {
+            Encoding::Hex => "hex",
+            Encoding::Dec => "dec",
+            Encoding::Base64 => "b64",
+            #[cfg(feature = "pem")]
+            Encoding::Pem => "pem",
+        }
+    }
+
+    fn private_field(self) -> &'static str {
+        match self {
+            Encoding::Hex => "private_key_hex",
+            Encoding::Dec => "private_key_dec",
+            Encoding::Base64 => "private_key_b64",
+            #[cfg(feature = "pem")]
+            Encoding::Pem => "private_key_pem",
+        }
+    }
+
+    fn public_field(self) -> &'static str {
+        match self {
+            Encoding::Hex => "public_key_hex",
+            Encoding::Dec => "public_key_dec",
+            Encoding::Base64 => "public_key_b64",
+            #[cfg(feature = "pem")]
+            Encoding::Pem => "public_key_pem",
+        }
+    }
+
+    /// `value` written this way. PEM encodes the whole key, not the number.
+    fn number(self, value: &BigUint) -> String {
+        match self {
+            Encoding::Hex => to_even_length_hex(value),
+            Encoding::Dec => value.to_str_radix(10),
+            Encoding::Base64 => BASE64.encode(value.to_bytes_be()),
+            #[cfg(feature = "pem")]
+            Encoding::Pem => unreachable!("PEM blocks come from the key record"),
+        }
+    }
+}
+
+/// Which bare values `--quiet` prints.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PrintTarget {
+    Private,
+    Public,
+    Both,
+}
+
+/// Label overrides for the plain `key=value` output.
+pub struct Labels {
+    pub prefix: String,
+    pub private: Option<String>,
+    pub public: Option<String>,
+}
+
+impl Labels {
+    fn label(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    fn private_label(&self, suffix: &str, single: bool) -> String {
+        self.key_label(self.private.as_deref(), "private_key", suffix, single)
+    }
+
+    fn public_label(&self, suffix: &str, single: bool) -> String {
+        self.key_label(self.public.as_deref(), "public_key", suffix, single)
+    }
+
+    /// An override names the only encoding as is, and several with a suffix.
+    fn key_label(&self, name: Option<&str>, default: &str, suffix: &str, single: bool) -> String {
+        match name {
+            Some(name) if single => self.label(name),
+            Some(name) => self.label(&format!("{name}_{suffix}")),
+            None => self.label(&format!("{default}_{suffix}")),
+        }
+    }
+}
+
+/// Key material and metadata read back from an envelope, a key file or the
+/// keystore.
+#[cfg(any(feature = "pem", feature = "json"))]
+pub struct Imported {
+    pub prime: BigUint,
+    pub generator: BigUint,
+    pub private_key: BigUint,
+    pub metadata: Option<Metadata>,
+}
+
+/// Everything produced by a single generation run, ready to be rendered.
+pub struct KeyRecord {
+    pub prime: BigUint,
+    pub generator: BigUint,
+    pub private_key: PrivateKey,
+    pub public_key: BigUint,
+    pub fingerprint: String,
+    pub key_id: String,
+    pub metadata: Option<Metadata>,
+    /// Entropy sources mixed into the private key, when `--hw-entropy` or a
+    /// seed input added any beyond the OS; empty otherwise (and for imported
+    /// keys).
+    pub entropy_sources: Vec<&'static str>,
+    /// The signed provenance document, when `--provenance-key` is given.
+    pub provenance: Option<String>,
+}
+
+impl KeyRecord {
+    /// Derives the public key, fingerprint and key ID for a private key.
+    #[cfg(any(feature = "pem", feature = "json"))]
+    pub fn new(
+        prime: BigUint,
+        generator: BigUint,
+        private_key: BigUint,
+        metadata: Option<Metadata>,
+        fingerprint_hash: FingerprintHash,
+    ) -> Self {
+        let public_key = ffdh::public_key(&prime, &generator, &private_key);
+        let spki = der::dh_subject_public_key_info(&prime, &generator, &public_key);
+        KeyRecord {
+            fingerprint: fingerprint::fingerprint(fingerprint_hash, &spki),
+            key_id: fingerprint::key_id(&spki),
+            metadata,
+            prime,
+            generator,
+            private_key: PrivateKey::new(private_key),
+            public_key,
+            entropy_sources: Vec::new(),
+            provenance: None,
+        }
+    }
+
+    /// Wraps a key pair from the library with its fingerprint and key ID.
+    pub fn from_key_pair(pair: KeyPair, metadata: Option<Metadata>, fingerprint_hash: FingerprintHash) -> Self {
+        let fingerprint = pair.fingerprint(fingerprint_hash);
+        let key_id = pair.key_id();
+        let (params, private_key, public_key) = pair.into_parts();
+        let (prime, generator) = params.into_parts();
+        KeyRecord {
+            prime,
+            generator,
+            private_key,
+            public_key,
+            fingerprint,
+            key_id,
+            metadata,
+            entropy_sources: Vec::new(),
+            provenance: None,
+        }
+    }
+
+    /// Named values exposed to templates, in a stable order.
+    pub fn variables(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("prime_bits", self.prime.bits().to_string()),
+            ("prime_hex", to_even_length_hex(&self.prime)),
+            ("generator", self.generator.to_string()),
+            ("private_key_hex", to_even_length_hex(self.private_key.expose())),
+            ("private_key_dec", self.private_key.expose().to_str_radix(10)),
+            ("private_key_b64", BASE64.encode(self.private_key.expose().to_bytes_be())),
+            ("public_key_hex", to_even_length_hex(&self.public_key)),
+            ("public_key_dec", self.public_key.to_str_radix(10)),
+            ("public_key_b64", BASE64.encode(self.public_key.to_bytes_be())),
+            ("fingerprint", self.fingerprint.clone()),
+            ("key_id", self.key_id.clone()),
+            ("entropy_sources", self.entropy_sources.join(",")),
+            ("created", self.metadata_field(|m| Some(m.created.clone()))),
+            ("tool_version", self.metadata_field(|m| Some(m.tool_version.clone()))),
+            ("group", self.metadata_field(|m| Some(m.group.clone()))),
+            ("label", self.metadata_field(|m| m.label.clone())),
+            ("operator", self.metadata_field(|m| m.operator.clone())),
+            ("not_after", self.metadata_field(|m| m.not_after.clone())),
+            ("tags", self.metadata_field(|m| Some(m.tag_list()))),
+        ]
+    }
+
+    fn private_encoded(&self, encoding: Encoding) -> String {
+        match encoding {
+            #[cfg(feature = "pem")]
+            Encoding::Pem => self.private_key_pem(),
+            _ => encoding.number(self.private_key.expose()),
+        }
+    }
+
+    fn public_encoded(&self, encoding: Encoding) -> String {
+        match encoding {
+            #[cfg(feature = "pem")]
+            Encoding::Pem => self.public_key_pem(),
+            _ => encoding.number(&self.public_key),
+        }
+    }
+
+    fn metadata_field(&self, field: impl Fn(&Metadata) -> Option<String>) -> String {
+        self.metadata.as_ref().and_then(field).unwrap_or_default()
+    }
+
+    /// PKCS #8 PEM encoding of the record's private key.
+    #[cfg(feature = "pem")]
+    pub fn private_key_pem(&self) -> String {
+        let der = der::dh_private_key_info(&self.prime, &self.generator, self.private_key.expose());
+        self.with_metadata(pem::encode(pem::PRIVATE_KEY_LABEL, &der))
+    }
+
+    /// PBES2-encrypted PKCS #8 PEM encoding of the record's private key.
+    #[cfg(feature = "pem")]
+    pub fn encrypted_private_key_pem(&self, encryption: &Encryption) -> Result<String, String> {
+        let der = der::dh_private_key_info(&self.prime, &self.generator, self.private_key.expose());
+        let encrypted = pbes2::encrypt(&der, encryption.passphrase.as_bytes(), encryption.kdf)?;
+        Ok(self.with_metadata(pem::encode(pem::ENCRYPTED_PRIVATE_KEY_LABEL, &encrypted)))
+    }
+
+    /// `SubjectPublicKeyInfo` PEM encoding of the record's public key.
+    #[cfg(feature = "pem")]
+    pub fn public_key_pem(&self) -> String {
+        let der = der::dh_subject_public_key_info(&self.prime, &self.generator, &self.public_key);
+        self.with_metadata(pem::encode(pem::PUBLIC_KEY_LABEL, &der))
+    }
+
+    /// Prefixes the block with `Name: value` lines. RFC 7468 lets parsers skip
+    /// text outside the encapsulation boundaries, so OpenSSL and friends still
+    /// load the key.
+    #[cfg(feature = "pem")]
+    fn with_metadata(&self, block: String) -> String {
+        let Some(ref metadata) = self.metadata else {
+            return block;
+        };
+        let mut pem = format!("Key-Id: {}\n", self.key_id);
+        for (name, value) in metadata.entries() {
+            pem.push_str(&format!("{name}: {value}\n"));
+        }
+        pem.push_str(&block);
+        pem
+    }
+}
+
+/// Hex digits per group in pretty output.
+const PRETTY_GROUP_CHARS: usize = 8;
+/// Groups per line in pretty output before wrapping.
+const PRETTY_GROUPS_PER_LINE: usize = 8;
+
+const ANSI_LABEL: &str = "\x1b[36m";
+const ANSI_SECRET: &str = "\x1b[1;33m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// One labelled line of the default output.
+struct Field {
+    /// The field's default label, whatever `labels` renamed it to.
+    name: &'static str,
+    label: String,
+    value: String,
+    /// Value is a long hex string that pretty output may group.
+    hex: bool,
+    /// Value is private key material.
+    secret: bool,
+    /// Value is a further encoding of the field before it.
+    repeat: bool,
+}
+
+impl Field {
+    fn new(name: &'static str, label: String, value: String) -> Self {
+        Field {
+            name,
+            label,
+            value,
+            hex: false,
+            secret: false,
+            repeat: false,
+        }
+    }
+
+    /// `label=value`; a PEM block starts on the line after `label=`.
+    fn line(&self) -> String {
+        match self.value.strip_suffix('\n') {
+            Some(block) => format!("{}=\n{block}\n", self.label),
+            None => format!("{}={}\n", self.label, self.value),
+        }
+    }
+
+    /// A field under its default name, with the labels' prefix.
+    fn labeled(labels: &Labels, name: &'static str, value: String) -> Self {
+        Field::new(name, labels.label(name), value)
+    }
+
+    /// Marks a key value as the `index`th encoding `encoding` of its key.
+    fn encoded(mut self, encoding: Encoding, index: usize) -> Self {
+        self.hex = encoding == Encoding::Hex;
+        self.repeat = index > 0;
+        self
+    }
+
+    fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+}
+
+/// `private` selects how the private key is shown; `None` leaves it out.
+fn fields(record: &KeyRecord, private: Option<OutputFormat>, public: OutputFormat, labels: &Labels) -> Vec<Field> {
+    let mut fields = vec![
+        Field::labeled(labels, "prime_bits", record.prime.bits().to_string()),
+        Field::labeled(labels, "generator", record.generator.to_string()),
+    ];
+
+    if let Some(private) = private {
+        let single = private.encodings().len() == 1;
+        for (index, &encoding) in private.encodings().iter().enumerate() {
+            let label = labels.private_label(encoding.suffix(), single);
+            let value = record.private_encoded(encoding);
+            fields.push(Field::new(encoding.private_field(), label, value).encoded(encoding, index).secret());
+        }
+    }
+    let single = public.encodings().len() == 1;
+    for (index, &encoding) in public.encodings().iter().enumerate() {
+        let label = labels.public_label(encoding.suffix(), single);
+        let value = record.public_encoded(encoding);
+        fields.push(Field::new(encoding.public_field(), label, value).encoded(encoding, index));
+    }
+    fields.push(Field::labeled(labels, "public_key_fingerprint", record.fingerprint.clone()));
+    fields.push(Field::labeled(labels, "key_id", record.key_id.clone()));
+    if !record.entropy_sources.is_empty() {
+        fields.push(Field::labeled(labels, "entropy_sources", record.entropy_sources.join(",")));
+    }
+    if let Some(ref document) = record.provenance {
+        fields.push(Field::labeled(labels, "provenance", BASE64.encode(document)));
+    }
+    fields
+}
+
+/// Renders the default `key=value` lines.
+pub fn render_plain(
+    record: &KeyRecord,
+    private: Option<OutputFormat>,
+    public: OutputFormat,
+    labels: &Labels,
+) -> String {
+    let mut rendered = String::new();
+    for field in fields(record, private, public, labels) {
+        rendered.push_str(&field.line());
+    }
+    rendered
+}
+
+/// The default `key=value` lines, each after the `# ` comment lines that
+/// `notes` gives for its field's default name and whether it repeats the
+/// value before it in another encoding.
+pub fn render_explained(
+    record: &KeyRecord,
+    private: Option<OutputFormat>,
+    public: OutputFormat,
+    labels: &Labels,
+    notes: impl Fn(&'static str, bool) -> Vec<String>,
+) -> String {
+    let mut rendered = String::new();
+    for field in fields(record, private, public, labels) {
+        for note in notes(field.name, field.repeat) {
+            match note.as_str() {
+                "" => rendered.push_str("#\n"),
+                note => rendered.push_str(&format!("# {note}\n")),
+            }
+        }
+        rendered.push_str(&field.line());
+    }
+    rendered
+}
+
+/// Field layout shared by the JSON and MessagePack encodings.
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct SerialRecord<'a> {
+    key_id: &'a str,
+    fingerprint: &'a str,
+    prime_bits: u64,
+    prime_hex: String,
+    generator: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key_hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key_dec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key_b64: Option<String>,
+    #[cfg(feature = "pem")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key_pem: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key_hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key_dec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key_b64: Option<String>,
+    #[cfg(feature = "pem")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key_pem: Option<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    entropy_sources: &'a [&'static str],
+    /// Base64 of the provenance document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a Metadata>,
+}
+
+/// `private` selects the private key encodings; `None` leaves it out.
+#[cfg(feature = "json")]
+fn serial_record(record: &KeyRecord, private: Option<OutputFormat>, public: OutputFormat) -> SerialRecord<'_> {
+    let private_as =
+        |encoding| OutputFormat::shows(private, encoding).then(|| record.private_encoded(encoding));
+    let public_as = |encoding| OutputFormat::shows(Some(public), encoding).then(|| record.public_encoded(encoding));
+    SerialRecord {
+        key_id: &record.key_id,
+        fingerprint: &record.fingerprint,
+        prime_bits: record.prime.bits(),
+        prime_hex: to_even_length_hex(&record.prime),
+        generator: record.generator.to_string(),
+        private_key_hex: private_as(Encoding::Hex),
+        private_key_dec: private_as(Encoding::Dec),
+        private_key_b64: private_as(Encoding::Base64),
+        #[cfg(feature = "pem")]
+        private_key_pem: private_as(Encoding::Pem),
+        public_key_hex: public_as(Encoding::Hex),
+        public_key_dec: public_as(Encoding::Dec),
+        public_key_b64: public_as(Encoding::Base64),
+        #[cfg(feature = "pem")]
+        public_key_pem: public_as(Encoding::Pem),
+        entropy_sources: &record.entropy_sources,
+        provenance: record.provenance.as_ref().map(|document| BASE64.encode(document)),
+        metadata: record.metadata.as_ref(),
+    }
+}
+
+/// Renders the record as a single-line JSON object (JSON Lines friendly).
+#[cfg(feature = "json")]
+pub fn render_json(record: &KeyRecord, private: Option<OutputFormat>, public: OutputFormat) -> String {
+    let mut rendered = serde_json::to_string(&serial_record(record, private, public)).expect("key record serializes");
+    rendered.push('\n');
+    rendered
+}
+
+/// Encodes the record as a MessagePack map with the same fields as the JSON
+/// output. MessagePack values are self-delimiting, so batches simply
+/// concatenate.
+#[cfg(feature = "json")]
+pub fn render_msgpack(record: &KeyRecord, private: Option<OutputFormat>, public: OutputFormat) -> Vec<u8> {
+    rmp_serde::to_vec_named(&serial_record(record, private, public)).expect("key record serializes")
+}
+
+/// Renders only the bare key values, one per line, for shell capture.
+pub fn render_quiet(record: &KeyRecord, private: OutputFormat, public: OutputFormat, target: PrintTarget) -> String {
+    let mut values = Vec::new();
+    if matches!(target, PrintTarget::Private | PrintTarget::Both) {
+        values.extend(private.encodings().iter().map(|&encoding| record.private_encoded(encoding)));
+    }
+    if matches!(target, PrintTarget::Public | PrintTarget::Both) {
+        values.extend(public.encodings().iter().map(|&encoding| record.public_encoded(encoding)));
+    }
+
+    let mut rendered = String::new();
+    for value in values {
+        // PEM blocks bring their own final newline.
+        rendered.push_str(value.trim_end_matches('\n'));
+        rendered.push('\n');
+    }
+    rendered
+}
+
+/// Renders the human-oriented layout: aligned labels, hex values split into
+/// groups and wrapped, and optional ANSI color.
+pub fn render_pretty(
+    record: &KeyRecord,
+    private: Option<OutputFormat>,
+    public: OutputFormat,
+    labels: &Labels,
+    color: bool,
+) -> String {
+    let fields = fields(record, private, public, labels);
+    let width = fields.iter().map(|field| field.label.len()).max().unwrap_or(0);
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("{code}{text}{ANSI_RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut rendered = String::new();
+    for field in fields {
+        let value_lines = if field.hex {
+            group_hex(&field.value)
+        } else {
+            field.value.lines().map(str::to_string).collect()
+        };
+        for (index, line) in value_lines.iter().enumerate() {
+            let label = if index == 0 { field.label.as_str() } else { "" };
+            let value = if field.secret { paint(ANSI_SECRET, line) } else { line.clone() };
+            rendered.push_str(&format!("{}  {value}\n", paint(ANSI_LABEL, &format!("{label:<width$}"))));
+        }
+    }
+    rendered
+}
+
+fn group_hex(hex: &str) -> Vec<String> {
+    let groups: Vec<&str> = hex
+        .as_bytes()
+        .chunks(PRETTY_GROUP_CHARS)
+        .map(|group| std::str::from_utf8(group).expect("hex is ASCII"))
+        .collect();
+    groups
+        .chunks(PRETTY_GROUPS_PER_LINE)
+        .map(|line| line.join(" "))
+        .collect()
+}
+
+/// Substitutes `{{name}}` placeholders with record variables.
+///
+/// This is substitution only, not a template language: there are no
+/// conditionals, loops, filters or escaping, and values are inserted as they
+/// are, never rescanned. Whitespace inside the braces is ignored; unknown
+/// names and unterminated placeholders are reported as errors rather than
+/// rendered verbatim. A lone `{` or `}}` outside a placeholder is literal.
+pub fn render_template(template: &str, record: &KeyRecord) -> Result<String, String> {
+    let variables = record.variables();
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let end = after_open
+            .find("}}")
+            .ok_or_else(|| "unterminated '{{' in template".to_string())?;
+        let name = after_open[..end].trim();
+        let value = variables
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("unknown template variable '{name}'"))?;
+        rendered.push_str(value);
+        rest = &after_open[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+pub fn to_even_length_hex(value: &BigUint) -> String {
+    let hex = format!("{value:X}");
+    if hex.len() % 2 == 0 {
+        hex
+    } else {
+        format!("0{hex}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use create_private_key::DhParams;
+    use create_private_key::params::DhGroup;
+
+    use super::*;
+
+    fn record(label: Option<&str>) -> KeyRecord {
+        let pair = KeyPair::from_private_key(DhParams::group(DhGroup::Modp14), BigUint::from(12345u32)).unwrap();
+        let metadata = Metadata::new("modp14", label.map(str::to_string), Some("ops".into()));
+        KeyRecord::from_key_pair(pair, Some(metadata), FingerprintHash::Sha256)
+    }
+
+    #[test]
+    fn template_substitutes_variables() {
+        let record = record(Some("vpn"));
+        let rendered = render_template("id={{key_id}} label={{ label }} bits={{prime_bits}}\n", &record).unwrap();
+        assert_eq!(rendered, format!("id={} label=vpn bits=2048\n", record.key_id));
+    }
+
+    #[test]
+    fn template_without_placeholders_is_unchanged() {
+        let text = "no placeholders { here } or }} there\n";
+        assert_eq!(render_template(text, &record(None)).unwrap(), text);
+    }
+
+    #[test]
+    fn template_values_are_not_rescanned() {
+        let rendered = render_template("{{label}}", &record(Some("{{key_id}}"))).unwrap();
+        assert_eq!(rendered, "{{key_id}}");
+    }
+
+    #[test]
+    fn template_rejects_unknown_variables() {
+        let err = render_template("a {{nope}} b", &record(None)).unwrap_err();
+        assert_eq!(err, "unknown template variable 'nope'");
+        let err = render_template("{{}}", &record(None)).unwrap_err();
+        assert_eq!(err, "unknown template variable ''");
+    }
+
+    #[test]
+    fn template_rejects_unterminated_placeholders() {
+        for text in ["{{key_id", "ok {{key_id}} then {{", "{{key_id}"] {
+            assert_eq!(render_template(text, &record(None)).unwrap_err(), "unterminated '{{' in template");
+        }
+    }
+}
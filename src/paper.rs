@@ -0,0 +1,157 @@
+use data_encoding::BASE32_NOPAD;
+
+use crate::output::KeyRecord;
+
+/// Key bytes per printed line; a multiple of 5 so every line is whole base32.
+const BYTES_PER_LINE: usize = 20;
+/// Base32 characters per visual group.
+const GROUP_CHARS: usize = 4;
+
+/// Renders the private key as a printable backup sheet.
+///
+/// The key is left-padded to the byte length of the prime and printed as
+/// base32 in numbered lines. Each line carries a CRC-16/CCITT over its line
+/// number and bytes, so a transcription error (or a swapped line) is pinned
+/// to one line; a CRC-32 over the whole key closes the sheet.
+pub fn render(record: &KeyRecord) -> String {
+    let key_len = record.prime.to_bytes_be().len();
+    let mut key = record.private_key.expose().to_bytes_be();
+    if key.len() < key_len {
+        let mut padded = vec![0u8; key_len - key.len()];
+        padded.extend_from_slice(&key);
+        key = padded;
+    }
+
+    let mut lines = vec![
+        "# create-private-key paper backup".to_string(),
+        format!("key_id={}", record.key_id),
+        format!("prime_bits={}", record.prime.bits()),
+        format!("generator={}", record.generator),
+        format!("public_key_fingerprint={}", record.fingerprint),
+        format!("key_bytes={}", key.len()),
+        "# line  data  crc16".to_string(),
+    ];
+
+    for (index, chunk) in key.chunks(BYTES_PER_LINE).enumerate() {
+        let line_number = index + 1;
+        let encoded = BASE32_NOPAD.encode(chunk);
+        let grouped = encoded
+            .as_bytes()
+            .chunks(GROUP_CHARS)
+            .map(|group| std::str::from_utf8(group).expect("base32 is ASCII"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut checked = vec![line_number as u8];
+        checked.extend_from_slice(chunk);
+        lines.push(format!("{line_number:02}  {grouped:<39}  {:04X}", crc16_ccitt(&checked)));
+    }
+
+    lines.push(format!("total_crc32={:08X}", crc32(&key)));
+
+    let mut rendered = lines.join("\n");
+    rendered.push('\n');
+    rendered
+}
+
+/// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF).
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// CRC-32 (IEEE 802.3, reflected poly 0xEDB88320).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use create_private_key::params::DhGroup;
+    use create_private_key::{DhParams, FingerprintHash, KeyPair};
+    use num_bigint::BigUint;
+
+    use super::*;
+
+    fn record(private_key: BigUint) -> KeyRecord {
+        let pair = KeyPair::from_private_key(DhParams::group(DhGroup::Modp14), private_key).unwrap();
+        KeyRecord::from_key_pair(pair, None, FingerprintHash::Sha256)
+    }
+
+    /// Reads the data lines back, checking each line's CRC-16 the way a
+    /// person restoring the sheet would; `Err` names the first bad line.
+    fn restore(sheet: &str) -> Result<Vec<u8>, usize> {
+        let mut key = Vec::new();
+        for line in sheet.lines().filter(|line| line.starts_with(|c: char| c.is_ascii_digit())) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (number, groups, crc) = (fields[0], &fields[1..fields.len() - 1], fields[fields.len() - 1]);
+            let number: usize = number.parse().unwrap();
+            let bytes = BASE32_NOPAD.decode(groups.concat().as_bytes()).map_err(|_| number)?;
+            let mut checked = vec![number as u8];
+            checked.extend_from_slice(&bytes);
+            if format!("{:04X}", crc16_ccitt(&checked)) != crc {
+                return Err(number);
+            }
+            key.extend_from_slice(&bytes);
+        }
+        let total = sheet.lines().find_map(|line| line.strip_prefix("total_crc32=")).unwrap();
+        assert_eq!(total, format!("{:08X}", crc32(&key)));
+        Ok(key)
+    }
+
+    #[test]
+    fn checksums_match_their_catalogue_values() {
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn sheet_restores_the_padded_key() {
+        // A short key is left-padded to the 256 bytes of the 2048-bit prime.
+        let private_key = BigUint::from_bytes_be(&[0xA5; 40]);
+        let sheet = render(&record(private_key.clone()));
+        assert!(sheet.contains("\nkey_bytes=256\n"));
+        let key = restore(&sheet).unwrap();
+        assert_eq!(key.len(), 256);
+        assert!(key[..216].iter().all(|&byte| byte == 0));
+        assert_eq!(BigUint::from_bytes_be(&key), private_key);
+        // 256 bytes at 20 per line: 12 full lines and a short 13th.
+        assert_eq!(sheet.lines().filter(|line| line.starts_with(|c: char| c.is_ascii_digit())).count(), 13);
+    }
+
+    #[test]
+    fn flipped_character_is_caught_by_its_line() {
+        let sheet = render(&record(BigUint::from_bytes_be(&[0x5A; 200])));
+        let mut lines: Vec<String> = sheet.lines().map(str::to_string).collect();
+        let line = lines.iter().position(|line| line.starts_with("05  ")).unwrap();
+        let flipped = if lines[line].as_bytes()[4] == b'A' { "B" } else { "A" };
+        lines[line].replace_range(4..5, flipped);
+        assert_eq!(restore(&lines.join("\n")), Err(5));
+    }
+
+    #[test]
+    fn swapped_lines_are_caught() {
+        let sheet = render(&record(BigUint::from_bytes_be(&[0x33; 200])));
+        let mut lines: Vec<String> = sheet.lines().map(str::to_string).collect();
+        let first = lines.iter().position(|line| line.starts_with("03  ")).unwrap();
+        // Swap the data and CRC of lines 3 and 4 but keep their numbers:
+        // identical bytes on both lines still fail, as the number is checked.
+        let (three, four) = (lines[first][2..].to_string(), lines[first + 1][2..].to_string());
+        lines[first].replace_range(2.., &four);
+        lines[first + 1].replace_range(2.., &three);
+        assert_eq!(restore(&lines.join("\n")), Err(3));
+    }
+}
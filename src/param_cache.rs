@@ -0,0 +1,146 @@
+//! `--param-cache`: the Miller-Rabin verdicts on custom parameters, kept
+//! between runs so the same prime is not tested again every time.
+//!
+//! One file per parameter set, named by a SHA-256 of `(p, g, q)`, holds the
+//! verdict, how many rounds backed it and where the bases came from. A
+//! verdict only stands in for a test at most as thorough as the one cached.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use create_private_key::Error;
+use data_encoding::HEXLOWER;
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+use crate::primality::{self, Bases};
+use crate::random;
+
+/// Set once at startup when `--param-cache` is given.
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// `$XDG_CACHE_HOME/create-private-key/params`, falling back to `~/.cache`
+/// (or `%LOCALAPPDATA%` on Windows).
+pub fn default_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("create-private-key").join("params")
+}
+
+/// Turns the cache on for the rest of the process, creating `dir` (mode
+/// 0700: a planted verdict would vouch for a composite).
+pub fn enable(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .map_err(|err| format!("failed to restrict {}: {err}", dir.display()))?;
+    }
+    tracing::debug!(dir = %dir.display(), "caching parameter checks");
+    let _ = DIR.set(dir.to_path_buf());
+    Ok(())
+}
+
+/// A safe-prime verdict, and whether it came from the cache.
+pub struct Verdict {
+    pub safe: bool,
+    pub cached: bool,
+}
+
+/// [`primality::is_safe_prime`] on `prime`, the parameters' `q` being
+/// `(p - 1) / 2`, answered from the cache when it can be.
+///
+/// "Composite" is final whatever the test, since a witness proves it. "Safe"
+/// is reused for at most as many rounds, and bases derived from `p` never
+/// stand in for random ones.
+pub fn is_safe_prime(prime: &BigUint, generator: &BigUint, rounds: u32, bases: Bases) -> Result<Verdict, Error> {
+    let Some(dir) = DIR.get() else {
+        return Ok(Verdict {
+            safe: primality::is_safe_prime(prime, rounds, bases)?,
+            cached: false,
+        });
+    };
+    let path = dir.join(key(prime, generator));
+    if let Some(entry) = std::fs::read_to_string(&path).ok().as_deref().and_then(Entry::parse) {
+        let bases_good_enough = entry.random || matches!(bases, Bases::Derived);
+        if !entry.safe || (entry.rounds >= rounds && bases_good_enough) {
+            tracing::debug!(path = %path.display(), safe = entry.safe, "safe-prime verdict from the cache");
+            return Ok(Verdict {
+                safe: entry.safe,
+                cached: true,
+            });
+        }
+    }
+    let safe = primality::is_safe_prime(prime, rounds, bases)?;
+    let entry = Entry {
+        safe,
+        rounds,
+        random: matches!(bases, Bases::Random),
+    };
+    // A cache that cannot be written only costs the next run the test.
+    if let Err(err) = write(&path, &entry.render()) {
+        tracing::warn!(path = %path.display(), error = %err, "failed to cache the safe-prime verdict");
+    }
+    Ok(Verdict { safe, cached: false })
+}
+
+/// The file name for `(p, g, q)`: each number length-prefixed, so no two
+/// parameter sets hash the same input.
+fn key(prime: &BigUint, generator: &BigUint) -> String {
+    let order: BigUint = (prime - 1u32) >> 1;
+    let mut hasher = Sha256::new();
+    hasher.update(b"create-private-key param-cache v1");
+    for number in [prime, generator, &order] {
+        let bytes = number.to_bytes_be();
+        hasher.update((bytes.len() as u64).to_be_bytes());
+        hasher.update(&bytes);
+    }
+    HEXLOWER.encode(&hasher.finalize())
+}
+
+struct Entry {
+    safe: bool,
+    rounds: u32,
+    /// Bases drawn from the RNG rather than derived from `p`.
+    random: bool,
+}
+
+impl Entry {
+    fn parse(text: &str) -> Option<Entry> {
+        let (mut safe, mut rounds, mut random) = (None, None, None);
+        for line in text.lines() {
+            match line.split_once('=')? {
+                ("safe_prime", value) => safe = value.parse().ok(),
+                ("rounds", value) => rounds = value.parse().ok(),
+                ("bases", "random") => random = Some(true),
+                ("bases", "derived") => random = Some(false),
+                _ => return None,
+            }
+        }
+        Some(Entry {
+            safe: safe?,
+            rounds: rounds?,
+            random: random?,
+        })
+    }
+
+    fn render(&self) -> String {
+        let bases = if self.random { "random" } else { "derived" };
+        format!("safe_prime={}\nrounds={}\nbases={bases}\n", self.safe, self.rounds)
+    }
+}
+
+/// Writes beside `path` and renames into place, so a concurrent run never
+/// reads half an entry.
+fn write(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut suffix = [0u8; 8];
+    random::fill(&mut suffix).map_err(std::io::Error::other)?;
+    let temp = path.with_extension(format!("{:016x}.tmp", u64::from_be_bytes(suffix)));
+    std::fs::write(&temp, contents).and_then(|()| std::fs::rename(&temp, path)).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })
+}
@@ -0,0 +1,117 @@
+//! `diff-params`: whether two parameter sets are the same group, and which
+//! published group each one is.
+
+use std::path::Path;
+
+use create_private_key::{Error, der, params, pem};
+use num_bigint::BigUint;
+use num_traits::One;
+
+use crate::known_groups::KnownGroup;
+use crate::param_cache;
+use crate::primality::Bases;
+
+/// One side of the comparison.
+pub struct Side {
+    pub prime: BigUint,
+    pub generator: BigUint,
+    /// `q` as the input states it: X9.42 parameters and published groups.
+    stated_order: Option<BigUint>,
+}
+
+impl Side {
+    /// Reads a published group's name, `0xPRIME[:GENERATOR]` (generator 2 by
+    /// default), or a PEM or DER parameters file (`-` for stdin).
+    pub fn read(spec: &str) -> Result<Side, Error> {
+        let side = if let Some(group) = KnownGroup::by_name(spec) {
+            Side {
+                prime: group.prime(),
+                generator: group.generator(),
+                stated_order: Some(group.order()),
+            }
+        } else if spec.starts_with("0x") || spec.starts_with("0X") {
+            let (prime, generator) = spec.split_once(':').unwrap_or((spec, "2"));
+            Side {
+                prime: params::parse_biguint(prime)?,
+                generator: params::parse_biguint(generator)?,
+                stated_order: None,
+            }
+        } else {
+            let bytes = crate::read_input_bytes(Path::new(spec))?;
+            let der = match std::str::from_utf8(&bytes) {
+                Ok(text) if text.contains("-----BEGIN ") => pem::decode(pem::DH_PARAMETERS_LABEL, text)
+                    .or_else(|_| pem::decode(pem::X942_DH_PARAMETERS_LABEL, text))
+                    .map_err(|_| Error::Parse(format!("{spec} holds no DH PARAMETERS or X9.42 DH PARAMETERS block")))?,
+                _ => bytes,
+            };
+            let (prime, generator, stated_order) =
+                der::parse_dh_parameters(&der).map_err(|err| Error::Parse(format!("{spec}: {err}")))?;
+            Side {
+                prime,
+                generator,
+                stated_order,
+            }
+        };
+        params::validate(&side.prime, &side.generator).map_err(|err| match err {
+            Error::Validation(message) => Error::Validation(format!("{spec}: {message}")),
+            err => err,
+        })?;
+        Ok(side)
+    }
+
+    /// Which published group this is, e.g. `ffdhe2048 (RFC 7919)`.
+    pub fn identify(&self) -> String {
+        match KnownGroup::by_prime(&self.prime) {
+            Some(group) if group.generator() == self.generator => format!("{} ({})", group.name, group.source),
+            Some(group) => format!("{}'s prime ({}) with another generator", group.name, group.source),
+            None => "none".into(),
+        }
+    }
+
+    /// The order of the generator, when it can be worked out: the stated or
+    /// published `q` if `g^q = 1`, and for a safe prime (`rounds` of
+    /// Miller-Rabin) `q` or `2q`.
+    pub fn generator_order(&self, rounds: u32) -> Result<Option<BigUint>, Error> {
+        let (q, safe) = match (&self.stated_order, KnownGroup::by_prime(&self.prime)) {
+            (Some(q), _) => (q.clone(), (&self.prime - 1u32) >> 1 == *q),
+            (None, Some(group)) => (group.order(), group.order_hex.is_none()),
+            (None, None) => {
+                // Derived bases keep this deterministic and off the RNG.
+                if !param_cache::is_safe_prime(&self.prime, &self.generator, rounds, Bases::Derived)?.safe {
+                    return Ok(None);
+                }
+                ((&self.prime - 1u32) >> 1, true)
+            }
+        };
+        Ok(if self.generator.modpow(&q, &self.prime).is_one() {
+            Some(q)
+        } else if !safe {
+            None
+        } else if self.generator == &self.prime - 1u32 {
+            Some(BigUint::from(2u32))
+        } else {
+            Some(q << 1)
+        })
+    }
+}
+
+/// `same`, or how two numbers differ: their sizes and the first byte (of
+/// the big-endian encoding, left-padded to the same length) that differs.
+pub fn compare(a: &BigUint, b: &BigUint) -> String {
+    if a == b {
+        return "same".into();
+    }
+    let (a_bytes, b_bytes) = (a.to_bytes_be(), b.to_bytes_be());
+    let len = a_bytes.len().max(b_bytes.len());
+    let pad = |bytes: Vec<u8>| [vec![0; len - bytes.len()], bytes].concat();
+    let first = pad(a_bytes)
+        .iter()
+        .zip(&pad(b_bytes))
+        .position(|(x, y)| x != y)
+        .expect("different numbers differ in some byte");
+    format!(
+        "differs ({} and {} bits, first difference at byte {first} of {len})",
+        a.bits(),
+        b.bits()
+    )
+}
@@ -0,0 +1,213 @@
+//! Diffie-Hellman group parameters: built-in groups, parsing, and validation.
+
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use clap::ValueEnum;
+use create_private_key_core::{self as ffdh, RFC3526_MODP14_PRIME_HEX};
+use num_bigint::BigUint;
+
+use crate::error::{Error, Result};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DhGroup {
+    /// RFC 3526 MODP group 14 (2048-bit safe prime, generator 2).
+    #[default]
+    Modp14,
+}
+
+impl DhGroup {
+    pub fn default_prime_hex(self) -> &'static str {
+        match self {
+            DhGroup::Modp14 => RFC3526_MODP14_PRIME_HEX,
+        }
+    }
+
+    /// The group's prime, built once per process from limbs the compiler
+    /// parsed, so long-running modes don't parse hex for every request.
+    pub fn prime(self) -> &'static BigUint {
+        static MODP14: LazyLock<BigUint> = LazyLock::new(|| ffdh::RFC3526_MODP14.modulus());
+        match self {
+            DhGroup::Modp14 => &MODP14,
+        }
+    }
+
+    pub fn default_generator(self) -> &'static str {
+        match self {
+            DhGroup::Modp14 => "2",
+        }
+    }
+
+    /// Name used in metadata and listings, e.g. `modp14`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DhGroup::Modp14 => "modp14",
+        }
+    }
+
+    /// The group called `name`, as accepted by `--group`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        DhGroup::value_variants().iter().copied().find(|group| group.name() == name)
+    }
+}
+
+/// The largest prime accepted unless [`set_max_prime_bits`] says otherwise:
+/// eight times the largest published group, and small enough that modpow
+/// with it still finishes.
+pub const DEFAULT_MAX_PRIME_BITS: u64 = 65536;
+
+static MAX_PRIME_BITS: AtomicU64 = AtomicU64::new(DEFAULT_MAX_PRIME_BITS);
+
+/// Sets the size limit on primes, and with it on every number parsed, for
+/// the rest of the process (`--max-prime-bits`).
+pub fn set_max_prime_bits(bits: u64) {
+    MAX_PRIME_BITS.store(bits, Ordering::Relaxed);
+}
+
+pub fn max_prime_bits() -> u64 {
+    MAX_PRIME_BITS.load(Ordering::Relaxed)
+}
+
+/// Picks the prime and generator from explicit values or the group defaults,
+/// then validates them.
+pub fn resolve(group: DhGroup, prime: Option<&str>, generator: Option<&str>) -> Result<(BigUint, BigUint)> {
+    let (prime, generator) = parse(group, prime, generator)?;
+    validate(&prime, &generator)?;
+    Ok((prime, generator))
+}
+
+/// Like [`resolve`], without validating the result.
+pub fn parse(group: DhGroup, prime: Option<&str>, generator: Option<&str>) -> Result<(BigUint, BigUint)> {
+    let prime = match prime {
+        Some(prime_str) => parse_biguint(prime_str)?,
+        None => {
+            tracing::debug!(group = group.name(), "using the built-in prime");
+            group.prime().clone()
+        }
+    };
+    let generator = match generator {
+        Some(gen_str) => parse_biguint(gen_str)?,
+        None => parse_biguint(group.default_generator())?,
+    };
+    Ok((prime, generator))
+}
+
+/// Basic sanity checks every prime/generator pair must pass.
+pub fn validate(prime: &BigUint, generator: &BigUint) -> Result<()> {
+    let result = check(prime, generator);
+    match result {
+        Ok(()) => tracing::debug!(prime_bits = prime.bits(), %generator, "group parameters pass the sanity checks"),
+        Err(ref reason) => tracing::debug!(prime_bits = prime.bits(), reason = %reason, "rejected group parameters"),
+    }
+    result
+}
+
+fn check(prime: &BigUint, generator: &BigUint) -> Result<()> {
+    let limit = max_prime_bits();
+    if prime.bits() > limit {
+        return Err(Error::Validation(format!(
+            "the prime has {} bits, more than the {limit}-bit limit (--max-prime-bits)",
+            prime.bits()
+        )));
+    }
+    Ok(ffdh::validate(prime, generator)?)
+}
+
+/// How [`parse_biguint_radix`] reads a number.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Radix {
+    /// Hex with a `0x` prefix, decimal without.
+    #[default]
+    Auto,
+    /// Hex, with or without `0x`.
+    Hex,
+    /// Decimal; a `0x` prefix is an error.
+    Dec,
+}
+
+/// Prime sizes whose hex form a strict parse refuses to read as decimal.
+const HEX_PRIME_BITS: [usize; 7] = [1024, 1536, 2048, 3072, 4096, 6144, 8192];
+
+/// Decimal, or hex with a `0x` prefix. Whitespace and `_` are ignored.
+pub fn parse_biguint(input: &str) -> Result<BigUint> {
+    parse_biguint_radix(input, Radix::Auto, false)
+}
+
+/// Reads `input` in `radix`. With `strict`, [`Radix::Auto`] also refuses
+/// unprefixed digits that are likely hex: a leading zero, or exactly as
+/// many digits as a common prime size has in hex.
+pub fn parse_biguint_radix(input: &str, radix: Radix, strict: bool) -> Result<BigUint> {
+    let cleaned: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '_')
+        .collect();
+
+    if cleaned.is_empty() {
+        return Err(Error::Parse("value cannot be empty".into()));
+    }
+
+    let prefixed = cleaned.strip_prefix("0x").or_else(|| cleaned.strip_prefix("0X"));
+    let (base, digits) = match (radix, prefixed) {
+        (Radix::Dec, Some(_)) => {
+            return Err(Error::Parse("a 0x prefix was given, but the number is read as decimal".into()));
+        }
+        (Radix::Dec, None) => (10, cleaned.as_str()),
+        (_, Some(stripped)) => (16, stripped),
+        (Radix::Hex, None) => (16, cleaned.as_str()),
+        (Radix::Auto, None) => {
+            if strict {
+                check_unambiguous(&cleaned)?;
+            }
+            (10, cleaned.as_str())
+        }
+    };
+    check_length(digits, base)?;
+
+    BigUint::parse_bytes(digits.as_bytes(), base).ok_or_else(|| {
+        let hex = digits.bytes().all(|byte| byte.is_ascii_hexdigit());
+        Error::Parse(if base == 10 && hex {
+            "failed to parse big integer: it has hex digits, but hex needs a 0x prefix".into()
+        } else {
+            "failed to parse big integer".into()
+        })
+    })
+}
+
+/// Refuses, before parsing, digits too many for a number within the prime
+/// size limit: no number this tool reads is larger than a prime, and a
+/// megabyte of decimal would take long to parse and longer to use.
+fn check_length(digits: &str, base: u32) -> Result<()> {
+    let significant = digits.trim_start_matches('0').len() as u64;
+    let bits_per_digit = if base == 16 { 4.0 } else { std::f64::consts::LOG2_10 };
+    // The fewest bits a number with this many significant digits can have.
+    let bits = (significant.saturating_sub(1) as f64 * bits_per_digit) as u64 + 1;
+    let limit = max_prime_bits();
+    if significant > 0 && bits > limit {
+        return Err(Error::Validation(format!(
+            "the number has at least {bits} bits, more than the {limit}-bit limit on primes (--max-prime-bits)"
+        )));
+    }
+    Ok(())
+}
+
+fn check_unambiguous(digits: &str) -> Result<()> {
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(());
+    }
+    let reason = if digits.len() > 1 && digits.starts_with('0') {
+        "starts with 0, as hex dumps often do".to_string()
+    } else if let Some(bits) = HEX_PRIME_BITS.iter().find(|&&bits| digits.len() * 4 == bits) {
+        format!("has exactly as many digits as a {bits}-bit number in hex")
+    } else {
+        return Ok(());
+    };
+    Err(Error::Parse(format!(
+        "the number {reason}, so it is ambiguous without a 0x prefix; \
+         add 0x if it is hex, or choose the radix explicitly"
+    )))
+}
+
+pub fn parse_hex_biguint(hex: &str) -> BigUint {
+    let cleaned: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    BigUint::parse_bytes(cleaned.as_bytes(), 16).expect("invalid hex prime literal")
+}
@@ -0,0 +1,40 @@
+use std::path::Path;
+
+/// Reads a passphrase from the first line of `file`, or prompts for it twice
+/// on the terminal without echo.
+pub fn obtain(file: Option<&Path>) -> Result<String, String> {
+    let passphrase = match file {
+        Some(path) => read_file(path)?,
+        None => {
+            let first = rpassword::prompt_password("Passphrase: ")
+                .map_err(|err| format!("failed to read passphrase: {err}"))?;
+            let second = rpassword::prompt_password("Confirm passphrase: ")
+                .map_err(|err| format!("failed to read passphrase: {err}"))?;
+            if first != second {
+                return Err("passphrases do not match".into());
+            }
+            first
+        }
+    };
+
+    if passphrase.is_empty() {
+        return Err("passphrase cannot be empty".into());
+    }
+    Ok(passphrase)
+}
+
+/// Reads an existing secret, such as the passphrase of a file being
+/// decrypted or a token PIN: the first line of `file`, or a single prompt.
+/// `name` labels the prompt and errors, e.g. `passphrase` or `PIN`.
+pub fn enter(file: Option<&Path>, name: &str) -> Result<String, String> {
+    match file {
+        Some(path) => read_file(path),
+        None => rpassword::prompt_password(format!("{name}: ")).map_err(|err| format!("failed to read {name}: {err}")),
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read passphrase file {}: {err}", path.display()))?;
+    Ok(contents.lines().next().unwrap_or_default().to_string())
+}
@@ -0,0 +1,50 @@
+//! PEM (RFC 7468) armor for DER structures.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::error::{Error, Result};
+
+/// Base64 characters per PEM body line (RFC 7468).
+const LINE_WIDTH: usize = 64;
+
+pub const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+pub const ENCRYPTED_PRIVATE_KEY_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+pub const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+/// PKCS #3 `DHParameter`, as written by `openssl dhparam`.
+pub const DH_PARAMETERS_LABEL: &str = "DH PARAMETERS";
+/// RFC 3279 `DomainParameters`, as written by `openssl genpkey -genparam
+/// -algorithm DHX`.
+pub const X942_DH_PARAMETERS_LABEL: &str = "X9.42 DH PARAMETERS";
+
+/// Wraps DER bytes in a PEM block with the given label.
+pub fn encode(label: &str, der: &[u8]) -> String {
+    let body = BASE64.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in body.as_bytes().chunks(LINE_WIDTH) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+/// Extracts the DER bytes of the first PEM block with the given label,
+/// ignoring any text around it.
+pub fn decode(label: &str, text: &str) -> Result<Vec<u8>> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let start = text
+        .find(&begin)
+        .ok_or_else(|| Error::Parse(format!("no '{begin}' block found")))?
+        + begin.len();
+    let stop = text[start..]
+        .find(&end)
+        .ok_or_else(|| Error::Parse(format!("missing '{end}'")))?
+        + start;
+
+    let body: String = text[start..stop].chars().filter(|c| !c.is_whitespace()).collect();
+    BASE64
+        .decode(body)
+        .map_err(|err| Error::Parse(format!("invalid base64 in PEM block: {err}")))
+}
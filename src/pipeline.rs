@@ -0,0 +1,134 @@
+//! `--pipeline`: newline-delimited JSON parameter records on stdin, one
+//! `--json` key record per line on stdout, from one long-running process.
+//!
+//! A record may set `group`, `prime`, `generator`, `private_bits`, `label`,
+//! `operator` and `tags`; whatever it leaves out comes from the command line,
+//! so `{}` asks for a key exactly like the plain `generate` invocation would.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use create_private_key::params::DhGroup;
+use create_private_key::{DhParams, generate_keypair};
+use serde::Deserialize;
+
+use crate::config::Policy;
+use crate::fingerprint::FingerprintHash;
+use crate::metadata::{self, Metadata};
+use crate::output::{KeyRecord, OutputFormat, render_json};
+use crate::random;
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Request {
+    group: Option<String>,
+    prime: Option<String>,
+    generator: Option<String>,
+    private_bits: Option<u64>,
+    label: Option<String>,
+    operator: Option<String>,
+    /// Added to the command line's `--tag` pairs.
+    tags: BTreeMap<String, String>,
+}
+
+/// What the command line gives the records that leave a field out.
+pub struct Defaults {
+    /// With `--private-bits` applied.
+    pub params: DhParams,
+    pub group: DhGroup,
+    /// The name recorded in metadata for `params`.
+    pub group_name: &'static str,
+    pub label: Option<String>,
+    pub operator: Option<String>,
+    pub metadata: bool,
+    pub valid_for: Option<Duration>,
+    pub tags: Vec<(String, String)>,
+    pub fingerprint_hash: FingerprintHash,
+    /// `None` when the private key is left out of the records.
+    pub private: Option<OutputFormat>,
+    pub public: OutputFormat,
+    pub entropy_sources: Vec<&'static str>,
+}
+
+/// Answers records until stdin closes. A record that cannot be served gets
+/// an `{"error": ...}` line in its place, so output lines keep matching input
+/// lines; the run then fails once stdin is exhausted.
+pub fn run(defaults: &Defaults, policy: &Policy) -> Result<(), String> {
+    let stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    let (mut served, mut failed) = (0u64, 0u64);
+    for (index, line) in stdin.lines().enumerate() {
+        let line = line.map_err(|err| format!("failed to read stdin: {err}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let rendered = match serve(&line, defaults, policy) {
+            Ok(rendered) => {
+                served += 1;
+                rendered
+            }
+            Err(message) => {
+                tracing::warn!(line = index + 1, error = %message, "pipeline record failed");
+                failed += 1;
+                format!("{}\n", serde_json::json!({ "error": message }))
+            }
+        };
+        stdout
+            .write_all(rendered.as_bytes())
+            .and_then(|()| stdout.flush())
+            .map_err(|err| format!("failed to write stdout: {err}"))?;
+    }
+    tracing::info!(served, failed, "pipeline input closed");
+    if failed > 0 {
+        return Err(format!("{failed} of {} pipeline records failed", served + failed));
+    }
+    Ok(())
+}
+
+fn serve(line: &str, defaults: &Defaults, policy: &Policy) -> Result<String, String> {
+    let request: Request = serde_json::from_str(line).map_err(|err| format!("invalid record: {err}"))?;
+    for (name, value) in &request.tags {
+        metadata::check_tag(name, value)?;
+    }
+    metadata::check_text(request.label.as_deref(), request.operator.as_deref())?;
+    let (params, group_name) = params(&request, defaults, policy)?;
+    let pair = generate_keypair(&params, &mut random::Source)?;
+    let metadata = defaults.metadata.then(|| {
+        Metadata::new(
+            group_name,
+            request.label.or_else(|| defaults.label.clone()),
+            request.operator.or_else(|| defaults.operator.clone()),
+        )
+        .valid_for(defaults.valid_for)
+        .tagged(&defaults.tags)
+        .tagged(&request.tags.into_iter().collect::<Vec<_>>())
+    });
+    let mut record = KeyRecord::from_key_pair(pair, metadata, defaults.fingerprint_hash);
+    if defaults.entropy_sources.len() > 1 {
+        record.entropy_sources = defaults.entropy_sources.clone();
+    }
+    Ok(render_json(&record, defaults.private, defaults.public))
+}
+
+/// The record's parameters, falling back to the command line's field by
+/// field.
+fn params(request: &Request, defaults: &Defaults, policy: &Policy) -> Result<(DhParams, &'static str), String> {
+    let (params, group_name) = if request.group.is_none() && request.prime.is_none() && request.generator.is_none() {
+        (defaults.params.clone(), defaults.group_name)
+    } else {
+        let group = match request.group {
+            Some(ref name) => DhGroup::from_str(name, true).map_err(|_| format!("unknown group '{name}'"))?,
+            None => defaults.group,
+        };
+        let params = DhParams::parse(group, request.prime.as_deref(), request.generator.as_deref())?;
+        (params, if request.prime.is_some() { "custom" } else { group.name() })
+    };
+    let params = match request.private_bits.or(defaults.params.private_bits()) {
+        Some(bits) => params.with_private_bits(bits)?,
+        None => params,
+    };
+    policy.check(&params, group_name == "custom").map_err(|violation| violation.to_string())?;
+    Ok((params, group_name))
+}
@@ -0,0 +1,87 @@
+//! Provisioning YubiKey PIV slots with `ykman`.
+//!
+//! PIV has no finite-field DH, so this generates an EC key pair on the
+//! device itself; the private key is created on the YubiKey and never exists
+//! in host memory. The attestation certificate, signed by the device's
+//! Yubico-issued attestation key, proves that. A certificate signing
+//! request for enrollment can be signed by the same key on the device, or a
+//! self-signed certificate made and stored in the slot.
+
+use clap::ValueEnum;
+
+use crate::external;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PivAlgorithm {
+    /// NIST P-256.
+    #[value(name = "eccp256")]
+    EccP256,
+    /// NIST P-384.
+    #[value(name = "eccp384")]
+    EccP384,
+}
+
+impl PivAlgorithm {
+    fn ykman_name(self) -> &'static str {
+        match self {
+            PivAlgorithm::EccP256 => "ECCP256",
+            PivAlgorithm::EccP384 => "ECCP384",
+        }
+    }
+}
+
+/// Public key and attestation certificate of a freshly generated slot key,
+/// both PEM.
+pub struct Provisioned {
+    pub public_key: String,
+    pub attestation: String,
+}
+
+/// Generates a key in `slot` (e.g. `9a`) and attests it. `management_key`
+/// is the hex PIV management key, when it is not the factory default.
+pub fn generate(slot: &str, algorithm: PivAlgorithm, management_key: Option<&str>) -> Result<Provisioned, String> {
+    let mut args = vec!["piv", "keys", "generate", "--algorithm", algorithm.ykman_name()];
+    if let Some(key) = management_key {
+        args.extend(["--management-key", key]);
+    }
+    args.extend([slot, "-"]);
+    let public_key = external::run_filter("ykman", &args, &[])?;
+    let attestation = external::run_filter("ykman", &["piv", "keys", "attest", slot, "-"], &[])?;
+
+    let text = |bytes: Vec<u8>| String::from_utf8(bytes).map_err(|_| "`ykman` printed invalid UTF-8".to_string());
+    Ok(Provisioned {
+        public_key: text(public_key)?,
+        attestation: text(attestation)?,
+    })
+}
+
+/// Has the device sign a PKCS #10 request (PEM) for the key in `slot`, whose
+/// public key PEM is `public_key`. `subject` is a distinguished name such as
+/// `CN=device-123`; the PIN unlocks the key for signing.
+pub fn request(slot: &str, public_key: &str, subject: &str, pin: &str) -> Result<String, String> {
+    let args = ["piv", "certificates", "request", "--subject", subject, "--pin", pin, slot, "-", "-"];
+    let csr = external::run_filter("ykman", &args, public_key.as_bytes())?;
+    String::from_utf8(csr).map_err(|_| "`ykman` printed invalid UTF-8".to_string())
+}
+
+/// Has the device sign a certificate for the key in `slot` with itself,
+/// valid for `days`, and store it in the slot; returns the certificate PEM.
+/// Storing it needs the management key as well as the PIN.
+pub fn self_sign(
+    slot: &str,
+    public_key: &str,
+    subject: &str,
+    days: u32,
+    pin: &str,
+    management_key: Option<&str>,
+) -> Result<String, String> {
+    let days = days.to_string();
+    let mut args = vec!["piv", "certificates", "generate", "--subject", subject, "--valid-days", &days, "--pin", pin];
+    if let Some(key) = management_key {
+        args.extend(["--management-key", key]);
+    }
+    args.extend([slot, "-"]);
+    external::run_filter("ykman", &args, public_key.as_bytes())?;
+    let certificate = external::run_filter("ykman", &["piv", "certificates", "export", slot, "-"], &[])?;
+    String::from_utf8(certificate).map_err(|_| "`ykman` printed invalid UTF-8".to_string())
+}
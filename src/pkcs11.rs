@@ -0,0 +1,198 @@
+//! Generating DH key pairs inside a PKCS #11 token (HSM), where the private
+//! key never leaves the device.
+//!
+//! Goes through the raw Cryptoki API because the key pair mechanism,
+//! `CKM_DH_PKCS_KEY_PAIR_GEN`, is not wrapped by the higher-level crates.
+
+use cryptoki_sys::{
+    CK_ATTRIBUTE, CK_BBOOL, CK_FALSE, CK_MECHANISM, CK_OBJECT_HANDLE, CK_RV, CK_SESSION_HANDLE, CK_TRUE, CK_ULONG,
+    CKA_BASE, CKA_DERIVE, CKA_EXTRACTABLE, CKA_ID, CKA_LABEL, CKA_PRIME, CKA_PRIVATE, CKA_SENSITIVE, CKA_TOKEN,
+    CKA_VALUE, CKF_RW_SESSION, CKF_SERIAL_SESSION, CKM_DH_PKCS_KEY_PAIR_GEN, CKR_CRYPTOKI_ALREADY_INITIALIZED, CKR_OK,
+    CKR_USER_ALREADY_LOGGED_IN, CKU_USER, Pkcs11,
+};
+use num_bigint::BigUint;
+use std::path::Path;
+use std::ptr;
+
+use crate::random;
+
+/// Bytes of the random `CKA_ID` shared by the two key objects.
+///
+/// Templates hold raw pointers into the local buffers below, so the same
+/// buffer can back attributes in both templates.
+const OBJECT_ID_LEN: usize = 16;
+
+/// A key pair generated on the token.
+pub struct HsmKey {
+    pub public_key: BigUint,
+    pub id: Vec<u8>,
+    pub public_handle: CK_OBJECT_HANDLE,
+    pub private_handle: CK_OBJECT_HANDLE,
+}
+
+/// Loads `module`, logs in to `slot` with `pin`, and generates a persistent
+/// key pair for the given group. The private key is created sensitive, not
+/// extractable and usable only for derivation.
+pub fn generate(
+    module: &Path,
+    slot: CK_ULONG,
+    pin: &str,
+    label: &str,
+    prime: &BigUint,
+    generator: &BigUint,
+) -> Result<HsmKey, String> {
+    // SAFETY: loading a PKCS #11 module runs its initialisers; the module is
+    // whatever the operator pointed us at, as with any PKCS #11 consumer.
+    let library = unsafe { Pkcs11::new(module) }
+        .map_err(|err| format!("failed to load PKCS#11 module {}: {err}", module.display()))?;
+    // The generated wrappers panic on a missing symbol, so check up front.
+    let complete = library.C_Initialize.is_ok()
+        && library.C_Finalize.is_ok()
+        && library.C_OpenSession.is_ok()
+        && library.C_CloseSession.is_ok()
+        && library.C_Login.is_ok()
+        && library.C_Logout.is_ok()
+        && library.C_GenerateKeyPair.is_ok()
+        && library.C_GetAttributeValue.is_ok();
+    if !complete {
+        return Err(format!("{} is not a PKCS#11 module", module.display()));
+    }
+    let token = Token { library: &library };
+
+    // SAFETY (here and below): every pointer handed to the module points at
+    // a live local buffer whose length is passed alongside it.
+    let rv = unsafe { library.C_Initialize(ptr::null_mut()) };
+    if rv != CKR_OK && rv != CKR_CRYPTOKI_ALREADY_INITIALIZED {
+        return Err(failure("C_Initialize", rv));
+    }
+
+    let mut session: CK_SESSION_HANDLE = 0;
+    check("C_OpenSession", unsafe {
+        library.C_OpenSession(slot, CKF_SERIAL_SESSION | CKF_RW_SESSION, ptr::null_mut(), None, &mut session)
+    })?;
+    let session = Session { token, handle: session };
+
+    let mut pin = pin.as_bytes().to_vec();
+    let rv = unsafe { library.C_Login(session.handle, CKU_USER, pin.as_mut_ptr(), pin.len() as CK_ULONG) };
+    if rv != CKR_OK && rv != CKR_USER_ALREADY_LOGGED_IN {
+        return Err(failure("C_Login", rv));
+    }
+
+    let mut id = vec![0u8; OBJECT_ID_LEN];
+    random::fill(&mut id)?;
+    let mut label = label.as_bytes().to_vec();
+    let mut prime = prime.to_bytes_be();
+    let mut base = generator.to_bytes_be();
+    let (mut yes, mut no): (CK_BBOOL, CK_BBOOL) = (CK_TRUE, CK_FALSE);
+
+    let mut public_template = [
+        attribute(CKA_TOKEN, &mut yes),
+        attribute(CKA_PRIME, prime.as_mut_slice()),
+        attribute(CKA_BASE, base.as_mut_slice()),
+        attribute(CKA_LABEL, label.as_mut_slice()),
+        attribute(CKA_ID, id.as_mut_slice()),
+    ];
+    let mut private_template = [
+        attribute(CKA_TOKEN, &mut yes),
+        attribute(CKA_PRIVATE, &mut yes),
+        attribute(CKA_SENSITIVE, &mut yes),
+        attribute(CKA_EXTRACTABLE, &mut no),
+        attribute(CKA_DERIVE, &mut yes),
+        attribute(CKA_LABEL, label.as_mut_slice()),
+        attribute(CKA_ID, id.as_mut_slice()),
+    ];
+
+    let mut mechanism = CK_MECHANISM {
+        mechanism: CKM_DH_PKCS_KEY_PAIR_GEN,
+        pParameter: ptr::null_mut(),
+        ulParameterLen: 0,
+    };
+    let (mut public_handle, mut private_handle): (CK_OBJECT_HANDLE, CK_OBJECT_HANDLE) = (0, 0);
+    check("C_GenerateKeyPair", unsafe {
+        library.C_GenerateKeyPair(
+            session.handle,
+            &mut mechanism,
+            public_template.as_mut_ptr(),
+            public_template.len() as CK_ULONG,
+            private_template.as_mut_ptr(),
+            private_template.len() as CK_ULONG,
+            &mut public_handle,
+            &mut private_handle,
+        )
+    })?;
+
+    let public_value = session.value(public_handle)?;
+    Ok(HsmKey {
+        public_key: BigUint::from_bytes_be(&public_value),
+        id,
+        public_handle,
+        private_handle,
+    })
+}
+
+fn attribute<T: ?Sized>(kind: CK_ULONG, value: &mut T) -> CK_ATTRIBUTE {
+    CK_ATTRIBUTE {
+        type_: kind,
+        pValue: value as *mut T as *mut std::ffi::c_void,
+        ulValueLen: std::mem::size_of_val(value) as CK_ULONG,
+    }
+}
+
+fn check(function: &str, rv: CK_RV) -> Result<(), String> {
+    if rv == CKR_OK { Ok(()) } else { Err(failure(function, rv)) }
+}
+
+fn failure(function: &str, rv: CK_RV) -> String {
+    format!("{function} failed with CKR 0x{rv:08X}")
+}
+
+/// Finalizes the library when dropped.
+struct Token<'a> {
+    library: &'a Pkcs11,
+}
+
+impl Drop for Token<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.library.C_Finalize(ptr::null_mut());
+        }
+    }
+}
+
+/// Logs out and closes the session when dropped, before the library is
+/// finalized.
+struct Session<'a> {
+    token: Token<'a>,
+    handle: CK_SESSION_HANDLE,
+}
+
+impl Session<'_> {
+    /// Reads `CKA_VALUE`: one call for the length, one for the bytes.
+    fn value(&self, object: CK_OBJECT_HANDLE) -> Result<Vec<u8>, String> {
+        let library = self.token.library;
+        let mut template = [CK_ATTRIBUTE {
+            type_: CKA_VALUE,
+            pValue: ptr::null_mut(),
+            ulValueLen: 0,
+        }];
+        check("C_GetAttributeValue", unsafe {
+            library.C_GetAttributeValue(self.handle, object, template.as_mut_ptr(), 1)
+        })?;
+        let mut value = vec![0u8; template[0].ulValueLen as usize];
+        template[0].pValue = value.as_mut_ptr().cast();
+        check("C_GetAttributeValue", unsafe {
+            library.C_GetAttributeValue(self.handle, object, template.as_mut_ptr(), 1)
+        })?;
+        value.truncate(template[0].ulValueLen as usize);
+        Ok(value)
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.token.library.C_Logout(self.handle);
+            self.token.library.C_CloseSession(self.handle);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! `pool`: keeps a directory, or the readers of a named pipe, supplied with
+//! ready-made ephemeral key pairs.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use create_private_key::{DhParams, generate_keypair};
+
+use crate::fingerprint::FingerprintHash;
+use crate::metadata::Metadata;
+use crate::output::KeyRecord;
+use crate::random;
+use crate::sink::{self, Overwrite};
+
+/// Suffix of the key files a directory pool holds. Files starting with `.`
+/// are in-progress writes and are not counted.
+const KEY_SUFFIX: &str = ".pem";
+
+pub struct Settings {
+    pub params: DhParams,
+    /// The name recorded in each key's metadata.
+    pub group_name: &'static str,
+    /// Keys to have ready after a top-up.
+    pub size: usize,
+    /// Top up once fewer keys than this are ready.
+    pub low_watermark: usize,
+    /// At most this many keys per second, however far below the mark.
+    pub rate: Option<f64>,
+    /// How often a directory is recounted.
+    pub interval: Duration,
+    /// Top a directory up once and return instead of watching it.
+    pub once: bool,
+}
+
+/// Paces key generation to `--rate`.
+struct Throttle {
+    period: Option<Duration>,
+    next: Instant,
+}
+
+impl Throttle {
+    fn new(rate: Option<f64>) -> Throttle {
+        Throttle {
+            period: rate.map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next: Instant::now(),
+        }
+    }
+
+    fn wait(&mut self) {
+        let Some(period) = self.period else { return };
+        let now = Instant::now();
+        if self.next > now {
+            std::thread::sleep(self.next - now);
+        }
+        self.next = self.next.max(now) + period;
+    }
+}
+
+/// Runs until killed, or after one top-up with `once`. A FIFO at `path`
+/// gets one key per reader; anything else is a directory of key files.
+pub fn run(path: &Path, settings: &Settings) -> Result<(), String> {
+    if is_fifo(path) {
+        if settings.once {
+            return Err("--once only applies to a directory pool".into());
+        }
+        return feed_pipe(path, settings);
+    }
+    std::fs::create_dir_all(path).map_err(|err| format!("failed to create {}: {err}", path.display()))?;
+    let mut throttle = Throttle::new(settings.rate);
+    loop {
+        let ready = count_keys(path)?;
+        if ready < settings.low_watermark {
+            for _ in ready..settings.size {
+                throttle.wait();
+                let record = generate(settings)?;
+                let file = path.join(format!("{}{KEY_SUFFIX}", record.key_id));
+                sink::write_private_file(&file, record.private_key_pem().as_bytes(), Overwrite::Refuse)?;
+            }
+            tracing::info!(dir = %path.display(), added = settings.size - ready, "topped up key pool");
+        }
+        if settings.once {
+            return Ok(());
+        }
+        std::thread::sleep(settings.interval);
+    }
+}
+
+/// Keeps up to `size` keys in memory and writes one to each process that
+/// opens the pipe for reading. A key whose write fails is dropped, never
+/// offered to the next reader.
+fn feed_pipe(path: &Path, settings: &Settings) -> Result<(), String> {
+    let mut throttle = Throttle::new(settings.rate);
+    let mut ready = Vec::with_capacity(settings.size);
+    loop {
+        if ready.len() < settings.low_watermark {
+            while ready.len() < settings.size {
+                throttle.wait();
+                ready.push(generate(settings)?);
+            }
+            tracing::debug!(pipe = %path.display(), ready = ready.len(), "topped up key pool");
+        }
+        let record = ready.pop().expect("the pool was just topped up");
+        // Blocks until a reader opens the other end.
+        let written = std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|mut pipe| std::io::Write::write_all(&mut pipe, record.private_key_pem().as_bytes()));
+        match written {
+            Ok(()) => tracing::info!(key_id = %record.key_id, "handed out pooled key"),
+            Err(err) => tracing::warn!(key_id = %record.key_id, error = %err, "discarded pooled key"),
+        }
+    }
+}
+
+fn generate(settings: &Settings) -> Result<KeyRecord, String> {
+    let pair = generate_keypair(&settings.params, &mut random::Source)?;
+    let metadata = Metadata::new(settings.group_name, None, None);
+    Ok(KeyRecord::from_key_pair(pair, Some(metadata), FingerprintHash::Sha256))
+}
+
+fn count_keys(dir: &Path) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir).map_err(|err| format!("failed to read {}: {err}", dir.display()))?;
+    let mut count = 0;
+    for entry in entries {
+        let name = entry.map_err(|err| format!("failed to read {}: {err}", dir.display()))?.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with('.') && name.ends_with(KEY_SUFFIX) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
@@ -0,0 +1,120 @@
+//! Probabilistic primality tests for parameters that do not come from a
+//! published group.
+//!
+//! The Miller-Rabin rounds are independent, so they run on every core and
+//! stop as soon as one finds a witness that the number is composite.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use create_private_key::Error;
+use create_private_key_core as ffdh;
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use sha2::{Digest, Sha256};
+
+use crate::random;
+
+/// Miller-Rabin rounds per number unless `--prime-checks` says otherwise; a
+/// composite survives all of them with probability below 2^-80.
+pub const ROUNDS: u32 = 40;
+
+const SMALL_PRIMES: [u32; 15] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+
+/// Where the Miller-Rabin bases come from.
+#[derive(Clone, Copy)]
+pub enum Bases {
+    /// Drawn from the RNG, so no composite can be built to pass.
+    Random,
+    /// Derived from `n` with SHA-256, for runs that must not touch the RNG.
+    /// Anyone can compute them, so this only vouches for numbers that were
+    /// not built to fool the test.
+    Derived,
+}
+
+/// Trial division by small primes, then `rounds` Miller-Rabin rounds.
+pub fn is_probable_prime(n: &BigUint, rounds: u32, bases: Bases) -> Result<bool, Error> {
+    for &small in &SMALL_PRIMES {
+        if *n == BigUint::from(small) {
+            return Ok(true);
+        }
+        if (n % small).is_zero() {
+            return Ok(false);
+        }
+    }
+    if *n < BigUint::from(2u32) {
+        return Ok(false);
+    }
+    let n_minus_one = n - 1u32;
+    // All drawn up front and in order, so a seeded RNG stays reproducible
+    // however the rounds are scheduled.
+    let bases = (0..rounds)
+        .map(|round| match bases {
+            Bases::Random => ffdh::random_range(&BigUint::from(2u32), &n_minus_one, random::fill),
+            Bases::Derived => Ok(derived_base(n, round)),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let threads = std::thread::available_parallelism().map_or(1, usize::from).min(bases.len());
+    if threads <= 1 {
+        return Ok(!bases.iter().any(|base| is_witness(n, base)));
+    }
+    let (next, composite) = (AtomicUsize::new(0), AtomicBool::new(false));
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while !composite.load(Ordering::Relaxed) {
+                    let Some(base) = bases.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+                    if is_witness(n, base) {
+                        composite.store(true, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    Ok(!composite.into_inner())
+}
+
+/// Whether `base` proves the odd `n` composite (one Miller-Rabin round).
+fn is_witness(n: &BigUint, base: &BigUint) -> bool {
+    let n_minus_one = n - 1u32;
+    let shift = n_minus_one.trailing_zeros().expect("n - 1 is even and nonzero");
+    let mut x = ffdh::montgomery::modpow(base, &(&n_minus_one >> shift), n);
+    if x.is_one() || x == n_minus_one {
+        return false;
+    }
+    for _ in 1..shift {
+        x = &x * &x % n;
+        if x == n_minus_one {
+            return false;
+        }
+    }
+    true
+}
+
+/// A base in `[2, n - 2]` from SHA-256 in counter mode over `n` and the
+/// round number, with 64 more bits than `n` so the reduction is unbiased
+/// enough not to matter.
+fn derived_base(n: &BigUint, round: u32) -> BigUint {
+    let encoded = n.to_bytes_be();
+    let mut bytes = Vec::new();
+    let mut block = 0u32;
+    while bytes.len() < encoded.len() + 8 {
+        let mut hash = Sha256::new();
+        hash.update(b"create-private-key miller-rabin base");
+        hash.update(&encoded);
+        hash.update(round.to_be_bytes());
+        hash.update(block.to_be_bytes());
+        bytes.extend_from_slice(&hash.finalize());
+        block += 1;
+    }
+    BigUint::from_bytes_be(&bytes) % (n - 3u32) + 2u32
+}
+
+/// Whether `p` and `(p - 1) / 2` are both (probably) prime.
+pub fn is_safe_prime(prime: &BigUint, rounds: u32, bases: Bases) -> Result<bool, Error> {
+    if prime.is_even() || !is_probable_prime(&((prime - 1u32) >> 1), rounds, bases)? {
+        return Ok(false);
+    }
+    is_probable_prime(prime, rounds, bases)
+}
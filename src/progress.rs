@@ -0,0 +1,71 @@
+//! A one-line progress bar on stderr for long batch runs.
+
+use std::time::{Duration, Instant};
+
+/// Width of the bar, in cells.
+const BAR_WIDTH: usize = 24;
+/// Redraws at most this often, so fast runs do not spend their time on it.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct Progress {
+    total: u64,
+    done: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    /// A bar counting `total` keys; `None` when it should not be shown.
+    pub fn new(total: u64, shown: bool) -> Option<Self> {
+        shown.then(|| Progress {
+            total,
+            done: 0,
+            started: Instant::now(),
+            drawn: None,
+        })
+    }
+
+    /// Counts one more key, redrawing when enough time has passed.
+    pub fn tick(&mut self) {
+        self.done += 1;
+        if self.done == self.total || self.drawn.is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL) {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
+        let filled = (self.done * BAR_WIDTH as u64 / self.total.max(1)) as usize;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { self.done as f64 / elapsed } else { 0.0 };
+        let eta = if rate > 0.0 {
+            format_duration(((self.total - self.done) as f64 / rate) as u64)
+        } else {
+            "?".into()
+        };
+        eprint!(
+            "\r[{}{}] {}/{} keys  {rate:.1}/s  eta {eta}\x1b[K",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            self.done,
+            self.total
+        );
+        self.drawn = Some(Instant::now());
+    }
+}
+
+/// Erases the bar, also when the run fails, so the error starts a clean line.
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.drawn.is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,103 @@
+//! Protection applied to private key files before they are written.
+
+use std::io::Write;
+
+use age::armor::{ArmoredWriter, Format};
+
+use crate::external;
+use crate::output::KeyRecord;
+use crate::pbes2::Encryption;
+#[cfg(feature = "cloud")]
+use crate::kms::{self, KmsKey};
+use crate::sealed::{self, Sealing};
+
+pub enum Protection {
+    /// Passphrase-encrypted PKCS #8.
+    Pkcs8(Encryption),
+    /// PKCS #8 PEM encrypted to age recipients, ASCII-armored.
+    Age(Vec<age::x25519::Recipient>),
+    /// PKCS #8 PEM encrypted by `gpg` to public keys, ASCII-armored.
+    Pgp(Vec<String>),
+    /// PKCS #8 PEM encrypted by `gpg` under a passphrase, ASCII-armored.
+    PgpSymmetric(String),
+    /// PKCS #8 PEM sealed with Argon2id and an AEAD (`--password-protect`).
+    Sealed(Sealing),
+    /// PKCS #8 PEM envelope-encrypted under a cloud KMS key.
+    #[cfg(feature = "cloud")]
+    Kms(KmsKey),
+}
+
+/// Parses `age1...` recipient strings.
+pub fn age_recipients(recipients: &[String]) -> Result<Vec<age::x25519::Recipient>, String> {
+    recipients
+        .iter()
+        .map(|recipient| {
+            recipient
+                .parse::<age::x25519::Recipient>()
+                .map_err(|err| format!("invalid age recipient '{recipient}': {err}"))
+        })
+        .collect()
+}
+
+/// Private key file contents: plain PKCS #8 PEM, or protected as requested.
+pub fn private_key_file(record: &KeyRecord, protection: Option<&Protection>) -> Result<Vec<u8>, String> {
+    match protection {
+        None => Ok(record.private_key_pem().into_bytes()),
+        Some(Protection::Pkcs8(encryption)) => record.encrypted_private_key_pem(encryption).map(String::into_bytes),
+        Some(protection) => protection.wrap(record.private_key_pem().as_bytes()),
+    }
+}
+
+impl Protection {
+    /// Encrypts arbitrary key bytes. PKCS #8 encryption works on the key
+    /// structure itself, not on bytes, so it is handled by the callers.
+    pub fn wrap(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Protection::Pkcs8(_) => unreachable!("PKCS#8 encryption is applied to the DER structure"),
+            Protection::Age(recipients) => age_encrypt(recipients, plaintext),
+            Protection::Pgp(recipients) => pgp_encrypt(recipients, plaintext),
+            Protection::PgpSymmetric(passphrase) => pgp_encrypt_symmetric(passphrase, plaintext),
+            Protection::Sealed(sealing) => sealed::seal(plaintext, sealing),
+            #[cfg(feature = "cloud")]
+            Protection::Kms(key) => kms::encrypt(key, plaintext),
+        }
+    }
+}
+
+/// Encrypts `plaintext` to every recipient and returns the armored file.
+pub fn age_encrypt(recipients: &[age::x25519::Recipient], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+        .map_err(|err| format!("age encryption failed: {err}"))?;
+
+    let mut ciphertext = Vec::new();
+    let io_err = |err: std::io::Error| format!("age encryption failed: {err}");
+    let armored = ArmoredWriter::wrap_output(&mut ciphertext, Format::AsciiArmor).map_err(io_err)?;
+    let mut writer = encryptor.wrap_output(armored).map_err(io_err)?;
+    writer.write_all(plaintext).map_err(io_err)?;
+    writer.finish().and_then(|armored| armored.finish()).map_err(io_err)?;
+    Ok(ciphertext)
+}
+
+/// Base `gpg` arguments: non-interactive, armored, result on stdout.
+const GPG_ARGS: &[&str] = &["--batch", "--yes", "--quiet", "--armor", "--output", "-"];
+
+fn pgp_encrypt(recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut args = GPG_ARGS.to_vec();
+    args.push("--encrypt");
+    for recipient in recipients {
+        args.extend(["--recipient", recipient.as_str()]);
+    }
+    external::run_filter("gpg", &args, plaintext)
+}
+
+fn pgp_encrypt_symmetric(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut args = GPG_ARGS.to_vec();
+    // gpg reads the first line of stdin as the passphrase and the rest as
+    // the message, which keeps the passphrase off the command line.
+    args.extend(["--symmetric", "--cipher-algo", "AES256", "--pinentry-mode", "loopback", "--passphrase-fd", "0"]);
+    let mut input = Vec::with_capacity(passphrase.len() + 1 + plaintext.len());
+    input.extend_from_slice(passphrase.as_bytes());
+    input.push(b'\n');
+    input.extend_from_slice(plaintext);
+    external::run_filter("gpg", &args, &input)
+}
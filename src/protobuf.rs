@@ -0,0 +1,93 @@
+//! Hand-rolled encoder for `proto/key_record.proto`, whose field writers
+//! also serve libsignal's key records.
+
+use crate::metadata::Metadata;
+use crate::output::KeyRecord;
+
+const WIRE_VARINT: u8 = 0;
+#[cfg(feature = "pem")]
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_tag(out: &mut Vec<u8>, field: u32, wire_type: u8) {
+    put_varint(out, (u64::from(field) << 3) | u64::from(wire_type));
+}
+
+/// Length-delimited field; empty values are omitted as proto3 defaults.
+pub fn put_bytes(out: &mut Vec<u8>, field: u32, value: &[u8]) {
+    if value.is_empty() {
+        return;
+    }
+    put_tag(out, field, WIRE_LEN);
+    put_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+pub fn put_uint(out: &mut Vec<u8>, field: u32, value: u64) {
+    if value == 0 {
+        return;
+    }
+    put_tag(out, field, WIRE_VARINT);
+    put_varint(out, value);
+}
+
+#[cfg(feature = "pem")]
+pub fn put_fixed64(out: &mut Vec<u8>, field: u32, value: u64) {
+    if value == 0 {
+        return;
+    }
+    put_tag(out, field, WIRE_FIXED64);
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn encode_metadata(metadata: &Metadata) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_bytes(&mut out, 1, metadata.created.as_bytes());
+    put_bytes(&mut out, 2, metadata.tool_version.as_bytes());
+    put_bytes(&mut out, 3, metadata.group.as_bytes());
+    put_bytes(&mut out, 4, metadata.label.as_deref().unwrap_or_default().as_bytes());
+    put_bytes(&mut out, 5, metadata.operator.as_deref().unwrap_or_default().as_bytes());
+    put_bytes(&mut out, 6, metadata.not_after.as_deref().unwrap_or_default().as_bytes());
+    for (name, value) in &metadata.tags {
+        // A map field is a repeated entry message of key 1 and value 2.
+        let mut entry = Vec::new();
+        put_bytes(&mut entry, 1, name.as_bytes());
+        put_bytes(&mut entry, 2, value.as_bytes());
+        put_bytes(&mut out, 7, &entry);
+    }
+    out
+}
+
+/// Encodes a `KeyRecord` message with its varint length prefix.
+pub fn encode_delimited(record: &KeyRecord, include_private: bool) -> Vec<u8> {
+    let mut message = Vec::new();
+    put_bytes(&mut message, 1, record.key_id.as_bytes());
+    put_bytes(&mut message, 2, record.fingerprint.as_bytes());
+    put_uint(&mut message, 3, record.prime.bits());
+    put_bytes(&mut message, 4, &record.prime.to_bytes_be());
+    put_bytes(&mut message, 5, &record.generator.to_bytes_be());
+    if include_private {
+        put_bytes(&mut message, 6, &record.private_key.expose().to_bytes_be());
+    }
+    put_bytes(&mut message, 7, &record.public_key.to_bytes_be());
+    if let Some(ref metadata) = record.metadata {
+        // An empty-but-present message still needs its tag.
+        put_tag(&mut message, 8, WIRE_LEN);
+        let encoded = encode_metadata(metadata);
+        put_varint(&mut message, encoded.len() as u64);
+        message.extend_from_slice(&encoded);
+    }
+
+    let mut out = Vec::with_capacity(message.len() + 5);
+    put_varint(&mut out, message.len() as u64);
+    out.extend_from_slice(&message);
+    out
+}
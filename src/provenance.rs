@@ -0,0 +1,249 @@
+//! `--provenance-key`: an Ed25519 signature (RFC 8032) over each public key,
+//! its parameters and metadata, naming the host that made it.
+//!
+//! A provenance document is `key=value` lines, the statement, ending with a
+//! `signature=` line over every byte before it. Downstream systems check it
+//! with `verify-provenance` and the host's public key, or with OpenSSL.
+//! Ed25519 is built on curve25519-dalek's group arithmetic.
+
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use create_private_key::der;
+use create_private_key::fingerprint::{self, FingerprintHash};
+use create_private_key::pem;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::{Scalar, clamp_integer};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::output::{KeyRecord, to_even_length_hex};
+use crate::random;
+
+const HEADER: &str = "create-private-key provenance v1";
+
+/// PKCS#8 `OneAsymmetricKey` for an Ed25519 seed (RFC 8410), up to the
+/// seed's 32 bytes.
+const PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// `SubjectPublicKeyInfo` for an Ed25519 key, up to the key's 32 bytes.
+const SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// An Ed25519 signing identity.
+pub struct Signer {
+    seed: Zeroizing<[u8; 32]>,
+    scalar: Scalar,
+    /// The second half of SHA-512(seed), hashed into each nonce.
+    prefix: Zeroizing<[u8; 32]>,
+    public: [u8; 32],
+}
+
+impl Drop for Signer {
+    fn drop(&mut self) {
+        self.scalar.zeroize();
+    }
+}
+
+impl Signer {
+    fn from_seed(seed: Zeroizing<[u8; 32]>) -> Signer {
+        let mut hash = Zeroizing::new(<[u8; 64]>::from(Sha512::digest(*seed)));
+        let mut low = Zeroizing::new([0u8; 32]);
+        low.copy_from_slice(&hash[..32]);
+        let scalar = Scalar::from_bytes_mod_order(clamp_integer(*low));
+        let mut prefix = Zeroizing::new([0u8; 32]);
+        prefix.copy_from_slice(&hash[32..]);
+        hash.zeroize();
+        let public = EdwardsPoint::mul_base(&scalar).compress().to_bytes();
+        Signer {
+            seed,
+            scalar,
+            prefix,
+            public,
+        }
+    }
+
+    /// A fresh identity from the configured random source.
+    pub fn generate() -> Result<Signer, String> {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        random::fill(&mut *seed).map_err(|err| err.to_string())?;
+        Ok(Signer::from_seed(seed))
+    }
+
+    /// Reads an unencrypted PKCS#8 `PRIVATE KEY` PEM, as `provenance-key`
+    /// or `openssl genpkey -algorithm ed25519` writes it.
+    pub fn load(path: &Path) -> Result<Signer, String> {
+        let text = Zeroizing::new(
+            std::fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?,
+        );
+        let der = pem::decode(pem::PRIVATE_KEY_LABEL, &text).map_err(|err| format!("{}: {err}", path.display()))?;
+        let der = Zeroizing::new(der);
+        let seed = der
+            .strip_prefix(&PKCS8_PREFIX[..])
+            .and_then(|seed| <[u8; 32]>::try_from(seed).ok())
+            .map(Zeroizing::new)
+            .ok_or_else(|| format!("{} is not an unencrypted Ed25519 PKCS#8 key", path.display()))?;
+        Ok(Signer::from_seed(seed))
+    }
+
+    /// The private key as PKCS#8 PEM.
+    pub fn private_pem(&self) -> Zeroizing<String> {
+        let der = Zeroizing::new([&PKCS8_PREFIX[..], &self.seed[..]].concat());
+        Zeroizing::new(pem::encode(pem::PRIVATE_KEY_LABEL, &der))
+    }
+
+    pub fn public_pem(&self) -> String {
+        pem::encode(pem::PUBLIC_KEY_LABEL, &[&SPKI_PREFIX[..], &self.public].concat())
+    }
+
+    /// `sha256:` over the public key's SPKI, as key fingerprints are written.
+    pub fn fingerprint(&self) -> String {
+        signer_fingerprint(&self.public)
+    }
+
+    /// RFC 8032 5.1.6: `R = rB`, `S = r + H(R, A, M) s` with `r = H(prefix, M)`.
+    fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let r = wide_scalar(&[&self.prefix[..], message]);
+        let big_r = EdwardsPoint::mul_base(&r).compress().to_bytes();
+        let k = wide_scalar(&[&big_r, &self.public, message]);
+        let s = r + k * self.scalar;
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&big_r);
+        signature[32..].copy_from_slice(s.as_bytes());
+        signature
+    }
+
+    /// The provenance document for `record`, made on `host`.
+    pub fn document(&self, record: &KeyRecord, host: &str) -> String {
+        let mut statement = format!("{HEADER}\nsigner={}\nhost={host}\n", self.fingerprint());
+        statement.push_str(&format!(
+            "key_id={}\nfingerprint={}\nprime_hex={}\ngenerator={}\npublic_key_hex={}\n",
+            record.key_id,
+            record.fingerprint,
+            to_even_length_hex(&record.prime),
+            record.generator,
+            to_even_length_hex(&record.public_key)
+        ));
+        if let Some(ref metadata) = record.metadata {
+            for (name, value) in metadata.entries() {
+                statement.push_str(&format!("{}={value}\n", name.to_ascii_lowercase().replace('-', "_")));
+            }
+        }
+        let signature = self.sign(statement.as_bytes());
+        format!("{statement}signature={}\n", BASE64.encode(signature))
+    }
+}
+
+/// SHA-512 of the parts as a scalar mod the group order.
+fn wide_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+fn signer_fingerprint(public: &[u8; 32]) -> String {
+    fingerprint::fingerprint(FingerprintHash::Sha256, &[&SPKI_PREFIX[..], public].concat())
+}
+
+/// Reads an Ed25519 `PUBLIC KEY` PEM.
+pub fn load_public(path: &Path) -> Result<[u8; 32], String> {
+    let text = std::fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+    let der = pem::decode(pem::PUBLIC_KEY_LABEL, &text).map_err(|err| format!("{}: {err}", path.display()))?;
+    der.strip_prefix(&SPKI_PREFIX[..])
+        .and_then(|key| <[u8; 32]>::try_from(key).ok())
+        .ok_or_else(|| format!("{} is not an Ed25519 public key", path.display()))
+}
+
+/// RFC 8032 5.1.7, with `S` required to be reduced: `SB = R + H(R, A, M) A`.
+fn verify_signature(public: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    let Some(point) = CompressedEdwardsY(*public).decompress() else { return false };
+    let big_r: [u8; 32] = signature[..32].try_into().expect("32 bytes");
+    let s_bytes: [u8; 32] = signature[32..].try_into().expect("32 bytes");
+    let Some(s) = Option::<Scalar>::from(Scalar::from_canonical_bytes(s_bytes)) else { return false };
+    let k = wide_scalar(&[&big_r, public, message]);
+    EdwardsPoint::vartime_double_scalar_mul_basepoint(&k, &-point, &s).compress().to_bytes() == big_r
+}
+
+/// The fields of a verified document, in order.
+pub type Fields = Vec<(String, String)>;
+
+/// Checks `document` (its text or the base64 of it, as key records carry
+/// it) against `signer`: the signature, the signer line, and that the
+/// fingerprint and key ID are those of the stated public key and parameters.
+pub fn verify(document: &str, signer: &[u8; 32]) -> Result<Fields, String> {
+    let decoded;
+    let text = if document.trim_start().starts_with(HEADER) {
+        document
+    } else {
+        let bytes = BASE64.decode(document.trim()).map_err(|_| "not a provenance document".to_string())?;
+        decoded = String::from_utf8(bytes).map_err(|_| "not a provenance document".to_string())?;
+        &decoded
+    }
+    .trim_start();
+    let (statement, signature) = text
+        .trim_end_matches('\n')
+        .rsplit_once('\n')
+        .and_then(|(statement, last)| Some((statement, last.strip_prefix("signature=")?)))
+        .ok_or("the document has no signature line")?;
+    let statement = format!("{statement}\n");
+    let signature: [u8; 64] = BASE64
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("the signature is not 64 bytes of base64")?;
+    if !verify_signature(signer, statement.as_bytes(), &signature) {
+        return Err("the signature does not verify with this signer's key".into());
+    }
+    let fields: Fields = statement
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or(format!("the document has no {name}"))
+    };
+    if field("signer")? != signer_fingerprint(signer) {
+        return Err("the document names another signer".into());
+    }
+    let number = |name: &str, radix: u32| {
+        BigUint::parse_bytes(field(name)?.as_bytes(), radix).ok_or(format!("{name} is not a number"))
+    };
+    let (prime, generator, public_key) = (number("prime_hex", 16)?, number("generator", 10)?, number("public_key_hex", 16)?);
+    let spki = der::dh_subject_public_key_info(&prime, &generator, &public_key);
+    let recorded = field("fingerprint")?;
+    let hash = recorded
+        .split_once(':')
+        .and_then(|(name, _)| FingerprintHash::from_str(name, true).ok())
+        .ok_or("the fingerprint's hash is unknown")?;
+    if fingerprint::fingerprint(hash, &spki) != recorded || fingerprint::key_id(&spki) != field("key_id")? {
+        return Err("the fingerprint or key ID is not that of the stated public key".into());
+    }
+    Ok(fields)
+}
+
+/// The machine's host name, for the `host` line.
+pub fn host_name() -> String {
+    #[cfg(unix)]
+    {
+        let mut buffer = [0u8; 256];
+        // SAFETY: the buffer is writable for its whole length, which is what
+        // gethostname is told; it may not NUL-terminate on truncation, so the
+        // name is cut at the first NUL or the end.
+        if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } == 0 {
+            let end = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+            return String::from_utf8_lossy(&buffer[..end]).into_owned();
+        }
+    }
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".into())
+}
@@ -0,0 +1,86 @@
+use clap::ValueEnum;
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, QrCode};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::output::{KeyRecord, to_even_length_hex};
+
+/// Pixels per QR module in PNG output.
+const PNG_MODULE_PIXELS: usize = 8;
+/// Quiet-zone width, in modules, required around a QR symbol.
+const QUIET_ZONE_MODULES: usize = 4;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum QrTarget {
+    Private,
+    Public,
+}
+
+impl QrTarget {
+    fn payload(self, record: &KeyRecord) -> String {
+        match self {
+            QrTarget::Private => to_even_length_hex(record.private_key.expose()),
+            QrTarget::Public => to_even_length_hex(&record.public_key),
+        }
+    }
+}
+
+fn encode(target: QrTarget, record: &KeyRecord) -> Result<QrCode, String> {
+    QrCode::new(target.payload(record)).map_err(|err| format!("failed to build QR code: {err}"))
+}
+
+/// Renders the chosen key as a Unicode half-block QR code for the terminal.
+pub fn render_terminal(target: QrTarget, record: &KeyRecord) -> Result<String, String> {
+    let code = encode(target, record)?;
+    Ok(code.render::<unicode::Dense1x2>().build())
+}
+
+/// Writes the chosen key as a QR code image; the format follows the file
+/// extension (`.svg` or `.png`).
+pub fn write_file(target: QrTarget, record: &KeyRecord, path: &Path) -> Result<(), String> {
+    let code = encode(target, record)?;
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("svg") => {
+            let image = code.render::<svg::Color>().min_dimensions(256, 256).build();
+            std::fs::write(path, image)
+                .map_err(|err| format!("failed to write {}: {err}", path.display()))
+        }
+        Some("png") => write_png(&code, path),
+        _ => Err("QR output file must end in .svg or .png".into()),
+    }
+}
+
+fn write_png(code: &QrCode, path: &Path) -> Result<(), String> {
+    let modules = code.width();
+    let colors = code.to_colors();
+    let side = (modules + 2 * QUIET_ZONE_MODULES) * PNG_MODULE_PIXELS;
+
+    let mut pixels = vec![0xFFu8; side * side];
+    for y in 0..modules {
+        for x in 0..modules {
+            if colors[y * modules + x] != Color::Dark {
+                continue;
+            }
+            let top = (y + QUIET_ZONE_MODULES) * PNG_MODULE_PIXELS;
+            let left = (x + QUIET_ZONE_MODULES) * PNG_MODULE_PIXELS;
+            for row in top..top + PNG_MODULE_PIXELS {
+                pixels[row * side + left..row * side + left + PNG_MODULE_PIXELS].fill(0);
+            }
+        }
+    }
+
+    let write_err = |err: &dyn std::fmt::Display| format!("failed to write {}: {err}", path.display());
+    let file = File::create(path).map_err(|err| write_err(&err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), side as u32, side as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|err| write_err(&err))?;
+    writer.write_image_data(&pixels).map_err(|err| write_err(&err))
+}
@@ -0,0 +1,273 @@
+//! The binary's [`KeySource`]. Every random value the tool produces
+//! (private keys, salts, nonces, wrapping keys, Shamir coefficients, token
+//! object IDs and temporary file names) is drawn through here, so changing
+//! the source here changes it everywhere. Only the `health` self-test reads
+//! the OS directly, since the OS source is what it tests. It is the operating system's CSPRNG unless
+//! `--rng` selects a userspace generator seeded from it. `--hw-entropy` and
+//! `--entropy-file`/`--entropy-stdin` mix hardware sources and user-supplied
+//! seed material into whatever is drawn from the OS.
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use create_private_key::{Error, KeySource};
+use rand::rngs::OsRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::drbg::{self, HmacDrbg};
+use crate::hardware::HwSource;
+
+/// Domain separation for the hash of the mixed-in inputs.
+const MIX_TAG: &[u8] = b"create-private-key entropy mix v1";
+/// Domain separation for the hash of the seed files.
+const SEED_TAG: &[u8] = b"create-private-key seed input v1";
+/// Bytes read from each hardware source per draw from the OS.
+const HARDWARE_SAMPLE_BYTES: usize = 32;
+/// Most bytes read from each seed input, so a device such as
+/// `/dev/urandom` can be named without reading forever.
+const MAX_SEED_BYTES: u64 = 1 << 20;
+
+/// `--rng`: the generator random bytes are drawn from.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RngBackend {
+    /// The operating system's CSPRNG (getrandom).
+    #[default]
+    Os,
+    /// ChaCha20 keyed from the operating system's CSPRNG.
+    Chacha20,
+    /// HMAC_DRBG with SHA-256 (SP 800-90A), seeded from the operating
+    /// system's CSPRNG.
+    HmacDrbg,
+}
+
+impl RngBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            RngBackend::Os => "os",
+            RngBackend::Chacha20 => "chacha20",
+            RngBackend::HmacDrbg => "hmac-drbg",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            RngBackend::Os => "operating system CSPRNG (getrandom)",
+            RngBackend::Chacha20 => "ChaCha20 seeded from the operating system CSPRNG",
+            RngBackend::HmacDrbg => "HMAC_DRBG (SHA-256) seeded from the operating system CSPRNG",
+        }
+    }
+}
+
+/// Inputs to `--rng hmac-drbg` besides the entropy.
+pub struct DrbgOptions {
+    pub personalization: Vec<u8>,
+    pub additional_input: Vec<u8>,
+    pub reseed_interval: u64,
+}
+
+impl Default for DrbgOptions {
+    fn default() -> Self {
+        DrbgOptions {
+            personalization: Vec::new(),
+            additional_input: Vec::new(),
+            reseed_interval: drbg::MAX_RESEED_INTERVAL,
+        }
+    }
+}
+
+enum Generator {
+    Os,
+    ChaCha20(Box<ChaCha20Rng>),
+    HmacDrbg {
+        drbg: Box<HmacDrbg>,
+        additional_input: Vec<u8>,
+    },
+}
+
+static GENERATOR: Mutex<Generator> = Mutex::new(Generator::Os);
+static MIXING: Mutex<Mixing> = Mutex::new(Mixing {
+    hardware: Vec::new(),
+    seed: None,
+    draws: 0,
+});
+
+/// What is mixed into every draw from the OS CSPRNG.
+struct Mixing {
+    hardware: Vec<HwSource>,
+    seed: Option<Seed>,
+    /// Draws so far, so a seed-only mix differs between draws.
+    draws: u64,
+}
+
+/// `--entropy-file` and `--entropy-stdin` input, hashed on reading.
+pub struct Seed {
+    digest: [u8; 32],
+    sources: Vec<&'static str>,
+}
+
+impl Seed {
+    /// Reads and hashes up to [`MAX_SEED_BYTES`] from each file and from
+    /// stdin if `stdin` is set; `None` when there is nothing to read. Empty
+    /// inputs are an error, as they were surely not what was meant.
+    pub fn read(files: &[PathBuf], stdin: bool) -> Result<Option<Seed>, Error> {
+        if files.is_empty() && !stdin {
+            return Ok(None);
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(SEED_TAG);
+        let mut sources = Vec::new();
+        for path in files {
+            let file = std::fs::File::open(path).map_err(|source| Error::Io {
+                context: format!("failed to open entropy file {}", path.display()),
+                source,
+            })?;
+            hash_input(&mut hasher, file, &path.display().to_string())?;
+            if !sources.contains(&"entropy-file") {
+                sources.push("entropy-file");
+            }
+        }
+        if stdin {
+            hash_input(&mut hasher, std::io::stdin().lock(), "stdin")?;
+            sources.push("entropy-stdin");
+        }
+        Ok(Some(Seed {
+            digest: hasher.finalize().into(),
+            sources,
+        }))
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.digest.zeroize();
+    }
+}
+
+/// Feeds `input` to `hasher` length-prefixed, so the split between inputs
+/// is part of the hash.
+fn hash_input(hasher: &mut Sha256, input: impl Read, name: &str) -> Result<(), Error> {
+    let mut contents = Vec::new();
+    input
+        .take(MAX_SEED_BYTES)
+        .read_to_end(&mut contents)
+        .map_err(|source| Error::Io {
+            context: format!("failed to read entropy from {name}"),
+            source,
+        })?;
+    if contents.is_empty() {
+        return Err(Error::Validation(format!("entropy input {name} is empty")));
+    }
+    hasher.update((contents.len() as u64).to_be_bytes());
+    hasher.update(&contents);
+    contents.zeroize();
+    Ok(())
+}
+
+/// Switches the tool's source to `backend`, mixing `hardware` and `seed`
+/// into every draw from the OS, and seeds it now so a broken OS RNG or
+/// hardware source fails before any work is done. `options` only apply to
+/// HMAC_DRBG.
+pub fn select(backend: RngBackend, options: DrbgOptions, hardware: Vec<HwSource>, seed: Option<Seed>) -> Result<(), Error> {
+    let probe = !hardware.is_empty();
+    *MIXING.lock().expect("entropy mixing lock") = Mixing { hardware, seed, draws: 0 };
+    let generator = match backend {
+        RngBackend::Os => {
+            if probe {
+                os_entropy(&mut [0u8; HARDWARE_SAMPLE_BYTES])?;
+            }
+            Generator::Os
+        }
+        RngBackend::Chacha20 => {
+            let mut seed = [0u8; 32];
+            os_entropy(&mut seed)?;
+            let rng = ChaCha20Rng::from_seed(seed);
+            seed.zeroize();
+            Generator::ChaCha20(Box::new(rng))
+        }
+        RngBackend::HmacDrbg => {
+            let mut entropy = [0u8; drbg::ENTROPY_BYTES];
+            let mut nonce = [0u8; drbg::NONCE_BYTES];
+            os_entropy(&mut entropy)?;
+            os_entropy(&mut nonce)?;
+            let drbg = HmacDrbg::new(&entropy, &nonce, &options.personalization, options.reseed_interval);
+            entropy.zeroize();
+            Generator::HmacDrbg {
+                drbg: Box::new(drbg),
+                additional_input: options.additional_input,
+            }
+        }
+    };
+    *GENERATOR.lock().expect("random source lock") = generator;
+    Ok(())
+}
+
+/// Fills `dest` from the tool's source.
+pub fn fill(dest: &mut [u8]) -> Result<(), Error> {
+    match *GENERATOR.lock().expect("random source lock") {
+        Generator::Os => os_entropy(dest),
+        Generator::ChaCha20(ref mut rng) => rng.fill(dest),
+        Generator::HmacDrbg {
+            ref mut drbg,
+            ref additional_input,
+        } => drbg.fill(dest, additional_input, os_entropy),
+    }
+}
+
+/// The entropy sources that contributed to the tool's output: the OS
+/// followed by each `--hw-entropy` source and the seed inputs.
+pub fn sources() -> Vec<&'static str> {
+    let mixing = MIXING.lock().expect("entropy mixing lock");
+    let hardware = mixing.hardware.iter().map(|source| source.name());
+    let seed = mixing.seed.iter().flat_map(|seed| seed.sources.iter().copied());
+    std::iter::once("os").chain(hardware).chain(seed).collect()
+}
+
+/// Fills `dest` from the OS CSPRNG, then XORs in a ChaCha20 stream keyed by
+/// SHA-256 of the seed inputs' hash, a draw counter and a fresh sample from
+/// each hardware source. The OS output alone stays as strong as it was,
+/// however weak or hostile the mixed-in inputs are.
+fn os_entropy(dest: &mut [u8]) -> Result<(), Error> {
+    OsRng.fill(dest)?;
+    let mut mixing = MIXING.lock().expect("entropy mixing lock");
+    if mixing.hardware.is_empty() && mixing.seed.is_none() {
+        return Ok(());
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(MIX_TAG);
+    if let Some(ref seed) = mixing.seed {
+        hasher.update(seed.digest);
+    }
+    hasher.update(mixing.draws.to_be_bytes());
+    mixing.draws += 1;
+    let mut sample = [0u8; HARDWARE_SAMPLE_BYTES];
+    for source in mixing.hardware.iter() {
+        let read = source.read(&mut sample);
+        hasher.update(sample);
+        sample.zeroize();
+        read?;
+    }
+    let mut seed: [u8; 32] = hasher.finalize().into();
+    let mut stream = ChaCha20Rng::from_seed(seed);
+    seed.zeroize();
+    let mut mask = vec![0u8; dest.len()];
+    stream.fill_bytes(&mut mask);
+    for (byte, mask) in dest.iter_mut().zip(&mask) {
+        *byte ^= mask;
+    }
+    mask.zeroize();
+    Ok(())
+}
+
+/// The tool's source as a value to pass to the library.
+pub struct Source;
+
+impl KeySource for Source {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        fill(dest)
+    }
+}
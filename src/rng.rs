@@ -0,0 +1,31 @@
+//! Injectable randomness.
+//!
+//! Everything that draws random numbers takes a [`KeySource`]. Any
+//! `rand` generator marked `CryptoRng` is one already (`OsRng`, a seeded
+//! `ChaCha20Rng` in tests); implement the trait directly for sources with
+//! their own failure modes, such as an HSM or a DRBG that needs reseeding.
+
+use num_bigint::BigUint;
+use rand::{CryptoRng, RngCore};
+
+use crate::error::{Error, Result};
+
+/// A cryptographically secure source of random bytes.
+pub trait KeySource {
+    /// Fills `dest` entirely, or fails with [`Error::Rng`].
+    fn fill(&mut self, dest: &mut [u8]) -> Result<()>;
+}
+
+impl<R: RngCore + CryptoRng + ?Sized> KeySource for R {
+    fn fill(&mut self, dest: &mut [u8]) -> Result<()> {
+        self.try_fill_bytes(dest).map_err(|err| Error::Rng(err.to_string()))
+    }
+}
+
+/// A uniform integer in `[low, high)`, by rejection sampling.
+pub fn random_range<S: KeySource + ?Sized>(source: &mut S, low: &BigUint, high: &BigUint) -> Result<BigUint> {
+    if high <= low {
+        return Err(Error::Validation("empty random range".into()));
+    }
+    create_private_key_core::random_range(low, high, |dest| source.fill(dest))
+}
@@ -0,0 +1,197 @@
+//! `--rpc`: JSON-RPC 2.0 over stdin/stdout, one request or response per line.
+//!
+//! | Method          | Params                                                        | Result          |
+//! |-----------------|---------------------------------------------------------------|-----------------|
+//! | `generate`      | group fields, `label`, `operator`                             | `GeneratedKey`  |
+//! | `derive-public` | group fields, `private_key`                                   | `key_id`, `fingerprint`, `public_key` |
+//! | `shared-secret` | group fields, `private_key`, `peer_public_key`, `hkdf_*`      | `secret`        |
+//! | `validate`      | group fields, `public_key`                                    | `Validation`    |
+//! | `groups`        |                                                               | `[GroupInfo]`   |
+//!
+//! Group fields (`group`, `prime`, `generator`) are optional; without them a
+//! request uses the group given on the command line, parsed once at startup.
+//! Keys and secrets are hex. Requests without an `id` are notifications and
+//! get no response.
+
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::api::{self, Group, GroupSpec};
+use crate::fingerprint::FingerprintHash;
+use crate::output::{KeyRecord, to_even_length_hex};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Application errors: bad parameters, out-of-range keys, and the like.
+const REQUEST_FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Envelope {
+    jsonrpc: Option<String>,
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DerivePublic {
+    #[serde(flatten)]
+    group: GroupSpec,
+    private_key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SharedSecret {
+    #[serde(flatten)]
+    group: GroupSpec,
+    private_key: String,
+    peer_public_key: String,
+    hkdf_length: Option<usize>,
+    #[serde(default)]
+    hkdf_salt: String,
+    #[serde(default)]
+    hkdf_info: String,
+}
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error {
+            code: REQUEST_FAILED,
+            message,
+        }
+    }
+}
+
+/// Answers requests until stdin closes.
+pub fn serve(default: Group) -> Result<(), String> {
+    let stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    for line in stdin.lines() {
+        let line = line.map_err(|err| format!("failed to read stdin: {err}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(&line, &default) {
+            writeln!(stdout, "{response}")
+                .and_then(|()| stdout.flush())
+                .map_err(|err| format!("failed to write stdout: {err}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn handle(line: &str, default: &Group) -> Option<Value> {
+    let (id, result) = match serde_json::from_str::<Value>(line) {
+        Err(err) => (Value::Null, Err(error(PARSE_ERROR, format!("parse error: {err}")))),
+        Ok(value) => match serde_json::from_value::<Envelope>(value) {
+            Err(err) => (Value::Null, Err(error(INVALID_REQUEST, format!("invalid request: {err}")))),
+            Ok(request) if request.jsonrpc.as_deref() != Some("2.0") => (
+                request.id.unwrap_or(Value::Null),
+                Err(error(INVALID_REQUEST, "jsonrpc must be \"2.0\"".into())),
+            ),
+            Ok(request) => {
+                let result = call(&request.method, request.params, default);
+                (request.id?, result)
+            }
+        },
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        }),
+    })
+}
+
+fn call(method: &str, params: Value, default: &Group) -> Result<Value, Error> {
+    match method {
+        "generate" => {
+            let request: api::GenerateRequest = parse_params(params)?;
+            let group = resolve(&request.group, default)?;
+            Ok(value(&api::generated_key(&api::generate_record(&group, request.label, request.operator)?)))
+        }
+        "derive-public" => {
+            let request: DerivePublic = parse_params(params)?;
+            let group = resolve(&request.group, default)?;
+            let private_key = api::parse_hex(&request.private_key)?;
+            api::check_private_key(&group, &private_key)?;
+            let record = KeyRecord::new(group.prime, group.generator, private_key, None, FingerprintHash::Sha256);
+            Ok(json!({
+                "key_id": record.key_id,
+                "fingerprint": record.fingerprint,
+                "public_key": to_even_length_hex(&record.public_key),
+            }))
+        }
+        "shared-secret" => {
+            let request: SharedSecret = parse_params(params)?;
+            let group = resolve(&request.group, default)?;
+            let hkdf = match request.hkdf_length {
+                Some(length) => Some(api::Hkdf {
+                    length,
+                    salt: decode_hex(&request.hkdf_salt)?,
+                    info: request.hkdf_info.into_bytes(),
+                }),
+                None => None,
+            };
+            let secret = api::shared_secret(
+                &group,
+                &api::parse_hex(&request.private_key)?,
+                &api::parse_hex(&request.peer_public_key)?,
+                hkdf.as_ref(),
+            )?;
+            let secret: String = secret.iter().map(|byte| format!("{byte:02X}")).collect();
+            Ok(json!({ "secret": secret }))
+        }
+        "validate" => {
+            let request: api::ValidateRequest = parse_params(params)?;
+            let result = resolve(&request.group, default)
+                .map_err(|err| err.message)
+                .and_then(|group| api::check_public_key(&group, request.public_key.as_deref()));
+            Ok(value(&api::validation(result)))
+        }
+        "groups" => Ok(value(&api::groups())),
+        _ => Err(error(METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+    }
+}
+
+/// Absent or `null` params are the same as `{}`.
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, Error> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| error(INVALID_PARAMS, format!("invalid params: {err}")))
+}
+
+fn resolve(spec: &GroupSpec, default: &Group) -> Result<Group, Error> {
+    if spec.is_empty() {
+        return Ok(default.clone());
+    }
+    Ok(spec.resolve()?)
+}
+
+fn value(result: &impl Serialize) -> Value {
+    serde_json::to_value(result).expect("result serializes")
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    data_encoding::HEXLOWER_PERMISSIVE
+        .decode(value.as_bytes())
+        .map_err(|err| format!("'{value}' is not valid hex: {err}"))
+}
+
+fn error(code: i64, message: String) -> Error {
+    Error { code, message }
+}
@@ -0,0 +1,64 @@
+//! [`PrivateKey`]: a private exponent that has to be asked for by name.
+
+use std::fmt;
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+use secrecy::{ExposeSecret, SecretBox};
+use zeroize::Zeroize;
+
+/// A DH private exponent. It has no `Display`, its `Debug` prints
+/// `PrivateKey([REDACTED])`, and the number is only reachable through
+/// [`PrivateKey::expose`], so it cannot end up in a log line or an error
+/// message by accident. Its digits are overwritten when it is dropped.
+pub struct PrivateKey(SecretBox<Exponent>);
+
+impl PrivateKey {
+    pub fn new(value: BigUint) -> Self {
+        PrivateKey(SecretBox::new(Box::new(Exponent(value))))
+    }
+
+    /// The exponent itself. Every use of the secret goes through here, so
+    /// the call sites are easy to find and review.
+    pub fn expose(&self) -> &BigUint {
+        &self.0.expose_secret().0
+    }
+}
+
+impl From<BigUint> for PrivateKey {
+    fn from(value: BigUint) -> Self {
+        PrivateKey::new(value)
+    }
+}
+
+impl Clone for PrivateKey {
+    fn clone(&self) -> Self {
+        PrivateKey::new(self.expose().clone())
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PrivateKey([REDACTED])")
+    }
+}
+
+struct Exponent(BigUint);
+
+impl Zeroize for Exponent {
+    fn zeroize(&mut self) {
+        // BigUint cannot wipe its digits, but assigning a value of the same
+        // length rewrites them in the existing allocation. The value ends in
+        // a 1 so normalizing does not shrink (and free) the buffer before
+        // the writes are observed. Spare capacity is not reached; this is
+        // best effort.
+        let digits = self.0.iter_u32_digits().len();
+        if digits > 0 {
+            let mut filler = vec![0u32; digits];
+            filler[digits - 1] = 1;
+            self.0.assign_from_slice(&filler);
+            std::hint::black_box(&self.0);
+        }
+        self.0.set_zero();
+    }
+}
@@ -0,0 +1,199 @@
+//! Known-answer tests for the primitives behind every key: the HMAC_DRBG,
+//! modular exponentiation over the published groups, and the KDFs. Run by
+//! the `selftest` subcommand and, with `--self-test`, before anything else.
+
+use create_private_key::{dh, params};
+use create_private_key_core::{
+    self as ffdh, RFC3526_MODP14_PRIME_HEX, RFC5114_2048_256_PRIME_HEX, RFC7919_FFDHE2048_PRIME_HEX,
+};
+use data_encoding::HEXLOWER;
+use num_bigint::BigUint;
+use num_traits::One;
+use sha2::{Digest, Sha256};
+
+use crate::drbg::{self, HmacDrbg};
+use crate::known_groups::{RFC5114_2048_256_GENERATOR_HEX, RFC5114_2048_256_ORDER_HEX};
+
+/// Fixed private exponents for the exchange tests, below every group's
+/// subgroup order.
+const PRIVATE_A_HEX: &str = "444E81C3A5EE49827E7BA55FB5BAFB001A195511A526C2903398F2DBBDD07008";
+const PRIVATE_B_HEX: &str = "3C4CBE52945A605C795C32B58CC25DE749B7410B9A71DA2A8DDA06B1172A85D5";
+
+/// A group and the SHA-256 of both public values and the shared secret
+/// (each left-padded to the prime's length) for the fixed exponents,
+/// computed independently of this tool.
+struct Exchange {
+    name: &'static str,
+    prime_hex: &'static str,
+    generator_hex: &'static str,
+    /// Subgroup order; `None` for a safe prime, where it is `(p - 1) / 2`.
+    order_hex: Option<&'static str>,
+    public_a: &'static str,
+    public_b: &'static str,
+    shared: &'static str,
+}
+
+const EXCHANGES: [Exchange; 3] = [
+    Exchange {
+        name: "modpow_rfc3526_modp14",
+        prime_hex: RFC3526_MODP14_PRIME_HEX,
+        generator_hex: "2",
+        order_hex: None,
+        public_a: "2436a9ffa7cf23f9cd33a5860becdc8e8cb58d752576b58052033693c0338243",
+        public_b: "db1a6f76afd1184e1b0a996263512a913b1b28bc25bbb6b297be53bbf4a88086",
+        shared: "52c7bb6deb4f044f538a6cf9e56ee7bf0085dfc1a693d537bb3e06e1c22b3297",
+    },
+    Exchange {
+        name: "modpow_rfc7919_ffdhe2048",
+        prime_hex: RFC7919_FFDHE2048_PRIME_HEX,
+        generator_hex: "2",
+        order_hex: None,
+        public_a: "d294740b916e60b08712bd44027eed12670c780c735b07492f5f0c6c4a8ada50",
+        public_b: "16b6a66d93b4623697e4202895c9aa22b4f29d266209e7ba8be745a3140786ab",
+        shared: "57635e476685762e2662a089d6b4e05d665eef6d55d3f38c72e2d0de0e318117",
+    },
+    Exchange {
+        name: "modpow_rfc5114_2048_256",
+        prime_hex: RFC5114_2048_256_PRIME_HEX,
+        generator_hex: RFC5114_2048_256_GENERATOR_HEX,
+        order_hex: Some(RFC5114_2048_256_ORDER_HEX),
+        public_a: "4810231a3328bd48c876ca2e14d4c65749e7e772cf9b01acfb742e798c214fef",
+        public_b: "c7c10c068336f379482274b986098c1c51a5b6fb85cb491c089fc28a06cc9146",
+        shared: "84270dfd40ed568045533493c50ad9a75c5696e8bd60497265aeaf9abacf0a9a",
+    },
+];
+
+/// NIST CAVP HMAC_DRBG SHA-256 vector (no reseed, no personalization, no
+/// additional input, COUNT = 0): the second 1024-bit generate call.
+const DRBG_ENTROPY_HEX: &str = "ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488";
+const DRBG_NONCE_HEX: &str = "659ba96c601dc69fc902940805ec0ca8";
+const DRBG_OUTPUT_HEX: &str = concat!(
+    "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89",
+    "d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1",
+    "07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668",
+    "961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8"
+);
+
+/// RFC 5869 test case 1.
+const HKDF_OUTPUT_HEX: &str = "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865";
+
+/// RFC 7914 section 11, first vector: PBKDF2-HMAC-SHA256("passwd", "salt", 1).
+#[cfg(feature = "pem")]
+const PBKDF2_OUTPUT_HEX: &str = concat!(
+    "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc",
+    "49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+);
+
+/// RFC 7914 section 12, second vector: scrypt("password", "NaCl", N = 1024,
+/// r = 8, p = 16).
+#[cfg(feature = "pem")]
+const SCRYPT_OUTPUT_HEX: &str = concat!(
+    "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162",
+    "2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+);
+
+/// Runs every test, returning each name with whether it passed.
+pub fn run() -> Vec<(&'static str, bool)> {
+    let mut results = vec![("hmac_drbg_sha256", hmac_drbg())];
+    for exchange in &EXCHANGES {
+        results.push((exchange.name, exchange.check()));
+    }
+    results.push(("hkdf_sha256", hkdf()));
+    #[cfg(feature = "pem")]
+    {
+        results.push(("pbkdf2_hmac_sha256", pbkdf2()));
+        results.push(("scrypt", scrypt()));
+    }
+    for &(name, passed) in &results {
+        tracing::debug!(test = name, passed, "self-test");
+    }
+    results
+}
+
+/// The power-on self-test: [`run`], failing with the names of the tests
+/// that did not pass.
+pub fn power_on() -> Result<(), String> {
+    let failed: Vec<&str> = run().into_iter().filter(|&(_, passed)| !passed).map(|(name, _)| name).collect();
+    if !failed.is_empty() {
+        return Err(format!("self-test failed: {}", failed.join(", ")));
+    }
+    tracing::info!("self-tests passed");
+    Ok(())
+}
+
+fn hmac_drbg() -> bool {
+    let entropy = hex(DRBG_ENTROPY_HEX);
+    let nonce = hex(DRBG_NONCE_HEX);
+    let mut generator = HmacDrbg::new(&entropy, &nonce, &[], drbg::MAX_RESEED_INTERVAL);
+    let mut output = [0u8; 128];
+    let no_reseed = |_: &mut [u8]| Err(());
+    generator.fill(&mut output, &[], no_reseed).is_ok()
+        && generator.fill(&mut output, &[], no_reseed).is_ok()
+        && HEXLOWER.encode(&output) == DRBG_OUTPUT_HEX
+}
+
+impl Exchange {
+    fn check(&self) -> bool {
+        let prime = params::parse_hex_biguint(self.prime_hex);
+        let generator = params::parse_hex_biguint(self.generator_hex);
+        let order = match self.order_hex {
+            Some(order) => params::parse_hex_biguint(order),
+            None => (&prime - 1u32) >> 1,
+        };
+        if generator.modpow(&order, &prime) != BigUint::one() {
+            return false;
+        }
+        let private_a = params::parse_hex_biguint(PRIVATE_A_HEX);
+        let private_b = params::parse_hex_biguint(PRIVATE_B_HEX);
+        let public_a = ffdh::public_key(&prime, &generator, &private_a);
+        let public_b = ffdh::public_key(&prime, &generator, &private_b);
+        let width = prime.to_bytes_be().len();
+        let (Ok(shared_ab), Ok(shared_ba)) = (
+            dh::shared_secret(&prime, &private_a, &public_b),
+            dh::shared_secret(&prime, &private_b, &public_a),
+        ) else {
+            return false;
+        };
+        digest(&padded(&public_a, width)) == self.public_a
+            && digest(&padded(&public_b, width)) == self.public_b
+            && shared_ab == shared_ba
+            && digest(&shared_ab) == self.shared
+    }
+}
+
+fn hkdf() -> bool {
+    let ikm = [0x0b; 22];
+    let salt: Vec<u8> = (0x00..=0x0c).collect();
+    let info: Vec<u8> = (0xf0..=0xf9).collect();
+    dh::hkdf_sha256(&ikm, &salt, &info, 42).is_ok_and(|okm| HEXLOWER.encode(&okm) == HKDF_OUTPUT_HEX)
+}
+
+#[cfg(feature = "pem")]
+fn pbkdf2() -> bool {
+    let mut output = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha256>(b"passwd", b"salt", 1, &mut output);
+    HEXLOWER.encode(&output) == PBKDF2_OUTPUT_HEX
+}
+
+#[cfg(feature = "pem")]
+fn scrypt() -> bool {
+    let mut output = [0u8; 64];
+    scrypt::Params::new(10, 8, 16, output.len())
+        .is_ok_and(|params| scrypt::scrypt(b"password", b"NaCl", &params, &mut output).is_ok())
+        && HEXLOWER.encode(&output) == SCRYPT_OUTPUT_HEX
+}
+
+fn hex(digits: &str) -> Vec<u8> {
+    HEXLOWER.decode(digits.as_bytes()).expect("built-in vector is hex")
+}
+
+fn padded(value: &BigUint, width: usize) -> Vec<u8> {
+    let bytes = value.to_bytes_be();
+    let mut padded = vec![0u8; width - bytes.len()];
+    padded.extend_from_slice(&bytes);
+    padded
+}
+
+fn digest(bytes: &[u8]) -> String {
+    HEXLOWER.encode(&Sha256::digest(bytes))
+}
@@ -0,0 +1,21 @@
+//! `#[serde(with = "serde_hex")]`: big integers as even-length hex strings
+//! without a prefix, the encoding `cpk-v1` envelopes use.
+
+use std::borrow::Borrow;
+
+use num_bigint::BigUint;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer, T: Borrow<BigUint>>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    let hex = format!("{:X}", value.borrow());
+    if hex.len() % 2 == 1 {
+        serializer.serialize_str(&format!("0{hex}"))
+    } else {
+        serializer.serialize_str(&hex)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    BigUint::parse_bytes(hex.as_bytes(), 16).ok_or_else(|| serde::de::Error::custom(format!("'{hex}' is not valid hex")))
+}
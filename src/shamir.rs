@@ -0,0 +1,314 @@
+//! Shamir secret sharing over GF(2^8) for splitting private keys.
+//!
+//! The secret is the PKCS #8 DER of the key, so the shares are enough on
+//! their own to rebuild a usable key file. Each share is a PEM block
+//! (`CPK KEY SHARE`) around:
+//!
+//! ```text
+//! version u8 (1) | threshold u8 | index u8 | SHA-256(secret)[..8] | share bytes
+//! ```
+//!
+//! The digest lets `combine` reject shares from different keys and confirm
+//! the reconstruction instead of emitting garbage.
+
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+use crate::pem;
+use crate::random;
+
+pub const SHARE_LABEL: &str = "CPK KEY SHARE";
+
+const VERSION: u8 = 1;
+const DIGEST_LEN: usize = 8;
+const HEADER_LEN: usize = 3 + DIGEST_LEN;
+
+/// A `K-of-N` split: any `threshold` of `shares` shares rebuild the key.
+#[derive(Clone, Copy, Debug)]
+pub struct SplitSpec {
+    pub threshold: u8,
+    pub shares: u8,
+}
+
+impl FromStr for SplitSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid split '{spec}' (expected K-of-N, e.g. 3-of-5)");
+        let (threshold, shares) = spec.split_once("-of-").ok_or_else(invalid)?;
+        let threshold: u8 = threshold.parse().map_err(|_| invalid())?;
+        let shares: u8 = shares.parse().map_err(|_| invalid())?;
+        if threshold < 2 || threshold > shares {
+            return Err(format!("split threshold must be between 2 and the share count, got {spec}"));
+        }
+        Ok(SplitSpec { threshold, shares })
+    }
+}
+
+/// One decoded share.
+pub struct Share {
+    threshold: u8,
+    index: u8,
+    digest: [u8; DIGEST_LEN],
+    bytes: Vec<u8>,
+}
+
+/// Splits `secret` and returns the PEM text of each share, in index order
+/// (1 to N). `key_id` is written above each block so holders can tell their
+/// shares apart.
+pub fn split(secret: &[u8], spec: SplitSpec, key_id: &str) -> Result<Vec<String>, String> {
+    let digest = secret_digest(secret);
+
+    // One random polynomial of degree K-1 per secret byte; the constant
+    // term is the byte itself.
+    let mut coefficients = Zeroizing::new(vec![0u8; secret.len() * (usize::from(spec.threshold) - 1)]);
+    random::fill(&mut coefficients)?;
+
+    Ok((1..=spec.shares)
+        .map(|x| {
+            let mut payload = vec![VERSION, spec.threshold, x];
+            payload.extend_from_slice(&digest);
+            for (position, &byte) in secret.iter().enumerate() {
+                let terms = &coefficients[position * (usize::from(spec.threshold) - 1)..][..usize::from(spec.threshold) - 1];
+                // Horner's rule, highest degree first.
+                let y = terms.iter().rev().fold(0u8, |acc, &c| gf_mul(acc, x) ^ c);
+                payload.push(gf_mul(y, x) ^ byte);
+            }
+            format!(
+                "Key-Id: {key_id}\nShare: {x} of {} (any {} rebuild the key)\n{}",
+                spec.shares,
+                spec.threshold,
+                pem::encode(SHARE_LABEL, &payload)
+            )
+        })
+        .collect())
+}
+
+/// Parses one share file.
+pub fn parse(text: &str) -> Result<Share, String> {
+    let payload = pem::decode(SHARE_LABEL, text)?;
+    if payload.len() <= HEADER_LEN || payload[0] != VERSION {
+        return Err("unsupported or truncated key share".into());
+    }
+    let (threshold, index) = (payload[1], payload[2]);
+    if threshold < 2 || index == 0 {
+        return Err("corrupted key share header".into());
+    }
+    Ok(Share {
+        threshold,
+        index,
+        digest: payload[3..HEADER_LEN].try_into().expect("digest length"),
+        bytes: payload[HEADER_LEN..].to_vec(),
+    })
+}
+
+/// Rebuilds the secret from at least `threshold` shares of the same key.
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let first = shares.first().ok_or("no shares given")?;
+    if shares
+        .iter()
+        .any(|share| share.digest != first.digest || share.threshold != first.threshold || share.bytes.len() != first.bytes.len())
+    {
+        return Err("shares belong to different keys or splits".into());
+    }
+    let mut indexes: Vec<u8> = shares.iter().map(|share| share.index).collect();
+    indexes.sort_unstable();
+    indexes.dedup();
+    if indexes.len() != shares.len() {
+        return Err("the same share was given more than once".into());
+    }
+    if shares.len() < usize::from(first.threshold) {
+        return Err(format!(
+            "{} shares given but {} are needed to rebuild the key",
+            shares.len(),
+            first.threshold
+        ));
+    }
+
+    let used = &shares[..usize::from(first.threshold)];
+    // Lagrange basis at x = 0: l_i = prod_{j != i} x_j / (x_j - x_i); in
+    // GF(2^8) subtraction is XOR.
+    let basis: Vec<u8> = used
+        .iter()
+        .map(|share| {
+            used.iter()
+                .filter(|other| other.index != share.index)
+                .fold(1u8, |acc, other| gf_mul(acc, gf_div(other.index, other.index ^ share.index)))
+        })
+        .collect();
+    let secret: Zeroizing<Vec<u8>> = Zeroizing::new(
+        (0..first.bytes.len())
+            .map(|position| {
+                used.iter()
+                    .zip(&basis)
+                    .fold(0u8, |acc, (share, &l)| acc ^ gf_mul(share.bytes[position], l))
+            })
+            .collect(),
+    );
+
+    if secret_digest(&secret) != first.digest {
+        return Err("shares are corrupted: the rebuilt key does not match its digest".into());
+    }
+    Ok(secret)
+}
+
+fn secret_digest(secret: &[u8]) -> [u8; DIGEST_LEN] {
+    Sha256::digest(secret)[..DIGEST_LEN].try_into().expect("digest length")
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1B;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// `a / b` for non-zero `b`, using b^254 = b^-1.
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1u8;
+    let mut power = b;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            inverse = gf_mul(inverse, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"0\x82\x01\x02 stand-in for a PKCS #8 private key";
+
+    fn shares(spec: &str) -> Vec<Share> {
+        split(SECRET, spec.parse().unwrap(), "test-key")
+            .unwrap()
+            .iter()
+            .map(|text| parse(text).unwrap())
+            .collect()
+    }
+
+    fn pick(shares: &[Share], indexes: &[u8]) -> Vec<Share> {
+        indexes
+            .iter()
+            .map(|&index| {
+                let share = &shares[usize::from(index) - 1];
+                Share {
+                    threshold: share.threshold,
+                    index: share.index,
+                    digest: share.digest,
+                    bytes: share.bytes.clone(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_threshold_subset_rebuilds_the_secret() {
+        for (spec, subsets) in [
+            ("2-of-2", vec![vec![1, 2], vec![2, 1]]),
+            ("2-of-3", vec![vec![1, 2], vec![1, 3], vec![2, 3], vec![3, 1]]),
+            ("3-of-5", vec![vec![1, 2, 3], vec![1, 3, 5], vec![2, 4, 5], vec![5, 4, 3], vec![1, 4, 5]]),
+            ("5-of-5", vec![vec![1, 2, 3, 4, 5], vec![5, 3, 1, 4, 2]]),
+        ] {
+            let shares = shares(spec);
+            assert_eq!(shares.len(), usize::from(spec.parse::<SplitSpec>().unwrap().shares));
+            for subset in subsets {
+                assert_eq!(combine(&pick(&shares, &subset)).unwrap().as_slice(), SECRET, "{spec} {subset:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn more_than_threshold_shares_rebuild_the_secret() {
+        let shares = shares("3-of-5");
+        assert_eq!(combine(&shares).unwrap().as_slice(), SECRET);
+        assert_eq!(combine(&pick(&shares, &[4, 2, 5, 1])).unwrap().as_slice(), SECRET);
+    }
+
+    #[test]
+    fn fewer_than_threshold_shares_are_rejected() {
+        let shares = shares("3-of-5");
+        let err = combine(&pick(&shares, &[1, 4])).unwrap_err();
+        assert_eq!(err, "2 shares given but 3 are needed to rebuild the key");
+        assert_eq!(combine(&[]).unwrap_err(), "no shares given");
+    }
+
+    #[test]
+    fn duplicate_indexes_are_rejected() {
+        let shares = shares("2-of-3");
+        let err = combine(&pick(&shares, &[2, 2])).unwrap_err();
+        assert_eq!(err, "the same share was given more than once");
+        let err = combine(&pick(&shares, &[1, 3, 1])).unwrap_err();
+        assert_eq!(err, "the same share was given more than once");
+    }
+
+    #[test]
+    fn tampered_shares_are_rejected() {
+        let mut shares = shares("2-of-3");
+        shares[1].bytes[5] ^= 0x01;
+        let err = combine(&pick(&shares, &[1, 2])).unwrap_err();
+        assert_eq!(err, "shares are corrupted: the rebuilt key does not match its digest");
+        // The untouched pair still works.
+        assert_eq!(combine(&pick(&shares, &[1, 3])).unwrap().as_slice(), SECRET);
+
+        shares[2].index = 2;
+        let err = combine(&pick(&shares, &[1, 3])).unwrap_err();
+        assert_eq!(err, "shares are corrupted: the rebuilt key does not match its digest");
+    }
+
+    #[test]
+    fn shares_of_different_splits_are_rejected() {
+        let first = shares("2-of-3");
+        let second = shares("3-of-3");
+        let mut mixed = pick(&first, &[1]);
+        mixed.extend(pick(&second, &[2, 3]));
+        assert_eq!(combine(&mixed).unwrap_err(), "shares belong to different keys or splits");
+
+        let other = split(b"another key", "2-of-3".parse().unwrap(), "other").unwrap();
+        let mut mixed = pick(&first, &[1]);
+        mixed.push(parse(&other[1]).unwrap());
+        assert_eq!(combine(&mixed).unwrap_err(), "shares belong to different keys or splits");
+    }
+
+    #[test]
+    fn malformed_shares_are_rejected() {
+        let error = |version: u8, threshold: u8, index: u8, body: &[u8]| {
+            let mut payload = vec![version, threshold, index];
+            payload.extend_from_slice(&[0; DIGEST_LEN]);
+            payload.extend_from_slice(body);
+            parse(&pem::encode(SHARE_LABEL, &payload)).err().unwrap()
+        };
+        assert_eq!(error(VERSION, 2, 1, &[]), "unsupported or truncated key share");
+        assert_eq!(error(VERSION + 1, 2, 1, &[7]), "unsupported or truncated key share");
+        assert_eq!(error(VERSION, 2, 0, &[7]), "corrupted key share header");
+        assert_eq!(error(VERSION, 1, 1, &[7]), "corrupted key share header");
+    }
+
+    #[test]
+    fn split_specs_are_checked() {
+        let spec: SplitSpec = "3-of-5".parse().unwrap();
+        assert_eq!((spec.threshold, spec.shares), (3, 5));
+        for bad in ["3", "3of5", "a-of-5", "3-of-256"] {
+            assert!(bad.parse::<SplitSpec>().unwrap_err().starts_with("invalid split"), "{bad}");
+        }
+        for bad in ["1-of-3", "4-of-3", "0-of-0"] {
+            assert!(bad.parse::<SplitSpec>().unwrap_err().starts_with("split threshold must be"), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! `signal`: an identity key, a signed prekey and one-time prekeys in the
+//! encodings libsignal reads, for prototype clients.
+//!
+//! libsignal's keys are Curve25519, not finite-field DH. A public key is
+//! serialized as the `DjbECPublicKey` type byte `0x05` followed by its 32
+//! bytes, a private key as its 32 clamped bytes. The signed prekey carries an
+//! XEdDSA signature by the identity key over its serialized public key, and
+//! the records are libsignal's `storage.proto` messages, which
+//! `IdentityKeyPair::try_from`, `SignedPreKeyRecord::deserialize` and
+//! `PreKeyRecord::deserialize` load as they are. Kyber prekeys are not made.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use create_private_key_core as ffdh;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::{Scalar, clamp_integer};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::protobuf::{put_bytes, put_fixed64, put_uint};
+use crate::random;
+
+/// `DjbECPublicKey`'s type byte.
+const DJB_TYPE: u8 = 0x05;
+
+/// Registration IDs are drawn from `[1, 16380)`, as libsignal's
+/// `generate_registration_id` does.
+const REGISTRATION_ID_LIMIT: u32 = 16380;
+
+struct KeyPair {
+    private: Zeroizing<[u8; 32]>,
+    public: [u8; 32],
+}
+
+impl KeyPair {
+    fn generate() -> Result<KeyPair, String> {
+        let mut private = Zeroizing::new([0u8; 32]);
+        random::fill(&mut *private).map_err(|err| err.to_string())?;
+        *private = clamp_integer(*private);
+        let public = MontgomeryPoint::mul_base_clamped(*private).to_bytes();
+        Ok(KeyPair { private, public })
+    }
+
+    /// `PublicKey::serialize`.
+    fn public_serialized(&self) -> Vec<u8> {
+        [&[DJB_TYPE][..], &self.public].concat()
+    }
+
+    /// XEdDSA as libsignal computes it: the Edwards key `A = aB` for the
+    /// Montgomery private key `a`, a nonce hashed from the key, the message
+    /// and 64 random bytes, and `A`'s sign bit in the top bit of `s`.
+    fn sign(&self, message: &[u8]) -> Result<[u8; 64], String> {
+        let mut random_bytes = Zeroizing::new([0u8; 64]);
+        random::fill(&mut *random_bytes).map_err(|err| err.to_string())?;
+        let mut a = Scalar::from_bytes_mod_order(*self.private);
+        let big_a = EdwardsPoint::mul_base(&a).compress().to_bytes();
+        let sign_bit = big_a[31] & 0x80;
+        let mut prefix = [0xffu8; 32];
+        prefix[0] = 0xfe;
+        let mut r = wide_scalar(&[&prefix, &self.private[..], message, &random_bytes[..]]);
+        let big_r = EdwardsPoint::mul_base(&r).compress().to_bytes();
+        let h = wide_scalar(&[&big_r, &big_a, message]);
+        let s = h * a + r;
+        a.zeroize();
+        r.zeroize();
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&big_r);
+        signature[32..].copy_from_slice(s.as_bytes());
+        signature[63] = (signature[63] & 0x7f) | sign_bit;
+        Ok(signature)
+    }
+}
+
+/// SHA-512 of the parts as a scalar mod the group order.
+fn wide_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+pub struct Bundle {
+    registration_id: u32,
+    identity: KeyPair,
+    signed_pre_key_id: u32,
+    signed_pre_key: KeyPair,
+    signature: [u8; 64],
+    /// Milliseconds since the epoch, as the record's `timestamp`.
+    timestamp: u64,
+    pre_keys: Vec<(u32, KeyPair)>,
+}
+
+impl Bundle {
+    /// A new identity with the signed prekey `signed_pre_key_id` and
+    /// `pre_key_count` one-time prekeys numbered from `first_pre_key_id`.
+    pub fn generate(signed_pre_key_id: u32, first_pre_key_id: u32, pre_key_count: u32) -> Result<Bundle, String> {
+        let last = first_pre_key_id.checked_add(pre_key_count).ok_or("the prekey IDs do not fit in 32 bits")?;
+        let registration_id = ffdh::random_range(
+            &BigUint::from(1u32),
+            &BigUint::from(REGISTRATION_ID_LIMIT),
+            random::fill,
+        )
+        .map_err(|err| err.to_string())?;
+        let identity = KeyPair::generate()?;
+        let signed_pre_key = KeyPair::generate()?;
+        let signature = identity.sign(&signed_pre_key.public_serialized())?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let pre_keys = (first_pre_key_id..last)
+            .map(|id| Ok((id, KeyPair::generate()?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Bundle {
+            registration_id: registration_id.try_into().expect("below 16380"),
+            identity,
+            signed_pre_key_id,
+            signed_pre_key,
+            signature,
+            timestamp,
+            pre_keys,
+        })
+    }
+
+    /// `IdentityKeyPairStructure`.
+    fn identity_record(&self) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::new());
+        put_bytes(&mut out, 1, &self.identity.public_serialized());
+        put_bytes(&mut out, 2, &self.identity.private[..]);
+        out
+    }
+
+    /// `SignedPreKeyRecordStructure`.
+    fn signed_pre_key_record(&self) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::new());
+        put_uint(&mut out, 1, self.signed_pre_key_id.into());
+        put_bytes(&mut out, 2, &self.signed_pre_key.public_serialized());
+        put_bytes(&mut out, 3, &self.signed_pre_key.private[..]);
+        put_bytes(&mut out, 4, &self.signature);
+        put_fixed64(&mut out, 5, self.timestamp);
+        out
+    }
+
+    /// `PreKeyRecordStructure`.
+    fn pre_key_record(id: u32, pair: &KeyPair) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::new());
+        put_uint(&mut out, 1, id.into());
+        put_bytes(&mut out, 2, &pair.public_serialized());
+        put_bytes(&mut out, 3, &pair.private[..]);
+        out
+    }
+
+    /// The public half a client uploads, as base64 `key=value` lines.
+    pub fn render_public(&self) -> String {
+        let mut rendered = format!(
+            "registration_id={}\nidentity_key={}\nsigned_pre_key_id={}\nsigned_pre_key={}\n\
+             signed_pre_key_signature={}\n",
+            self.registration_id,
+            BASE64.encode(self.identity.public_serialized()),
+            self.signed_pre_key_id,
+            BASE64.encode(self.signed_pre_key.public_serialized()),
+            BASE64.encode(self.signature)
+        );
+        for (id, pair) in &self.pre_keys {
+            rendered.push_str(&format!("pre_key_{id}={}\n", BASE64.encode(pair.public_serialized())));
+        }
+        rendered
+    }
+
+    /// The public lines followed by the records, which hold the private keys.
+    pub fn render(&self) -> Zeroizing<String> {
+        let mut rendered = Zeroizing::new(self.render_public());
+        rendered.push_str(&format!("identity_key_pair={}\n", BASE64.encode(&*self.identity_record())));
+        rendered.push_str(&format!("signed_pre_key_record={}\n", BASE64.encode(&*self.signed_pre_key_record())));
+        for (id, pair) in &self.pre_keys {
+            let record = Bundle::pre_key_record(*id, pair);
+            rendered.push_str(&format!("pre_key_{id}_record={}\n", BASE64.encode(&*record)));
+        }
+        rendered
+    }
+}
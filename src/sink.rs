@@ -0,0 +1,211 @@
+//! Destinations for key material other than stdout.
+
+use std::fs::File;
+#[cfg(feature = "pem")]
+use std::fs::OpenOptions;
+#[cfg(feature = "pem")]
+use std::io::{self, Write};
+#[cfg(feature = "pem")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "pem")]
+use create_private_key::Error;
+
+#[cfg(feature = "pem")]
+use crate::random;
+
+/// Permissions for private key files: owner read/write only.
+#[cfg(feature = "pem")]
+const PRIVATE_FILE_MODE: u32 = 0o600;
+/// Permissions for public key files: world readable.
+#[cfg(feature = "pem")]
+const PUBLIC_FILE_MODE: u32 = 0o644;
+
+/// Takes an inherited file descriptor, such as one opened by the shell with
+/// `3>file`, for writing the private key. Standard streams are rejected so
+/// the secret cannot end up interleaved with the loggable output.
+///
+/// Called before any key is generated so a bad descriptor fails early.
+#[cfg(unix)]
+pub fn open_fd(fd: i32) -> Result<File, String> {
+    use std::os::fd::BorrowedFd;
+
+    if fd <= 2 {
+        return Err(format!("refusing to write the private key to standard stream fd {fd}"));
+    }
+
+    // SAFETY: the fd is only borrowed long enough to duplicate it; if it is
+    // not open the duplication fails and no descriptor is touched.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    borrowed
+        .try_clone_to_owned()
+        .map(File::from)
+        .map_err(|err| format!("file descriptor {fd} is not usable: {err}"))
+}
+
+#[cfg(not(unix))]
+pub fn open_fd(_fd: i32) -> Result<File, String> {
+    Err("--private-fd is only supported on Unix platforms".into())
+}
+
+/// How a key file write treats an existing file at the destination.
+#[cfg(feature = "pem")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overwrite {
+    Refuse,
+    Replace,
+}
+
+/// Writes a private key file readable only by the current user.
+///
+/// Without `Overwrite::Replace` an existing file is never touched, even if it
+/// appears between the early check and the final link.
+#[cfg(feature = "pem")]
+pub fn write_private_file(path: &Path, contents: &[u8], overwrite: Overwrite) -> Result<(), Error> {
+    write_atomically(path, contents, PRIVATE_FILE_MODE, overwrite, true)
+}
+
+/// Writes a public key file, replacing any previous one.
+#[cfg(feature = "pem")]
+pub fn write_public_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    write_atomically(path, contents, PUBLIC_FILE_MODE, Overwrite::Replace, false)
+}
+
+/// Fails early if a private key file already exists and may not be replaced.
+#[cfg(feature = "pem")]
+pub fn ensure_writable(path: &Path, overwrite: Overwrite) -> Result<(), Error> {
+    if overwrite == Overwrite::Refuse && path.exists() {
+        return Err(already_exists(path));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "pem")]
+pub fn already_exists(path: &Path) -> Error {
+    Error::Io {
+        context: format!("{} already exists", path.display()),
+        source: io::Error::new(io::ErrorKind::AlreadyExists, "pass --force to overwrite it"),
+    }
+}
+
+/// Writes `contents` to a fresh temp file beside `path` (created with
+/// `O_EXCL` and its final mode), syncs it, then moves it into place.
+///
+/// Replacement uses `rename`, which is atomic. No-clobber uses `hard_link`,
+/// which atomically fails if the destination exists; filesystems without hard
+/// links fall back to an existence check followed by `rename`.
+#[cfg(feature = "pem")]
+fn write_atomically(
+    path: &Path,
+    contents: &[u8],
+    mode: u32,
+    overwrite: Overwrite,
+    private: bool,
+) -> Result<(), Error> {
+    let temp = temp_path(path)?;
+    let result = write_new_file(&temp, contents, mode)
+        .and_then(|()| if private { restrict_to_owner(&temp) } else { Ok(()) })
+        .and_then(|()| move_into_place(&temp, path, overwrite));
+    let _ = std::fs::remove_file(&temp);
+    result?;
+    sync_parent(path);
+    Ok(())
+}
+
+/// A hidden name beside `path` with a random suffix, drawn like every other
+/// random value through [`random::fill`].
+#[cfg(feature = "pem")]
+fn temp_path(path: &Path) -> Result<PathBuf, Error> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut suffix = [0u8; 8];
+    random::fill(&mut suffix)?;
+    Ok(path.with_file_name(format!(".{name}.{:016x}.tmp", u64::from_be_bytes(suffix))))
+}
+
+#[cfg(feature = "pem")]
+fn write_new_file(path: &Path, contents: &[u8], mode: u32) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options.open(path).map_err(|source| Error::Io {
+        context: format!("failed to create {}", path.display()),
+        source,
+    })?;
+    file.write_all(contents)
+        .and_then(|()| file.sync_all())
+        .map_err(|source| Error::Io {
+            context: format!("failed to write {}", path.display()),
+            source,
+        })
+}
+
+#[cfg(feature = "pem")]
+fn move_into_place(temp: &Path, path: &Path, overwrite: Overwrite) -> Result<(), Error> {
+    let rename = || {
+        std::fs::rename(temp, path).map_err(|source| Error::Io {
+            context: format!("failed to write {}", path.display()),
+            source,
+        })
+    };
+    if overwrite == Overwrite::Replace {
+        return rename();
+    }
+
+    match std::fs::hard_link(temp, path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Err(already_exists(path)),
+        Err(_) if path.exists() => Err(already_exists(path)),
+        Err(_) => rename(),
+    }
+}
+
+/// Best-effort fsync of the containing directory so the new name is durable.
+#[cfg(feature = "pem")]
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(all(feature = "pem", windows))]
+fn restrict_to_owner(path: &Path) -> Result<(), Error> {
+    let failed = |source| Error::Io {
+        context: format!("failed to restrict {} to its owner", path.display()),
+        source,
+    };
+    // Drop inherited ACEs and grant full control to the current user only.
+    let user = std::env::var("USERNAME").map_err(|_| failed(io::Error::other("USERNAME is not set")))?;
+    let status = std::process::Command::new("icacls")
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r"])
+        .arg(format!("{user}:F"))
+        .stdout(std::process::Stdio::null())
+        .status()
+        .map_err(failed)?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failed(io::Error::other(format!("icacls exited with {status}"))))
+    }
+}
+
+#[cfg(all(feature = "pem", not(windows)))]
+fn restrict_to_owner(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
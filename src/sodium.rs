@@ -0,0 +1,65 @@
+//! `sodium`: X25519 key pairs laid out as libsodium's `crypto_box` and
+//! `crypto_kx` expect them, for NaCl-based services.
+//!
+//! libsodium has no finite-field DH, so these are not the tool's DH keys. As
+//! `crypto_box_keypair` and `crypto_kx_keypair` do, the secret key is 32
+//! random bytes and the public key is X25519 of it with the base point; the
+//! clamping happens inside the scalar multiplication, so the secret key is
+//! kept as drawn. The `keypair` layout is the secret key followed by the
+//! public key, 64 bytes.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use data_encoding::HEXLOWER;
+use zeroize::Zeroizing;
+
+use crate::random;
+
+/// How keys are printed: libsodium's `sodium_bin2base64` with the original
+/// variant, or `sodium_bin2hex`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Encoding {
+    Base64,
+    Hex,
+}
+
+impl Encoding {
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Base64 => BASE64.encode(bytes),
+            Encoding::Hex => HEXLOWER.encode(bytes),
+        }
+    }
+}
+
+pub struct KeyPair {
+    pub secret_key: Zeroizing<[u8; 32]>,
+    pub public_key: [u8; 32],
+}
+
+impl KeyPair {
+    /// A fresh key pair from the configured random source.
+    pub fn generate() -> Result<KeyPair, String> {
+        let mut secret_key = Zeroizing::new([0u8; 32]);
+        random::fill(&mut *secret_key).map_err(|err| err.to_string())?;
+        let public_key = MontgomeryPoint::mul_base_clamped(*secret_key).to_bytes();
+        Ok(KeyPair { secret_key, public_key })
+    }
+
+    /// `secret_key || public_key`.
+    pub fn keypair(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new([&self.secret_key[..], &self.public_key].concat())
+    }
+
+    /// `public_key=` and, with `secret`, `secret_key=` and `keypair=` lines.
+    pub fn render(&self, encoding: Encoding, secret: bool) -> Zeroizing<String> {
+        let mut rendered = Zeroizing::new(format!("public_key={}\n", encoding.encode(&self.public_key)));
+        if secret {
+            rendered.push_str(&format!("secret_key={}\n", encoding.encode(&*self.secret_key)));
+            rendered.push_str(&format!("keypair={}\n", encoding.encode(&self.keypair())));
+        }
+        rendered
+    }
+}
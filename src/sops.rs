@@ -0,0 +1,59 @@
+//! SOPS-encrypted key documents, produced by running the `sops` CLI on the
+//! `cpk-v1` envelope.
+
+use clap::ValueEnum;
+
+use crate::envelope;
+use crate::external;
+use crate::output::KeyRecord;
+
+/// Only the private key is encrypted; parameters, public key and metadata
+/// stay readable in review, and the SOPS MAC still covers the whole file.
+const ENCRYPTED_REGEX: &str = "^private_key$";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SopsFormat {
+    Yaml,
+    Json,
+}
+
+/// Master keys the document is encrypted to. Each list is passed to `sops`
+/// comma-separated, so any mix of key types may be used together.
+pub struct SopsKeys {
+    pub age: Vec<String>,
+    pub kms: Vec<String>,
+    pub gcp_kms: Vec<String>,
+}
+
+impl SopsKeys {
+    pub fn is_empty(&self) -> bool {
+        self.age.is_empty() && self.kms.is_empty() && self.gcp_kms.is_empty()
+    }
+}
+
+/// Renders `record` as a `cpk-v1` envelope encrypted by `sops`.
+pub fn render(record: &KeyRecord, keys: &SopsKeys, format: SopsFormat) -> Result<Vec<u8>, String> {
+    let output_type = match format {
+        SopsFormat::Yaml => "yaml",
+        SopsFormat::Json => "json",
+    };
+    let (age, kms, gcp_kms) = (keys.age.join(","), keys.kms.join(","), keys.gcp_kms.join(","));
+
+    let mut args = vec![
+        "--encrypt",
+        "--input-type",
+        "json",
+        "--output-type",
+        output_type,
+        "--encrypted-regex",
+        ENCRYPTED_REGEX,
+    ];
+    for (flag, value) in [("--age", &age), ("--kms", &kms), ("--gcp-kms", &gcp_kms)] {
+        if !value.is_empty() {
+            args.extend([flag, value.as_str()]);
+        }
+    }
+    args.push("/dev/stdin");
+
+    external::run_filter("sops", &args, envelope::render(record).as_bytes())
+}
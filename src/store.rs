@@ -0,0 +1,267 @@
+//! Secret stores that can hold the private key instead of a file.
+
+use clap::ValueEnum;
+
+use crate::external;
+use crate::output::KeyRecord;
+
+/// Service name keys are filed under in the OS keychain.
+const KEYRING_SERVICE: &str = "create-private-key";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Store {
+    /// The platform secret store: Secret Service, macOS Keychain or Windows
+    /// Credential Manager.
+    Keyring,
+    /// The Linux kernel user keyring (`keyctl`, `@u`): shared by the user's
+    /// processes until logout or reboot. Nothing is written to disk.
+    KernelUser,
+    /// The Linux kernel session keyring (`@s`): gone when the session ends.
+    KernelSession,
+    /// A HashiCorp Vault KV version 2 secret; `VAULT_ADDR` and `VAULT_TOKEN`
+    /// (and optionally `VAULT_NAMESPACE`) come from the environment.
+    Vault,
+    /// AWS Secrets Manager, through the `aws` CLI and its usual credentials
+    /// (environment, profile or instance/Lambda role).
+    AwsSm,
+    /// Google Secret Manager, through `gcloud` and its active account or
+    /// metadata-server credentials.
+    GcpSm,
+    /// Azure Key Vault, through `az` and its login, managed identity or
+    /// service principal environment.
+    AzureKv,
+}
+
+impl Store {
+    /// Picks the entry location: stores addressed by path (`--path`) or by
+    /// name (`--name`).
+    pub fn location<'a>(self, name: Option<&'a str>, path: Option<&'a str>) -> Result<&'a str, String> {
+        match self {
+            Store::Vault => path.ok_or_else(|| "--store vault needs --path".to_string()),
+            Store::AzureKv => path.ok_or_else(|| "--store azure-kv needs --path VAULT/SECRET".to_string()),
+            _ => name.ok_or_else(|| "--store needs --name".to_string()),
+        }
+    }
+}
+
+/// Saves the record's private key, as PKCS #8 PEM, at `location` and returns
+/// a reference to the stored secret. An existing entry is only replaced when
+/// `replace` is set.
+pub fn save(store: Store, location: &str, record: &KeyRecord, replace: bool) -> Result<String, String> {
+    let secret = record.private_key_pem();
+    match store {
+        Store::Keyring => {
+            save_keyring(location, &secret, replace)?;
+            Ok(format!("keyring:{KEYRING_SERVICE}/{location}"))
+        }
+        Store::KernelUser | Store::KernelSession => {
+            let serial = kernel::add_user_key(store == Store::KernelUser, location, secret.as_bytes(), replace)?;
+            Ok(format!("kernel key {serial} (keyctl print {serial})"))
+        }
+        Store::Vault => vault::put(location, record, &secret, replace),
+        Store::AwsSm => aws_secrets_manager(location, &secret, replace),
+        Store::GcpSm => gcp_secret_manager(location, &secret, replace),
+        Store::AzureKv => azure_key_vault(location, &secret, replace),
+    }
+}
+
+fn save_keyring(name: &str, secret: &str, replace: bool) -> Result<(), String> {
+    let error = |err: keyring::Error| format!("keyring: {err}");
+    let entry = keyring::Entry::new(KEYRING_SERVICE, name).map_err(error)?;
+    if !replace {
+        match entry.get_password() {
+            Ok(_) => return Err(format!("keyring entry '{name}' already exists; pass --force to overwrite it")),
+            Err(keyring::Error::NoEntry) => {}
+            Err(err) => return Err(error(err)),
+        }
+    }
+    entry.set_password(secret).map_err(error)
+}
+
+/// Creates the secret, or with `replace` adds a new version to an existing
+/// one. The value travels on the CLI's stdin, never its command line.
+fn aws_secrets_manager(name: &str, secret: &str, replace: bool) -> Result<String, String> {
+    let query = ["--secret-string", "file:///dev/stdin", "--query", "ARN", "--output", "text"];
+    let mut create = vec!["secretsmanager", "create-secret", "--name", name];
+    create.extend(query);
+    let arn = match external::run_filter("aws", &create, secret.as_bytes()) {
+        Ok(arn) => arn,
+        Err(err) if err.contains("ResourceExistsException") => {
+            if !replace {
+                return Err(format!("AWS secret '{name}' already exists; pass --force to overwrite it"));
+            }
+            let mut put = vec!["secretsmanager", "put-secret-value", "--secret-id", name];
+            put.extend(query);
+            external::run_filter("aws", &put, secret.as_bytes())?
+        }
+        Err(err) => return Err(err),
+    };
+    Ok(format!("aws-sm:{}", String::from_utf8_lossy(&arn).trim()))
+}
+
+/// Creates the secret with its first version, or with `replace` adds a new
+/// version to an existing one.
+fn gcp_secret_manager(name: &str, secret: &str, replace: bool) -> Result<String, String> {
+    let format = "--format=value(name)";
+    let created = external::run_filter(
+        "gcloud",
+        &["secrets", "create", name, "--data-file=-", "--replication-policy=automatic", format],
+        secret.as_bytes(),
+    );
+    let reference = match created {
+        Ok(_) => external::run_filter("gcloud", &["secrets", "versions", "describe", "latest", "--secret", name, format], &[])?,
+        Err(err) if err.contains("ALREADY_EXISTS") || err.contains("already exists") => {
+            if !replace {
+                return Err(format!("GCP secret '{name}' already exists; pass --force to overwrite it"));
+            }
+            external::run_filter("gcloud", &["secrets", "versions", "add", name, "--data-file=-", format], secret.as_bytes())?
+        }
+        Err(err) => return Err(err),
+    };
+    Ok(format!("gcp-sm:{}", String::from_utf8_lossy(&reference).trim()))
+}
+
+/// Sets `VAULT/SECRET` in Azure Key Vault. Key Vault keeps every value as a
+/// version, so an existing secret is checked for first and only replaced with
+/// `replace`.
+fn azure_key_vault(path: &str, secret: &str, replace: bool) -> Result<String, String> {
+    let (vault, name) = path
+        .split_once('/')
+        .ok_or_else(|| format!("Azure Key Vault path '{path}' must be VAULT/SECRET"))?;
+    if !replace {
+        let existing = external::run_filter(
+            "az",
+            &["keyvault", "secret", "show", "--vault-name", vault, "--name", name, "--query", "id", "-o", "tsv"],
+            &[],
+        );
+        match existing {
+            Ok(_) => return Err(format!("Azure secret '{path}' already exists; pass --force to overwrite it")),
+            Err(err) if err.contains("SecretNotFound") || err.contains("not found") => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let id = external::run_filter(
+        "az",
+        &[
+            "keyvault", "secret", "set", "--vault-name", vault, "--name", name, "--file", "/dev/stdin", "--encoding",
+            "utf-8", "--query", "id", "-o", "tsv",
+        ],
+        secret.as_bytes(),
+    )?;
+    Ok(format!("azure-kv:{}", String::from_utf8_lossy(&id).trim()))
+}
+
+#[cfg(target_os = "linux")]
+mod kernel {
+    use std::ffi::CString;
+
+    /// Adds a `user`-type key with `description` to the user (`@u`) or
+    /// session (`@s`) keyring and returns its serial number.
+    pub fn add_user_key(user_keyring: bool, description: &str, payload: &[u8], replace: bool) -> Result<i64, String> {
+        let keyring = if user_keyring {
+            libc::KEY_SPEC_USER_KEYRING
+        } else {
+            libc::KEY_SPEC_SESSION_KEYRING
+        };
+        let key_type = CString::new("user").expect("no NUL");
+        let description =
+            CString::new(description).map_err(|_| "key description cannot contain NUL bytes".to_string())?;
+
+        if !replace {
+            // SAFETY: the strings are NUL-terminated and outlive the call; a
+            // zero destination keyring means the found key is not linked anywhere.
+            let found = unsafe {
+                libc::syscall(
+                    libc::SYS_keyctl,
+                    libc::KEYCTL_SEARCH,
+                    keyring,
+                    key_type.as_ptr(),
+                    description.as_ptr(),
+                    0,
+                )
+            };
+            if found >= 0 {
+                return Err(format!(
+                    "kernel key '{}' already exists; pass --force to overwrite it",
+                    description.to_string_lossy()
+                ));
+            }
+        }
+
+        // SAFETY: as above; `payload` is valid for `payload.len()` bytes.
+        // add_key updates the payload of a matching key rather than adding a
+        // second one.
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+                keyring,
+            )
+        };
+        if serial < 0 {
+            return Err(format!("add_key failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(serial)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod kernel {
+    pub fn add_user_key(_user_keyring: bool, _description: &str, _payload: &[u8], _replace: bool) -> Result<i64, String> {
+        Err("the kernel keyring is only available on Linux".into())
+    }
+}
+
+mod vault {
+    use serde_json::json;
+
+    use crate::output::KeyRecord;
+
+    /// Writes the key to a KV v2 secret. `path` starts with the mount
+    /// (`secret/dh/my-key` is key `dh/my-key` on mount `secret`). Without
+    /// `replace` the write uses check-and-set 0, so Vault itself refuses to
+    /// overwrite an existing secret.
+    pub fn put(path: &str, record: &KeyRecord, private_key: &str, replace: bool) -> Result<String, String> {
+        let address = std::env::var("VAULT_ADDR").map_err(|_| "VAULT_ADDR is not set".to_string())?;
+        let token = std::env::var("VAULT_TOKEN").map_err(|_| "VAULT_TOKEN is not set".to_string())?;
+        let (mount, key) = path
+            .trim_matches('/')
+            .split_once('/')
+            .ok_or_else(|| format!("Vault path '{path}' needs a mount and a key, e.g. secret/dh/my-key"))?;
+
+        let mut body = json!({
+            "data": {
+                "private_key": private_key,
+                "public_key": record.public_key_pem(),
+                "key_id": record.key_id,
+                "fingerprint": record.fingerprint,
+            }
+        });
+        if !replace {
+            body["options"] = json!({ "cas": 0 });
+        }
+
+        let url = format!("{}/v1/{mount}/data/{key}", address.trim_end_matches('/'));
+        let mut request = ureq::post(&url).set("X-Vault-Token", &token);
+        if let Ok(namespace) = std::env::var("VAULT_NAMESPACE") {
+            request = request.set("X-Vault-Namespace", &namespace);
+        }
+        let response: serde_json::Value = match request.send_json(body) {
+            Ok(response) => response.into_json().map_err(|err| format!("invalid Vault response: {err}"))?,
+            Err(ureq::Error::Status(status, response)) => {
+                let detail = response.into_string().unwrap_or_default();
+                if status == 400 && detail.contains("check-and-set") {
+                    return Err(format!("Vault secret '{path}' already exists; pass --force to overwrite it"));
+                }
+                return Err(format!("Vault rejected the write ({status}): {}", detail.trim()));
+            }
+            Err(err) => return Err(format!("failed to reach Vault at {address}: {err}")),
+        };
+
+        let version = response["data"]["version"].as_u64().unwrap_or_default();
+        Ok(format!("vault:{mount}/{key} (version {version})"))
+    }
+}
@@ -0,0 +1,105 @@
+//! systemd integration for the daemon modes: socket activation
+//! (`sd_listen_fds(3)`) and readiness notification (`sd_notify(3)`),
+//! implemented from the documented protocols rather than libsystemd.
+
+/// A listening socket passed in by the service manager.
+pub enum Listener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::net::UnixDatagram;
+
+    use super::Listener;
+
+    /// First file descriptor passed by the service manager.
+    const LISTEN_FDS_START: RawFd = 3;
+
+    pub fn listener() -> Result<Option<Listener>, String> {
+        let ours = std::env::var("LISTEN_PID")
+            .ok()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .is_some_and(|pid| pid == std::process::id());
+        if !ours {
+            return Ok(None);
+        }
+        let count: i32 = std::env::var("LISTEN_FDS")
+            .ok()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0);
+        match count {
+            0 => return Ok(None),
+            1 => {}
+            _ => return Err(format!("systemd passed {count} sockets; configure exactly one")),
+        }
+
+        let fd = LISTEN_FDS_START;
+        // SAFETY: `fd` was passed to this process (LISTEN_PID matches) and
+        // nothing else owns it; getsockname writes at most `len` bytes.
+        let family = unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            let mut address: libc::sockaddr_storage = std::mem::zeroed();
+            let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            if libc::getsockname(fd, (&raw mut address).cast(), &mut len) != 0 {
+                return Err("the socket passed by systemd is not usable".into());
+            }
+            i32::from(address.ss_family)
+        };
+        // SAFETY: as above; ownership moves into the listener.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        match family {
+            libc::AF_INET | libc::AF_INET6 => Ok(Some(Listener::Tcp(fd.into()))),
+            libc::AF_UNIX => Ok(Some(Listener::Unix(fd.into()))),
+            _ => Err(format!("the socket passed by systemd has unsupported address family {family}")),
+        }
+    }
+
+    pub fn notify(state: &str) {
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+            return;
+        };
+        let Ok(socket) = UnixDatagram::unbound() else {
+            return;
+        };
+        let path = path.to_string_lossy().into_owned();
+        match path.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                if let Ok(address) = std::os::unix::net::SocketAddr::from_abstract_name(name) {
+                    let _ = socket.send_to_addr(state.as_bytes(), &address);
+                }
+            }
+            _ => {
+                let _ = socket.send_to(state.as_bytes(), &path);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::Listener;
+
+    pub fn listener() -> Result<Option<Listener>, String> {
+        Ok(None)
+    }
+
+    pub fn notify(_state: &str) {}
+}
+
+/// The socket systemd passed to this process when it was socket activated,
+/// or `None` when it should bind its own. Exactly one socket is expected.
+pub fn listener() -> Result<Option<Listener>, String> {
+    imp::listener()
+}
+
+/// Tells systemd the service is accepting connections. Does nothing
+/// outside a `Type=notify` unit.
+pub fn ready(listening: &str) {
+    imp::notify(&format!("READY=1\nSTATUS=listening on {listening}"));
+}
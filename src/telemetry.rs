@@ -0,0 +1,87 @@
+//! `--otlp-endpoint`: OpenTelemetry spans for the daemon modes, exported
+//! over OTLP/gRPC.
+//!
+//! Each request handled by `serve`, `grpc` or `agent` becomes a span named
+//! after its operation (`generate`, `validate`, `shared_secret`, ...). The
+//! spans come from `tracing` and carry the same fields as the logs, so key
+//! material never reaches them either. The usual `OTEL_*` environment
+//! variables (service name, headers, timeout) apply.
+
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing_subscriber::Layer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Keeps the exporter running; spans still queued are flushed on drop.
+pub struct Telemetry {
+    provider: TracerProvider,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        let _entered = self.runtime.enter();
+        let _ = self.provider.shutdown();
+    }
+}
+
+/// Connects lazily to the collector at `endpoint` (e.g.
+/// `http://localhost:4317`) and returns the layer that turns spans into
+/// OTLP spans, plus the guard that owns the exporter.
+pub fn layer<S>(endpoint: &str) -> Result<(impl Layer<S>, Telemetry), String>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    // The batch exporter runs on its own runtime so the synchronous daemons
+    // need none, and the gRPC server's runtime is left alone.
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("otlp-export")
+        .enable_all()
+        .build()
+        .map_err(|err| format!("failed to start the OTLP exporter: {err}"))?;
+    let _entered = runtime.enter();
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|err| format!("--otlp-endpoint '{endpoint}': {err}"))?;
+    let resource = if std::env::var_os("OTEL_SERVICE_NAME").is_some() {
+        Resource::default()
+    } else {
+        Resource::new_with_defaults([KeyValue::new("service.name", env!("CARGO_PKG_NAME"))])
+    };
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(resource)
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    drop(_entered);
+
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(tracing_subscriber::filter::filter_fn(|metadata| metadata.is_span()));
+    Ok((layer, Telemetry { provider, runtime }))
+}
+
+/// The span for one daemon request. It is exported with `--otlp-endpoint`
+/// and shows up as context on `-v` logs.
+pub fn request_span(operation: &'static str) -> tracing::Span {
+    tracing::info_span!(
+        "request",
+        otel.name = operation,
+        otel.kind = "server",
+        otel.status_code = tracing::field::Empty,
+        otel.status_message = tracing::field::Empty,
+    )
+}
+
+/// Marks `span` as failed with `error`, a message safe to export.
+pub fn record_error(span: &tracing::Span, error: &str) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", error);
+}
@@ -0,0 +1,253 @@
+//! `testvectors`: runs published test-vector files through the same DH and
+//! KDF code that makes keys, and reports each case.
+//!
+//! Two layouts are read. NIST CAVP's KAS FFC response files (`P`, `Q` and
+//! `G` followed by `COUNT` blocks) check public key derivation, peer key
+//! validation and the shared secret `Z`. Project Wycheproof's JSON files
+//! check HKDF-SHA-256 and PBKDF2-HMAC-SHA-256; groups for primitives this
+//! tool does not implement, such as ECDH, are reported as skipped.
+
+use clap::ValueEnum;
+use create_private_key::dh;
+use num_bigint::BigUint;
+use num_traits::One;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Suite {
+    /// Project Wycheproof JSON (`testGroups` of `tests`).
+    #[cfg(feature = "json")]
+    Wycheproof,
+    /// NIST CAVP KAS FFC `.rsp` files.
+    Cavp,
+}
+
+/// How one case went.
+pub enum Status {
+    Pass,
+    Fail(String),
+    Skipped(String),
+}
+
+pub struct Case {
+    /// `tcId` for Wycheproof, `[section] COUNT` for CAVP.
+    pub id: String,
+    pub status: Status,
+}
+
+/// Every case in `text`, in file order.
+pub fn run(suite: Suite, text: &str) -> Result<Vec<Case>, String> {
+    match suite {
+        #[cfg(feature = "json")]
+        Suite::Wycheproof => wycheproof(text),
+        Suite::Cavp => cavp(text),
+    }
+}
+
+/// Passes when the outcome matches the expectation: a valid case computes
+/// the expected value, an invalid one is refused or computes something else.
+fn judge(expected_valid: bool, outcome: Result<(), String>) -> Status {
+    match (expected_valid, outcome) {
+        (true, Ok(())) => Status::Pass,
+        (true, Err(reason)) => Status::Fail(reason),
+        (false, Ok(())) => Status::Fail("accepted an invalid case".into()),
+        (false, Err(_)) => Status::Pass,
+    }
+}
+
+/// The domain parameters in force and the case being read.
+#[derive(Default)]
+struct CavpReader {
+    section: String,
+    p: Option<BigUint>,
+    q: Option<BigUint>,
+    g: Option<BigUint>,
+    /// `COUNT` and the lines after it.
+    fields: Option<Vec<(String, String)>>,
+    cases: Vec<Case>,
+}
+
+impl CavpReader {
+    fn finish_case(&mut self) {
+        if let Some(fields) = self.fields.take() {
+            let case = cavp_case(&self.section, &fields, self.p.as_ref(), self.q.as_ref(), self.g.as_ref());
+            self.cases.push(case);
+        }
+    }
+}
+
+fn cavp(text: &str) -> Result<Vec<Case>, String> {
+    let mut reader = CavpReader::default();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            reader.finish_case();
+            reader.section = name.to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let (key, value) = (key.trim(), value.trim());
+        match key {
+            "P" | "Q" | "G" => {
+                reader.finish_case();
+                let number = BigUint::parse_bytes(value.as_bytes(), 16).ok_or(format!("{key} is not hex: {value}"))?;
+                match key {
+                    "P" => reader.p = Some(number),
+                    "Q" => reader.q = Some(number),
+                    _ => reader.g = Some(number),
+                }
+            }
+            "COUNT" => {
+                reader.finish_case();
+                reader.fields = Some(vec![(key.to_string(), value.to_string())]);
+            }
+            _ => {
+                if let Some(ref mut fields) = reader.fields {
+                    fields.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+    }
+    reader.finish_case();
+    Ok(reader.cases)
+}
+
+fn cavp_case(
+    section: &str,
+    fields: &[(String, String)],
+    p: Option<&BigUint>,
+    q: Option<&BigUint>,
+    g: Option<&BigUint>,
+) -> Case {
+    let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    let count = field("COUNT").unwrap_or("?");
+    let id = if section.is_empty() { count.to_string() } else { format!("{section}/{count}") };
+    let skipped = |reason: &str| Case {
+        id: id.clone(),
+        status: Status::Skipped(reason.into()),
+    };
+    let (Some(p), Some(g)) = (p, g) else { return skipped("no P and G before the case") };
+    if field("Z").is_none() {
+        return skipped("no Z to compare");
+    }
+    if field("XstatIUT").is_none() && field("XephemIUT").is_none() {
+        return skipped("no XstatIUT or XephemIUT private key");
+    }
+    // `Result = P` or `Result = F (reason)` in validity files; function
+    // files only hold cases that should work.
+    let expected_valid = field("Result").is_none_or(|result| result.starts_with('P'));
+    let status = judge(expected_valid, cavp_check(&field, p, q, g));
+    Case { id, status }
+}
+
+/// Derives each IUT public key, validates each CAVS key, and compares the
+/// shared secrets with `Z`: static then ephemeral, the order dhHybrid1
+/// concatenates them in.
+fn cavp_check<'a>(
+    field: &dyn Fn(&str) -> Option<&'a str>,
+    p: &BigUint,
+    q: Option<&BigUint>,
+    g: &BigUint,
+) -> Result<(), String> {
+    let number = |name: &str| -> Result<BigUint, String> {
+        let value = field(name).ok_or(format!("no {name}"))?;
+        BigUint::parse_bytes(value.as_bytes(), 16).ok_or(format!("{name} is not hex"))
+    };
+    let mut z = Vec::new();
+    for kind in ["stat", "ephem"] {
+        if field(&format!("X{kind}IUT")).is_none() {
+            continue;
+        }
+        let private = number(&format!("X{kind}IUT"))?;
+        let public = number(&format!("Y{kind}IUT"))?;
+        let peer = number(&format!("Y{kind}CAVS"))?;
+        if create_private_key_core::public_key(p, g, &private) != public {
+            return Err(format!("Y{kind}IUT is not g^X{kind}IUT mod P"));
+        }
+        if let Some(q) = q
+            && !peer.modpow(q, p).is_one()
+        {
+            return Err(format!("Y{kind}CAVS is not in the subgroup of order Q"));
+        }
+        z.extend(dh::shared_secret(p, &private, &peer).map_err(|err| err.to_string())?);
+    }
+    if BigUint::from_bytes_be(&z) != number("Z")? {
+        return Err("Z differs".into());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+fn wycheproof(text: &str) -> Result<Vec<Case>, String> {
+    use serde_json::Value;
+
+    let file: Value = serde_json::from_str(text).map_err(|err| format!("not a Wycheproof JSON file: {err}"))?;
+    let algorithm = file.get("algorithm").and_then(Value::as_str).unwrap_or_default();
+    let groups = file
+        .get("testGroups")
+        .and_then(Value::as_array)
+        .ok_or("not a Wycheproof JSON file: no testGroups")?;
+    let mut cases = Vec::new();
+    for group in groups {
+        let kind = group.get("type").and_then(Value::as_str).unwrap_or_default();
+        for test in group.get("tests").and_then(Value::as_array).into_iter().flatten() {
+            let id = test.get("tcId").map_or_else(|| "?".into(), Value::to_string);
+            let expected_valid = test.get("result").and_then(Value::as_str) != Some("invalid");
+            let acceptable = test.get("result").and_then(Value::as_str) == Some("acceptable");
+            let outcome = match (kind, algorithm) {
+                ("HkdfTest", "HKDF-SHA-256") => Some(hkdf_case(test)),
+                #[cfg(feature = "pem")]
+                ("PbkdfTest", "PBKDF2-HMACSHA256") => Some(pbkdf2_case(test)),
+                _ => None,
+            };
+            let status = match outcome {
+                None => Status::Skipped(format!("{kind} for {algorithm} is not implemented here")),
+                // Either answer is allowed.
+                Some(_) if acceptable => Status::Pass,
+                Some(outcome) => judge(expected_valid, outcome),
+            };
+            cases.push(Case { id, status });
+        }
+    }
+    Ok(cases)
+}
+
+#[cfg(feature = "json")]
+fn hex_field(test: &serde_json::Value, name: &str) -> Result<Vec<u8>, String> {
+    let value = test.get(name).and_then(serde_json::Value::as_str).ok_or(format!("no {name}"))?;
+    data_encoding::HEXLOWER_PERMISSIVE
+        .decode(value.as_bytes())
+        .map_err(|err| format!("{name} is not hex: {err}"))
+}
+
+#[cfg(feature = "json")]
+fn number_field(test: &serde_json::Value, name: &str) -> Result<usize, String> {
+    let value = test.get(name).and_then(serde_json::Value::as_u64).ok_or(format!("no {name}"))?;
+    usize::try_from(value).map_err(|_| format!("{name} is too large"))
+}
+
+#[cfg(feature = "json")]
+fn hkdf_case(test: &serde_json::Value) -> Result<(), String> {
+    let (ikm, salt, info) = (hex_field(test, "ikm")?, hex_field(test, "salt")?, hex_field(test, "info")?);
+    let okm = dh::hkdf_sha256(&ikm, &salt, &info, number_field(test, "size")?).map_err(|err| err.to_string())?;
+    if okm != hex_field(test, "okm")? {
+        return Err("okm differs".into());
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "json", feature = "pem"))]
+fn pbkdf2_case(test: &serde_json::Value) -> Result<(), String> {
+    let (password, salt) = (hex_field(test, "password")?, hex_field(test, "salt")?);
+    let rounds = u32::try_from(number_field(test, "iterationCount")?).map_err(|_| "iterationCount is too large")?;
+    if rounds == 0 {
+        return Err("zero iterations".into());
+    }
+    let mut dk = vec![0u8; number_field(test, "dkLen")?];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(&password, &salt, rounds, &mut dk);
+    if dk != hex_field(test, "dk")? {
+        return Err("dk differs".into());
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! `--timings`: where a `generate` run spends its time, and its peak memory.
+
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+pub enum Stage {
+    /// Turning `--prime`/`--generator` (or the group's) into numbers.
+    Parse,
+    /// Parameter checks, the key policy and, with `--fips`, public-key
+    /// validation.
+    Validation,
+    /// Drawing the private exponent.
+    Sampling,
+    /// `g^x mod p`.
+    Modpow,
+}
+
+/// Report order and names.
+const STAGES: [&str; 4] = ["parse", "validation", "sampling", "modpow"];
+
+/// Time spent per stage, summed over every key of a batch.
+#[derive(Default)]
+pub struct Timings {
+    totals: [Duration; STAGES.len()],
+    counts: [u32; STAGES.len()],
+}
+
+impl Timings {
+    /// Runs `work`, adding its duration to `stage`.
+    pub fn time<T>(&mut self, stage: Stage, work: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = work();
+        self.totals[stage as usize] += started.elapsed();
+        self.counts[stage as usize] += 1;
+        result
+    }
+
+    /// `timing.<stage>_us` lines (means per key for the per-key stages when
+    /// there are several), then the total and `peak_memory_kib`.
+    pub fn render(&self, total: Duration) -> String {
+        let mut text = String::new();
+        for ((stage, elapsed), &count) in STAGES.iter().zip(self.totals).zip(&self.counts) {
+            match count {
+                0 => continue,
+                1 => text.push_str(&format!("timing.{stage}_us={}\n", elapsed.as_micros())),
+                _ => text.push_str(&format!(
+                    "timing.{stage}_us={} ({count} runs, {} each)\n",
+                    elapsed.as_micros(),
+                    (elapsed / count).as_micros()
+                )),
+            }
+        }
+        text.push_str(&format!("timing.total_us={}\n", total.as_micros()));
+        match peak_memory_kib() {
+            Some(kib) => text.push_str(&format!("peak_memory_kib={kib}\n")),
+            None => text.push_str("peak_memory_kib=unavailable\n"),
+        }
+        text
+    }
+}
+
+/// The process's maximum resident set size so far.
+#[cfg(unix)]
+fn peak_memory_kib() -> Option<u64> {
+    // SAFETY: getrusage only writes the struct it is given.
+    let usage = unsafe {
+        let mut usage = std::mem::zeroed::<libc::rusage>();
+        (libc::getrusage(libc::RUSAGE_SELF, &mut usage) == 0).then_some(usage)
+    }?;
+    let max_rss = u64::try_from(usage.ru_maxrss).ok()?;
+    // macOS reports bytes, the other Unixes KiB.
+    Some(if cfg!(target_os = "macos") { max_rss / 1024 } else { max_rss })
+}
+
+#[cfg(not(unix))]
+fn peak_memory_kib() -> Option<u64> {
+    None
+}
@@ -0,0 +1,181 @@
+//! Sealing private keys to a TPM 2.0 with `tpm2-tools`.
+//!
+//! TPMs have no finite-field DH and seal at most 128 bytes, so the key is
+//! generated as usual and encrypted with AES-256-GCM under a random data
+//! key; only the data key is sealed, as a TPM data object under the owner
+//! hierarchy's primary key. Its policy requires the chosen PCRs to hold the
+//! values they had at sealing time, so the file only opens on that device in
+//! that boot state.
+//!
+//! The sealed file is PEM (`CPK TPM SEALED KEY`) around, integers big-endian:
+//!
+//! ```text
+//! "CPKTPM01" | pcrs len u8 | pcrs | public len u32 | TPM2B_PUBLIC
+//!            | private len u32 | TPM2B_PRIVATE | nonce [12] | ciphertext || tag
+//! ```
+//!
+//! Everything before the nonce is authenticated as associated data.
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use std::path::PathBuf;
+
+use crate::external;
+use crate::pem;
+use crate::random;
+
+pub const SEALED_LABEL: &str = "CPK TPM SEALED KEY";
+/// Default PCR selection: firmware (0) and Secure Boot state (7).
+pub const DEFAULT_PCRS: &str = "sha256:0,7";
+
+const MAGIC: &[u8; 8] = b"CPKTPM01";
+const DATA_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// Encrypts `secret` under a new data key sealed to this TPM and bound to
+/// `pcrs`, returning the sealed file.
+pub fn seal(secret: &[u8], pcrs: &str) -> Result<Vec<u8>, String> {
+    let pcr_bytes = u8::try_from(pcrs.len()).map_err(|_| "PCR selection is too long".to_string())?;
+    let mut data_key = [0u8; DATA_KEY_LEN];
+    random::fill(&mut data_key)?;
+
+    let work = WorkDir::new()?;
+    let (primary, policy) = (work.file("primary.ctx"), work.file("policy.dat"));
+    let (sealed_public, sealed_private) = (work.file("seal.pub"), work.file("seal.priv"));
+    create_primary(&primary)?;
+    tpm2(&["tpm2_createpolicy", "-Q", "--policy-pcr", "-l", pcrs, "-L", &policy], &[])?;
+    // Without `userwithauth` the object can only be used through its policy.
+    tpm2(
+        &[
+            "tpm2_create", "-Q", "-C", &primary, "-L", &policy, "-a", "fixedtpm|fixedparent", "-i", "-", "-u",
+            &sealed_public, "-r", &sealed_private,
+        ],
+        &data_key,
+    )?;
+
+    let mut header = MAGIC.to_vec();
+    header.push(pcr_bytes);
+    header.extend_from_slice(pcrs.as_bytes());
+    for path in [&sealed_public, &sealed_private] {
+        let blob = std::fs::read(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+        header.extend_from_slice(&(blob.len() as u32).to_be_bytes());
+        header.extend_from_slice(&blob);
+    }
+
+    let mut nonce = [0u8; NONCE_LEN];
+    random::fill(&mut nonce)?;
+    let ciphertext = Aes256Gcm::new((&data_key).into())
+        .encrypt(
+            (&nonce).into(),
+            Payload {
+                msg: secret,
+                aad: &header,
+            },
+        )
+        .map_err(|_| "encryption failed".to_string())?;
+
+    let mut sealed = header;
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(pem::encode(SEALED_LABEL, &sealed).into_bytes())
+}
+
+/// Whether `input` looks like a TPM-sealed key file.
+pub fn is_sealed(input: &[u8]) -> bool {
+    std::str::from_utf8(input).is_ok_and(|text| text.contains(&format!("-----BEGIN {SEALED_LABEL}-----")))
+}
+
+/// Unseals the data key through the PCR policy and decrypts the key file.
+pub fn unseal(input: &[u8]) -> Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(input).map_err(|_| "TPM-sealed key is not valid UTF-8".to_string())?;
+    let sealed = pem::decode(SEALED_LABEL, text)?;
+    let truncated = || "TPM-sealed key is truncated".to_string();
+    if !sealed.starts_with(MAGIC) {
+        return Err("not a TPM-sealed key (bad header)".into());
+    }
+
+    let mut offset = MAGIC.len();
+    let pcr_len = usize::from(*sealed.get(offset).ok_or_else(truncated)?);
+    let pcrs = sealed.get(offset + 1..offset + 1 + pcr_len).ok_or_else(truncated)?;
+    let pcrs = std::str::from_utf8(pcrs).map_err(|_| "TPM-sealed key has a corrupted PCR selection".to_string())?;
+    offset += 1 + pcr_len;
+    let mut blobs = Vec::with_capacity(2);
+    for _ in 0..2 {
+        let len = sealed.get(offset..offset + 4).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(len.try_into().expect("four bytes")) as usize;
+        blobs.push(sealed.get(offset + 4..offset + 4 + len).ok_or_else(truncated)?);
+        offset += 4 + len;
+    }
+    let header = &sealed[..offset];
+    let nonce: [u8; NONCE_LEN] = sealed
+        .get(offset..offset + NONCE_LEN)
+        .ok_or_else(truncated)?
+        .try_into()
+        .expect("nonce length");
+    let ciphertext = &sealed[offset + NONCE_LEN..];
+
+    let work = WorkDir::new()?;
+    let (primary, object) = (work.file("primary.ctx"), work.file("seal.ctx"));
+    let (sealed_public, sealed_private) = (work.file("seal.pub"), work.file("seal.priv"));
+    for (path, blob) in [(&sealed_public, blobs[0]), (&sealed_private, blobs[1])] {
+        std::fs::write(path, blob).map_err(|err| format!("failed to write {path}: {err}"))?;
+    }
+    create_primary(&primary)?;
+    tpm2(
+        &["tpm2_load", "-Q", "-C", &primary, "-u", &sealed_public, "-r", &sealed_private, "-c", &object],
+        &[],
+    )?;
+    let data_key = external::run_filter("tpm2_unseal", &["-c", &object, "-p", &format!("pcr:{pcrs}")], &[])?;
+
+    Aes256Gcm::new_from_slice(&data_key)
+        .map_err(|_| "the TPM returned a data key of the wrong size".to_string())?
+        .decrypt(
+            (&nonce).into(),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| "TPM-sealed key failed to decrypt (corrupted file)".to_string())
+}
+
+/// The owner hierarchy's default primary key, recreated identically on
+/// every call from the TPM's seed.
+fn create_primary(context: &str) -> Result<(), String> {
+    tpm2(&["tpm2_createprimary", "-Q", "-C", "o", "-c", context], &[])
+}
+
+fn tpm2(command: &[&str], input: &[u8]) -> Result<(), String> {
+    external::run_filter(command[0], &command[1..], input).map(drop)
+}
+
+/// Scratch directory for TPM context files, removed when dropped.
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new() -> Result<Self, String> {
+        let mut suffix = [0u8; 8];
+        random::fill(&mut suffix)?;
+        let path = std::env::temp_dir().join(format!("create-private-key-tpm-{:016x}", u64::from_be_bytes(suffix)));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder
+            .create(&path)
+            .map_err(|err| format!("failed to create {}: {err}", path.display()))?;
+        Ok(WorkDir(path))
+    }
+
+    fn file(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
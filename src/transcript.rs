@@ -0,0 +1,242 @@
+//! `--transcript`: a key-ceremony record of one `generate` run, and
+//! `--verify-transcript`, which replays it.
+//!
+//! The file holds the command line, the parameters as resolved, each
+//! validation step and each key's public values, as `key=value` lines under
+//! `[section]` headings. The private key is never written. `[end]` closes it
+//! with a SHA-256 over every line before, so an edited or truncated
+//! transcript is caught; it is a checksum, not a signature, so keep the file
+//! where it cannot be replaced wholesale.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+use clap::ValueEnum;
+use create_private_key::fingerprint::{self, FingerprintHash};
+use create_private_key::params::DhGroup;
+use create_private_key::{DhParams, Error, der, dh};
+use data_encoding::HEXLOWER;
+use num_bigint::BigUint;
+use num_traits::One;
+use sha2::{Digest, Sha256};
+
+use crate::metadata;
+use crate::output::{KeyRecord, to_even_length_hex};
+
+const HEADER: &str = "# create-private-key transcript v1";
+
+/// What the run was asked for and settled on, before any key.
+pub struct Inputs<'a> {
+    pub group: &'a str,
+    pub params: &'a DhParams,
+    pub rng: &'a str,
+    pub fips: bool,
+    pub imported: bool,
+    pub validation: &'a [String],
+}
+
+/// A transcript being written. Each line is flushed as it is written, so a
+/// run that dies leaves a transcript without `[end]`.
+pub struct Transcript {
+    file: File,
+    hasher: Sha256,
+    keys: usize,
+}
+
+impl Transcript {
+    /// Creates the file, refusing one that exists: a ceremony's record is
+    /// never overwritten.
+    pub fn create(path: &Path) -> Result<Transcript, String> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|err| format!("failed to create transcript {}: {err}", path.display()))?;
+        let mut transcript = Transcript {
+            file,
+            hasher: Sha256::new(),
+            keys: 0,
+        };
+        transcript.line(HEADER)?;
+        Ok(transcript)
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let line = format!("{line}\n");
+        self.hasher.update(line.as_bytes());
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.flush())
+            .map_err(|err| format!("failed to write the transcript: {err}"))
+    }
+
+    fn section(&mut self, name: &str, fields: &[(&str, String)]) -> Result<(), String> {
+        self.line(&format!("[{name}]"))?;
+        for (key, value) in fields {
+            // Values are single lines; a newline in an argument would
+            // otherwise start a field of its own.
+            self.line(&format!("{key}={}", value.replace('\n', "\\n")))?;
+        }
+        Ok(())
+    }
+
+    /// The command line, parameters and validation steps.
+    pub fn begin(&mut self, inputs: &Inputs) -> Result<(), String> {
+        let argv: Vec<String> = std::env::args().collect();
+        self.section(
+            "run",
+            &[
+                ("tool_version", env!("CARGO_PKG_VERSION").to_string()),
+                ("started", metadata::rfc3339_utc(SystemTime::now())),
+                ("argv", argv.join(" ")),
+            ],
+        )?;
+        let private_bits = inputs.params.private_bits().map_or_else(|| "full".into(), |bits| bits.to_string());
+        self.section(
+            "inputs",
+            &[
+                ("group", inputs.group.to_string()),
+                ("prime_hex", to_even_length_hex(inputs.params.prime())),
+                ("generator", inputs.params.generator().to_string()),
+                ("private_bits", private_bits),
+                ("rng", inputs.rng.to_string()),
+                ("fips", inputs.fips.to_string()),
+                ("source", if inputs.imported { "imported" } else { "generated" }.into()),
+            ],
+        )?;
+        let steps: Vec<(&str, String)> = inputs.validation.iter().map(|step| ("step", step.clone())).collect();
+        self.section("validation", &steps)
+    }
+
+    /// One key's public values.
+    pub fn key(&mut self, record: &KeyRecord) -> Result<(), String> {
+        self.keys += 1;
+        self.section(
+            "key",
+            &[
+                ("index", self.keys.to_string()),
+                ("public_key_hex", to_even_length_hex(&record.public_key)),
+                ("fingerprint", record.fingerprint.clone()),
+                ("key_id", record.key_id.clone()),
+            ],
+        )
+    }
+
+    /// `[end]` with the key count and the checksum.
+    pub fn finish(mut self) -> Result<(), String> {
+        let fields = [
+            ("keys", self.keys.to_string()),
+            ("finished", metadata::rfc3339_utc(SystemTime::now())),
+        ];
+        self.section("end", &fields)?;
+        let digest = HEXLOWER.encode(&self.hasher.clone().finalize());
+        self.line(&format!("sha256={digest}"))?;
+        self.file.sync_all().map_err(|err| format!("failed to sync the transcript: {err}"))
+    }
+}
+
+/// What a verified transcript holds.
+pub struct Summary {
+    pub group: String,
+    pub prime_bits: u64,
+    pub keys: usize,
+}
+
+/// Checks the checksum, re-validates the recorded parameters and confirms
+/// that every public key belongs to them: in `[2, p - 2]`, in the subgroup
+/// `g` generates when that is the order-`q` one, and with the recorded
+/// fingerprint and key ID.
+pub fn verify(text: &str) -> Result<Summary, Error> {
+    let invalid = |message: String| Error::Validation(format!("transcript: {message}"));
+    let (body, digest) = text
+        .trim_end_matches('\n')
+        .rsplit_once('\n')
+        .filter(|(_, last)| last.starts_with("sha256="))
+        .ok_or_else(|| invalid("no sha256 line; the run did not finish or the file is truncated".into()))?;
+    let expected = HEXLOWER.encode(&Sha256::digest(format!("{body}\n").as_bytes()));
+    if digest.trim_start_matches("sha256=") != expected {
+        return Err(invalid("the checksum does not match; the file was changed".into()));
+    }
+    if body.lines().next() != Some(HEADER) {
+        return Err(invalid("not a create-private-key transcript".into()));
+    }
+
+    let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for line in body.lines().skip(1) {
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            sections.push((name.to_string(), Vec::new()));
+        } else if let (Some((key, value)), Some((_, fields))) = (line.split_once('='), sections.last_mut()) {
+            fields.push((key.to_string(), value.to_string()));
+        } else {
+            return Err(invalid(format!("unexpected line: {line}")));
+        }
+    }
+    let section = |name: &str| sections.iter().find(|(section, _)| section == name).map(|(_, fields)| fields);
+    let field = |fields: &[(String, String)], name: &str| -> Result<String, Error> {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| invalid(format!("no {name}")))
+    };
+
+    let inputs = section("inputs").ok_or_else(|| invalid("no [inputs] section".into()))?;
+    let prime = BigUint::parse_bytes(field(inputs, "prime_hex")?.as_bytes(), 16)
+        .ok_or_else(|| invalid("prime_hex is not hex".into()))?;
+    let generator =
+        field(inputs, "generator")?.parse::<BigUint>().map_err(|_| invalid("generator is not a number".into()))?;
+    let params = DhParams::new(prime.clone(), generator.clone())?;
+    match field(inputs, "private_bits")?.as_str() {
+        "full" => {}
+        bits => {
+            params.with_private_bits(bits.parse().map_err(|_| invalid(format!("private_bits is {bits}")))?)?;
+        }
+    }
+    let group = field(inputs, "group")?;
+    if let Some(&builtin) = DhGroup::value_variants().iter().find(|builtin| builtin.name() == group)
+        && DhParams::group(builtin).into_parts() != (prime.clone(), generator.clone())
+    {
+        return Err(invalid(format!("the recorded prime and generator are not {group}'s")));
+    }
+
+    let order: BigUint = (&prime - 1u32) >> 1;
+    let subgroup = generator.modpow(&order, &prime).is_one();
+    let mut keys = 0;
+    for (_, fields) in sections.iter().filter(|(name, _)| name == "key") {
+        keys += 1;
+        let index = field(fields, "index")?;
+        let at = |message: &str| invalid(format!("key {index}: {message}"));
+        if index != keys.to_string() {
+            return Err(at("out of order"));
+        }
+        let public_key = BigUint::parse_bytes(field(fields, "public_key_hex")?.as_bytes(), 16)
+            .ok_or_else(|| at("public_key_hex is not hex"))?;
+        dh::check_peer(&prime, &public_key).map_err(|err| at(&err.to_string()))?;
+        if subgroup && !public_key.modpow(&order, &prime).is_one() {
+            return Err(at("not in the subgroup the generator generates"));
+        }
+        let spki = der::dh_subject_public_key_info(&prime, &generator, &public_key);
+        let recorded = field(fields, "fingerprint")?;
+        let hash = recorded
+            .split_once(':')
+            .and_then(|(name, _)| FingerprintHash::from_str(name, true).ok())
+            .ok_or_else(|| at("unknown fingerprint hash"))?;
+        if fingerprint::fingerprint(hash, &spki) != recorded {
+            return Err(at("the fingerprint is not that of the public key and parameters"));
+        }
+        if fingerprint::key_id(&spki) != field(fields, "key_id")? {
+            return Err(at("the key ID is not that of the public key and parameters"));
+        }
+    }
+    let end = section("end").ok_or_else(|| invalid("no [end] section".into()))?;
+    if field(end, "keys")? != keys.to_string() {
+        return Err(invalid(format!("[end] counts {} keys but {keys} are recorded", field(end, "keys")?)));
+    }
+    Ok(Summary {
+        group,
+        prime_bits: prime.bits(),
+        keys,
+    })
+}
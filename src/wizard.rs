@@ -0,0 +1,79 @@
+//! Prompts for `--interactive`, asked on stderr and answered on stdin.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Fails unless stdin is a terminal; a wizard reading a pipe would quietly
+/// take whatever the pipe holds as its answers.
+pub fn require_terminal() -> Result<(), String> {
+    if !std::io::stdin().is_terminal() {
+        return Err("--interactive needs a terminal on stdin".into());
+    }
+    Ok(())
+}
+
+/// Asks `question` until `parse` accepts the answer. An empty answer takes
+/// `default`, or asks again when there is none.
+pub fn ask<T>(question: &str, default: Option<&str>, parse: impl Fn(&str) -> Result<T, String>) -> Result<T, String> {
+    loop {
+        match default {
+            Some(default) => eprint!("{question} [{default}]: "),
+            None => eprint!("{question}: "),
+        }
+        let line = read_line()?;
+        let answer = match (line.trim(), default) {
+            ("", Some(default)) => default,
+            ("", None) => continue,
+            (answer, _) => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(err) => eprintln!("  {err}"),
+        }
+    }
+}
+
+/// Lists `options` as numbered `name  description` lines and returns the
+/// index picked by number or name.
+pub fn choose(question: &str, options: &[(String, String)], default: usize) -> Result<usize, String> {
+    eprintln!("{question}:");
+    let width = options.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (index, (name, description)) in options.iter().enumerate() {
+        eprintln!("  {}) {name:width$}  {description}", index + 1);
+    }
+    ask("Choice", Some(&options[default].0), |answer| {
+        if let Some(index) = options.iter().position(|(name, _)| name.eq_ignore_ascii_case(answer)) {
+            return Ok(index);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=options.len()).contains(&number) => Ok(number - 1),
+            _ => Err(format!("pick 1 to {} or one of the names", options.len())),
+        }
+    })
+}
+
+/// Asks a yes/no question that defaults to no.
+pub fn confirm(question: &str) -> Result<bool, String> {
+    ask(question, Some("n"), |answer| match answer.to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(true),
+        "n" | "no" => Ok(false),
+        _ => Err("answer y or n".into()),
+    })
+}
+
+/// Quotes `word` for a POSIX shell when it needs it, so the command the
+/// wizard prints can be pasted back.
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain { word.to_string() } else { format!("'{}'", word.replace('\'', "'\\''")) }
+}
+
+fn read_line() -> Result<String, String> {
+    std::io::stderr().flush().map_err(|err| format!("failed to write the prompt: {err}"))?;
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Err("no answer: stdin was closed".into()),
+        Ok(_) => Ok(line),
+        Err(err) => Err(format!("failed to read the answer: {err}")),
+    }
+}
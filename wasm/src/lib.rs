@@ -0,0 +1,87 @@
+//! WebAssembly bindings for browsers, built with wasm-bindgen on top of
+//! `create-private-key-core`, the arithmetic the command itself uses.
+//!
+//! Numbers cross the boundary as hex strings (even length, no prefix, as in
+//! the `cpk-v1` envelope). A missing prime or generator means RFC 3526
+//! group 14 and generator 2. Randomness comes from `crypto.getRandomValues`.
+//!
+//! ```js
+//! import init, { generate, derivePublic, sharedSecret } from "./create_private_key_wasm.js";
+//! await init();
+//! const alice = generate();
+//! const bob = generate();
+//! sharedSecret(alice.privateKey, bob.publicKey) === sharedSecret(bob.privateKey, alice.publicKey);
+//! ```
+
+use create_private_key_core as ffdh;
+use num_bigint::BigUint;
+use wasm_bindgen::prelude::*;
+
+/// A freshly generated key pair.
+#[wasm_bindgen(getter_with_clone)]
+pub struct KeyPair {
+    #[wasm_bindgen(js_name = privateKey)]
+    pub private_key: String,
+    #[wasm_bindgen(js_name = publicKey)]
+    pub public_key: String,
+}
+
+/// Generates a key pair in the given group.
+#[wasm_bindgen]
+pub fn generate(prime: Option<String>, generator: Option<String>) -> Result<KeyPair, JsError> {
+    let (prime, generator) = group(prime.as_deref(), generator.as_deref())?;
+    let private_key = ffdh::generate_private_key(&prime, None, getrandom::getrandom)
+        .map_err(|err| JsError::new(&format!("random number generator failed: {err}")))?;
+    let public_key = ffdh::public_key(&prime, &generator, &private_key);
+    Ok(KeyPair {
+        private_key: to_hex(&private_key),
+        public_key: to_hex(&public_key),
+    })
+}
+
+/// The public value `g^x mod p` of a private key.
+#[wasm_bindgen(js_name = derivePublic)]
+pub fn derive_public(private_key: &str, prime: Option<String>, generator: Option<String>) -> Result<String, JsError> {
+    let (prime, generator) = group(prime.as_deref(), generator.as_deref())?;
+    let private_key = parse("private key", private_key)?;
+    ffdh::check_private_key(&prime, &private_key).map_err(rejected)?;
+    Ok(to_hex(&ffdh::public_key(&prime, &generator, &private_key)))
+}
+
+/// The DH shared secret with a peer, padded to the prime's length. The
+/// peer's value is range-checked first.
+#[wasm_bindgen(js_name = sharedSecret)]
+pub fn shared_secret(private_key: &str, peer_public_key: &str, prime: Option<String>) -> Result<String, JsError> {
+    let (prime, _) = group(prime.as_deref(), None)?;
+    let private_key = parse("private key", private_key)?;
+    ffdh::check_private_key(&prime, &private_key).map_err(rejected)?;
+    let peer = parse("peer public key", peer_public_key)?;
+    let secret = ffdh::shared_secret(&prime, &private_key, &peer).map_err(rejected)?;
+    Ok(secret.iter().map(|byte| format!("{byte:02X}")).collect())
+}
+
+fn group(prime: Option<&str>, generator: Option<&str>) -> Result<(BigUint, BigUint), JsError> {
+    let prime = match prime {
+        Some(prime) => parse("prime", prime)?,
+        None => parse("prime", ffdh::RFC3526_MODP14_PRIME_HEX)?,
+    };
+    let generator = match generator {
+        Some(generator) => parse("generator", generator)?,
+        None => BigUint::from(2u32),
+    };
+    ffdh::validate(&prime, &generator).map_err(rejected)?;
+    Ok((prime, generator))
+}
+
+fn parse(name: &str, hex: &str) -> Result<BigUint, JsError> {
+    BigUint::parse_bytes(hex.as_bytes(), 16).ok_or_else(|| JsError::new(&format!("{name} is not valid hex")))
+}
+
+fn to_hex(value: &BigUint) -> String {
+    let hex = format!("{value:X}");
+    if hex.len() % 2 == 1 { format!("0{hex}") } else { hex }
+}
+
+fn rejected(rejection: ffdh::Rejection) -> JsError {
+    JsError::new(&rejection.to_string())
+}